@@ -0,0 +1,36 @@
+CREATE TABLE vehicles (
+    id UUID PRIMARY KEY,
+    manufacturer TEXT NOT NULL,
+    manufacturer_raw TEXT,
+    model TEXT NOT NULL,
+    year SMALLINT NOT NULL,
+    vin TEXT,
+    mileage_km BIGINT,
+    color TEXT,
+    license_plate TEXT,
+    price NUMERIC,
+    fuel_type TEXT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    metadata JSONB NOT NULL DEFAULT '{}',
+    engine JSONB,
+    owner_id UUID,
+    first_registered DATE,
+    status TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ,
+    version BIGINT NOT NULL
+);
+
+-- Soft-deleted vehicles keep their VIN, but give their license plate up
+CREATE UNIQUE INDEX vehicles_vin_key ON vehicles (vin);
+CREATE UNIQUE INDEX vehicles_live_plate_key ON vehicles (license_plate) WHERE deleted_at IS NULL;
+CREATE INDEX vehicles_identity_idx ON vehicles (lower(manufacturer), lower(model), year);
+CREATE INDEX vehicles_owner_idx ON vehicles (owner_id);
+
+CREATE TABLE vehicle_revisions (
+    vehicle_id UUID NOT NULL REFERENCES vehicles (id) ON DELETE CASCADE,
+    version BIGINT NOT NULL,
+    revision JSONB NOT NULL,
+    PRIMARY KEY (vehicle_id, version)
+);
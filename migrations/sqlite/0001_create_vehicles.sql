@@ -0,0 +1,42 @@
+CREATE TABLE vehicles (
+    id BLOB PRIMARY KEY,
+    manufacturer TEXT NOT NULL,
+    manufacturer_raw TEXT,
+    model TEXT NOT NULL,
+    year INTEGER NOT NULL,
+    vin TEXT,
+    mileage_km INTEGER,
+    color TEXT,
+    license_plate TEXT,
+    -- Decimal string, so no precision is lost
+    price TEXT,
+    fuel_type TEXT,
+    -- JSON
+    tags TEXT NOT NULL DEFAULT '[]',
+    metadata TEXT NOT NULL DEFAULT '{}',
+    engine TEXT,
+    owner_id BLOB,
+    first_registered TEXT,
+    status TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    deleted_at TEXT,
+    version INTEGER NOT NULL,
+    -- Lowercased copies for case-insensitive filters and search; SQLite's lower() is ASCII-only
+    manufacturer_key TEXT NOT NULL,
+    model_key TEXT NOT NULL,
+    color_key TEXT
+);
+
+-- Soft-deleted vehicles keep their VIN, but give their license plate up
+CREATE UNIQUE INDEX vehicles_vin_key ON vehicles (vin);
+CREATE UNIQUE INDEX vehicles_live_plate_key ON vehicles (license_plate) WHERE deleted_at IS NULL;
+CREATE INDEX vehicles_identity_idx ON vehicles (manufacturer_key, model_key, year);
+CREATE INDEX vehicles_owner_idx ON vehicles (owner_id);
+
+CREATE TABLE vehicle_revisions (
+    vehicle_id BLOB NOT NULL REFERENCES vehicles (id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    revision TEXT NOT NULL,
+    PRIMARY KEY (vehicle_id, version)
+);
@@ -0,0 +1,173 @@
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    debug_handler,
+    extract::{Query, State, rejection::JsonRejection},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::{StreamExt, stream};
+use serde_json::Value;
+use std::io;
+use tracing::{Span, field, info, instrument, warn};
+
+use crate::{
+    AppState,
+    features::{
+        admin::model::{DUMP_BODY_LIMIT, RestoreParams, RestoreResult},
+        vehicle::{
+            handler::stream_error,
+            model::{DumpConflict, RestoreMode, VehicleFilter},
+            persistent_repo::{VehicleRecord, parse_record},
+            repo::vehicle_chunks,
+        },
+    },
+    utils::{
+        response::ApiResponse,
+        validator::{ServerError, json_rejection},
+    },
+};
+
+/// Vehicles written per chunk of a dump
+const DUMP_CHUNK_SIZE: usize = 500;
+
+/// Stream every vehicle, soft-deleted ones included, as a JSON array of the same versioned
+/// records the snapshot holds, so `import_dump` takes it back as is
+#[debug_handler]
+#[instrument(skip(state))]
+pub async fn export_dump(State(state): State<AppState>) -> Result<Response, ServerError> {
+    info!("Exporting a dump of every vehicle");
+
+    let filter = VehicleFilter {
+        include_deleted: Some(true),
+        ..Default::default()
+    };
+    let mut first = true;
+    let records = vehicle_chunks(state.vehicle_repo.clone(), filter, None, DUMP_CHUNK_SIZE).map(
+        move |vehicles| {
+            let vehicles = vehicles.map_err(stream_error)?;
+            let mut chunk = Vec::new();
+            for vehicle in vehicles {
+                if !std::mem::take(&mut first) {
+                    chunk.push(b',');
+                }
+                serde_json::to_writer(&mut chunk, &VehicleRecord::from(vehicle))?;
+            }
+            Ok::<_, io::Error>(Bytes::from(chunk))
+        },
+    );
+    let body = stream::once(async { Ok(Bytes::from_static(b"[")) })
+        .chain(records)
+        .chain(stream::once(async { Ok(Bytes::from_static(b"]")) }));
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+/// Restore a dump from `export_dump`, migrating records written by older versions. A replace
+/// that conflicts with itself answers 409 naming the conflicts and leaves the store untouched; a
+/// merge restores what it can and lists the vehicles it left out
+#[debug_handler]
+#[instrument(skip(state, records), fields(restored = field::Empty, conflicts = field::Empty))]
+pub async fn import_dump(
+    State(state): State<AppState>,
+    Query(params): Query<RestoreParams>,
+    records: Result<Json<Vec<Value>>, JsonRejection>,
+) -> Result<ApiResponse<RestoreResult>, ServerError> {
+    let Json(records) = records.map_err(|rejection| json_rejection(rejection, DUMP_BODY_LIMIT))?;
+    let mode = params.mode.unwrap_or_default();
+    info!(
+        "Restoring a dump of {} vehicles ({:?})",
+        records.len(),
+        mode
+    );
+
+    let vehicles = records
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| {
+            parse_record(record).map_err(|e| ServerError::InvalidRecord {
+                index,
+                reason: e.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let report = state.vehicle_repo.restore_dump(vehicles, mode).await?;
+    Span::current().record("restored", report.restored.len());
+    Span::current().record("conflicts", report.conflicts.len());
+
+    if mode == RestoreMode::Replace && !report.conflicts.is_empty() {
+        warn!(
+            "Replace aborted, the dump has {} conflicts",
+            report.conflicts.len()
+        );
+        return Err(ServerError::Conflict(replace_conflicts(&report.conflicts)));
+    }
+    Ok(ApiResponse::new(RestoreResult {
+        mode,
+        restored: report.restored.len(),
+        conflicts: report.conflicts,
+    }))
+}
+
+/// Why a replace was refused, one clause per conflicting vehicle
+fn replace_conflicts(conflicts: &[DumpConflict]) -> String {
+    let clauses: Vec<String> = conflicts
+        .iter()
+        .map(|c| {
+            format!(
+                "record {} ({}) has the {} of {}",
+                c.index, c.id, c.field, c.existing_id
+            )
+        })
+        .collect();
+    format!(
+        "the dump conflicts with itself, nothing was restored: {}",
+        clauses.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{features::vehicle::repo::InMemoryVehicleRepo, test_app::TestApp};
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn a_replace_that_conflicts_with_itself_is_a_conflict_problem() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let vehicle = json!({ "manufacturer": "Toyota", "model": "Corolla", "year": 2020 });
+        app.json(Method::POST, "/api/v1/vehicles", &vehicle).await;
+        let dump = app.get("/api/v1/admin/export").await.json();
+        let record = dump[0].clone();
+        let id = record["id"].as_str().unwrap().to_string();
+
+        let response = app
+            .json(
+                Method::POST,
+                "/api/v1/admin/import?mode=replace",
+                &Value::Array(vec![record.clone(), record]),
+            )
+            .await;
+
+        assert_eq!(response.status, StatusCode::CONFLICT);
+        let problem = response.json();
+        assert_eq!(problem["code"], "conflict");
+        assert_eq!(
+            problem["detail"],
+            format!(
+                "the dump conflicts with itself, nothing was restored: record 1 ({id}) has the id of {id}"
+            )
+        );
+        assert_eq!(
+            app.get("/api/v1/vehicles").await.json()["data"]
+                .as_array()
+                .map(Vec::len),
+            Some(1)
+        );
+    }
+}
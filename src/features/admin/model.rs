@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::features::vehicle::model::{DumpConflict, RestoreMode};
+
+/// Largest dump `import` accepts; the default limit would refuse all but small stores
+pub const DUMP_BODY_LIMIT: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RestoreParams {
+    /// Defaults to merging into the vehicles already stored
+    pub mode: Option<RestoreMode>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreResult {
+    pub mode: RestoreMode,
+    pub restored: usize,
+    pub conflicts: Vec<DumpConflict>,
+}
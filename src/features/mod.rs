@@ -1 +1,3 @@
+pub mod admin;
+pub mod owner;
 pub mod vehicle;
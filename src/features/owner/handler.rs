@@ -0,0 +1,176 @@
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use tracing::{Span, field, info, instrument, warn};
+
+use crate::{
+    AppState,
+    features::{
+        owner::{
+            model::{CreateOwnerRequest, CreatedOwner, Owner, OwnerDeleteParams, OwnerId},
+            repo::OwnerRepo,
+        },
+        vehicle::model::{PageParams, PageRequest, PageResult, VehicleFilter, VehicleResponse},
+    },
+    utils::{
+        response::{ApiListResponse, ApiResponse, RequestContext},
+        validator::{ServerError, ValidatedPath, ValidatedPayload, ValidatedQuery},
+    },
+};
+
+#[debug_handler]
+#[instrument(skip(state, ctx, params))]
+pub async fn get_owners(
+    State(state): State<AppState>,
+    ctx: RequestContext,
+    ValidatedQuery(params): ValidatedQuery<PageParams>,
+) -> Result<ApiListResponse<Owner>, ServerError> {
+    let (limit, offset) = (params.limit(), params.offset());
+    info!("Fetching owners (offset: {}, limit: {})", offset, limit);
+
+    let (owners, total) = state.owner_repo.list_owners(offset, limit).await;
+
+    info!("Found {} of {} owners", owners.len(), total);
+    Ok(ApiListResponse::new(owners, &ctx)
+        .total(total)
+        .page(limit, offset))
+}
+
+#[debug_handler]
+#[instrument(skip(state))]
+pub async fn get_owner(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<OwnerId>,
+) -> Result<ApiResponse<Owner>, ServerError> {
+    info!("Fetching owner with ID: {}", id);
+
+    match state.owner_repo.get_owner(id).await {
+        Some(owner) => Ok(ApiResponse::new(owner)),
+        None => {
+            warn!("Owner not found with ID: {}", id);
+            Err(ServerError::not_found("owner", id))
+        }
+    }
+}
+
+#[debug_handler]
+#[instrument(skip(state, owner), fields(owner_id = field::Empty))]
+pub async fn post_owner(
+    State(state): State<AppState>,
+    ValidatedPayload(owner): ValidatedPayload<CreateOwnerRequest>,
+) -> ApiResponse<CreatedOwner> {
+    info!("Creating new owner: {}", owner.name);
+
+    let id = state.owner_repo.post_owner(owner).await;
+    Span::current().record("owner_id", id.to_string());
+
+    info!("Owner created with ID: {}", id);
+    ApiResponse::new(CreatedOwner { id })
+}
+
+#[debug_handler]
+#[instrument(skip(state, owner))]
+pub async fn put_owner(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<OwnerId>,
+    ValidatedPayload(owner): ValidatedPayload<CreateOwnerRequest>,
+) -> Result<ApiResponse<Owner>, ServerError> {
+    info!("Updating owner with ID: {}", id);
+
+    match state.owner_repo.update_owner(id, owner).await {
+        Some(owner) => {
+            info!("Owner updated: {:?}", owner);
+            Ok(ApiResponse::new(owner))
+        }
+        None => {
+            warn!("Owner not found with ID: {}", id);
+            Err(ServerError::not_found("owner", id))
+        }
+    }
+}
+
+/// Owners still referenced by vehicles are only deleted with `?unassign=true`, which clears
+/// the owner on those vehicles first
+#[debug_handler]
+#[instrument(skip(state, params), fields(unassigned = field::Empty))]
+pub async fn delete_owner(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<OwnerId>,
+    Query(params): Query<OwnerDeleteParams>,
+) -> Result<StatusCode, ServerError> {
+    info!("Deleting owner with ID: {}", id);
+
+    if !state.owner_repo.exists(id).await {
+        warn!("Owner not found with ID: {}", id);
+        return Err(ServerError::not_found("owner", id));
+    }
+
+    // Soft-deleted vehicles count too, since restoring them would bring the reference back
+    let assigned = VehicleFilter {
+        owner_id: Some(id),
+        include_deleted: Some(true),
+        ..Default::default()
+    };
+    let vehicle_count = state.vehicle_repo.count(Some(&assigned)).await?;
+    if vehicle_count > 0 {
+        if !params.unassign.unwrap_or(false) {
+            warn!(
+                "Owner {} is still assigned to {} vehicles",
+                id, vehicle_count
+            );
+            return Err(ServerError::OwnerInUse { vehicle_count });
+        }
+        let unassigned = state.vehicle_repo.unassign_owner(id).await?;
+        Span::current().record("unassigned", unassigned);
+        info!("Unassigned owner {} from {} vehicles", id, unassigned);
+    }
+
+    match state.owner_repo.delete_owner(id).await {
+        Some(_) => {
+            info!("Owner deleted with ID: {}", id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(ServerError::not_found("owner", id)),
+    }
+}
+
+#[debug_handler]
+#[instrument(skip(state, ctx, params))]
+pub async fn get_owner_vehicles(
+    State(state): State<AppState>,
+    ctx: RequestContext,
+    ValidatedPath(id): ValidatedPath<OwnerId>,
+    ValidatedQuery(params): ValidatedQuery<PageParams>,
+) -> Result<ApiListResponse<VehicleResponse>, ServerError> {
+    let (limit, offset) = (params.limit(), params.offset());
+    info!("Fetching vehicles of owner with ID: {}", id);
+
+    if !state.owner_repo.exists(id).await {
+        warn!("Owner not found with ID: {}", id);
+        return Err(ServerError::not_found("owner", id));
+    }
+
+    let PageResult {
+        items: vehicles,
+        total,
+    } = state
+        .vehicle_repo
+        .list(PageRequest {
+            filter: VehicleFilter {
+                owner_id: Some(id),
+                ..Default::default()
+            },
+            offset,
+            limit,
+            sort: Vec::new(),
+        })
+        .await?;
+
+    info!("Found {} of {} vehicles", vehicles.len(), total);
+    let items = vehicles.into_iter().map(VehicleResponse::from).collect();
+    Ok(ApiListResponse::new(items, &ctx)
+        .total(total)
+        .page(limit, offset))
+}
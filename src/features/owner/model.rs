@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+use validator::{Validate, ValidationErrors};
+
+use crate::utils::validator::{Normalise, normalise_whitespace};
+
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CreateOwnerRequest {
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "name must be between 1 and 100 characters"
+    ))]
+    pub name: String,
+    #[validate(email(message = "email must be a valid email address"))]
+    pub email: String,
+}
+
+impl Normalise for CreateOwnerRequest {
+    fn normalise(&mut self) {
+        normalise_whitespace(&mut self.name);
+        normalise_whitespace(&mut self.email);
+    }
+}
+
+/// A person vehicles can be assigned to
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Owner {
+    pub id: OwnerId,
+    pub name: String,
+    /// Stored lowercase
+    pub email: String,
+}
+
+/// Owner identifier, serialized as the hyphenated UUID string
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OwnerId(pub Uuid);
+
+/// An id that parsed is valid, this lets it be taken with `ValidatedPath`
+impl Validate for OwnerId {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+impl OwnerId {
+    /// Fresh time-ordered (UUIDv7) id
+    pub fn generate() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl fmt::Display for OwnerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for OwnerId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
+/// Body returned when an owner is created: `{ "id": "..." }`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedOwner {
+    pub id: OwnerId,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct OwnerDeleteParams {
+    /// Unassign the owner's vehicles instead of refusing the delete while any reference them
+    pub unassign: Option<bool>,
+}
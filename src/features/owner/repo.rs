@@ -0,0 +1,70 @@
+use crate::features::owner::model::{CreateOwnerRequest, Owner, OwnerId};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+pub trait OwnerRepo: Sync + Send {
+    async fn get_owner(&self, id: OwnerId) -> Option<Owner>;
+    async fn exists(&self, id: OwnerId) -> bool;
+    /// A page of owners in id order, plus the total count
+    async fn list_owners(&self, offset: usize, limit: usize) -> (Vec<Owner>, usize);
+    async fn post_owner(&self, owner: CreateOwnerRequest) -> OwnerId;
+    /// Replace the name and email, or `None` if the owner does not exist
+    async fn update_owner(&self, id: OwnerId, owner: CreateOwnerRequest) -> Option<Owner>;
+    async fn delete_owner(&self, id: OwnerId) -> Option<Owner>;
+}
+
+/// Emails are stored lowercase
+fn normalise_email(email: String) -> String {
+    email.to_lowercase()
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryOwnerRepo {
+    pub store: Arc<Mutex<BTreeMap<OwnerId, Owner>>>,
+}
+
+impl OwnerRepo for InMemoryOwnerRepo {
+    async fn get_owner(&self, id: OwnerId) -> Option<Owner> {
+        self.store.lock().unwrap().get(&id).cloned()
+    }
+
+    async fn exists(&self, id: OwnerId) -> bool {
+        self.store.lock().unwrap().contains_key(&id)
+    }
+
+    async fn list_owners(&self, offset: usize, limit: usize) -> (Vec<Owner>, usize) {
+        let store = self.store.lock().unwrap();
+        let owners = store.values().skip(offset).take(limit).cloned().collect();
+
+        (owners, store.len())
+    }
+
+    async fn post_owner(&self, owner: CreateOwnerRequest) -> OwnerId {
+        let id = OwnerId::generate();
+        self.store.lock().unwrap().insert(
+            id,
+            Owner {
+                id,
+                name: owner.name,
+                email: normalise_email(owner.email),
+            },
+        );
+
+        id
+    }
+
+    async fn update_owner(&self, id: OwnerId, owner: CreateOwnerRequest) -> Option<Owner> {
+        let mut store = self.store.lock().unwrap();
+        let stored = store.get_mut(&id)?;
+        stored.name = owner.name;
+        stored.email = normalise_email(owner.email);
+
+        Some(stored.clone())
+    }
+
+    async fn delete_owner(&self, id: OwnerId) -> Option<Owner> {
+        self.store.lock().unwrap().remove(&id)
+    }
+}
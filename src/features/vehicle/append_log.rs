@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+};
+use tracing::{info, warn};
+
+use crate::features::vehicle::{
+    model::{Vehicle, VehicleId},
+    persistent_repo::{SnapshotError, VehicleRecord, parse_record, with_suffix},
+};
+
+/// One change as appended to the log, carrying the vehicle as the change left it so replaying
+/// the entries in order ends in the same state however many of them the snapshot already has
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum LogEntry {
+    Insert {
+        at: DateTime<Utc>,
+        vehicle: VehicleRecord,
+    },
+    Update {
+        at: DateTime<Utc>,
+        vehicle: VehicleRecord,
+    },
+    Delete {
+        at: DateTime<Utc>,
+        id: VehicleId,
+    },
+}
+
+/// A log entry as read back, before its vehicle is migrated
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum StoredLogEntry {
+    Insert { vehicle: Value },
+    Update { vehicle: Value },
+    Delete { id: VehicleId },
+}
+
+/// A logged change to apply on top of the snapshot
+pub enum Replayed {
+    Put(Box<Vehicle>),
+    Delete(VehicleId),
+}
+
+/// Append-only log of the changes made since the last snapshot, one JSON line per change, in
+/// numbered segment files next to it (`<DATA_FILE>.wal.<n>`).
+///
+/// Appends go through a buffer that only reaches the disk on `sync`, so a crash loses at most
+/// what was appended since the last one. A segment that reaches its size limit is closed and the
+/// next one started; segments a snapshot covers are removed once it has been written.
+pub struct AppendLog {
+    base: PathBuf,
+    /// Number of the segment being appended to
+    segment: u64,
+    writer: BufWriter<File>,
+    /// Bytes appended to the current segment
+    size: u64,
+    max_segment_bytes: u64,
+}
+
+impl AppendLog {
+    /// Read back every segment logged next to `base`, oldest first, then start a new segment
+    /// after them so a line torn by a crash is never appended to. Lines that can't be parsed,
+    /// such as that torn last line, are skipped with a warning
+    pub async fn open(
+        base: &Path,
+        max_segment_bytes: u64,
+    ) -> Result<(Self, Vec<Replayed>), SnapshotError> {
+        let segments = segments(base).await?;
+        let mut replayed = Vec::new();
+        for (_, path) in &segments {
+            // Lossy, as a torn write can end mid-character
+            let contents = tokio::fs::read(path).await?;
+            let contents = String::from_utf8_lossy(&contents);
+            for (index, line) in contents.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str::<StoredLogEntry>(line)
+                    .map_err(|e| SnapshotError::Corrupt(e.to_string()))
+                    .and_then(|entry| match entry {
+                        StoredLogEntry::Insert { vehicle } | StoredLogEntry::Update { vehicle } => {
+                            parse_record(vehicle).map(|vehicle| Replayed::Put(Box::new(vehicle)))
+                        }
+                        StoredLogEntry::Delete { id } => Ok(Replayed::Delete(id)),
+                    });
+                match entry {
+                    Ok(entry) => replayed.push(entry),
+                    Err(SnapshotError::Corrupt(reason)) => warn!(
+                        "Skipping unreadable line {} of {}: {}",
+                        index + 1,
+                        path.display(),
+                        reason
+                    ),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if !segments.is_empty() {
+            info!(
+                "Replaying {} logged changes from {} segments",
+                replayed.len(),
+                segments.len()
+            );
+        }
+
+        let segment = segments.last().map_or(1, |(segment, _)| segment + 1);
+        let log = Self {
+            base: base.to_path_buf(),
+            segment,
+            writer: create_segment(base, segment).await?,
+            size: 0,
+            max_segment_bytes,
+        };
+        Ok((log, replayed))
+    }
+
+    /// Buffer `entry`, moving on to the next segment once this one is full
+    pub async fn append(&mut self, entry: &LogEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+        self.size += line.len() as u64;
+        if self.size >= self.max_segment_bytes {
+            self.rotate().await?;
+        }
+        Ok(())
+    }
+
+    /// Write out the buffer and wait for the disk to have it
+    pub async fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.flush().await?;
+        self.writer.get_ref().sync_data().await
+    }
+
+    /// Close the current segment unless it is still empty, returning the first segment holding
+    /// changes made from now on; everything before it is covered by a snapshot taken now
+    pub async fn checkpoint(&mut self) -> std::io::Result<u64> {
+        if self.size > 0 {
+            self.rotate().await?;
+        }
+        Ok(self.segment)
+    }
+
+    /// Delete the segments before `segment`, returning how many there were
+    pub async fn remove_before(&self, segment: u64) -> std::io::Result<usize> {
+        let mut removed = 0;
+        for (_, path) in segments(&self.base)
+            .await?
+            .into_iter()
+            .filter(|(number, _)| *number < segment)
+        {
+            tokio::fs::remove_file(path).await?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        self.sync().await?;
+        self.segment += 1;
+        self.writer = create_segment(&self.base, self.segment).await?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+async fn create_segment(base: &Path, segment: u64) -> std::io::Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(with_suffix(base, &format!(".wal.{segment:08}")))
+        .await?;
+    Ok(BufWriter::new(file))
+}
+
+/// The segments logged next to `base`, in the order they were written
+async fn segments(base: &Path) -> std::io::Result<Vec<(u64, PathBuf)>> {
+    let directory = match base.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Some(name) = base.file_name().and_then(|name| name.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{name}.wal.");
+
+    let mut entries = match tokio::fs::read_dir(directory).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut segments = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        if let Some(segment) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|number| number.parse().ok())
+        {
+            segments.push((segment, entry.path()));
+        }
+    }
+    segments.sort_unstable_by_key(|(segment, _)| *segment);
+    Ok(segments)
+}
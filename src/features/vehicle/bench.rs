@@ -0,0 +1,238 @@
+//! Timing runs rather than checks, ignored by default since their numbers only mean something
+//! in a release build on an otherwise idle machine:
+//! `cargo test --release bench -- --ignored --nocapture --test-threads=1`
+
+use crate::{
+    features::vehicle::{
+        dashmap_repo::DashMapVehicleRepo,
+        model::{PageRequest, Vehicle, VehicleFilter, VehicleId, VehicleSortField},
+        repo::{InMemoryVehicleRepo, StoredVehicle, VehicleRepo},
+        testing::VehicleBuilder,
+    },
+    utils::sorting::{self, Direction, SortKey},
+};
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+/// Store `count` random vehicles, returning their ids
+async fn seed(repo: &dyn VehicleRepo, count: u64) -> Vec<VehicleId> {
+    let vehicles = (0..count)
+        .map(|seed| VehicleBuilder::random(seed).request())
+        .collect();
+    repo.post_vehicles(vehicles).await.expect("seed vehicles")
+}
+
+/// Point reads with every fifth operation an update, split over 1, 8 and 64 tasks, against
+/// the single-lock store and the sharded one
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "benchmark"]
+async fn point_operations_by_task_count() {
+    const OPERATIONS: usize = 64_000;
+    println!("{:<8} {:>5} {:>12}", "repo", "tasks", "ops/s");
+    for tasks in [1, 8, 64] {
+        let repos: [(&str, Arc<dyn VehicleRepo>); 2] = [
+            ("memory", Arc::new(InMemoryVehicleRepo::default())),
+            ("dashmap", Arc::new(DashMapVehicleRepo::default())),
+        ];
+        for (name, repo) in repos {
+            let ids = Arc::new(seed(repo.as_ref(), 10_000).await);
+            let started = Instant::now();
+            let workers: Vec<_> = (0..tasks)
+                .map(|task| {
+                    let (repo, ids) = (repo.clone(), ids.clone());
+                    tokio::spawn(async move {
+                        for op in (task..OPERATIONS).step_by(tasks) {
+                            let id = ids[op * 7919 % ids.len()];
+                            if op % 5 == 0 {
+                                let vehicle = Vehicle::builder().mileage_km(op as u32).request();
+                                repo.update_vehicle(id, vehicle, None, true).await.unwrap();
+                            } else {
+                                repo.get_vehicle(id).await.unwrap();
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.await.unwrap();
+            }
+            let rate = OPERATIONS as f64 / started.elapsed().as_secs_f64();
+            println!("{name:<8} {tasks:>5} {rate:>12.0}");
+        }
+    }
+}
+
+/// Names as the store used to keep them, one `String` each per vehicle, against one shared
+/// `Arc<str>` per distinct name: heap taken by the names and time to clone every vehicle, as
+/// listing the whole store does
+#[test]
+#[ignore = "benchmark"]
+fn interned_names_by_memory_and_clone_time() {
+    const VEHICLES: u64 = 500_000;
+    let owned: Vec<Vehicle> = (0..VEHICLES)
+        .map(|seed| VehicleBuilder::random(seed).build())
+        .collect();
+    let mut names: HashSet<Arc<str>> = HashSet::new();
+    let mut intern = |name: String| match names.get(name.as_str()) {
+        Some(interned) => interned.clone(),
+        None => {
+            let interned: Arc<str> = name.into();
+            names.insert(interned.clone());
+            interned
+        }
+    };
+    let interned: Vec<StoredVehicle> = owned
+        .iter()
+        .cloned()
+        .map(|vehicle| vehicle.map_names(&mut intern))
+        .collect();
+
+    let owned_bytes: usize = owned
+        .iter()
+        .map(|v| v.manufacturer.capacity() + v.model.capacity())
+        .sum::<usize>()
+        + owned.len() * 2 * size_of::<String>();
+    // Each distinct name is one allocation behind the two reference counts
+    let interned_bytes: usize = names
+        .iter()
+        .map(|name| name.len() + 2 * size_of::<usize>())
+        .sum::<usize>()
+        + interned.len() * 2 * size_of::<Arc<str>>();
+    let owned_clone = time(|| owned.clone());
+    let interned_clone = time(|| interned.clone());
+
+    println!("{:<10} {:>14} {:>12}", "names", "bytes", "clone ms");
+    println!("{:<10} {owned_bytes:>14} {owned_clone:>12.1}", "String");
+    println!(
+        "{:<10} {interned_bytes:>14} {interned_clone:>12.1}",
+        "Arc<str>"
+    );
+}
+
+/// Milliseconds `f` took, its result dropped outside the timing
+fn time<T>(f: impl FnOnce() -> T) -> f64 {
+    let started = Instant::now();
+    let result = f();
+    let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+    drop(result);
+    elapsed
+}
+
+/// Unfiltered and filtered counts and first pages taken while four writer tasks keep
+/// updating, against the single-lock store and the sharded one, whose listings walk every
+/// shard and so don't see one moment of the store
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "benchmark"]
+async fn listing_while_writing() {
+    const READS: usize = 2_000;
+    const WRITERS: usize = 4;
+    let toyotas = VehicleFilter {
+        manufacturer: Some("Toyota".to_string()),
+        ..Default::default()
+    };
+    println!("{:<8} {:>12} {:>12}", "repo", "reads/s", "writes/s");
+    let repos: [(&str, Arc<dyn VehicleRepo>); 2] = [
+        ("memory", Arc::new(InMemoryVehicleRepo::default())),
+        ("dashmap", Arc::new(DashMapVehicleRepo::default())),
+    ];
+    for (name, repo) in repos {
+        let ids = Arc::new(seed(repo.as_ref(), 10_000).await);
+        let done = Arc::new(AtomicBool::new(false));
+        let writes = Arc::new(AtomicUsize::new(0));
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let (repo, ids) = (repo.clone(), ids.clone());
+                let (done, writes) = (done.clone(), writes.clone());
+                tokio::spawn(async move {
+                    let mut op = writer;
+                    while !done.load(Ordering::Relaxed) {
+                        let id = ids[op * 7919 % ids.len()];
+                        let vehicle = Vehicle::builder()
+                            .mileage_km((op % 100_000) as u32)
+                            .request();
+                        repo.update_vehicle(id, vehicle, None, true).await.unwrap();
+                        writes.fetch_add(1, Ordering::Relaxed);
+                        op += WRITERS;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        let started = Instant::now();
+        for read in 0..READS {
+            let filter = match read % 2 {
+                0 => VehicleFilter::default(),
+                _ => toyotas.clone(),
+            };
+            repo.count(Some(&filter)).await.unwrap();
+            let page = PageRequest {
+                filter,
+                limit: 20,
+                ..Default::default()
+            };
+            repo.list(page).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+        done.store(true, Ordering::Relaxed);
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        let reads = 2.0 * READS as f64 / elapsed;
+        let writes = writes.load(Ordering::Relaxed) as f64 / elapsed;
+        println!("{name:<8} {reads:>12.0} {writes:>12.0}");
+    }
+}
+
+/// A first page of 20 out of 100,000 vehicles, in id order and sorted by year, through
+/// `list` against cloning every vehicle and paging the copy as listing used to
+#[tokio::test]
+#[ignore = "benchmark"]
+async fn paging_a_hundred_thousand_vehicles() {
+    const REQUESTS: usize = 50;
+    let repo = InMemoryVehicleRepo::default();
+    seed(&repo, 100_000).await;
+    let by_year = vec![SortKey {
+        field: VehicleSortField::Year,
+        direction: Direction::Descending,
+    }];
+    println!(
+        "{:<12} {:<8} {:>10} {:>16}",
+        "listing", "order", "ms/req", "cloned/req"
+    );
+    for (order, sort) in [("id", Vec::new()), ("year", by_year)] {
+        let page = PageRequest {
+            limit: 20,
+            sort,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let mut cloned = 0;
+        for _ in 0..REQUESTS {
+            cloned += repo.list(page.clone()).await.unwrap().items.len();
+        }
+        let windowed = started.elapsed().as_secs_f64() * 1000.0 / REQUESTS as f64;
+
+        let started = Instant::now();
+        let mut copied = 0;
+        for _ in 0..REQUESTS {
+            let mut all = repo.vehicles();
+            copied += all.len();
+            all.sort_by(|a, b| sorting::compare(&page.sort, a, b));
+            let window: Vec<Vehicle> = all.into_iter().take(page.limit).collect();
+            assert_eq!(window.len(), page.limit);
+        }
+        let whole = started.elapsed().as_secs_f64() * 1000.0 / REQUESTS as f64;
+
+        let (cloned, copied) = (cloned / REQUESTS, copied / REQUESTS);
+        println!("{:<12} {order:<8} {windowed:>10.2} {cloned:>16}", "list");
+        println!("{:<12} {order:<8} {whole:>10.2} {copied:>16}", "clone all");
+    }
+}
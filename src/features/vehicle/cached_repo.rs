@@ -0,0 +1,482 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        model::{
+            CreateVehicleRequest, PageRequest, PageResult, RestoreMode, RestoreReport, Vehicle,
+            VehicleFilter, VehicleId, VehicleRevision, VehicleSearch, VehicleStats, VehicleStatus,
+            VehicleSummary,
+        },
+        persistent_repo::SnapshotStatus,
+        repo::{ReadPath, RepoError, RepoHealth, StoreCapacity, VehicleRepo},
+    },
+};
+use crate::utils::etag::IfMatch;
+use async_trait::async_trait;
+use hashlink::LruCache;
+use metrics::counter;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// Most first pages kept per listing kind; filters vary too much for more to pay off
+const MAX_CACHED_PAGES: usize = 8;
+
+struct Entry<T> {
+    value: T,
+    stored_at: Instant,
+}
+
+impl<T: Clone> Entry<T> {
+    fn fresh(&self, ttl: Duration) -> Option<T> {
+        (self.stored_at.elapsed() < ttl).then(|| self.value.clone())
+    }
+}
+
+/// First pages of one listing kind, most recently stored last
+struct PageCache<T> {
+    pages: Vec<(PageRequest, Entry<PageResult<T>>)>,
+}
+
+impl<T: Clone> PageCache<T> {
+    fn get(&self, page: &PageRequest, ttl: Duration) -> Option<PageResult<T>> {
+        self.pages
+            .iter()
+            .find(|(request, _)| request == page)
+            .and_then(|(_, entry)| entry.fresh(ttl))
+    }
+
+    fn insert(&mut self, page: PageRequest, value: PageResult<T>) {
+        self.pages.retain(|(request, _)| *request != page);
+        if self.pages.len() >= MAX_CACHED_PAGES {
+            self.pages.remove(0);
+        }
+        let entry = Entry {
+            value,
+            stored_at: Instant::now(),
+        };
+        self.pages.push((page, entry));
+    }
+}
+
+struct CacheState {
+    vehicles: LruCache<VehicleId, Entry<Vehicle>>,
+    lists: PageCache<Vehicle>,
+    summaries: PageCache<VehicleSummary>,
+    /// Bumped by every write, so a read that started before one doesn't cache what it fetched
+    generation: u64,
+}
+
+/// Wraps any backend with an in-process LRU of vehicles by id and, when `list_ttl` is set,
+/// of the first page of recent listings. Every write through the wrapper drops the vehicles it
+/// touched and all cached pages before returning, so a read after it never sees the old data;
+/// writes that reach the backend some other way (another instance, a Redis TTL) are only
+/// picked up once the entry is older than `ttl`
+pub struct CachedRepo<R: VehicleRepo + ?Sized> {
+    inner: Arc<R>,
+    ttl: Duration,
+    list_ttl: Option<Duration>,
+    state: Mutex<CacheState>,
+}
+
+impl<R: VehicleRepo + ?Sized> CachedRepo<R> {
+    /// Keep up to `max_entries` vehicles, each for at most `ttl`
+    pub fn new(inner: Arc<R>, max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            list_ttl: None,
+            state: Mutex::new(CacheState {
+                vehicles: LruCache::new(max_entries.max(1)),
+                lists: PageCache { pages: Vec::new() },
+                summaries: PageCache { pages: Vec::new() },
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Also keep first pages (offset 0) of listings, each for at most `ttl`
+    pub fn with_list_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.list_ttl = ttl;
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run a write against the backend, then forget what it may have changed whether or not it
+    /// succeeded, since a failed write can still have partly applied
+    async fn invalidating<T>(
+        &self,
+        ids: &[VehicleId],
+        call: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        let result = call.await;
+        let mut state = self.state();
+        state.generation += 1;
+        state.lists.pages.clear();
+        state.summaries.pages.clear();
+        for id in ids {
+            state.vehicles.remove(id);
+        }
+        result
+    }
+
+    /// Like `invalidating`, for writes that can touch any number of vehicles
+    async fn invalidating_all<T>(
+        &self,
+        call: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        let result = self.invalidating(&[], call).await;
+        self.state().vehicles.clear();
+        result
+    }
+}
+
+fn record(cache: &'static str, hit: bool) {
+    let name = if hit {
+        "vehicle_cache_hits_total"
+    } else {
+        "vehicle_cache_misses_total"
+    };
+    counter!(name, "cache" => cache).increment(1);
+}
+
+#[async_trait]
+impl<R: VehicleRepo + ?Sized> VehicleRepo for CachedRepo<R> {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let generation = {
+            let mut state = self.state();
+            if let Some(vehicle) = state.vehicles.get(&id).and_then(|e| e.fresh(self.ttl)) {
+                record("vehicle", true);
+                return Ok(Some(vehicle));
+            }
+            state.generation
+        };
+        record("vehicle", false);
+        let vehicle = self.inner.get_vehicle(id).await?;
+        if let Some(vehicle) = &vehicle {
+            let mut state = self.state();
+            if state.generation == generation {
+                let entry = Entry {
+                    value: vehicle.clone(),
+                    stored_at: Instant::now(),
+                };
+                state.vehicles.insert(id, entry);
+            }
+        }
+        Ok(vehicle)
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.inner.get_vehicle_by_vin(vin).await
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.inner.get_vehicle_by_plate(plate).await
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        self.inner.exists(id).await
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        self.inner.get_vehicles_by_ids(ids).await
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let Some(ttl) = self.list_ttl.filter(|_| page.offset == 0) else {
+            return self.inner.list(page).await;
+        };
+        let generation = {
+            let state = self.state();
+            if let Some(result) = state.lists.get(&page, ttl) {
+                record("list", true);
+                return Ok(result);
+            }
+            state.generation
+        };
+        record("list", false);
+        let result = self.inner.list(page.clone()).await?;
+        let mut state = self.state();
+        if state.generation == generation {
+            state.lists.insert(page, result.clone());
+        }
+        Ok(result)
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        let Some(ttl) = self.list_ttl.filter(|_| page.offset == 0) else {
+            return self.inner.list_summaries(page).await;
+        };
+        let generation = {
+            let state = self.state();
+            let cached = state.summaries.get(&page, ttl).or_else(|| {
+                let full = state.lists.get(&page, ttl)?;
+                Some(full.map(|vehicle| VehicleSummary::from(&vehicle)))
+            });
+            if let Some(result) = cached {
+                record("list", true);
+                return Ok(result);
+            }
+            state.generation
+        };
+        record("list", false);
+        let result = self.inner.list_summaries(page.clone()).await?;
+        let mut state = self.state();
+        if state.generation == generation {
+            state.summaries.insert(page, result.clone());
+        }
+        Ok(result)
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        self.inner.get_vehicles_after(filter, cursor, limit).await
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        self.inner.search_vehicles(search, offset, limit).await
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        self.inner.count(filter).await
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        self.inner.stats(filter).await
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        self.inner.manufacturers().await
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        self.invalidating(&[], self.inner.post_vehicle(vehicle, allow_duplicate))
+            .await
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.invalidating(&[], self.inner.post_vehicles(vehicles))
+            .await
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.invalidating(&[], self.inner.post_vehicles_atomic(vehicles))
+            .await
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.invalidating(
+            &[id],
+            self.inner.update_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.invalidating(
+            &[id],
+            self.inner.upsert_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.invalidating(&[id], self.inner.delete_vehicle(id))
+            .await
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.invalidating(&[id], self.inner.soft_delete(id)).await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.invalidating(&[id], self.inner.restore(id)).await
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.invalidating(&[id], self.inner.add_tags(id, tags))
+            .await
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        self.invalidating(&[id], self.inner.remove_tag(id, tag))
+            .await
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.invalidating(&[id], self.inner.set_status(id, status, if_match))
+            .await
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        self.invalidating(ids, self.inner.delete_vehicles(ids))
+            .await
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        self.invalidating_all(self.inner.unassign_owner(owner_id))
+            .await
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        self.inner.get_history(id).await
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        self.inner.dump().await
+    }
+
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        self.invalidating_all(self.inner.restore_dump(vehicles, mode))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.inner.ping().await
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.inner.health_check().await
+    }
+
+    async fn flush(&self) -> Result<(), RepoError> {
+        self.inner.flush().await
+    }
+
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        self.inner.snapshot_status()
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        self.inner.capacity()
+    }
+
+    fn read_path(&self) -> Option<ReadPath> {
+        self.inner.read_path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::vehicle::mock_repo::MockVehicleRepo;
+
+    fn first_page() -> PageRequest {
+        PageRequest {
+            filter: VehicleFilter::default(),
+            offset: 0,
+            limit: 10,
+            sort: Vec::new(),
+        }
+    }
+
+    /// A cache over a mock, with a vehicle already read once so it is cached
+    async fn cached_vehicle() -> (Arc<MockVehicleRepo>, CachedRepo<MockVehicleRepo>, VehicleId) {
+        let inner = Arc::new(MockVehicleRepo::default());
+        let cache = CachedRepo::new(inner.clone(), 100, Duration::from_secs(3600))
+            .with_list_ttl(Some(Duration::from_secs(3600)));
+        let vehicle = Vehicle::builder().model("Corolla").request();
+        let id = cache.post_vehicle(vehicle, true).await.unwrap();
+        cache.get_vehicle(id).await.unwrap();
+        cache.list(first_page()).await.unwrap();
+
+        assert!(cache.get_vehicle(id).await.unwrap().is_some());
+        cache.list(first_page()).await.unwrap();
+        assert_eq!(
+            inner.calls_to("get_vehicle").len(),
+            1,
+            "served from the cache"
+        );
+        assert_eq!(inner.calls_to("list").len(), 1, "served from the cache");
+        (inner, cache, id)
+    }
+
+    #[tokio::test]
+    async fn an_updated_vehicle_is_never_read_stale() {
+        let (inner, cache, id) = cached_vehicle().await;
+
+        let yaris = Vehicle::builder().model("Yaris").request();
+        cache.update_vehicle(id, yaris, None, false).await.unwrap();
+
+        let vehicle = cache.get_vehicle(id).await.unwrap().unwrap();
+        assert_eq!(vehicle.model, "Yaris");
+        let page = cache.list(first_page()).await.unwrap();
+        assert_eq!(page.items[0].model, "Yaris");
+        assert_eq!(inner.calls_to("get_vehicle").len(), 2);
+        assert_eq!(inner.calls_to("list").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_deleted_vehicle_is_never_read_stale() {
+        let (_inner, cache, id) = cached_vehicle().await;
+
+        cache.delete_vehicle(id).await.unwrap();
+
+        assert!(cache.get_vehicle(id).await.unwrap().is_none());
+        assert_eq!(cache.list(first_page()).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn a_soft_deleted_vehicle_is_never_read_stale() {
+        let (_inner, cache, id) = cached_vehicle().await;
+
+        cache.soft_delete(id).await.unwrap();
+
+        assert!(cache.get_vehicle(id).await.unwrap().is_none());
+        assert_eq!(cache.list(first_page()).await.unwrap().total, 0);
+    }
+}
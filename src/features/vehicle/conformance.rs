@@ -0,0 +1,602 @@
+//! Behaviour every `VehicleRepo` has to share, run against each backend by `conformance_tests!`.
+//! Cases only count vehicles they created themselves, under a manufacturer no other case
+//! uses, so backends behind a shared server (`CONFORMANCE_DATABASE_URL`, `CONFORMANCE_REDIS_URL`
+//! and `CONFORMANCE_MONGODB_URI`, skipped when unset) can run them in parallel
+
+use crate::{
+    features::vehicle::{
+        dashmap_repo::DashMapVehicleRepo,
+        model::{
+            FuelType, PageRequest, Vehicle, VehicleFilter, VehicleId, VehicleSortField,
+            VehicleStatus,
+        },
+        repo::{InMemoryVehicleRepo, RepoError, VehicleRepo},
+    },
+    utils::{
+        sorting::{Direction, SortKey},
+        validator::validate_vin,
+    },
+};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// A file in the temp dir, removed with its SQLite side files when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    fn new(extension: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("conformance-{}.{extension}", Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// A repo under test plus whatever has to outlive it
+struct Backend {
+    repo: Box<dyn VehicleRepo>,
+    _file: Option<TempFile>,
+}
+
+impl Backend {
+    fn new(repo: impl VehicleRepo + 'static) -> Self {
+        Self {
+            repo: Box::new(repo),
+            _file: None,
+        }
+    }
+}
+
+async fn in_memory() -> Option<Backend> {
+    Some(Backend::new(InMemoryVehicleRepo::with_history_limit(10)))
+}
+
+async fn dashmap() -> Option<Backend> {
+    Some(Backend::new(DashMapVehicleRepo::with_history_limit(10)))
+}
+
+#[cfg(feature = "sqlite")]
+async fn sqlite() -> Option<Backend> {
+    use crate::features::vehicle::sqlite_repo::SqliteVehicleRepo;
+
+    let file = TempFile::new("db");
+    let repo = SqliteVehicleRepo::connect(&file.0.to_string_lossy(), 10)
+        .await
+        .expect("temp SQLite database opens");
+    Some(Backend {
+        repo: Box::new(repo),
+        _file: Some(file),
+    })
+}
+
+#[cfg(feature = "postgres")]
+async fn postgres() -> Option<Backend> {
+    use crate::features::vehicle::postgres_repo::PostgresVehicleRepo;
+
+    let url = std::env::var("CONFORMANCE_DATABASE_URL").ok()?;
+    let repo = PostgresVehicleRepo::connect(&url, 10)
+        .await
+        .expect("CONFORMANCE_DATABASE_URL is reachable");
+    Some(Backend::new(repo))
+}
+
+#[cfg(feature = "redis")]
+async fn redis() -> Option<Backend> {
+    use crate::features::vehicle::redis_repo::RedisVehicleRepo;
+
+    let url = std::env::var("CONFORMANCE_REDIS_URL").ok()?;
+    let repo = RedisVehicleRepo::connect(&url, 10)
+        .await
+        .expect("CONFORMANCE_REDIS_URL is reachable");
+    Some(Backend::new(repo))
+}
+
+#[cfg(feature = "mongodb")]
+async fn mongodb() -> Option<Backend> {
+    use crate::features::vehicle::mongo_repo::MongoVehicleRepo;
+
+    let uri = std::env::var("CONFORMANCE_MONGODB_URI").ok()?;
+    let repo = MongoVehicleRepo::connect(&uri, 10)
+        .await
+        .expect("CONFORMANCE_MONGODB_URI is reachable");
+    Some(Backend::new(repo))
+}
+
+/// A manufacturer no other case, or earlier run against the same server, has used
+fn unique_manufacturer() -> String {
+    format!("Make{}", &Uuid::new_v4().simple().to_string()[..12])
+}
+
+/// A plate no other case has used, in the shape of the default pattern
+fn unique_plate() -> String {
+    Uuid::new_v4().simple().to_string()[..10].to_uppercase()
+}
+
+/// A VIN no other case has used, with whichever check digit makes it valid
+fn unique_vin() -> String {
+    let random = Uuid::new_v4().simple().to_string().to_uppercase();
+    "0123456789X"
+        .chars()
+        .map(|check| format!("{}{check}{}", &random[..8], &random[9..17]))
+        .find(|vin| validate_vin(vin).is_ok())
+        .expect("one check digit fits")
+}
+
+fn only(manufacturer: &str) -> VehicleFilter {
+    VehicleFilter {
+        manufacturer: Some(manufacturer.to_string()),
+        ..Default::default()
+    }
+}
+
+async fn create(repo: &dyn VehicleRepo, manufacturer: &str, model: &str) -> VehicleId {
+    let vehicle = Vehicle::builder()
+        .manufacturer(manufacturer)
+        .model(model)
+        .mileage_km(12_000)
+        .request();
+    repo.post_vehicle(vehicle, true)
+        .await
+        .expect("vehicle is created")
+}
+
+async fn get_returns_what_was_created(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let plate = unique_plate();
+    let vehicle = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .model("Corolla")
+        .year(2019)
+        .mileage_km(42_000)
+        .license_plate(plate.to_lowercase())
+        .tag("Fleet")
+        .request();
+    let id = repo.post_vehicle(vehicle, false).await.unwrap();
+
+    let stored = repo
+        .get_vehicle(id)
+        .await
+        .unwrap()
+        .expect("vehicle is found");
+    assert_eq!(stored.id, id);
+    assert_eq!(stored.manufacturer, manufacturer);
+    assert_eq!(stored.model, "Corolla");
+    assert_eq!(stored.year, 2019);
+    assert_eq!(stored.mileage_km, Some(42_000));
+    assert_eq!(stored.license_plate.as_deref(), Some(plate.as_str()));
+    assert_eq!(stored.tags, ["fleet"]);
+    assert_eq!(stored.version, 1);
+    assert_eq!(stored.created_at, stored.updated_at);
+    assert!(stored.deleted_at.is_none());
+}
+
+async fn get_unknown_id_is_none(repo: &dyn VehicleRepo) {
+    assert!(
+        repo.get_vehicle(VehicleId::generate())
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+async fn create_rejects_duplicates_unless_allowed(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let existing_id = create(repo, &manufacturer, "Corolla").await;
+    let duplicate = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .model("Corolla")
+        .mileage_km(12_000)
+        .request();
+
+    let error = repo
+        .post_vehicle(duplicate.clone(), false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::Duplicate { existing_id: id } if id == existing_id));
+    assert!(repo.post_vehicle(duplicate, true).await.is_ok());
+}
+
+async fn create_rejects_a_taken_plate(repo: &dyn VehicleRepo) {
+    let plate = unique_plate();
+    let first = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .license_plate(&plate)
+        .request();
+    let existing_id = repo.post_vehicle(first, true).await.unwrap();
+    let second = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .license_plate(&plate)
+        .request();
+
+    let error = repo.post_vehicle(second, true).await.unwrap_err();
+    assert!(matches!(error, RepoError::PlateTaken { existing_id: id } if id == existing_id));
+}
+
+async fn list_pages_through_matching_vehicles(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let mut ids = Vec::new();
+    for model in ["Corolla", "Yaris", "Prius"] {
+        ids.push(create(repo, &manufacturer, model).await);
+    }
+    create(repo, &unique_manufacturer(), "Golf").await;
+
+    let first = repo
+        .list(PageRequest {
+            filter: only(&manufacturer),
+            offset: 0,
+            limit: 2,
+            sort: Vec::new(),
+        })
+        .await
+        .unwrap();
+    let rest = repo
+        .list(PageRequest {
+            filter: only(&manufacturer),
+            offset: 2,
+            limit: 2,
+            sort: Vec::new(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!((first.total, rest.total), (3, 3));
+    let listed: Vec<VehicleId> = first
+        .items
+        .iter()
+        .chain(&rest.items)
+        .map(|v| v.id)
+        .collect();
+    assert_eq!(listed, ids, "pages are in id order without a sort");
+}
+
+async fn list_hides_soft_deleted_vehicles(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let kept = create(repo, &manufacturer, "Corolla").await;
+    let deleted = create(repo, &manufacturer, "Yaris").await;
+    repo.soft_delete(deleted).await.unwrap();
+
+    let page = PageRequest {
+        filter: only(&manufacturer),
+        limit: 10,
+        ..Default::default()
+    };
+    let listed = repo.list(page.clone()).await.unwrap();
+    assert_eq!(
+        listed.items.iter().map(|v| v.id).collect::<Vec<_>>(),
+        [kept]
+    );
+
+    let mut with_deleted = page;
+    with_deleted.filter.include_deleted = Some(true);
+    assert_eq!(repo.list(with_deleted).await.unwrap().total, 2);
+}
+
+/// Four vehicles of one manufacturer, from 2010 to 2022, petrol but for the 2020 one
+async fn create_model_years(repo: &dyn VehicleRepo, manufacturer: &str) -> Vec<VehicleId> {
+    let mut ids = Vec::new();
+    for (year, fuel_type) in [
+        (2010, FuelType::Petrol),
+        (2015, FuelType::Petrol),
+        (2020, FuelType::Electric),
+        (2022, FuelType::Petrol),
+    ] {
+        let vehicle = Vehicle::builder()
+            .manufacturer(manufacturer)
+            .model(format!("Model {year}"))
+            .year(year)
+            .fuel_type(fuel_type)
+            .request();
+        ids.push(repo.post_vehicle(vehicle, true).await.unwrap());
+    }
+    ids
+}
+
+async fn list_filters_on_every_given_field(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let ids = create_model_years(repo, &manufacturer).await;
+    let listed = |filter: VehicleFilter| async move {
+        let page = repo
+            .list(PageRequest {
+                filter,
+                limit: 10,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, page.items.len());
+        page.items.into_iter().map(|v| v.id).collect::<Vec<_>>()
+    };
+
+    let in_range = VehicleFilter {
+        year_min: Some(2014),
+        year_max: Some(2020),
+        ..only(&manufacturer)
+    };
+    assert_eq!(listed(in_range.clone()).await, ids[1..3]);
+    let petrol_in_range = VehicleFilter {
+        fuel_type: Some(FuelType::Petrol),
+        ..in_range
+    };
+    assert_eq!(listed(petrol_in_range).await, [ids[1]]);
+    let lowercase = only(&manufacturer.to_lowercase());
+    assert_eq!(listed(lowercase).await, ids);
+}
+
+async fn list_sorts_before_paging(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let ids = create_model_years(repo, &manufacturer).await;
+
+    let page = repo
+        .list(PageRequest {
+            filter: only(&manufacturer),
+            offset: 1,
+            limit: 2,
+            sort: vec![SortKey {
+                field: VehicleSortField::Year,
+                direction: Direction::Descending,
+            }],
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(page.total, 4);
+    let years: Vec<u16> = page.items.iter().map(|v| v.year).collect();
+    assert_eq!(years, [2020, 2015]);
+    assert_eq!(page.items[0].id, ids[2]);
+}
+
+async fn exists_reports_stored_vehicles_only(repo: &dyn VehicleRepo) {
+    let id = create(repo, &unique_manufacturer(), "Corolla").await;
+
+    assert!(repo.exists(id).await.unwrap());
+    assert!(!repo.exists(VehicleId::generate()).await.unwrap());
+    repo.soft_delete(id).await.unwrap();
+    assert!(!repo.exists(id).await.unwrap());
+    repo.restore(id).await.unwrap();
+    assert!(repo.exists(id).await.unwrap());
+    repo.delete_vehicle(id).await.unwrap();
+    assert!(!repo.exists(id).await.unwrap());
+}
+
+async fn count_agrees_with_list_with_and_without_filters(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let ids = create_model_years(repo, &manufacturer).await;
+    repo.soft_delete(ids[0]).await.unwrap();
+
+    // Other cases may share the backend, so the unfiltered count is only bounded below
+    let everything = repo.count(None).await.unwrap();
+    assert!(everything >= 3, "{everything}");
+    assert_eq!(repo.count(Some(&only(&manufacturer))).await.unwrap(), 3);
+    let with_deleted = VehicleFilter {
+        include_deleted: Some(true),
+        ..only(&manufacturer)
+    };
+    assert_eq!(repo.count(Some(&with_deleted)).await.unwrap(), 4);
+    let electric = VehicleFilter {
+        fuel_type: Some(FuelType::Electric),
+        ..only(&manufacturer)
+    };
+    assert_eq!(repo.count(Some(&electric)).await.unwrap(), 1);
+    let page = PageRequest {
+        filter: only(&manufacturer),
+        limit: 1,
+        ..Default::default()
+    };
+    assert_eq!(repo.list(page).await.unwrap().total, 3);
+}
+
+async fn update_keeps_id_and_created_at(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let id = create(repo, &manufacturer, "Corolla").await;
+    let created = repo.get_vehicle(id).await.unwrap().unwrap();
+
+    let replacement = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .model("Yaris")
+        .year(2021)
+        .mileage_km(15_000)
+        .request();
+    let updated = repo
+        .update_vehicle(id, replacement, None, false)
+        .await
+        .unwrap();
+
+    assert_eq!(updated.id, id);
+    assert_eq!(updated.model, "Yaris");
+    assert_eq!(updated.year, 2021);
+    assert_eq!(updated.version, 2);
+    assert_eq!(updated.created_at, created.created_at);
+    assert!(updated.updated_at >= created.updated_at);
+    let stored = repo.get_vehicle(id).await.unwrap().unwrap();
+    assert_eq!(stored.model, "Yaris");
+    assert_eq!(stored.version, 2);
+}
+
+async fn update_unknown_id_is_not_found(repo: &dyn VehicleRepo) {
+    let vehicle = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .request();
+    let error = repo
+        .update_vehicle(VehicleId::generate(), vehicle, None, false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+}
+
+async fn update_rejects_lower_mileage_unless_correcting(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let id = create(repo, &manufacturer, "Corolla").await;
+    let rolled_back = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .mileage_km(1_000)
+        .request();
+
+    let error = repo
+        .update_vehicle(id, rolled_back.clone(), None, false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::MileageDecrease { .. }));
+    let corrected = repo.update_vehicle(id, rolled_back, None, true).await;
+    assert_eq!(corrected.unwrap().mileage_km, Some(1_000));
+}
+
+async fn delete_removes_the_vehicle_and_frees_its_plate(repo: &dyn VehicleRepo) {
+    let plate = unique_plate();
+    let vehicle = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .license_plate(&plate)
+        .request();
+    let id = repo.post_vehicle(vehicle, true).await.unwrap();
+
+    let deleted = repo.delete_vehicle(id).await.unwrap();
+    assert_eq!(deleted.map(|v| v.id), Some(id));
+    assert!(repo.get_vehicle(id).await.unwrap().is_none());
+    assert!(repo.get_vehicle_by_plate(&plate).await.unwrap().is_none());
+    assert!(repo.delete_vehicle(id).await.unwrap().is_none());
+
+    let reuse = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .license_plate(&plate)
+        .request();
+    assert!(repo.post_vehicle(reuse, true).await.is_ok());
+}
+
+async fn soft_deleted_vehicles_are_gone_until_restored(repo: &dyn VehicleRepo) {
+    let manufacturer = unique_manufacturer();
+    let vin = unique_vin();
+    let vehicle = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .model("Corolla")
+        .vin(&vin)
+        .mileage_km(12_000)
+        .request();
+    let id = repo.post_vehicle(vehicle, true).await.unwrap();
+    let kept = create(repo, &manufacturer, "Auris").await;
+    repo.soft_delete(id).await.unwrap();
+    let replacement = Vehicle::builder()
+        .manufacturer(&manufacturer)
+        .model("Yaris")
+        .mileage_km(15_000)
+        .request();
+
+    assert!(repo.get_vehicle(id).await.unwrap().is_none());
+    assert!(repo.get_vehicle_by_vin(&vin).await.unwrap().is_none());
+    assert!(!repo.exists(id).await.unwrap());
+    let (found, missing) = repo.get_vehicles_by_ids(&[id, kept]).await.unwrap();
+    assert_eq!(found.iter().map(|v| v.id).collect::<Vec<_>>(), [kept]);
+    assert_eq!(missing, [id]);
+    assert!(repo.get_history(id).await.unwrap().is_none());
+    let error = repo
+        .update_vehicle(id, replacement.clone(), None, false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+    let error = repo
+        .upsert_vehicle(id, replacement.clone(), None, false)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+
+    let restored = repo.restore(id).await.unwrap();
+    assert_eq!(restored.model, "Corolla");
+    assert!(restored.deleted_at.is_none());
+    assert!(repo.get_vehicle(id).await.unwrap().is_some());
+    assert_eq!(
+        repo.get_vehicle_by_vin(&vin).await.unwrap().map(|v| v.id),
+        Some(id)
+    );
+    let (found, _) = repo.get_vehicles_by_ids(&[id]).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(repo.get_history(id).await.unwrap().is_some());
+    let (updated, created) = repo
+        .upsert_vehicle(id, replacement, None, false)
+        .await
+        .unwrap();
+    assert!(!created);
+    assert_eq!(updated.model, "Yaris");
+}
+
+async fn soft_deleted_vehicles_cannot_be_tagged_or_have_their_status_changed(
+    repo: &dyn VehicleRepo,
+) {
+    let vehicle = Vehicle::builder()
+        .manufacturer(unique_manufacturer())
+        .model("Corolla")
+        .tag("fleet")
+        .request();
+    let id = repo.post_vehicle(vehicle, true).await.unwrap();
+    let deleted = repo.soft_delete(id).await.unwrap().unwrap();
+
+    let error = repo.add_tags(id, vec!["lease".into()]).await.unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+    let error = repo.remove_tag(id, "fleet").await.unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+    let error = repo
+        .set_status(id, VehicleStatus::Sold, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RepoError::NotFound));
+
+    let restored = repo.restore(id).await.unwrap();
+    assert_eq!(restored.tags, ["fleet"]);
+    assert_eq!(restored.status, VehicleStatus::Available);
+    assert_eq!(restored.version, deleted.version + 1);
+}
+
+/// One `#[tokio::test]` per case for each backend, e.g. `conformance::sqlite::update_…`;
+/// a backend whose constructor returns `None` isn't configured and its cases pass as skipped
+macro_rules! conformance_tests {
+    ($($backend:ident),* $(,)?) => {
+        $(
+            mod $backend {
+                conformance_tests!(@cases $backend;
+                    get_returns_what_was_created,
+                    get_unknown_id_is_none,
+                    create_rejects_duplicates_unless_allowed,
+                    create_rejects_a_taken_plate,
+                    list_pages_through_matching_vehicles,
+                    list_hides_soft_deleted_vehicles,
+                    list_filters_on_every_given_field,
+                    list_sorts_before_paging,
+                    exists_reports_stored_vehicles_only,
+                    count_agrees_with_list_with_and_without_filters,
+                    update_keeps_id_and_created_at,
+                    update_unknown_id_is_not_found,
+                    update_rejects_lower_mileage_unless_correcting,
+                    delete_removes_the_vehicle_and_frees_its_plate,
+                    soft_deleted_vehicles_are_gone_until_restored,
+                    soft_deleted_vehicles_cannot_be_tagged_or_have_their_status_changed,
+                );
+            }
+        )*
+    };
+    (@cases $backend:ident; $($case:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $case() {
+                match super::$backend().await {
+                    Some(backend) => super::$case(backend.repo.as_ref()).await,
+                    None => eprintln!("{} is not configured, skipped", stringify!($backend)),
+                }
+            }
+        )*
+    };
+}
+
+conformance_tests!(in_memory, dashmap);
+#[cfg(feature = "sqlite")]
+conformance_tests!(sqlite);
+#[cfg(feature = "postgres")]
+conformance_tests!(postgres);
+#[cfg(feature = "redis")]
+conformance_tests!(redis);
+#[cfg(feature = "mongodb")]
+conformance_tests!(mongodb);
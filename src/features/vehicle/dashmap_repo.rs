@@ -0,0 +1,1158 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        manufacturer::ManufacturerAliases,
+        model::{
+            ChangeType, CreateVehicleRequest, MAX_TAGS, PageRequest, PageResult, RestoreMode,
+            RestoreReport, Vehicle, VehicleFilter, VehicleId, VehicleRevision, VehicleSearch,
+            VehicleStats, VehicleStatus, VehicleSummary, normalise_color, normalise_plate,
+            normalise_tags, normalise_vin,
+        },
+        repo::{
+            CapacityPolicy, RepoError, RepoHealth, StoreCapacity, VehicleIdentity, VehicleRepo,
+            check_mileage, check_not_deleted, collect_stats, dump_conflicts, identity,
+        },
+    },
+};
+use crate::utils::{etag::IfMatch, sorting};
+use async_trait::async_trait;
+use chrono::Utc;
+use dashmap::{DashMap, mapref::entry::Entry};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use tracing::{info, warn};
+
+/// Vehicle repo without a global lock: vehicles and their VIN/plate indexes live in sharded
+/// maps, so writers only contend when they touch the same shard.
+///
+/// Guards are always taken vehicle first, index second and never the other way round, so
+/// nested access cannot deadlock. Listings, counts and stats visit the shards one at a time
+/// and are only weakly consistent with writes running at the same moment; duplicate detection
+/// is best-effort for the same reason, while VIN and plate uniqueness are enforced by the
+/// indexes.
+///
+/// Every call also holds `gate` shared, which costs readers and writers no waiting on each
+/// other. The few calls that have to see or change all shards at once hold it exclusively:
+/// atomic batches, restoring a dump and, while a capacity is set, anything that may add a
+/// vehicle, since the count it checks must not move underneath it.
+#[derive(Default)]
+pub struct DashMapVehicleRepo {
+    vehicles: DashMap<VehicleId, Vehicle>,
+    /// Every stored vehicle by manufacturer/model/year, soft-deleted ones included
+    identities: DashMap<VehicleIdentity, BTreeSet<VehicleId>>,
+    /// Held by every stored vehicle, soft-deleted ones included
+    vins: DashMap<String, VehicleId>,
+    /// Held by live vehicles only; soft deletes give the plate up and restores claim it back
+    plates: DashMap<String, VehicleId>,
+    history: DashMap<VehicleId, VecDeque<VehicleRevision>>,
+    history_limit: usize,
+    /// Most vehicles held at once, if limited
+    capacity: Option<usize>,
+    capacity_policy: CapacityPolicy,
+    aliases: ManufacturerAliases,
+    gate: RwLock<()>,
+}
+
+/// Claim `key` in `index` for `id`, or report the vehicle already holding it
+fn claim(
+    index: &DashMap<String, VehicleId>,
+    key: Option<&str>,
+    id: VehicleId,
+) -> Result<(), VehicleId> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+    match index.entry(key.to_string()) {
+        Entry::Occupied(holder) if *holder.get() != id => Err(*holder.get()),
+        Entry::Occupied(_) => Ok(()),
+        Entry::Vacant(slot) => {
+            slot.insert(id);
+            Ok(())
+        }
+    }
+}
+
+/// Give `key` up, unless another vehicle has claimed it meanwhile
+fn release(index: &DashMap<String, VehicleId>, key: Option<&str>, id: VehicleId) {
+    if let Some(key) = key {
+        index.remove_if(key, |_, holder| *holder == id);
+    }
+}
+
+impl DashMapVehicleRepo {
+    /// Keep up to `history_limit` revisions per vehicle; 0 disables history
+    pub fn with_history_limit(history_limit: usize) -> Self {
+        Self {
+            history_limit,
+            ..Default::default()
+        }
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Split every map into `shards` independently locked shards, rounded up to a power of two
+    /// and at least 2 as DashMap requires; anything already stored is dropped, so call it before
+    /// the first write
+    pub fn with_shards(self, shards: usize) -> Self {
+        let shards = shards.max(2).next_power_of_two();
+        Self {
+            vehicles: DashMap::with_shard_amount(shards),
+            identities: DashMap::with_shard_amount(shards),
+            vins: DashMap::with_shard_amount(shards),
+            plates: DashMap::with_shard_amount(shards),
+            history: DashMap::with_shard_amount(shards),
+            ..self
+        }
+    }
+
+    /// Hold at most `limit` vehicles, handling new ones past it according to `policy`
+    pub fn with_capacity(self, limit: Option<usize>, policy: CapacityPolicy) -> Self {
+        Self {
+            capacity: limit,
+            capacity_policy: policy,
+            ..self
+        }
+    }
+
+    /// The gate as every call takes it. A call that panicked poisons it, but the gate guards no
+    /// data of its own, so the poison is cleared instead
+    fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        self.gate.read().unwrap_or_else(|poisoned| {
+            warn!("Vehicle store gate was poisoned by a panicking call, recovering");
+            self.gate.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// The gate as calls that must see or change every shard at once take it
+    fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.gate.write().unwrap_or_else(|poisoned| {
+            warn!("Vehicle store gate was poisoned by a panicking call, recovering");
+            self.gate.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// The gate for a call that may add vehicles: exclusive while a capacity is set, shared
+    /// otherwise
+    fn gate_for_insert(
+        &self,
+    ) -> (
+        Option<RwLockReadGuard<'_, ()>>,
+        Option<RwLockWriteGuard<'_, ()>>,
+    ) {
+        match self.capacity {
+            Some(_) => (None, Some(self.exclusive())),
+            None => (Some(self.shared()), None),
+        }
+    }
+
+    /// Refuse `incoming` new vehicles that can't fit, now or, under `EvictOldest`, even after
+    /// evicting everything else; callers hold the gate exclusively
+    fn check_capacity(&self, incoming: usize) -> Result<(), RepoError> {
+        let Some(limit) = self.capacity else {
+            return Ok(());
+        };
+        let needed = match self.capacity_policy {
+            CapacityPolicy::Reject => self.vehicles.len() + incoming,
+            CapacityPolicy::EvictOldest => incoming,
+        };
+        if needed > limit {
+            return Err(RepoError::CapacityExceeded { limit });
+        }
+        Ok(())
+    }
+
+    /// Under `EvictOldest`, remove the oldest vehicles until `incoming` more fit; callers hold
+    /// the gate exclusively and no vehicle guard, since evicting locks other vehicles
+    fn evict_for(&self, incoming: usize) {
+        let Some(limit) = self.capacity else {
+            return;
+        };
+        if self.capacity_policy != CapacityPolicy::EvictOldest {
+            return;
+        }
+        let excess = (self.vehicles.len() + incoming).saturating_sub(limit);
+        if excess == 0 {
+            return;
+        }
+        let mut ids: Vec<VehicleId> = self.vehicles.iter().map(|v| v.id).collect();
+        ids.sort_unstable();
+        for oldest in ids.into_iter().take(excess) {
+            self.remove(oldest);
+            info!(
+                "Evicted vehicle {} to stay within the capacity of {} vehicles",
+                oldest, limit
+            );
+        }
+    }
+
+    /// Under a capacity, refuse or make room for a vehicle about to be created with this id,
+    /// VIN and plate. Conflicts are checked first, so nothing is evicted for a vehicle that
+    /// is then refused; callers hold the gate exclusively and no vehicle guard
+    fn make_room(
+        &self,
+        id: VehicleId,
+        vin: Option<&str>,
+        plate: Option<&str>,
+    ) -> Result<(), RepoError> {
+        if self.capacity.is_none() || self.vehicles.contains_key(&id) {
+            return Ok(());
+        }
+        if let Some(existing_id) = vin.and_then(|vin| self.vins.get(vin).map(|holder| *holder)) {
+            return Err(RepoError::VinTaken { existing_id });
+        }
+        if let Some(existing_id) =
+            plate.and_then(|plate| self.plates.get(plate).map(|holder| *holder))
+        {
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        self.check_capacity(1)?;
+        self.evict_for(1);
+        Ok(())
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    /// Snapshot `vehicle` into its bounded revision history
+    fn record(&self, vehicle: &Vehicle, change_type: ChangeType) {
+        if self.history_limit == 0 {
+            return;
+        }
+        let mut revisions = self.history.entry(vehicle.id).or_default();
+        if revisions.len() == self.history_limit {
+            revisions.pop_front();
+        }
+        revisions.push_back(VehicleRevision {
+            version: vehicle.version,
+            changed_at: Utc::now(),
+            change_type,
+            snapshot: vehicle.clone().into(),
+        });
+    }
+
+    /// Copies of the vehicles matching `filter`, in id order
+    fn matching(&self, filter: &VehicleFilter) -> Vec<Vehicle> {
+        let filter = self.canonical_filter(filter);
+        let mut matching: Vec<Vehicle> = self
+            .vehicles
+            .iter()
+            .filter(|v| filter.matches(v.value()))
+            .map(|v| v.value().clone())
+            .collect();
+        matching.sort_by_key(|v| v.id);
+        matching
+    }
+
+    /// Ids of the vehicles matching `filter`, in id order, without cloning any of them
+    fn matching_ids(&self, filter: &VehicleFilter) -> Vec<VehicleId> {
+        let filter = self.canonical_filter(filter);
+        let mut ids: Vec<VehicleId> = self
+            .vehicles
+            .iter()
+            .filter(|v| filter.matches(v.value()))
+            .map(|v| v.id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Copies of the vehicles with these ids, skipping any removed since they were looked up
+    fn fetch(&self, ids: &[VehicleId]) -> Vec<Vehicle> {
+        ids.iter()
+            .filter_map(|id| self.vehicles.get(id).map(|v| v.value().clone()))
+            .collect()
+    }
+
+    /// Filters name manufacturers the way clients do, so resolve aliases like stored values
+    fn canonical_filter(&self, filter: &VehicleFilter) -> VehicleFilter {
+        VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        }
+    }
+
+    /// First live vehicle sharing the manufacturer/model/year of `vehicle`
+    fn find_duplicate(&self, vehicle: &CreateVehicleRequest) -> Option<VehicleId> {
+        let manufacturer = self.aliases.canonical(&vehicle.manufacturer);
+        let key = identity(&manufacturer, &vehicle.model, vehicle.year);
+        // Copied out so the index guard is gone before any vehicle is locked
+        let candidates = self.identities.get(&key)?.clone();
+        candidates.into_iter().find(|id| {
+            self.vehicles
+                .get(id)
+                .is_some_and(|v| v.deleted_at.is_none())
+        })
+    }
+
+    /// Whether `id` is stored and not soft-deleted
+    fn is_live(&self, id: VehicleId) -> bool {
+        self.vehicles
+            .get(&id)
+            .is_some_and(|v| v.deleted_at.is_none())
+    }
+
+    /// A copy of `id` unless it is missing or soft-deleted
+    fn live(&self, id: VehicleId) -> Option<Vehicle> {
+        self.vehicles
+            .get(&id)
+            .filter(|v| v.deleted_at.is_none())
+            .map(|v| v.clone())
+    }
+
+    /// File `vehicle` under its manufacturer/model/year, moving it from `old`'s when it changed
+    fn index_identity(&self, vehicle: &Vehicle, old: Option<&Vehicle>) {
+        let key = identity(&vehicle.manufacturer, &vehicle.model, vehicle.year);
+        if let Some(old) = old {
+            let old_key = identity(&old.manufacturer, &old.model, old.year);
+            if old_key == key {
+                return;
+            }
+            self.unindex_identity(old_key, old.id);
+        }
+        self.identities.entry(key).or_default().insert(vehicle.id);
+    }
+
+    /// Drop `id` from the vehicles filed under `key`, and the key once nobody is left
+    fn unindex_identity(&self, key: VehicleIdentity, id: VehicleId) {
+        if let Some(mut ids) = self.identities.get_mut(&key) {
+            ids.remove(&id);
+        }
+        self.identities.remove_if(&key, |_, ids| ids.is_empty());
+    }
+
+    /// Remove `id` along with its keys and history, handing back what was stored
+    fn remove(&self, id: VehicleId) -> Option<Vehicle> {
+        let (_, removed) = self.vehicles.remove(&id)?;
+        release(&self.vins, removed.vin.as_deref(), id);
+        release(&self.plates, removed.license_plate.as_deref(), id);
+        self.unindex_identity(
+            identity(&removed.manufacturer, &removed.model, removed.year),
+            id,
+        );
+        self.history.remove(&id);
+        Some(removed)
+    }
+
+    /// Another vehicle, by id, VIN or live plate, already holding what `vehicle` needs
+    fn find_holder(&self, vehicle: &Vehicle) -> Option<(&'static str, VehicleId)> {
+        if self.vehicles.contains_key(&vehicle.id) {
+            return Some(("id", vehicle.id));
+        }
+        if let Some(holder) = vehicle.vin.as_deref().and_then(|vin| self.vins.get(vin)) {
+            return Some(("vin", *holder));
+        }
+        if vehicle.deleted_at.is_some() {
+            return None;
+        }
+        vehicle
+            .license_plate
+            .as_deref()
+            .and_then(|plate| self.plates.get(plate))
+            .map(|holder| ("licensePlate", *holder))
+    }
+
+    /// Put a dumped vehicle back as it was; callers hold the gate exclusively and have already
+    /// checked that nothing it claims is taken
+    fn insert_dumped(&self, vehicle: Vehicle) -> Result<(), RepoError> {
+        self.claim_keys(&vehicle)?;
+        self.index_identity(&vehicle, None);
+        self.vehicles.insert(vehicle.id, vehicle);
+        Ok(())
+    }
+
+    /// Claim the VIN and, for live vehicles, the plate of `vehicle`; nothing stays claimed on
+    /// failure
+    fn claim_keys(&self, vehicle: &Vehicle) -> Result<(), RepoError> {
+        let vin_was_held = vehicle.vin.as_deref().is_some_and(|vin| {
+            self.vins
+                .get(vin)
+                .is_some_and(|holder| *holder == vehicle.id)
+        });
+        claim(&self.vins, vehicle.vin.as_deref(), vehicle.id)
+            .map_err(|existing_id| RepoError::VinTaken { existing_id })?;
+        if vehicle.deleted_at.is_none()
+            && let Err(existing_id) =
+                claim(&self.plates, vehicle.license_plate.as_deref(), vehicle.id)
+        {
+            if !vin_was_held {
+                release(&self.vins, vehicle.vin.as_deref(), vehicle.id);
+            }
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        Ok(())
+    }
+
+    /// Give up whatever `old` held that `new` no longer does
+    fn release_stale_keys(&self, old: &Vehicle, new: &Vehicle) {
+        if old.vin != new.vin {
+            release(&self.vins, old.vin.as_deref(), old.id);
+        }
+        if old.license_plate != new.license_plate || new.deleted_at.is_some() {
+            release(&self.plates, old.license_plate.as_deref(), old.id);
+        }
+    }
+
+    /// Replace the vehicle with this id, creating it when `create` is set; the flag is true
+    /// when it was created
+    fn write(
+        &self,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+        create: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let manufacturer_raw = self.canonicalise(&mut vehicle);
+        let vin = normalise_vin(vehicle.vin.take());
+        let license_plate = normalise_plate(vehicle.license_plate.take());
+        if create {
+            self.make_room(id, vin.as_deref(), license_plate.as_deref())?;
+        }
+        let entry = self.vehicles.entry(id);
+        let existing = match &entry {
+            Entry::Occupied(stored) => Some(stored.get().clone()),
+            Entry::Vacant(_) if create => None,
+            Entry::Vacant(_) => return Err(RepoError::NotFound),
+        };
+        check_not_deleted(existing.as_ref())?;
+        // If-Match can only be satisfied by a vehicle that already exists
+        if let Some(if_match) = if_match
+            && !existing
+                .as_ref()
+                .is_some_and(|v| if_match.matches(&v.etag()))
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: existing.map(|v| v.version),
+            });
+        }
+        check_mileage(existing.as_ref(), vehicle.mileage_km, correction)?;
+        let created = existing.is_none();
+        let now = Utc::now();
+        let stored = Vehicle {
+            id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin,
+            mileage_km: vehicle.mileage_km,
+            color: normalise_color(vehicle.color),
+            license_plate,
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: normalise_tags(vehicle.tags),
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: existing
+                .as_ref()
+                .map_or_else(VehicleStatus::default, |v| v.status),
+            created_at: existing.as_ref().map_or(now, |v| v.created_at),
+            updated_at: now,
+            deleted_at: existing.as_ref().and_then(|v| v.deleted_at),
+            expires_at: None,
+            version: existing.as_ref().map_or(1, |v| v.version + 1),
+        };
+        self.claim_keys(&stored)?;
+        if let Some(existing) = &existing {
+            self.release_stale_keys(existing, &stored);
+        }
+        self.index_identity(&stored, existing.as_ref());
+        entry.insert(stored.clone());
+        let change_type = if created {
+            ChangeType::Created
+        } else {
+            ChangeType::Updated
+        };
+        self.record(&stored, change_type);
+
+        Ok((stored, created))
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for DashMapVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        Ok(self.live(id))
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        let id = self
+            .vins
+            .get(&vin.trim().to_ascii_uppercase())
+            .map(|id| *id);
+        Ok(id.and_then(|id| self.live(id)))
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        let id = normalise_plate(Some(plate.to_string()))
+            .and_then(|plate| self.plates.get(&plate).map(|id| *id));
+        Ok(id.and_then(|id| self.live(id)))
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        let _gate = self.shared();
+        Ok(self.is_live(id))
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        let _gate = self.shared();
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for id in ids {
+            match self.vehicles.get(id).filter(|v| v.deleted_at.is_none()) {
+                Some(vehicle) => found.push(vehicle.clone()),
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        // Id order needs only the ids to page, so only the page itself is cloned
+        if page.sort.is_empty() {
+            let ids = self.matching_ids(&page.filter);
+            let start = page.offset.min(ids.len());
+            let end = start.saturating_add(page.limit).min(ids.len());
+            return Ok(PageResult {
+                items: self.fetch(&ids[start..end]),
+                total: ids.len(),
+            });
+        }
+        let mut matching = self.matching(&page.filter);
+        let total = matching.len();
+
+        matching.sort_by(|a, b| sorting::compare(&page.sort, a, b));
+        let items = matching
+            .into_iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .collect();
+
+        Ok(PageResult { items, total })
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        Ok(self.list(page).await?.map(|v| VehicleSummary::from(&v)))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let _gate = self.shared();
+        let ids = self.matching_ids(filter);
+        let start = cursor.map_or(0, |cursor| ids.partition_point(|&id| id <= cursor));
+        let end = start.saturating_add(limit).min(ids.len());
+        let items = self.fetch(&ids[start..end]);
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = (start < end && end < ids.len()).then(|| ids[end - 1]);
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let _gate = self.shared();
+        let mut matching: Vec<Vehicle> = self
+            .vehicles
+            .iter()
+            .filter(|v| search.matches(v.value()))
+            .map(|v| v.value().clone())
+            .collect();
+        matching.sort_by_key(|v| v.id);
+        let total = matching.len();
+        let items = matching.into_iter().skip(offset).take(limit).collect();
+
+        Ok((items, total))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let _gate = self.shared();
+        let everything = VehicleFilter::default();
+        let filter = self.canonical_filter(filter.unwrap_or(&everything));
+        Ok(self
+            .vehicles
+            .iter()
+            .filter(|v| filter.matches(v.value()))
+            .count() as u64)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        let _gate = self.shared();
+        Ok(collect_stats(&self.matching(filter)))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let _gate = self.shared();
+        let manufacturers: BTreeSet<String> = self
+            .vehicles
+            .iter()
+            .filter(|v| v.deleted_at.is_none())
+            .map(|v| v.manufacturer.clone())
+            .collect();
+
+        Ok(manufacturers.into_iter().collect())
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        let _gate = self.gate_for_insert();
+        if !allow_duplicate && let Some(existing_id) = self.find_duplicate(&vehicle) {
+            return Err(RepoError::Duplicate { existing_id });
+        }
+        let id = VehicleId::generate();
+        self.write(id, vehicle, None, false, true)?;
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let _gate = self.gate_for_insert();
+        // Checked for the whole batch up front, so it is never left half inserted
+        self.check_capacity(vehicles.len())?;
+        let ids = vehicles
+            .into_iter()
+            .filter_map(|vehicle| {
+                let id = VehicleId::generate();
+                self.write(id, vehicle, None, false, true).ok().map(|_| id)
+            })
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Every conflict is found before the first insert and nothing else runs until the last,
+    /// so the batch lands whole or not at all and no reader sees part of it
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let _gate = self.exclusive();
+        self.check_capacity(vehicles.len())?;
+        let mut vins = HashMap::new();
+        let mut plates = HashMap::new();
+        let mut prepared = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            if let Some(vin) = normalise_vin(vehicle.vin.clone())
+                && let Some(existing_id) = self
+                    .vins
+                    .get(&vin)
+                    .map(|holder| *holder)
+                    .or_else(|| vins.insert(vin, id))
+            {
+                return Err(RepoError::VinTaken { existing_id });
+            }
+            if let Some(plate) = normalise_plate(vehicle.license_plate.clone())
+                && let Some(existing_id) = self
+                    .plates
+                    .get(&plate)
+                    .map(|holder| *holder)
+                    .or_else(|| plates.insert(plate, id))
+            {
+                return Err(RepoError::PlateTaken { existing_id });
+            }
+            prepared.push((id, vehicle));
+        }
+
+        let mut ids = Vec::with_capacity(prepared.len());
+        for (id, vehicle) in prepared {
+            if let Err(e) = self.write(id, vehicle, None, false, true) {
+                for id in ids {
+                    self.remove(id);
+                }
+                return Err(e);
+            }
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        let _gate = self.shared();
+        self.write(id, vehicle, if_match, correction, false)
+            .map(|(updated, _)| updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let _gate = self.gate_for_insert();
+        self.write(id, vehicle, if_match, correction, true)
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        Ok(self.remove(id))
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let _gate = self.shared();
+        let Some(mut stored) = self.vehicles.get_mut(&id) else {
+            return Ok(None);
+        };
+        // Keep the original timestamp when deleting an already deleted vehicle
+        if stored.deleted_at.is_some() {
+            return Ok(Some(stored.clone()));
+        }
+        let now = Utc::now();
+        stored.deleted_at = Some(now);
+        stored.updated_at = now;
+        stored.version += 1;
+        let deleted = stored.clone();
+        release(&self.plates, deleted.license_plate.as_deref(), id);
+        self.record(&deleted, ChangeType::Deleted);
+
+        Ok(Some(deleted))
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        let _gate = self.shared();
+        let mut stored = self.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        if stored.deleted_at.is_none() {
+            return Ok(stored.clone());
+        }
+        claim(&self.plates, stored.license_plate.as_deref(), id)
+            .map_err(|existing_id| RepoError::PlateTaken { existing_id })?;
+        stored.deleted_at = None;
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let restored = stored.clone();
+        self.record(&restored, ChangeType::Restored);
+
+        Ok(restored)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        let _gate = self.shared();
+        let mut stored = self.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        check_not_deleted(Some(&*stored))?;
+        let merged = normalise_tags(stored.tags.iter().cloned().chain(tags).collect());
+        if merged.len() == stored.tags.len() {
+            return Ok(stored.clone());
+        }
+        if merged.len() > MAX_TAGS {
+            return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+        }
+        stored.tags = merged;
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let updated = stored.clone();
+        self.record(&updated, ChangeType::Updated);
+
+        Ok(updated)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let _gate = self.shared();
+        let mut stored = self.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        check_not_deleted(Some(&*stored))?;
+        let tag = tag.trim().to_lowercase();
+        if !stored.tags.contains(&tag) {
+            return Ok(stored.clone());
+        }
+        stored.tags.retain(|t| *t != tag);
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let updated = stored.clone();
+        self.record(&updated, ChangeType::Updated);
+
+        Ok(updated)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        let _gate = self.shared();
+        let mut stored = self.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        check_not_deleted(Some(&*stored))?;
+        if let Some(if_match) = if_match
+            && !if_match.matches(&stored.etag())
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: Some(stored.version),
+            });
+        }
+        if stored.status == status {
+            return Ok(stored.clone());
+        }
+        stored.status = status;
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let updated = stored.clone();
+        self.record(&updated, ChangeType::StatusChanged);
+
+        Ok(updated)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let _gate = self.shared();
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            match self.remove(*id) {
+                Some(_) => deleted += 1,
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let _gate = self.shared();
+        let ids: Vec<VehicleId> = self
+            .vehicles
+            .iter()
+            .filter(|v| v.owner_id == Some(owner_id))
+            .map(|v| v.id)
+            .collect();
+        let now = Utc::now();
+        let mut unassigned = 0;
+        for id in ids {
+            // The owner may have changed since the scan, so check again under the guard
+            let Some(mut stored) = self.vehicles.get_mut(&id) else {
+                continue;
+            };
+            if stored.owner_id != Some(owner_id) {
+                continue;
+            }
+            stored.owner_id = None;
+            stored.updated_at = now;
+            stored.version += 1;
+            self.record(&stored, ChangeType::Updated);
+            unassigned += 1;
+        }
+
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        let _gate = self.shared();
+        if !self.is_live(id) {
+            return Ok(None);
+        }
+        let revisions = self
+            .history
+            .get(&id)
+            .map(|revisions| revisions.iter().rev().cloned().collect())
+            .unwrap_or_default();
+
+        Ok(Some(revisions))
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        let _gate = self.shared();
+        Ok(RepoHealth {
+            vehicles: Some(self.vehicles.len()),
+        })
+    }
+
+    /// Both modes hold the gate exclusively, so readers see the store before or after the
+    /// restore and never part of it
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        let _gate = self.exclusive();
+        match mode {
+            RestoreMode::Merge => {
+                let conflicts = dump_conflicts(&vehicles, |vehicle| self.find_holder(vehicle));
+                let skipped: HashSet<usize> = conflicts.iter().map(|c| c.index).collect();
+                let incoming = vehicles.len() - skipped.len();
+                self.check_capacity(incoming)?;
+                self.evict_for(incoming);
+                let mut restored = Vec::with_capacity(incoming);
+                for (index, vehicle) in vehicles.into_iter().enumerate() {
+                    if !skipped.contains(&index) {
+                        restored.push(vehicle.id);
+                        self.insert_dumped(vehicle)?;
+                    }
+                }
+                Ok(RestoreReport {
+                    restored,
+                    conflicts,
+                })
+            }
+            RestoreMode::Replace => {
+                let conflicts = dump_conflicts(&vehicles, |_| None);
+                if !conflicts.is_empty() {
+                    return Ok(RestoreReport {
+                        restored: Vec::new(),
+                        conflicts,
+                    });
+                }
+                if let Some(limit) = self.capacity
+                    && vehicles.len() > limit
+                {
+                    return Err(RepoError::CapacityExceeded { limit });
+                }
+                self.vehicles.clear();
+                self.identities.clear();
+                self.vins.clear();
+                self.plates.clear();
+                self.history.clear();
+                let restored = vehicles.iter().map(|vehicle| vehicle.id).collect();
+                for vehicle in vehicles {
+                    self.insert_dumped(vehicle)?;
+                }
+                Ok(RestoreReport {
+                    restored,
+                    conflicts: Vec::new(),
+                })
+            }
+        }
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        let _gate = self.shared();
+        self.capacity.map(|limit| StoreCapacity {
+            used: self.vehicles.len(),
+            limit,
+            policy: self.capacity_policy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn ids(vehicles: &[Vehicle]) -> Vec<VehicleId> {
+        vehicles.iter().map(|v| v.id).collect()
+    }
+
+    #[tokio::test]
+    async fn a_full_store_rejects_the_vehicle_past_its_capacity() {
+        let repo = DashMapVehicleRepo::default().with_capacity(Some(3), CapacityPolicy::Reject);
+        let vehicle = || Vehicle::builder().request();
+        let two = repo
+            .post_vehicles(vec![vehicle(), vehicle()])
+            .await
+            .unwrap();
+
+        let batch = repo.post_vehicles(vec![vehicle(), vehicle()]).await;
+        assert!(matches!(
+            batch,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+        assert_eq!(
+            repo.dump().await.unwrap().len(),
+            2,
+            "a batch that won't fit adds nothing"
+        );
+        let third = repo.post_vehicle(vehicle(), true).await.unwrap();
+        let fourth = repo.post_vehicle(vehicle(), true).await;
+        assert!(matches!(
+            fourth,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+
+        assert_eq!(ids(&repo.dump().await.unwrap()), [two[0], two[1], third]);
+        let capacity = repo.capacity().unwrap();
+        assert_eq!((capacity.used, capacity.limit), (3, 3));
+    }
+
+    #[tokio::test]
+    async fn a_full_store_evicts_its_oldest_vehicle_for_the_next() {
+        let repo =
+            DashMapVehicleRepo::default().with_capacity(Some(3), CapacityPolicy::EvictOldest);
+        let vehicle = || Vehicle::builder().request();
+        let mut created = Vec::new();
+        for _ in 0..3 {
+            created.push(repo.post_vehicle(vehicle(), true).await.unwrap());
+        }
+
+        let fourth = repo.post_vehicle(vehicle(), true).await.unwrap();
+
+        assert_eq!(
+            ids(&repo.dump().await.unwrap()),
+            [created[1], created[2], fourth]
+        );
+        assert!(repo.get_history(created[0]).await.unwrap().is_none());
+        let too_many = repo
+            .post_vehicles((0..4).map(|_| vehicle()).collect())
+            .await;
+        assert!(matches!(
+            too_many,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn an_atomic_batch_with_a_taken_plate_leaves_nothing_behind() {
+        let repo = DashMapVehicleRepo::default();
+        let holder = repo
+            .post_vehicle(Vehicle::builder().license_plate("AB12CDE").request(), true)
+            .await
+            .unwrap();
+
+        let batch = repo
+            .post_vehicles_atomic(vec![
+                Vehicle::builder().vin("1HGCM82633A004352").request(),
+                Vehicle::builder().license_plate("ab12 cde").request(),
+            ])
+            .await;
+
+        assert!(matches!(
+            batch,
+            Err(RepoError::PlateTaken { existing_id }) if existing_id == holder
+        ));
+        assert_eq!(ids(&repo.dump().await.unwrap()), [holder]);
+        assert!(
+            repo.get_vehicle_by_vin("1HGCM82633A004352")
+                .await
+                .unwrap()
+                .is_none(),
+            "the VIN claimed by the first vehicle is released"
+        );
+    }
+
+    /// Readers counting a manufacturer while atomic batches of it land must never see part of
+    /// a batch
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn readers_never_see_part_of_an_atomic_batch() {
+        const BATCH: usize = 20;
+        let repo = Arc::new(DashMapVehicleRepo::default());
+        let filter = VehicleFilter {
+            manufacturer: Some("Ford".to_string()),
+            ..Default::default()
+        };
+        let writer = {
+            let repo = repo.clone();
+            tokio::spawn(async move {
+                for _ in 0..20 {
+                    let batch = (0..BATCH)
+                        .map(|_| Vehicle::builder().manufacturer("Ford").request())
+                        .collect();
+                    repo.post_vehicles_atomic(batch).await.unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let counted = repo.count(Some(&filter)).await.unwrap();
+            assert_eq!(counted % BATCH as u64, 0, "saw {counted} vehicles");
+            tokio::task::yield_now().await;
+        }
+        writer.await.unwrap();
+        assert_eq!(repo.count(Some(&filter)).await.unwrap(), 20 * BATCH as u64);
+    }
+
+    #[tokio::test]
+    async fn replacing_swaps_every_vehicle_for_the_dump() {
+        let repo = DashMapVehicleRepo::default();
+        let old = repo
+            .post_vehicle(Vehicle::builder().license_plate("OLD1").request(), true)
+            .await
+            .unwrap();
+        let dumped = vec![
+            Vehicle::builder().license_plate("OLD1").build(),
+            Vehicle::builder().build(),
+        ];
+
+        let report = repo
+            .restore_dump(dumped.clone(), RestoreMode::Replace)
+            .await
+            .unwrap();
+
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.restored, ids(&dumped));
+        let mut expected = ids(&dumped);
+        expected.sort();
+        assert_eq!(ids(&repo.dump().await.unwrap()), expected);
+        assert!(repo.get_vehicle(old).await.unwrap().is_none());
+        assert_eq!(
+            repo.get_vehicle_by_plate("OLD1")
+                .await
+                .unwrap()
+                .map(|v| v.id),
+            Some(dumped[0].id)
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicates_follow_a_vehicle_whose_model_changes() {
+        let repo = DashMapVehicleRepo::default();
+        let corolla = || Vehicle::builder().model("Corolla").year(2020).request();
+        let id = repo.post_vehicle(corolla(), false).await.unwrap();
+        let duplicate = repo.post_vehicle(corolla(), false).await;
+        assert!(matches!(
+            duplicate,
+            Err(RepoError::Duplicate { existing_id }) if existing_id == id
+        ));
+
+        let yaris = Vehicle::builder().model("Yaris").year(2020).request();
+        repo.update_vehicle(id, yaris, None, false).await.unwrap();
+
+        repo.post_vehicle(corolla(), false).await.unwrap();
+        let duplicate = repo
+            .post_vehicle(
+                Vehicle::builder().model("yaris").year(2020).request(),
+                false,
+            )
+            .await;
+        assert!(matches!(
+            duplicate,
+            Err(RepoError::Duplicate { existing_id }) if existing_id == id
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use csv::Writer;
+
+use crate::features::vehicle::model::Vehicle;
+
+/// Columns of an exported file; `parse_csv` picks the ones it understands back up by name
+const HEADER: [&str; 16] = [
+    "id",
+    "manufacturer",
+    "model",
+    "year",
+    "vin",
+    "mileage_km",
+    "color",
+    "license_plate",
+    "price",
+    "fuel_type",
+    "first_registered",
+    "tags",
+    "status",
+    "created_at",
+    "updated_at",
+    "metadata",
+];
+
+/// Write vehicles as CSV, after a header row if `header` is set, so an export can be written a
+/// chunk at a time; tags are `;`-separated and metadata is a JSON object
+pub fn write_csv(vehicles: &[Vehicle], header: bool) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = Writer::from_writer(Vec::new());
+    if header {
+        writer.write_record(HEADER)?;
+    }
+    for vehicle in vehicles {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        writer.write_record([
+            vehicle.id.to_string(),
+            vehicle.manufacturer.clone(),
+            vehicle.model.clone(),
+            vehicle.year.to_string(),
+            optional(vehicle.vin.clone()),
+            optional(vehicle.mileage_km.map(|km| km.to_string())),
+            optional(vehicle.color.clone()),
+            optional(vehicle.license_plate.clone()),
+            optional(vehicle.price.map(|price| price.to_string())),
+            optional(vehicle.fuel_type.map(|fuel| fuel.as_str().to_string())),
+            optional(vehicle.first_registered.map(|date| date.to_string())),
+            vehicle.tags.join(";"),
+            vehicle.status.as_str().to_string(),
+            vehicle.created_at.to_rfc3339(),
+            vehicle.updated_at.to_rfc3339(),
+            serde_json::to_string(&vehicle.metadata).unwrap_or_default(),
+        ])?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
@@ -0,0 +1,496 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        model::{
+            CreateVehicleRequest, PageRequest, PageResult, RestoreMode, RestoreReport, Vehicle,
+            VehicleFilter, VehicleId, VehicleRevision, VehicleSearch, VehicleStats, VehicleStatus,
+            VehicleSummary,
+        },
+        persistent_repo::SnapshotStatus,
+        repo::{InMemoryVehicleRepo, ReadPath, RepoError, RepoHealth, StoreCapacity, VehicleRepo},
+    },
+};
+use crate::utils::etag::IfMatch;
+use async_trait::async_trait;
+use std::{
+    cell::Cell,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+tokio::task_local! {
+    /// Set once a read in the current request was answered by the secondary
+    static DEGRADED: Cell<bool>;
+}
+
+/// Run `call`, e.g. a request's handler, reporting whether any read in it fell back to a
+/// secondary copy
+pub async fn track_degraded<T>(call: impl Future<Output = T>) -> (T, bool) {
+    DEGRADED
+        .scope(Cell::new(false), async {
+            let output = call.await;
+            (output, DEGRADED.with(Cell::get))
+        })
+        .await
+}
+
+/// A change to copy to the secondary, applied in the order the writes returned
+enum Mirror {
+    /// Vehicles as the primary returned them
+    Put(Vec<Vehicle>),
+    /// Vehicles to read back from the primary, when the write only returned ids
+    Fetch(Vec<VehicleId>),
+    Remove(Vec<VehicleId>),
+    /// The primary's store was replaced wholesale, so copy all of it again
+    Reload,
+}
+
+/// Wraps a primary backend, e.g. Postgres, with an in-memory copy that answers reads while the
+/// primary is unavailable. Writes only ever go to the primary and are mirrored to the copy in
+/// the background once they succeed, so reads during an outage may miss the last few changes,
+/// and history is never served from the copy since it isn't mirrored
+pub struct FallbackRepo<P: VehicleRepo + ?Sized> {
+    primary: Arc<P>,
+    secondary: InMemoryVehicleRepo,
+    /// Whether the last read had to fall back
+    degraded: AtomicBool,
+    mirror: mpsc::UnboundedSender<Mirror>,
+}
+
+impl<P: VehicleRepo + ?Sized + 'static> FallbackRepo<P> {
+    /// Fall back to `secondary`, starting the task that keeps it in step with `primary`
+    pub fn new(primary: Arc<P>, secondary: InMemoryVehicleRepo) -> Self {
+        let (mirror, changes) = mpsc::unbounded_channel();
+        tokio::spawn(run_mirror(primary.clone(), secondary.clone(), changes));
+        Self {
+            primary,
+            secondary,
+            degraded: AtomicBool::new(false),
+            mirror,
+        }
+    }
+
+    /// Copy every vehicle the primary holds, soft-deleted ones included, into the secondary,
+    /// returning how many there were
+    pub async fn warm(&self) -> Result<usize, RepoError> {
+        let vehicles = self.primary.dump().await?;
+        let copied = vehicles.len();
+        self.secondary.load_vehicles(vehicles);
+        Ok(copied)
+    }
+}
+
+impl<P: VehicleRepo + ?Sized> FallbackRepo<P> {
+    /// Answer from the primary, or from the secondary while the primary is unavailable
+    async fn read<T>(
+        &self,
+        primary: impl Future<Output = Result<T, RepoError>>,
+        secondary: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        match primary.await {
+            Err(RepoError::Unavailable) => {
+                if !self.degraded.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "Primary vehicle store unavailable, serving reads from the fallback copy"
+                    );
+                }
+                let _ = DEGRADED.try_with(|degraded| degraded.set(true));
+                secondary.await
+            }
+            result => {
+                if result.is_ok() && self.degraded.swap(false, Ordering::Relaxed) {
+                    info!("Primary vehicle store is back, serving reads from it again");
+                }
+                result
+            }
+        }
+    }
+
+    fn mirror(&self, change: Mirror) {
+        // Only fails once the mirror task is gone, i.e. the runtime is shutting down
+        let _ = self.mirror.send(change);
+    }
+
+    /// Mirror the vehicle a successful write returned
+    fn put<T>(
+        &self,
+        result: Result<T, RepoError>,
+        vehicle: impl Fn(&T) -> &Vehicle,
+    ) -> Result<T, RepoError> {
+        if let Ok(value) = &result {
+            self.mirror(Mirror::Put(vec![vehicle(value).clone()]));
+        }
+        result
+    }
+}
+
+async fn run_mirror<P: VehicleRepo + ?Sized>(
+    primary: Arc<P>,
+    secondary: InMemoryVehicleRepo,
+    mut changes: mpsc::UnboundedReceiver<Mirror>,
+) {
+    while let Some(change) = changes.recv().await {
+        let vehicles = match change {
+            Mirror::Put(vehicles) => vehicles,
+            Mirror::Fetch(ids) => match primary.get_vehicles_by_ids(&ids).await {
+                Ok((vehicles, _)) => vehicles,
+                Err(e) => {
+                    warn!(
+                        "Failed to mirror {} vehicles to the fallback copy: {:?}",
+                        ids.len(),
+                        e
+                    );
+                    continue;
+                }
+            },
+            Mirror::Remove(ids) => {
+                for id in ids {
+                    secondary.unload_vehicle(id);
+                }
+                continue;
+            }
+            Mirror::Reload => {
+                let reloaded = match primary.dump().await {
+                    Ok(vehicles) => secondary.restore_dump(vehicles, RestoreMode::Replace).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = reloaded {
+                    warn!("Failed to reload the fallback copy: {:?}", e);
+                }
+                continue;
+            }
+        };
+        // Concurrent writes can return out of order, so never replace a newer version
+        let mut newer = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let current = secondary.stored_vehicle(vehicle.id);
+            if current.is_none_or(|current| current.version <= vehicle.version) {
+                newer.push(vehicle);
+            }
+        }
+        secondary.load_vehicles(newer);
+    }
+}
+
+#[async_trait]
+impl<P: VehicleRepo + ?Sized> VehicleRepo for FallbackRepo<P> {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.read(self.primary.get_vehicle(id), self.secondary.get_vehicle(id))
+            .await
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.read(
+            self.primary.get_vehicle_by_vin(vin),
+            self.secondary.get_vehicle_by_vin(vin),
+        )
+        .await
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.read(
+            self.primary.get_vehicle_by_plate(plate),
+            self.secondary.get_vehicle_by_plate(plate),
+        )
+        .await
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        self.read(self.primary.exists(id), self.secondary.exists(id))
+            .await
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        self.read(
+            self.primary.get_vehicles_by_ids(ids),
+            self.secondary.get_vehicles_by_ids(ids),
+        )
+        .await
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        self.read(self.primary.list(page.clone()), self.secondary.list(page))
+            .await
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        self.read(
+            self.primary.list_summaries(page.clone()),
+            self.secondary.list_summaries(page),
+        )
+        .await
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        self.read(
+            self.primary.get_vehicles_after(filter, cursor, limit),
+            self.secondary.get_vehicles_after(filter, cursor, limit),
+        )
+        .await
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        self.read(
+            self.primary.search_vehicles(search, offset, limit),
+            self.secondary.search_vehicles(search, offset, limit),
+        )
+        .await
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        self.read(self.primary.count(filter), self.secondary.count(filter))
+            .await
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        self.read(self.primary.stats(filter), self.secondary.stats(filter))
+            .await
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        self.read(self.primary.manufacturers(), self.secondary.manufacturers())
+            .await
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        let id = self.primary.post_vehicle(vehicle, allow_duplicate).await?;
+        self.mirror(Mirror::Fetch(vec![id]));
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let ids = self.primary.post_vehicles(vehicles).await?;
+        self.mirror(Mirror::Fetch(ids.clone()));
+        Ok(ids)
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let ids = self.primary.post_vehicles_atomic(vehicles).await?;
+        self.mirror(Mirror::Fetch(ids.clone()));
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        let result = self
+            .primary
+            .update_vehicle(id, vehicle, if_match, correction)
+            .await;
+        self.put(result, |vehicle| vehicle)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let result = self
+            .primary
+            .upsert_vehicle(id, vehicle, if_match, correction)
+            .await;
+        self.put(result, |(vehicle, _)| vehicle)
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let deleted = self.primary.delete_vehicle(id).await?;
+        self.mirror(Mirror::Remove(vec![id]));
+        Ok(deleted)
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let deleted = self.primary.soft_delete(id).await?;
+        if let Some(vehicle) = &deleted {
+            self.mirror(Mirror::Put(vec![vehicle.clone()]));
+        }
+        Ok(deleted)
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        let result = self.primary.restore(id).await;
+        self.put(result, |vehicle| vehicle)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        let result = self.primary.add_tags(id, tags).await;
+        self.put(result, |vehicle| vehicle)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let result = self.primary.remove_tag(id, tag).await;
+        self.put(result, |vehicle| vehicle)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        let result = self.primary.set_status(id, status, if_match).await;
+        self.put(result, |vehicle| vehicle)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let result = self.primary.delete_vehicles(ids).await?;
+        self.mirror(Mirror::Remove(ids.to_vec()));
+        Ok(result)
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let unassigned = self.primary.unassign_owner(owner_id).await?;
+        // The copy still has the owner on the same vehicles, so it knows which to refresh
+        let filter = VehicleFilter {
+            owner_id: Some(owner_id),
+            include_deleted: Some(true),
+            ..Default::default()
+        };
+        let ids = self
+            .secondary
+            .vehicles()
+            .into_iter()
+            .filter(|vehicle| filter.matches(vehicle))
+            .map(|vehicle| vehicle.id)
+            .collect();
+        self.mirror(Mirror::Fetch(ids));
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        self.primary.get_history(id).await
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        self.read(self.primary.dump(), self.secondary.dump()).await
+    }
+
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        let report = self.primary.restore_dump(vehicles, mode).await?;
+        match mode {
+            RestoreMode::Merge => self.mirror(Mirror::Fetch(report.restored.clone())),
+            RestoreMode::Replace if report.conflicts.is_empty() => self.mirror(Mirror::Reload),
+            RestoreMode::Replace => {}
+        }
+        Ok(report)
+    }
+
+    /// Ready while either store can answer reads; `read_path` tells which one is
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.read(self.primary.ping(), self.secondary.ping()).await
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.read(self.primary.health_check(), self.secondary.health_check())
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), RepoError> {
+        self.primary.flush().await
+    }
+
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        self.primary.snapshot_status()
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        self.primary.capacity()
+    }
+
+    fn read_path(&self) -> Option<ReadPath> {
+        Some(if self.degraded.load(Ordering::Relaxed) {
+            ReadPath::Secondary
+        } else {
+            ReadPath::Primary
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{features::vehicle::mock_repo::MockVehicleRepo, test_app::TestApp};
+    use axum::http::{Method, StatusCode, header};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn reads_keep_working_from_the_copy_while_the_primary_is_down() {
+        let primary = Arc::new(MockVehicleRepo::default());
+        let fallback = Arc::new(FallbackRepo::new(
+            primary.clone(),
+            InMemoryVehicleRepo::default(),
+        ));
+        let app = TestApp::with_repo(fallback.clone());
+        let body = json!({ "manufacturer": "Toyota", "model": "Corolla", "year": 2020 });
+        let created = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+        let uri = created.headers[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        fallback.warm().await.unwrap();
+
+        primary.fail_next("get_vehicle", RepoError::Unavailable);
+        primary.fail_next("list_summaries", RepoError::Unavailable);
+        let one = app.get(&uri).await;
+        let all = app.get("/api/v1/vehicles").await;
+
+        assert_eq!(one.status, StatusCode::OK);
+        assert_eq!(one.headers["x-degraded"], "true");
+        assert_eq!(one.json()["data"]["model"], "Corolla");
+        assert_eq!(all.status, StatusCode::OK);
+        assert_eq!(all.headers["x-degraded"], "true");
+        assert_eq!(all.json()["data"].as_array().map(Vec::len), Some(1));
+        primary.fail_next("health_check", RepoError::Unavailable);
+        let ready = app.get("/health/ready").await;
+        assert_eq!(ready.status, StatusCode::OK);
+        assert_eq!(ready.json()["checks"]["read_path"], "secondary");
+
+        let recovered = app.get(&uri).await;
+        assert_eq!(recovered.status, StatusCode::OK);
+        assert!(recovered.headers.get("x-degraded").is_none());
+        let ready = app.get("/health/ready").await.json();
+        assert_eq!(ready["checks"]["read_path"], "primary");
+    }
+}
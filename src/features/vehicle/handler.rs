@@ -1,63 +1,1768 @@
+use anyhow::anyhow;
 use axum::{
-    Json, debug_handler,
-    extract::{Path, State},
-    http::StatusCode,
+    body::{Body, Bytes},
+    debug_handler,
+    extract::{Path, Query, State, rejection::StringRejection},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use tracing::{info, instrument, warn};
-use uuid::Uuid;
+use futures_util::{StreamExt, stream};
+use serde_json::Value;
+use std::{io, sync::Arc};
+use tracing::{Span, error, field, info, instrument, warn};
+use validator::Validate;
 
 use crate::{
     AppState,
-    features::vehicle::{
-        model::{Vehicle, VehicleId},
-        repo::{InMemoryVehicleRepo, VehicleRepo},
+    features::{
+        owner::{
+            model::OwnerId,
+            repo::{InMemoryOwnerRepo, OwnerRepo},
+        },
+        vehicle::{
+            export::write_csv,
+            import::{parse_csv, validation_messages},
+            model::{
+                BatchCreateRequest, BatchCreateResult, BatchItemResult, BatchParams,
+                BulkDeleteRequest, BulkDeleteResult, CSV_MEDIA_TYPES, CUSTOM_COLOR_PREFIX,
+                CreateParams, CreateVehicleRequest, CreatedVehicle, DeleteParams, FieldsParams,
+                IMPORT_BODY_LIMIT, ImportParams, ImportResult, ListParams, ListView, LookupQuery,
+                LookupRequest, LookupResult, PageParams, PageResult, SearchParams, StatusChange,
+                StatusParams, TagPath, TagsRequest, UpdateParams, VEHICLE_COLORS, Vehicle,
+                VehicleColors, VehicleCount, VehicleFilter, VehicleId, VehicleManufacturers,
+                VehiclePatch, VehicleResponse, VehicleRevision, VehicleSearch, VehicleStats,
+                VehicleSummary, VinPath,
+            },
+            repo::{RepoError, VehicleRepo, vehicle_chunks},
+        },
+    },
+    utils::{
+        config::AppConfig,
+        etag::{IfMatch, if_none_match},
+        response::{ApiListResponse, ApiResponse, RequestContext},
+        validator::{
+            Normalise, ServerError, ValidateWithConfig, ValidatedPath, ValidatedPayload,
+            ValidatedQuery, has_media_type, string_rejection,
+        },
     },
-    utils::validator::ValidatedPayload,
 };
 
 #[debug_handler]
-#[instrument(skip(state), fields(vehicle_id = %id))]
+#[instrument(skip(state, headers), fields(vehicle_id = %id))]
 pub async fn get_vehicle(
-    State(state): State<AppState<InMemoryVehicleRepo>>,
-    Path(id): Path<Uuid>,
-) -> Result<Json<Vehicle>, StatusCode> {
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    ValidatedQuery(fields): ValidatedQuery<FieldsParams>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
     info!("Fetching vehicle with ID: {}", id);
-    
-    match state.vehicle_repo.get_vehicle(id).await {
+
+    match state.vehicle_repo.get_vehicle(id).await? {
         Some(vehicle) => {
+            let etag = vehicle.etag();
+            if if_none_match(&headers, &etag) {
+                info!("Vehicle not modified: {}", etag);
+                return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+            }
+
             info!("Vehicle found: {:?}", vehicle);
-            Ok(Json::from(vehicle))
+            Ok((
+                [(header::ETAG, etag)],
+                ApiResponse::new(fields.project(&vehicle)),
+            )
+                .into_response())
         }
         None => {
             warn!("Vehicle not found with ID: {}", id);
-            Err(StatusCode::NOT_FOUND)
+            Err(ServerError::not_found("vehicle", id))
         }
     }
 }
 
 #[debug_handler]
-#[instrument(skip(state))]
+#[instrument(skip(state, headers), fields(vin = %vin))]
+pub async fn get_vehicle_by_vin(
+    State(state): State<AppState>,
+    ValidatedPath(VinPath { vin }): ValidatedPath<VinPath>,
+    ValidatedQuery(fields): ValidatedQuery<FieldsParams>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    info!("Fetching vehicle with VIN: {}", vin);
+
+    let Some(vehicle) = state.vehicle_repo.get_vehicle_by_vin(&vin).await? else {
+        warn!("Vehicle not found with VIN: {}", vin);
+        return Err(ServerError::not_found("vehicle", vin));
+    };
+    let etag = vehicle.etag();
+    if if_none_match(&headers, &etag) {
+        info!("Vehicle not modified: {}", etag);
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    info!("Vehicle found: {:?}", vehicle);
+    Ok((
+        [(header::ETAG, etag)],
+        ApiResponse::new(fields.project(&vehicle)),
+    )
+        .into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, headers), fields(license_plate = %plate))]
+pub async fn get_vehicle_by_plate(
+    State(state): State<AppState>,
+    Path(plate): Path<String>,
+    ValidatedQuery(fields): ValidatedQuery<FieldsParams>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    info!("Fetching vehicle with license plate: {}", plate);
+
+    let Some(vehicle) = state.vehicle_repo.get_vehicle_by_plate(&plate).await? else {
+        warn!("Vehicle not found with license plate: {}", plate);
+        return Err(ServerError::not_found("vehicle", plate));
+    };
+    let etag = vehicle.etag();
+    if if_none_match(&headers, &etag) {
+        info!("Vehicle not modified: {}", etag);
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    info!("Vehicle found: {:?}", vehicle);
+    Ok((
+        [(header::ETAG, etag)],
+        ApiResponse::new(fields.project(&vehicle)),
+    )
+        .into_response())
+}
+
+/// Rows fetched from the repo per lock acquisition while streaming
+const NDJSON_CHUNK_SIZE: usize = 100;
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+/// Vehicles written per chunk of a CSV export
+const CSV_CHUNK_SIZE: usize = 500;
+
+fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains(NDJSON_CONTENT_TYPE))
+}
+
+/// A repo error met after the status line went out, which can only cut the body short
+pub fn stream_error(e: RepoError) -> io::Error {
+    error!("Streaming vehicles failed: {:?}", e);
+    io::Error::other(format!("{e:?}"))
+}
+
+/// Stream every matching vehicle as one JSON document per line, walking the repo in id order
+/// chunk by chunk so the lock is never held across an await point
+fn ndjson_response(
+    repo: Arc<dyn VehicleRepo>,
+    filter: VehicleFilter,
+    fields: FieldsParams,
+    summary: bool,
+    cursor: Option<VehicleId>,
+) -> Response {
+    let chunks = vehicle_chunks(repo, filter, cursor, NDJSON_CHUNK_SIZE).map(move |items| {
+        let items = items.map_err(stream_error)?;
+        let mut chunk = Vec::new();
+        for vehicle in list_items(&items, &fields, summary) {
+            serde_json::to_writer(&mut chunk, &vehicle)?;
+            chunk.push(b'\n');
+        }
+        Ok::<_, io::Error>(Bytes::from(chunk))
+    });
+
+    (
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(chunks),
+    )
+        .into_response()
+}
+
+/// List entries as summaries, or as (projected) full vehicles when those were asked for
+fn list_items(items: &[Vehicle], fields: &FieldsParams, summary: bool) -> Vec<Value> {
+    if !summary {
+        return fields.project_all(items);
+    }
+    items
+        .iter()
+        .map(|vehicle| serde_json::to_value(VehicleSummary::from(vehicle)).unwrap_or_default())
+        .collect()
+}
+
+#[debug_handler]
+#[instrument(skip(state), fields(vehicle_id = %id, found = field::Empty))]
+pub async fn head_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+) -> Result<StatusCode, ServerError> {
+    let found = state.vehicle_repo.exists(id).await?;
+    Span::current().record("found", found);
+
+    if found {
+        Ok(StatusCode::OK)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
+
+#[debug_handler]
+#[instrument(skip(state, ctx, headers))]
 pub async fn get_vehicles(
-    State(state): State<AppState<InMemoryVehicleRepo>>,
-) -> Result<Json<Vec<Vehicle>>, StatusCode> {
-    info!("Fetching all vehicles");
-    
-    let vehicles = state.vehicle_repo.get_vehicles().await;
-    
-    info!("Found {} vehicles", vehicles.len());
-    Ok(Json::from(vehicles))
+    State(state): State<AppState>,
+    ctx: RequestContext,
+    ValidatedQuery((params, filter, fields)): ValidatedQuery<(
+        ListParams,
+        VehicleFilter,
+        FieldsParams,
+    )>,
+    Query(pairs): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let filter = filter.with_tags(&pairs);
+    let limit = params.limit();
+    // A fieldset selects from the full vehicle, so it implies the full view
+    let summary = fields.fields.is_none() && params.view.unwrap_or_default() == ListView::Summary;
+    let cursor = match params.cursor.as_deref() {
+        None => None,
+        Some("") => Some(None),
+        Some(c) => Some(Some(
+            c.parse::<VehicleId>()
+                .map_err(|e| ServerError::InvalidCursor(e.to_string()))?,
+        )),
+    };
+
+    if accepts_ndjson(&headers) {
+        info!("Streaming vehicles as NDJSON");
+        return Ok(ndjson_response(
+            state.vehicle_repo.clone(),
+            filter,
+            fields,
+            summary,
+            cursor.flatten(),
+        ));
+    }
+
+    if let Some(cursor) = cursor {
+        info!("Fetching vehicles (cursor: {:?}, limit: {})", cursor, limit);
+
+        let (items, next_cursor) = state
+            .vehicle_repo
+            .get_vehicles_after(&filter, cursor, limit)
+            .await?;
+
+        info!("Found {} vehicles", items.len());
+        return Ok(
+            ApiListResponse::new(list_items(&items, &fields, summary), &ctx)
+                .cursor(limit, next_cursor.map(|id| id.to_string()))
+                .into_response(),
+        );
+    }
+
+    let offset = params.offset();
+    let page = params.page_request(filter);
+    info!("Fetching vehicles (offset: {}, limit: {})", offset, limit);
+
+    if summary {
+        let PageResult { items, total } = state.vehicle_repo.list_summaries(page).await?;
+
+        info!("Found {} of {} vehicles", items.len(), total);
+        return Ok(ApiListResponse::new(items, &ctx)
+            .total(total)
+            .page(limit, offset)
+            .into_response());
+    }
+
+    let PageResult { items, total } = state.vehicle_repo.list(page).await?;
+
+    info!("Found {} of {} vehicles", items.len(), total);
+    Ok(ApiListResponse::new(fields.project_all(&items), &ctx)
+        .total(total)
+        .page(limit, offset)
+        .into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, query))]
+pub async fn lookup_vehicles_query(
+    State(state): State<AppState>,
+    ValidatedQuery(query): ValidatedQuery<LookupQuery>,
+) -> Result<ApiResponse<LookupResult>, ServerError> {
+    let request = LookupRequest::from(query);
+    request
+        .validate()
+        .map_err(|errors| ServerError::validation(errors, &state.config))?;
+
+    lookup_vehicles(state.vehicle_repo.as_ref(), &request).await
+}
+
+#[debug_handler]
+#[instrument(skip(state, request))]
+pub async fn lookup_vehicles_body(
+    State(state): State<AppState>,
+    ValidatedPayload(request): ValidatedPayload<LookupRequest>,
+) -> Result<ApiResponse<LookupResult>, ServerError> {
+    lookup_vehicles(state.vehicle_repo.as_ref(), &request).await
+}
+
+async fn lookup_vehicles(
+    repo: &dyn VehicleRepo,
+    request: &LookupRequest,
+) -> Result<ApiResponse<LookupResult>, ServerError> {
+    let ids = request.vehicle_ids();
+    info!("Looking up {} vehicles", ids.len());
+
+    let (items, missing) = repo.get_vehicles_by_ids(&ids).await?;
+
+    info!("Found {} vehicles, {} missing", items.len(), missing.len());
+    Ok(ApiResponse::new(LookupResult {
+        items: items.into_iter().map(VehicleResponse::from).collect(),
+        missing,
+    }))
+}
+
+#[debug_handler]
+#[instrument(skip(state))]
+pub async fn count_vehicles(
+    State(state): State<AppState>,
+    ValidatedQuery(filter): ValidatedQuery<VehicleFilter>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<ApiResponse<VehicleCount>, ServerError> {
+    let filter = filter.with_tags(&pairs);
+    info!("Counting vehicles");
+
+    let count = state.vehicle_repo.count(Some(&filter)).await?;
+
+    info!("Counted {} vehicles", count);
+    Ok(ApiResponse::new(VehicleCount { count }))
+}
+
+#[debug_handler]
+#[instrument]
+pub async fn get_vehicle_colors() -> ApiResponse<VehicleColors> {
+    ApiResponse::new(VehicleColors {
+        colors: VEHICLE_COLORS,
+        custom_prefix: CUSTOM_COLOR_PREFIX,
+    })
+}
+
+#[debug_handler]
+#[instrument(skip(state))]
+pub async fn get_vehicle_manufacturers(
+    State(state): State<AppState>,
+) -> Result<ApiResponse<VehicleManufacturers>, ServerError> {
+    let manufacturers = state.vehicle_repo.manufacturers().await?;
+    info!("Found {} manufacturers", manufacturers.len());
+    Ok(ApiResponse::new(VehicleManufacturers { manufacturers }))
 }
 
 #[debug_handler]
-#[instrument(skip(state, v), fields(vehicle_manufacturer = %v.manufacturer, vehicle_model = %v.model))]
+#[instrument(skip(state))]
+pub async fn get_vehicle_stats(
+    State(state): State<AppState>,
+    ValidatedQuery(filter): ValidatedQuery<VehicleFilter>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<ApiResponse<VehicleStats>, ServerError> {
+    let filter = filter.with_tags(&pairs);
+    info!("Computing vehicle stats");
+
+    let stats = state.vehicle_repo.stats(&filter).await?;
+
+    info!("Computed stats over {} vehicles", stats.total);
+    Ok(ApiResponse::new(stats))
+}
+
+#[debug_handler]
+#[instrument(skip(state, ctx, params), fields(query = %params.q, hits = field::Empty))]
+pub async fn search_vehicles(
+    State(state): State<AppState>,
+    ctx: RequestContext,
+    ValidatedQuery(params): ValidatedQuery<SearchParams>,
+) -> Result<ApiListResponse<VehicleResponse>, ServerError> {
+    let (limit, offset) = (params.limit(), params.offset());
+    info!("Searching vehicles for: {}", params.q);
+
+    let search = VehicleSearch::new(&params.q);
+    let (items, total) = state
+        .vehicle_repo
+        .search_vehicles(&search, offset, limit)
+        .await?;
+
+    Span::current().record("hits", total);
+    info!("Search matched {} vehicles", total);
+    let items = items.into_iter().map(VehicleResponse::from).collect();
+    Ok(ApiListResponse::new(items, &ctx)
+        .total(total)
+        .page(limit, offset))
+}
+
+#[debug_handler]
+#[instrument(skip(state, params, v), fields(vehicle_manufacturer = %v.manufacturer, vehicle_model = %v.model))]
 pub async fn post_vehicle(
-    State(state): State<AppState<InMemoryVehicleRepo>>,
-    ValidatedPayload(v): ValidatedPayload<Vehicle>,
-) -> Json<VehicleId> {
+    State(state): State<AppState>,
+    Query(params): Query<CreateParams>,
+    ValidatedPayload(v): ValidatedPayload<CreateVehicleRequest>,
+) -> Result<Response, ServerError> {
     info!("Creating new vehicle: {} {}", v.manufacturer, v.model);
-    
-    let vehicle_id = state.vehicle_repo.post_vehicle(v).await.unwrap();
-    
-    info!("Vehicle created with ID: {}", vehicle_id.id);
-    Json::from(vehicle_id)
+    check_owner(&state.owner_repo, v.owner_id).await?;
+
+    let vehicle_id = state
+        .vehicle_repo
+        .post_vehicle(v, params.allow_duplicate.unwrap_or(false))
+        .await
+        .inspect_err(|e| warn!("Vehicle creation rejected: {:?}", e))?;
+
+    info!("Vehicle created with ID: {}", vehicle_id);
+    let location = [(header::LOCATION, format!("/api/v1/vehicles/{vehicle_id}"))];
+    let body = ApiResponse::new(CreatedVehicle { id: vehicle_id });
+    Ok((StatusCode::CREATED, location, body).into_response())
+}
+
+/// With `?atomic=true` nothing is written unless every vehicle can be: a vehicle failing
+/// validation refuses the batch with a `422` batch-rejected problem, and a VIN or plate taken in
+/// the store answers the repo's usual `409`
+#[debug_handler]
+#[instrument(skip(state, request), fields(requested = request.vehicles.len(), created = field::Empty))]
+pub async fn post_vehicles_batch(
+    State(state): State<AppState>,
+    Query(params): Query<BatchParams>,
+    ValidatedPayload(request): ValidatedPayload<BatchCreateRequest>,
+) -> Result<ApiResponse<BatchCreateResult>, ServerError> {
+    let atomic = params.atomic.unwrap_or(false);
+    info!(
+        "Creating {} vehicles in a batch (atomic: {})",
+        request.vehicles.len(),
+        atomic
+    );
+
+    // Check every vehicle first, so an atomic batch is refused before anything is written
+    let mut results = Vec::with_capacity(request.vehicles.len());
+    let mut valid = Vec::with_capacity(request.vehicles.len());
+    for (index, mut vehicle) in request.vehicles.into_iter().enumerate() {
+        vehicle.normalise();
+        let errors = match vehicle.validate_with_config(&state.config) {
+            Err(e) => validation_messages(&e),
+            Ok(()) => match check_owner(&state.owner_repo, vehicle.owner_id).await {
+                Err(e) => vec![e.to_string()],
+                Ok(()) => Vec::new(),
+            },
+        };
+        if errors.is_empty() {
+            valid.push((index, vehicle));
+        }
+        results.push(BatchItemResult {
+            index,
+            id: None,
+            errors,
+        });
+    }
+
+    if atomic {
+        if valid.len() < results.len() {
+            let failed = results.len() - valid.len();
+            warn!(
+                "Atomic batch refused, {} vehicles failed validation",
+                failed
+            );
+            Span::current().record("created", 0);
+            return Err(ServerError::BatchRejected {
+                failed,
+                report: BatchCreateResult::new(0, results, state.config.max_validation_errors),
+            });
+        }
+        let ids = state
+            .vehicle_repo
+            .post_vehicles_atomic(valid.into_iter().map(|(_, v)| v).collect())
+            .await
+            .inspect_err(|e| warn!("Atomic batch rejected: {:?}", e))?;
+        for (result, id) in results.iter_mut().zip(ids) {
+            result.id = Some(id);
+        }
+    } else {
+        // Each vehicle stands alone; only a store that can't be reached fails the request
+        for (index, vehicle) in valid {
+            match state.vehicle_repo.post_vehicle(vehicle, true).await {
+                Ok(id) => results[index].id = Some(id),
+                Err(e @ (RepoError::Backend(_) | RepoError::Unavailable)) => return Err(e.into()),
+                Err(e) => results[index].errors = vec![ServerError::from(e).to_string()],
+            }
+        }
+    }
+
+    let created = results.iter().filter(|r| r.id.is_some()).count();
+    Span::current().record("created", created);
+    info!("Created {} of {} batch vehicles", created, results.len());
+    Ok(ApiResponse::new(BatchCreateResult::new(
+        created,
+        results,
+        state.config.max_validation_errors,
+    )))
+}
+
+#[debug_handler]
+#[instrument(skip(state, overrides), fields(source_id = %id, vehicle_id = field::Empty))]
+pub async fn clone_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    overrides: Option<ValidatedPayload<VehiclePatch>>,
+) -> Result<Response, ServerError> {
+    info!("Cloning vehicle with ID: {}", id);
+
+    let Some(source) = state.vehicle_repo.get_vehicle(id).await? else {
+        warn!("Vehicle not found with ID: {}", id);
+        return Err(ServerError::not_found("vehicle", id));
+    };
+    // VINs and plates are unique, so the copy only gets them if the overrides provide them
+    let source = CreateVehicleRequest {
+        vin: None,
+        license_plate: None,
+        ..source.into()
+    };
+    let vehicle = match overrides {
+        Some(ValidatedPayload(patch)) => patch.apply(source),
+        None => source,
+    };
+    check_owner(&state.owner_repo, vehicle.owner_id).await?;
+
+    // A clone is a duplicate by definition, so skip the uniqueness check
+    let vehicle_id = state
+        .vehicle_repo
+        .post_vehicle(vehicle, true)
+        .await
+        .inspect_err(|e| warn!("Vehicle clone rejected: {:?}", e))?;
+    Span::current().record("vehicle_id", vehicle_id.to_string());
+
+    let Some(clone) = state.vehicle_repo.get_vehicle(vehicle_id).await? else {
+        return Err(ServerError::Internal(anyhow!(
+            "vehicle {vehicle_id} is gone right after being cloned"
+        )));
+    };
+
+    info!("Vehicle cloned with ID: {}", vehicle_id);
+    let location = [(header::LOCATION, format!("/api/v1/vehicles/{vehicle_id}"))];
+    let etag = [(header::ETAG, clone.etag())];
+    let body = ApiResponse::new(VehicleResponse::from(clone));
+    Ok((StatusCode::CREATED, location, etag, body).into_response())
+}
+
+/// Streams the CSV a chunk of rows at a time, in id order, so the export never holds every
+/// vehicle in memory; a repo failure part-way through cuts the file short
+#[debug_handler]
+#[instrument(skip(state))]
+pub async fn export_vehicles(
+    State(state): State<AppState>,
+    ValidatedQuery(filter): ValidatedQuery<VehicleFilter>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Response, ServerError> {
+    let filter = filter.with_tags(&pairs);
+    info!("Exporting vehicles as CSV");
+
+    let columns = write_csv(&[], true).map_err(|e| ServerError::Internal(e.into()))?;
+    let rows =
+        vehicle_chunks(state.vehicle_repo.clone(), filter, None, CSV_CHUNK_SIZE).map(|vehicles| {
+            let vehicles = vehicles.map_err(stream_error)?;
+            Ok::<_, io::Error>(Bytes::from(write_csv(&vehicles, false)?))
+        });
+    let body = stream::once(async { Ok(Bytes::from(columns)) }).chain(rows);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv")],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, headers, body), fields(imported = field::Empty, failed = field::Empty))]
+pub async fn import_vehicles(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    headers: HeaderMap,
+    body: Result<String, StringRejection>,
+) -> Result<ApiResponse<ImportResult>, ServerError> {
+    if !has_media_type(&headers, CSV_MEDIA_TYPES) {
+        return Err(ServerError::UnsupportedMediaType {
+            accepted: CSV_MEDIA_TYPES,
+        });
+    }
+    let body = body.map_err(|rejection| string_rejection(rejection, IMPORT_BODY_LIMIT))?;
+    info!("Importing vehicles from CSV ({} bytes)", body.len());
+
+    let (vehicles, failed) = parse_csv(&body, &state.config);
+    Span::current().record("failed", failed.len());
+
+    if params.strict.unwrap_or(false) && !failed.is_empty() {
+        warn!("Strict import aborted, {} rows failed", failed.len());
+        Span::current().record("imported", 0);
+        return Err(ServerError::ImportRejected { failed });
+    }
+
+    let imported = state.vehicle_repo.post_vehicles(vehicles).await?.len();
+
+    Span::current().record("imported", imported);
+    info!(
+        "Imported {} vehicles, {} rows failed",
+        imported,
+        failed.len()
+    );
+    Ok(ApiResponse::new(ImportResult { imported, failed }))
+}
+
+#[debug_handler]
+#[instrument(skip(state, headers, v), fields(vehicle_id = %id, created = field::Empty))]
+pub async fn put_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    Query(params): Query<UpdateParams>,
+    headers: HeaderMap,
+    ValidatedPayload(v): ValidatedPayload<CreateVehicleRequest>,
+) -> Result<Response, ServerError> {
+    let if_match = if_match_precondition(&headers, &state.config)?;
+    info!("Upserting vehicle with ID: {}", id);
+    check_owner(&state.owner_repo, v.owner_id).await?;
+
+    let (vehicle, created) = state
+        .vehicle_repo
+        .upsert_vehicle(id, v, if_match.as_ref(), params.correction.unwrap_or(false))
+        .await
+        .inspect_err(|e| warn!("Upsert rejected for vehicle {}: {:?}", id, e))?;
+
+    Span::current().record("created", created);
+    let etag = [(header::ETAG, vehicle.etag())];
+    if created {
+        info!("Vehicle created with ID: {}", id);
+        let location = [(header::LOCATION, format!("/api/v1/vehicles/{id}"))];
+        Ok((
+            StatusCode::CREATED,
+            location,
+            etag,
+            ApiResponse::new(VehicleResponse::from(vehicle)),
+        )
+            .into_response())
+    } else {
+        info!("Vehicle updated: {:?}", vehicle);
+        Ok((etag, ApiResponse::new(VehicleResponse::from(vehicle))).into_response())
+    }
+}
+
+/// Resolve the `If-Match` precondition, requiring one when strict concurrency is enabled
+fn if_match_precondition(
+    headers: &HeaderMap,
+    config: &AppConfig,
+) -> Result<Option<IfMatch>, ServerError> {
+    match IfMatch::from_headers(headers) {
+        None if config.strict_concurrency => Err(ServerError::PreconditionRequired),
+        if_match => Ok(if_match),
+    }
+}
+
+/// Vehicles may only reference owners that exist
+async fn check_owner(
+    owners: &InMemoryOwnerRepo,
+    owner_id: Option<OwnerId>,
+) -> Result<(), ServerError> {
+    match owner_id {
+        Some(owner_id) if !owners.exists(owner_id).await => {
+            warn!("Unknown owner referenced: {}", owner_id);
+            Err(ServerError::UnknownOwner {
+                owner_id: owner_id.to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[debug_handler]
+#[instrument(skip(state, params), fields(vehicle_id = %id, permanent = params.permanent.unwrap_or(false)))]
+pub async fn delete_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    Query(params): Query<DeleteParams>,
+) -> Result<StatusCode, ServerError> {
+    let deleted = if params.permanent.unwrap_or(false) {
+        info!("Permanently deleting vehicle with ID: {}", id);
+        state.vehicle_repo.delete_vehicle(id).await?
+    } else {
+        info!("Soft deleting vehicle with ID: {}", id);
+        state.vehicle_repo.soft_delete(id).await?
+    };
+
+    match deleted {
+        Some(_) => {
+            info!("Vehicle deleted with ID: {}", id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => {
+            warn!("Vehicle not found with ID: {}", id);
+            Err(ServerError::not_found("vehicle", id))
+        }
+    }
+}
+
+#[debug_handler]
+#[instrument(skip(state), fields(vehicle_id = %id))]
+pub async fn restore_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+) -> Result<ApiResponse<VehicleResponse>, ServerError> {
+    info!("Restoring vehicle with ID: {}", id);
+
+    let vehicle = state
+        .vehicle_repo
+        .restore(id)
+        .await
+        .inspect_err(|e| warn!("Restore rejected for vehicle {}: {:?}", id, e))?;
+
+    info!("Vehicle restored: {:?}", vehicle);
+    Ok(ApiResponse::new(vehicle.into()))
+}
+
+#[debug_handler]
+#[instrument(skip(state, request), fields(vehicle_id = %id, added = request.tags.len()))]
+pub async fn add_vehicle_tags(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    ValidatedPayload(request): ValidatedPayload<TagsRequest>,
+) -> Result<Response, ServerError> {
+    info!("Adding {} tags to vehicle {}", request.tags.len(), id);
+
+    let vehicle = state
+        .vehicle_repo
+        .add_tags(id, request.tags)
+        .await
+        .inspect_err(|e| warn!("Adding tags rejected for vehicle {}: {:?}", id, e))?;
+
+    info!("Vehicle tags are now {:?}", vehicle.tags);
+    let etag = [(header::ETAG, vehicle.etag())];
+    Ok((etag, ApiResponse::new(VehicleResponse::from(vehicle))).into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state), fields(vehicle_id = %id, tag = %tag))]
+pub async fn remove_vehicle_tag(
+    State(state): State<AppState>,
+    ValidatedPath(TagPath { id, tag }): ValidatedPath<TagPath>,
+) -> Result<Response, ServerError> {
+    info!("Removing tag {} from vehicle {}", tag, id);
+
+    let vehicle = state
+        .vehicle_repo
+        .remove_tag(id, &tag)
+        .await
+        .inspect_err(|e| warn!("Removing tag rejected for vehicle {}: {:?}", id, e))?;
+
+    info!("Vehicle tags are now {:?}", vehicle.tags);
+    let etag = [(header::ETAG, vehicle.etag())];
+    Ok((etag, ApiResponse::new(VehicleResponse::from(vehicle))).into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, headers, change), fields(vehicle_id = %id, status = change.status.as_str()))]
+pub async fn put_vehicle_status(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    Query(params): Query<StatusParams>,
+    headers: HeaderMap,
+    ValidatedPayload(change): ValidatedPayload<StatusChange>,
+) -> Result<Response, ServerError> {
+    let if_match = if_match_precondition(&headers, &state.config)?;
+    info!(
+        "Changing status of vehicle {} to {}",
+        id,
+        change.status.as_str()
+    );
+
+    let Some(existing) = state.vehicle_repo.get_vehicle(id).await? else {
+        warn!("Vehicle not found with ID: {}", id);
+        return Err(ServerError::not_found("vehicle", id));
+    };
+    if !existing
+        .status
+        .can_transition(change.status, params.force.unwrap_or(false))
+    {
+        warn!(
+            "Refused status transition {} -> {}",
+            existing.status.as_str(),
+            change.status.as_str()
+        );
+        return Err(ServerError::InvalidTransition {
+            current_status: existing.status.as_str().to_string(),
+            requested_status: change.status.as_str().to_string(),
+        });
+    }
+
+    // Without a client precondition, guard against the status changing since it was checked
+    let if_match = if_match.unwrap_or_else(|| IfMatch::Tags(vec![existing.etag()]));
+    let vehicle = state
+        .vehicle_repo
+        .set_status(id, change.status, Some(&if_match))
+        .await
+        .inspect_err(|e| warn!("Status change rejected for vehicle {}: {:?}", id, e))?;
+
+    info!("Vehicle status changed: {:?}", vehicle);
+    let etag = [(header::ETAG, vehicle.etag())];
+    Ok((etag, ApiResponse::new(VehicleResponse::from(vehicle))).into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, request), fields(requested = request.ids.len(), deleted = field::Empty))]
+pub async fn delete_vehicles(
+    State(state): State<AppState>,
+    ValidatedPayload(request): ValidatedPayload<BulkDeleteRequest>,
+) -> Result<ApiResponse<BulkDeleteResult>, ServerError> {
+    info!("Bulk deleting {} vehicles", request.ids.len());
+
+    let (deleted, missing) = state
+        .vehicle_repo
+        .delete_vehicles(&request.vehicle_ids())
+        .await?;
+
+    Span::current().record("deleted", deleted);
+    info!("Deleted {} vehicles, {} missing", deleted, missing.len());
+    Ok(ApiResponse::new(BulkDeleteResult { deleted, missing }))
+}
+
+#[debug_handler]
+#[instrument(skip(state, headers, patch), fields(vehicle_id = %id))]
+pub async fn patch_vehicle(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    Query(params): Query<UpdateParams>,
+    headers: HeaderMap,
+    ValidatedPayload(patch): ValidatedPayload<VehiclePatch>,
+) -> Result<Response, ServerError> {
+    let if_match = if_match_precondition(&headers, &state.config)?;
+    info!("Patching vehicle with ID: {}", id);
+
+    let Some(existing) = state.vehicle_repo.get_vehicle(id).await? else {
+        warn!("Vehicle not found with ID: {}", id);
+        return Err(ServerError::not_found("vehicle", id));
+    };
+
+    // Merged metadata can outgrow the limits even when the patch alone is within them
+    let vehicle = patch.apply(existing.into());
+    vehicle
+        .validate_with_config(&state.config)
+        .map_err(|errors| ServerError::validation(errors, &state.config))?;
+    check_owner(&state.owner_repo, vehicle.owner_id).await?;
+    let vehicle = state
+        .vehicle_repo
+        .update_vehicle(
+            id,
+            vehicle,
+            if_match.as_ref(),
+            params.correction.unwrap_or(false),
+        )
+        .await
+        .inspect_err(|e| warn!("Patch rejected for vehicle {}: {:?}", id, e))?;
+
+    info!("Vehicle patched: {:?}", vehicle);
+    let etag = [(header::ETAG, vehicle.etag())];
+    Ok((etag, ApiResponse::new(VehicleResponse::from(vehicle))).into_response())
+}
+
+#[debug_handler]
+#[instrument(skip(state, ctx, params), fields(vehicle_id = %id))]
+pub async fn get_vehicle_history(
+    State(state): State<AppState>,
+    ctx: RequestContext,
+    ValidatedPath(id): ValidatedPath<VehicleId>,
+    ValidatedQuery(params): ValidatedQuery<PageParams>,
+) -> Result<ApiListResponse<VehicleRevision>, ServerError> {
+    let (limit, offset) = (params.limit(), params.offset());
+    info!("Fetching history for vehicle with ID: {}", id);
+
+    let Some(revisions) = state.vehicle_repo.get_history(id).await? else {
+        warn!("Vehicle not found with ID: {}", id);
+        return Err(ServerError::not_found("vehicle", id));
+    };
+
+    let total = revisions.len();
+    let items = revisions.into_iter().skip(offset).take(limit).collect();
+    info!("Found {} revisions", total);
+    Ok(ApiListResponse::new(items, &ctx)
+        .total(total)
+        .page(limit, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        features::vehicle::{
+            mock_repo::MockVehicleRepo,
+            repo::{CapacityPolicy, InMemoryVehicleRepo},
+        },
+        test_app::{TestApp, TestResponse},
+    };
+    use axum::{extract::Request, http::Method};
+    use chrono::{DateTime, Datelike, Utc};
+    use serde_json::json;
+
+    fn corolla() -> Value {
+        json!({ "manufacturer": "Toyota", "model": "Corolla", "year": 2020 })
+    }
+
+    #[tokio::test]
+    async fn post_vehicle_answers_201_with_its_location() {
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+
+        assert_eq!(response.status, StatusCode::CREATED);
+        let id = response.json()["data"]["id"].as_str().unwrap().to_string();
+        assert_eq!(
+            response.headers[header::LOCATION],
+            format!("/api/v1/vehicles/{id}")
+        );
+        assert_eq!(repo.calls_to("post_vehicle").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn post_vehicle_answers_503_when_the_backend_is_unavailable() {
+        let repo = Arc::new(
+            MockVehicleRepo::default().with_failure("post_vehicle", RepoError::Unavailable),
+        );
+        let app = TestApp::with_repo(repo.clone());
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        let problem = response.json();
+        assert_eq!(problem["code"], "unavailable");
+        assert_eq!(
+            problem["detail"],
+            "the vehicle store can't be reached right now"
+        );
+        assert_eq!(repo.calls_to("post_vehicle").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn post_vehicle_answers_500_when_the_backend_fails() {
+        let repo = Arc::new(MockVehicleRepo::default().with_failure(
+            "post_vehicle",
+            RepoError::Backend("corrupt record".to_string()),
+        ));
+        let app = TestApp::with_repo(repo);
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+
+        assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.json();
+        assert_eq!(body["code"], "internal_error");
+        assert!(
+            !body.to_string().contains("corrupt record"),
+            "backend details stay in the logs"
+        );
+    }
+
+    #[tokio::test]
+    async fn post_vehicle_applies_the_model_year_offset_from_app_state() {
+        let next_year = Utc::now().year() + 1;
+        let body = json!({ "manufacturer": "Toyota", "model": "Corolla", "year": next_year });
+        let pinned = |model_year_offset| {
+            let config = AppConfig {
+                model_year_offset,
+                ..AppConfig::default()
+            };
+            TestApp::with_config(Arc::new(MockVehicleRepo::default()), config)
+        };
+
+        let response = pinned(0)
+            .json(Method::POST, "/api/v1/vehicles", &body)
+            .await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.json()["invalid-params"][0]["code"], "model_year");
+
+        let response = pinned(1)
+            .json(Method::POST, "/api/v1/vehicles", &body)
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn validation_errors_name_nested_fields_by_their_path() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let mut body = corolla();
+        body["engine"] = json!({ "displacementCc": 1800, "cylinders": 20, "powerKw": 90 });
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let params = &response.json()["invalid-params"];
+        assert_eq!(params.as_array().map(Vec::len), Some(1));
+        assert_eq!(params[0]["name"], "engine.cylinders");
+    }
+
+    #[tokio::test]
+    async fn validation_errors_are_capped_at_the_configured_limit() {
+        let config = AppConfig {
+            max_validation_errors: 2,
+            ..AppConfig::default()
+        };
+        let app = TestApp::with_config(Arc::new(MockVehicleRepo::default()), config);
+        let body =
+            json!({ "manufacturer": "X", "model": "Y", "year": 1700, "mileageKm": 3_000_000 });
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response.json();
+        let names: Vec<&str> = body["invalid-params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|param| param["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["manufacturer", "mileageKm"]);
+        assert_eq!(body["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn batch_errors_keep_their_nested_path_and_one_per_failed_vehicle() {
+        let config = AppConfig {
+            max_validation_errors: 1,
+            ..AppConfig::default()
+        };
+        let app = TestApp::with_config(Arc::new(MockVehicleRepo::default()), config);
+        let mut bad_engine = corolla();
+        bad_engine["engine"] = json!({ "displacementCc": 1800, "cylinders": 20, "powerKw": 0 });
+        let body = json!({
+            "vehicles": [
+                bad_engine,
+                corolla(),
+                { "manufacturer": "X", "model": "Y", "year": 2020 },
+            ]
+        });
+
+        let response = app
+            .json(Method::POST, "/api/v1/vehicles/batch", &body)
+            .await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        let body = response.json()["data"].clone();
+        assert_eq!(body["created"], 1);
+        assert_eq!(
+            body["results"][0]["errors"],
+            json!(["engine.cylinders: cylinders must be between 0 and 16"])
+        );
+        assert!(body["results"][1]["id"].is_string());
+        assert_eq!(
+            body["results"][2]["errors"],
+            json!(["manufacturer: manufacturer must be between 3 and 25 characters"])
+        );
+        assert_eq!(body["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn malformed_vehicle_ids_are_invalid_path_parameters() {
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+
+        for id in ["not-a-uuid", "67e55044-10b1-426f-9247-bb680e5fe0c"] {
+            let response = app.get(&format!("/api/v1/vehicles/{id}")).await;
+
+            assert_eq!(response.status, StatusCode::BAD_REQUEST, "{id}");
+            let problem = response.json();
+            assert_eq!(problem["code"], "invalid_path_parameter", "{id}");
+            assert_eq!(problem["type"], "/problems/invalid-path", "{id}");
+            assert_eq!(problem["value"], id);
+        }
+        assert!(repo.calls_to("get_vehicle").is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_refused_atomic_batch_is_a_validation_problem() {
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+        let body = json!({
+            "vehicles": [corolla(), { "manufacturer": "X", "model": "Y", "year": 2020 }]
+        });
+
+        let response = app
+            .json(Method::POST, "/api/v1/vehicles/batch?atomic=true", &body)
+            .await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.headers[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let problem = response.json();
+        assert_eq!(problem["code"], "validation_failed");
+        assert_eq!(problem["type"], "/problems/batch-rejected");
+        assert_eq!(
+            problem["detail"],
+            "1 of 2 vehicles failed validation, none were created"
+        );
+        assert_eq!(problem["results"][0], json!({ "index": 0 }));
+        assert_eq!(problem["results"][1]["index"], 1);
+        assert!(repo.calls_to("post_vehicles_atomic").is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_refused_strict_import_is_a_bad_request_problem() {
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles/import?strict=true")
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body(Body::from(
+                "manufacturer,model,year\nToyota,Corolla,2020\nX,Y,2020\n",
+            ))
+            .unwrap();
+
+        let response = app.send(request).await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        let problem = response.json();
+        assert_eq!(problem["code"], "bad_request");
+        assert_eq!(problem["type"], "/problems/import-rejected");
+        assert_eq!(problem["detail"], "1 rows failed, none were imported");
+        assert_eq!(problem["failed"][0]["line"], 3);
+        assert!(repo.calls_to("post_vehicles").is_empty());
+    }
+
+    #[tokio::test]
+    async fn problems_carry_the_request_id_of_their_header() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let atomic = json!({ "vehicles": [{ "manufacturer": "X", "model": "Y", "year": 2020 }] });
+        let responses = [
+            app.get(&format!("/api/v1/vehicles/{}", uuid::Uuid::new_v4()))
+                .await,
+            app.get("/api/v1/vehicles/not-a-uuid").await,
+            app.get("/api/v1/no-such-route").await,
+            app.json(Method::POST, "/api/v1/vehicles", &json!({})).await,
+            app.json(Method::POST, "/api/v1/vehicles/batch?atomic=true", &atomic)
+                .await,
+            app.json(Method::DELETE, "/api/v1/vehicles/stats", &json!({}))
+                .await,
+        ];
+
+        for response in responses {
+            let header = response.headers["x-request-id"].to_str().unwrap();
+            assert!(!header.is_empty());
+            assert_eq!(response.json()["request_id"], header, "{}", response.status);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_request_id_sent_by_the_client_is_the_one_in_the_problem() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let request = Request::builder()
+            .uri("/api/v1/vehicles/not-a-uuid")
+            .header("x-request-id", "client-chosen-id")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.send(request).await;
+
+        assert_eq!(response.headers["x-request-id"], "client-chosen-id");
+        assert_eq!(response.json()["request_id"], "client-chosen-id");
+    }
+
+    #[tokio::test]
+    async fn soft_deleted_vehicles_answer_404_until_restored() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let created = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+        let uri = created.headers[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let deleted = app.json(Method::DELETE, &uri, &json!({})).await;
+        assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+
+        assert_eq!(app.get(&uri).await.status, StatusCode::NOT_FOUND);
+        let put = app.json(Method::PUT, &uri, &corolla()).await;
+        assert_eq!(put.status, StatusCode::NOT_FOUND);
+        let patch = app
+            .json(Method::PATCH, &uri, &json!({ "model": "Yaris" }))
+            .await;
+        assert_eq!(patch.status, StatusCode::NOT_FOUND);
+
+        let restored = app
+            .json(Method::POST, &format!("{uri}/restore"), &json!({}))
+            .await;
+        assert_eq!(restored.status, StatusCode::OK);
+        assert_eq!(app.get(&uri).await.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_full_store_answers_507_with_its_limit() {
+        let repo = InMemoryVehicleRepo::default().with_capacity(Some(1), CapacityPolicy::Reject);
+        let app = TestApp::with_repo(Arc::new(repo));
+        let first = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+        assert_eq!(first.status, StatusCode::CREATED);
+        let mut yaris = corolla();
+        yaris["model"] = json!("Yaris");
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &yaris).await;
+
+        assert_eq!(response.status, StatusCode::INSUFFICIENT_STORAGE);
+        let problem = response.json();
+        assert_eq!(problem["type"], "/problems/capacity-exceeded");
+        assert_eq!(problem["limit"], 1);
+    }
+
+    /// The whole problem, its request id checked and then dropped since it differs per run
+    async fn validation_problem(body: Value) -> Value {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let response = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let mut problem = response.json();
+        let request_id = problem
+            .as_object_mut()
+            .unwrap()
+            .remove("request_id")
+            .unwrap();
+        assert_eq!(
+            request_id,
+            response.headers["x-request-id"].to_str().unwrap()
+        );
+        problem
+    }
+
+    #[tokio::test]
+    async fn a_single_invalid_field_has_the_exact_problem_shape() {
+        let mut body = corolla();
+        body["manufacturer"] = json!("X");
+
+        assert_eq!(
+            validation_problem(body).await,
+            json!({
+                "type": "/problems/validation-failed",
+                "title": "Unprocessable Entity",
+                "status": 422,
+                "detail": "the request has fields that failed validation",
+                "instance": "/api/v1/vehicles",
+                "code": "validation_failed",
+                "invalid-params": [{
+                    "name": "manufacturer",
+                    "reason": "manufacturer must be between 3 and 25 characters",
+                    "code": "length",
+                    "params": { "min": 3, "max": 25 },
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn every_invalid_field_is_listed_by_name_with_its_rule() {
+        let body = json!({ "manufacturer": "X", "model": "Y", "year": 1700 });
+
+        let problem = validation_problem(body).await;
+
+        assert_eq!(
+            problem["invalid-params"],
+            json!([
+                {
+                    "name": "manufacturer",
+                    "reason": "manufacturer must be between 3 and 25 characters",
+                    "code": "length",
+                    "params": { "min": 3, "max": 25 },
+                },
+                {
+                    "name": "model",
+                    "reason": "model must be between 3 and 25 characters",
+                    "code": "length",
+                    "params": { "min": 3, "max": 25 },
+                },
+                {
+                    "name": "year",
+                    "reason": "year must be between 1886 and 2100",
+                    "code": "range",
+                    "params": { "min": 1886, "max": 2100 },
+                },
+            ])
+        );
+        assert!(problem.get("truncated").is_none());
+    }
+
+    #[tokio::test]
+    async fn a_nested_invalid_field_is_named_by_its_dotted_path() {
+        let mut body = corolla();
+        body["engine"] = json!({ "displacementCc": 1800, "cylinders": 20, "powerKw": 0 });
+
+        let problem = validation_problem(body).await;
+
+        assert_eq!(
+            problem["invalid-params"],
+            json!([
+                {
+                    "name": "engine.cylinders",
+                    "reason": "cylinders must be between 0 and 16",
+                    "code": "range",
+                    "params": { "max": 16 },
+                },
+                {
+                    "name": "engine.powerKw",
+                    "reason": "powerKw must be between 1 and 2000",
+                    "code": "range",
+                    "params": { "min": 1, "max": 2000 },
+                },
+            ])
+        );
+    }
+
+    /// The members every problem has, whatever produced it
+    fn assert_problem(response: &TestResponse, status: StatusCode, kind: &str, instance: &str) {
+        assert_eq!(response.status, status);
+        assert_eq!(
+            response.headers[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let problem = response.json();
+        assert_eq!(problem["type"], kind);
+        assert_eq!(problem["title"], status.canonical_reason().unwrap());
+        assert_eq!(problem["status"], status.as_u16());
+        assert_eq!(problem["instance"], instance);
+        assert_eq!(
+            problem["request_id"],
+            response.headers["x-request-id"].to_str().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn an_unknown_route_is_a_not_found_problem() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+
+        let response = app.get("/api/v1/no-such-route").await;
+
+        assert_problem(
+            &response,
+            StatusCode::NOT_FOUND,
+            "about:blank",
+            "/api/v1/no-such-route",
+        );
+    }
+
+    #[tokio::test]
+    async fn an_unsupported_method_is_a_method_not_allowed_problem() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+
+        let response = app
+            .json(Method::DELETE, "/api/v1/vehicles/stats", &json!({}))
+            .await;
+
+        assert_problem(
+            &response,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "about:blank",
+            "/api/v1/vehicles/stats",
+        );
+    }
+
+    #[tokio::test]
+    async fn a_missing_vehicle_is_a_not_found_problem() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let id = uuid::Uuid::new_v4();
+        let uri = format!("/api/v1/vehicles/{id}");
+
+        let response = app.get(&uri).await;
+
+        assert_problem(&response, StatusCode::NOT_FOUND, "about:blank", &uri);
+        let problem = response.json();
+        assert_eq!(problem["resource"], "vehicle");
+        assert_eq!(problem["id"], id.to_string());
+    }
+
+    #[tokio::test]
+    async fn an_invalid_vehicle_is_a_validation_problem() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let body = json!({ "manufacturer": "X", "model": "Corolla", "year": 2020 });
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+
+        assert_problem(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "/problems/validation-failed",
+            "/api/v1/vehicles",
+        );
+        assert_eq!(response.json()["invalid-params"][0]["name"], "manufacturer");
+    }
+
+    /// `body` posted as a new vehicle exactly as written, malformed or not
+    async fn post_raw(body: &str) -> TestResponse {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.send(request).await
+    }
+
+    #[tokio::test]
+    async fn unreadable_mistyped_and_invalid_bodies_answer_differently() {
+        let truncated = post_raw(r#"{"manufacturer": "Toyota", "model": "#).await;
+        let mistyped = post_raw(r#"{"manufacturer": "Toyota", "model": 5, "year": 2020}"#).await;
+        let too_short =
+            post_raw(r#"{"manufacturer": "To", "model": "Corolla", "year": 2020}"#).await;
+
+        assert_eq!(truncated.status, StatusCode::BAD_REQUEST);
+        let problem = truncated.json();
+        assert_eq!(problem["type"], "/problems/invalid-body");
+        assert_eq!(problem["kind"], "syntax");
+
+        assert_eq!(mistyped.status, StatusCode::BAD_REQUEST);
+        let problem = mistyped.json();
+        assert_eq!(problem["type"], "/problems/invalid-body");
+        assert_eq!(problem["kind"], "type_mismatch");
+        assert_eq!(problem["field"], "model");
+
+        assert_eq!(too_short.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let problem = too_short.json();
+        assert_eq!(problem["type"], "/problems/validation-failed");
+        assert_eq!(problem["invalid-params"][0]["name"], "manufacturer");
+        assert_eq!(problem["invalid-params"][0]["code"], "length");
+    }
+
+    #[tokio::test]
+    async fn a_syntax_error_is_reported_with_its_line_and_column() {
+        let response = post_raw("{\n  \"manufacturer\": \"Toyota\",\n  \"year\": 2020,\n}").await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        let problem = response.json();
+        assert_eq!(problem["kind"], "syntax");
+        assert_eq!(problem["category"], "syntax");
+        assert_eq!(problem["line"], 4);
+        assert_eq!(problem["column"], 1);
+        assert!(problem["field"].is_null());
+    }
+
+    #[tokio::test]
+    async fn a_mistyped_year_is_a_data_error_naming_the_field() {
+        let response =
+            post_raw(r#"{"manufacturer": "Toyota", "model": "Corolla", "year": [2020]}"#).await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        let problem = response.json();
+        assert_eq!(problem["kind"], "type_mismatch");
+        assert_eq!(problem["category"], "data");
+        assert_eq!(problem["field"], "year");
+    }
+
+    #[tokio::test]
+    async fn a_truncated_body_is_an_eof_error_where_it_stops() {
+        let response = post_raw(r#"{"manufacturer": "Toyota", "model": "#).await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        let problem = response.json();
+        assert_eq!(problem["kind"], "syntax");
+        assert_eq!(problem["category"], "eof");
+        assert_eq!(problem["line"], 1);
+        assert_eq!(problem["column"], 36);
+    }
+
+    #[tokio::test]
+    async fn an_oversized_body_answers_413_with_the_limit() {
+        let config = AppConfig {
+            max_body_bytes: 256,
+            ..AppConfig::default()
+        };
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_config(repo.clone(), config);
+        let mut body = corolla();
+        body["notes"] = json!("x".repeat(1024));
+        let form = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!(
+                "manufacturer=Toyota&model=Corolla&year=2020&notes={}",
+                "x".repeat(1024)
+            )))
+            .unwrap();
+
+        let responses = [
+            app.json(Method::POST, "/api/v1/vehicles", &body).await,
+            app.send(form).await,
+        ];
+
+        for response in responses {
+            assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+            let problem = response.json();
+            assert_eq!(problem["type"], "/problems/payload-too-large");
+            assert_eq!(problem["limit"], 256);
+            assert_eq!(
+                problem["detail"],
+                "the request body is larger than the 256 byte limit"
+            );
+        }
+        assert!(repo.calls_to("post_vehicle").is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_import_accepts_bodies_over_the_api_limit() {
+        let config = AppConfig {
+            max_body_bytes: 256,
+            ..AppConfig::default()
+        };
+        let app = TestApp::with_config(Arc::new(MockVehicleRepo::default()), config);
+        let mut csv = String::from("manufacturer,model,year\n");
+        for year in 1990..2020 {
+            csv.push_str(&format!("Toyota,Corolla,{year}\n"));
+        }
+        assert!(csv.len() > 256);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles/import")
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body(Body::from(csv))
+            .unwrap();
+
+        let response = app.send(request).await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.json()["data"]["imported"], 30);
+    }
+
+    /// The vehicle stored by `request`, without the fields the server assigns per vehicle
+    async fn stored_by(request: Request) -> Value {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let created = app.send(request).await;
+        assert_eq!(created.status, StatusCode::CREATED);
+        let uri = created.headers[header::LOCATION].to_str().unwrap();
+        let mut vehicle = app.get(uri).await.json()["data"].clone();
+        for assigned in ["id", "createdAt", "updatedAt"] {
+            vehicle.as_object_mut().unwrap().remove(assigned);
+        }
+        vehicle
+    }
+
+    #[tokio::test]
+    async fn a_vehicle_posted_as_json_or_as_a_form_is_stored_the_same() {
+        let json_body = json!({
+            "manufacturer": "Toyota",
+            "model": "Corolla",
+            "year": 2020,
+            "mileageKm": 12000,
+            "color": "blue",
+        });
+        let as_json = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json_body.to_string()))
+            .unwrap();
+        let as_form = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/vehicles")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(
+                "manufacturer=Toyota&model=Corolla&year=2020&mileageKm=12000&color=blue",
+            ))
+            .unwrap();
+
+        let from_json = stored_by(as_json).await;
+        let from_form = stored_by(as_form).await;
+
+        assert_eq!(from_json["manufacturer"], "Toyota");
+        assert_eq!(from_json["mileageKm"], 12000);
+        assert_eq!(from_json, from_form);
+    }
+
+    #[tokio::test]
+    async fn padding_doesnt_count_towards_the_minimum_length() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let mut body = corolla();
+        body["manufacturer"] = json!("  ab  ");
+
+        let response = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let param = &response.json()["invalid-params"][0];
+        assert_eq!(param["name"], "manufacturer");
+        assert_eq!(param["code"], "length");
+    }
+
+    #[tokio::test]
+    async fn strings_are_stored_trimmed_with_single_spaces() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let body =
+            json!({ "manufacturer": " Toyota ", "model": "  Land   Cruiser ", "year": 2020 });
+
+        let created = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+        assert_eq!(created.status, StatusCode::CREATED);
+        let uri = created.headers[header::LOCATION].to_str().unwrap();
+
+        let vehicle = app.get(uri).await.json()["data"].clone();
+        assert_eq!(vehicle["manufacturer"], "Toyota");
+        assert_eq!(vehicle["model"], "Land Cruiser");
+        let listed = app.get("/api/v1/vehicles?manufacturer=Toyota").await.json();
+        assert_eq!(listed["data"].as_array().map(Vec::len), Some(1));
+    }
+
+    async fn get_if_none_match(app: &TestApp, uri: &str, etag: &str) -> TestResponse {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap();
+        app.send(request).await
+    }
+
+    #[tokio::test]
+    async fn the_etag_round_trips_until_the_vehicle_changes() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let created = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+        let uri = created.headers[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let first = app.get(&uri).await;
+        let etag = first.headers[header::ETAG].to_str().unwrap().to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'), "{etag}");
+
+        let unchanged = get_if_none_match(&app, &uri, &etag).await;
+        assert_eq!(unchanged.status, StatusCode::NOT_MODIFIED);
+        assert!(unchanged.body.is_empty());
+        assert_eq!(unchanged.headers[header::ETAG], etag.as_str());
+
+        let mut yaris = corolla();
+        yaris["model"] = json!("Yaris");
+        let put = app.json(Method::PUT, &uri, &yaris).await;
+        assert_eq!(put.status, StatusCode::OK);
+        let after_put = get_if_none_match(&app, &uri, &etag).await;
+        assert_eq!(after_put.status, StatusCode::OK);
+        assert_eq!(after_put.json()["data"]["model"], "Yaris");
+        let put_etag = after_put.headers[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_ne!(put_etag, etag);
+
+        let patch = app
+            .json(Method::PATCH, &uri, &json!({ "mileageKm": 5000 }))
+            .await;
+        assert_eq!(patch.status, StatusCode::OK);
+        let after_patch = get_if_none_match(&app, &uri, &put_etag).await;
+        assert_eq!(after_patch.status, StatusCode::OK);
+        assert_ne!(after_patch.headers[header::ETAG], put_etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn a_put_moves_updated_at_on_and_keeps_created_at() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let created = app.json(Method::POST, "/api/v1/vehicles", &corolla()).await;
+        let uri = created.headers[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let timestamps = |vehicle: &Value| {
+            let at = |field: &str| {
+                DateTime::parse_from_rfc3339(vehicle[field].as_str().unwrap())
+                    .unwrap()
+                    .with_timezone(&Utc)
+            };
+            (at("createdAt"), at("updatedAt"))
+        };
+        let (created_at, updated_at) = timestamps(&app.get(&uri).await.json()["data"]);
+        assert_eq!(created_at, updated_at);
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let mut yaris = corolla();
+        yaris["model"] = json!("Yaris");
+        let put = app.json(Method::PUT, &uri, &yaris).await;
+        assert_eq!(put.status, StatusCode::OK);
+
+        let (created_after_put, updated_after_put) =
+            timestamps(&app.get(&uri).await.json()["data"]);
+        assert_eq!(created_after_put, created_at);
+        assert!(
+            updated_after_put > created_at,
+            "{updated_after_put} > {created_at}"
+        );
+        assert_eq!(timestamps(&put.json()["data"]).1, updated_after_put);
+    }
+
+    #[tokio::test]
+    async fn lists_show_summaries_unless_the_full_view_is_asked_for() {
+        let app = TestApp::with_repo(Arc::new(InMemoryVehicleRepo::default()));
+        let mut body = corolla();
+        body["mileageKm"] = json!(12000);
+        body["color"] = json!("blue");
+        let created = app.json(Method::POST, "/api/v1/vehicles", &body).await;
+        let uri = created.headers[header::LOCATION].to_str().unwrap();
+        let stored = app.get(uri).await.json()["data"].clone();
+        let keys = |value: &Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+
+        let summaries = app.get("/api/v1/vehicles").await;
+        let explicit = app.get("/api/v1/vehicles?view=summary").await;
+        let full = app.get("/api/v1/vehicles?view=full").await;
+
+        let summary = &summaries.json()["data"][0];
+        assert_eq!(keys(summary), ["id", "manufacturer", "model", "year"]);
+        assert_eq!(summary["id"], stored["id"]);
+        assert_eq!(summary["year"], 2020);
+        assert_eq!(explicit.json()["data"][0], *summary);
+        assert_eq!(full.json()["data"][0], stored);
+        assert!(keys(&stored).len() > 4);
+    }
+
+    #[tokio::test]
+    async fn a_limit_past_the_maximum_is_a_field_level_error() {
+        let repo = Arc::new(MockVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+
+        let response = app.get("/api/v1/vehicles?limit=99999").await;
+
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let problem = response.json();
+        assert_eq!(problem["type"], "/problems/validation-failed");
+        assert_eq!(
+            problem["invalid-params"],
+            json!([{
+                "name": "limit",
+                "reason": "limit must be between 1 and 500",
+                "code": "range",
+                "params": { "min": 1, "max": 500 },
+            }])
+        );
+        assert!(repo.calls_to("list_summaries").is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_negative_offset_is_an_invalid_query() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+
+        let response = app.get("/api/v1/vehicles?offset=-1").await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["type"], "/problems/invalid-query");
+    }
+
+    #[tokio::test]
+    async fn an_atomic_batch_with_a_taken_vin_creates_nothing() {
+        let repo = Arc::new(InMemoryVehicleRepo::default());
+        let app = TestApp::with_repo(repo.clone());
+        let mut owner = corolla();
+        owner["vin"] = json!("1HGCM82633A004352");
+        let first = app.json(Method::POST, "/api/v1/vehicles", &owner).await;
+        assert_eq!(first.status, StatusCode::CREATED);
+        let mut yaris = corolla();
+        yaris["model"] = json!("Yaris");
+        let mut taken = corolla();
+        taken["model"] = json!("Prius");
+        taken["vin"] = json!("1HGCM82633A004352");
+        let body = json!({ "vehicles": [yaris, taken] });
+
+        let response = app
+            .json(Method::POST, "/api/v1/vehicles/batch?atomic=true", &body)
+            .await;
+
+        assert_eq!(response.status, StatusCode::CONFLICT);
+        assert_eq!(response.json()["type"], "/problems/vin-conflict");
+        assert_eq!(repo.vehicles().len(), 1);
+    }
 }
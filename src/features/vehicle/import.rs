@@ -0,0 +1,201 @@
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use std::collections::BTreeMap;
+use validator::ValidationErrors;
+
+use crate::{
+    features::vehicle::model::{
+        CreateVehicleRequest, FuelType, ImportFailure, parse_date, parse_year,
+    },
+    utils::{
+        config::AppConfig,
+        validator::{Normalise, ValidateWithConfig, field_errors},
+    },
+};
+
+/// Column positions of the vehicle fields within a CSV row
+struct Columns {
+    manufacturer: usize,
+    model: usize,
+    year: usize,
+    /// Optional, so rows without a reading are still importable
+    mileage_km: Option<usize>,
+    fuel_type: Option<usize>,
+    /// JSON object, as written by the export
+    metadata: Option<usize>,
+    first_registered: Option<usize>,
+}
+
+impl Columns {
+    /// Resolve positions from a header row, or `None` if the row isn't a header
+    fn from_header(record: &StringRecord) -> Option<Self> {
+        let position = |name: &str| {
+            record
+                .iter()
+                .position(|column| column.eq_ignore_ascii_case(name))
+        };
+        Some(Self {
+            manufacturer: position("manufacturer")?,
+            model: position("model")?,
+            year: position("year")?,
+            mileage_km: position("mileage_km"),
+            fuel_type: position("fuel_type"),
+            metadata: position("metadata"),
+            first_registered: position("first_registered"),
+        })
+    }
+
+    fn vehicle(&self, record: &StringRecord) -> Result<CreateVehicleRequest, String> {
+        let field = |index: usize, name: &str| {
+            record
+                .get(index)
+                .map(String::from)
+                .ok_or_else(|| format!("{name}: missing column"))
+        };
+        Ok(CreateVehicleRequest {
+            manufacturer: field(self.manufacturer, "manufacturer")?,
+            model: field(self.model, "model")?,
+            year: parse_year(&field(self.year, "year")?).map_err(|e| format!("year: {e}"))?,
+            vin: None,
+            mileage_km: self.mileage_km(record)?,
+            color: None,
+            license_plate: None,
+            price: None,
+            fuel_type: self.fuel_type(record)?,
+            tags: Vec::new(),
+            metadata: self.metadata(record)?,
+            engine: None,
+            owner_id: None,
+            first_registered: self.first_registered(record)?,
+        })
+    }
+
+    fn mileage_km(&self, record: &StringRecord) -> Result<Option<u32>, String> {
+        match self.mileage_km.and_then(|index| record.get(index)) {
+            None | Some("") => Ok(None),
+            Some(text) => text
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("mileage_km: '{text}' is not a valid mileage")),
+        }
+    }
+
+    fn fuel_type(&self, record: &StringRecord) -> Result<Option<FuelType>, String> {
+        match self.fuel_type.and_then(|index| record.get(index)) {
+            None | Some("") => Ok(None),
+            Some(text) => text
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("fuel_type: {e}")),
+        }
+    }
+
+    fn first_registered(&self, record: &StringRecord) -> Result<Option<NaiveDate>, String> {
+        match self.first_registered.and_then(|index| record.get(index)) {
+            None | Some("") => Ok(None),
+            Some(text) => parse_date(text)
+                .map(Some)
+                .map_err(|e| format!("first_registered: {e}")),
+        }
+    }
+
+    fn metadata(&self, record: &StringRecord) -> Result<BTreeMap<String, String>, String> {
+        match self.metadata.and_then(|index| record.get(index)) {
+            None | Some("") => Ok(BTreeMap::new()),
+            Some(text) => serde_json::from_str(text).map_err(|e| format!("metadata: {e}")),
+        }
+    }
+}
+
+/// Headerless files are read as `manufacturer,model,year[,mileage_km[,fuel_type]]`
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            manufacturer: 0,
+            model: 1,
+            year: 2,
+            mileage_km: Some(3),
+            fuel_type: Some(4),
+            metadata: None,
+            first_registered: None,
+        }
+    }
+}
+
+/// Parse and validate a CSV body, splitting it into importable vehicles and failed lines
+pub fn parse_csv(
+    body: &str,
+    config: &AppConfig,
+) -> (Vec<CreateVehicleRequest>, Vec<ImportFailure>) {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(body.as_bytes());
+
+    let mut columns: Option<Columns> = None;
+    let mut vehicles = Vec::new();
+    let mut failed = Vec::new();
+
+    for (index, result) in reader.records().enumerate() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(index as u64 + 1, |p| p.line());
+                failed.push(ImportFailure {
+                    line,
+                    errors: vec![e.to_string()],
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(index as u64 + 1, |p| p.line());
+
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let columns = match &columns {
+            Some(columns) => columns,
+            None => {
+                let detected = Columns::from_header(&record);
+                let is_header = detected.is_some();
+                let columns = columns.insert(detected.unwrap_or_default());
+                if is_header {
+                    continue;
+                }
+                columns
+            }
+        };
+
+        let vehicle = columns
+            .vehicle(&record)
+            .map_err(|error| vec![error])
+            .and_then(|mut vehicle| {
+                vehicle.normalise();
+                match vehicle.validate_with_config(config) {
+                    Ok(()) => Ok(vehicle),
+                    Err(e) => Err(validation_messages(&e)),
+                }
+            });
+        match vehicle {
+            Ok(vehicle) => vehicles.push(vehicle),
+            Err(errors) => failed.push(ImportFailure { line, errors }),
+        }
+    }
+
+    (vehicles, failed)
+}
+
+/// Field errors as `field: message` lines, nested fields included by their path, sorted so the
+/// output is stable
+pub fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    let mut messages: Vec<String> = field_errors(errors)
+        .into_iter()
+        .map(|(field, error)| match &error.message {
+            Some(message) => format!("{field}: {message}"),
+            None => format!("{field}: {}", error.code),
+        })
+        .collect();
+    messages.sort();
+    messages
+}
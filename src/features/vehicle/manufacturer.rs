@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+/// Built-in `(alias, canonical)` pairs; canonical names also map to themselves so other
+/// casings of them are folded too
+const BUILT_IN_ALIASES: &[(&str, &str)] = &[
+    ("alfa", "Alfa Romeo"),
+    ("alfa romeo", "Alfa Romeo"),
+    ("audi", "Audi"),
+    ("bmw", "BMW"),
+    ("chevrolet", "Chevrolet"),
+    ("chevy", "Chevrolet"),
+    ("citroen", "Citroën"),
+    ("citroën", "Citroën"),
+    ("ford", "Ford"),
+    ("honda", "Honda"),
+    ("hyundai", "Hyundai"),
+    ("kia", "Kia"),
+    ("mercedes", "Mercedes-Benz"),
+    ("mercedes benz", "Mercedes-Benz"),
+    ("mercedes-benz", "Mercedes-Benz"),
+    ("merc", "Mercedes-Benz"),
+    ("nissan", "Nissan"),
+    ("peugeot", "Peugeot"),
+    ("porsche", "Porsche"),
+    ("renault", "Renault"),
+    ("skoda", "Škoda"),
+    ("škoda", "Škoda"),
+    ("tesla", "Tesla"),
+    ("toyota", "Toyota"),
+    ("volkswagen", "Volkswagen"),
+    ("vw", "Volkswagen"),
+    ("volvo", "Volvo"),
+];
+
+/// Canonical manufacturer names keyed by lowercase alias, so `VW`, `Volkswagen` and
+/// `volkswagen` are stored, filtered and aggregated as one manufacturer
+#[derive(Clone, Debug)]
+pub struct ManufacturerAliases(HashMap<String, String>);
+
+impl Default for ManufacturerAliases {
+    fn default() -> Self {
+        Self(
+            BUILT_IN_ALIASES
+                .iter()
+                .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl ManufacturerAliases {
+    /// Add `alias=Canonical` pairs separated by commas on top of the built-in table, e.g.
+    /// `"chevy=Chevrolet,gm=General Motors"`; entries without a `=` are ignored
+    pub fn extend_from(mut self, raw: &str) -> Self {
+        for (alias, canonical) in raw.split(',').filter_map(|pair| pair.split_once('=')) {
+            let (alias, canonical) = (alias.trim(), canonical.trim());
+            if alias.is_empty() || canonical.is_empty() {
+                continue;
+            }
+            // The canonical name should fold other casings of itself as well
+            self.0
+                .insert(canonical.to_lowercase(), canonical.to_string());
+            self.0.insert(alias.to_lowercase(), canonical.to_string());
+        }
+        self
+    }
+
+    /// The canonical name for `manufacturer`, or the name itself when it has no alias
+    pub fn canonical(&self, manufacturer: &str) -> String {
+        self.0
+            .get(&manufacturer.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| manufacturer.to_string())
+    }
+}
@@ -0,0 +1,278 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        model::{
+            CreateVehicleRequest, PageRequest, PageResult, RestoreMode, RestoreReport, Vehicle,
+            VehicleFilter, VehicleId, VehicleRevision, VehicleSearch, VehicleStats, VehicleStatus,
+            VehicleSummary,
+        },
+        persistent_repo::SnapshotStatus,
+        repo::{ReadPath, RepoError, RepoHealth, StoreCapacity, VehicleRepo},
+    },
+};
+use crate::utils::etag::IfMatch;
+use async_trait::async_trait;
+use metrics::{counter, histogram};
+use std::{sync::Arc, time::Instant};
+use tracing::{Instrument, info_span};
+
+/// Wraps any backend to count and time every operation, labelled by operation and by
+/// `success`/`error`, and to run each one in its own span under the request's
+pub struct MeteredRepo<R: VehicleRepo + ?Sized> {
+    inner: Arc<R>,
+}
+
+impl<R: VehicleRepo + ?Sized> MeteredRepo<R> {
+    pub fn new(inner: Arc<R>) -> Self {
+        Self { inner }
+    }
+
+    async fn observe<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        let span = info_span!("vehicle_repo", operation);
+        let start = Instant::now();
+        let result = call.instrument(span).await;
+        let outcome = if result.is_ok() { "success" } else { "error" };
+        let labels = [("operation", operation), ("outcome", outcome)];
+        counter!("vehicle_repo_operations_total", &labels).increment(1);
+        histogram!("vehicle_repo_operation_duration_seconds", &labels)
+            .record(start.elapsed().as_secs_f64());
+        result
+    }
+}
+
+#[async_trait]
+impl<R: VehicleRepo + ?Sized> VehicleRepo for MeteredRepo<R> {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.observe("get_vehicle", self.inner.get_vehicle(id))
+            .await
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.observe("get_vehicle_by_vin", self.inner.get_vehicle_by_vin(vin))
+            .await
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.observe(
+            "get_vehicle_by_plate",
+            self.inner.get_vehicle_by_plate(plate),
+        )
+        .await
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        self.observe("exists", self.inner.exists(id)).await
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        self.observe("get_vehicles_by_ids", self.inner.get_vehicles_by_ids(ids))
+            .await
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        self.observe("list", self.inner.list(page)).await
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        self.observe("list_summaries", self.inner.list_summaries(page))
+            .await
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        self.observe(
+            "get_vehicles_after",
+            self.inner.get_vehicles_after(filter, cursor, limit),
+        )
+        .await
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        self.observe(
+            "search_vehicles",
+            self.inner.search_vehicles(search, offset, limit),
+        )
+        .await
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        self.observe("count", self.inner.count(filter)).await
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        self.observe("stats", self.inner.stats(filter)).await
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        self.observe("manufacturers", self.inner.manufacturers())
+            .await
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        self.observe(
+            "post_vehicle",
+            self.inner.post_vehicle(vehicle, allow_duplicate),
+        )
+        .await
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.observe("post_vehicles", self.inner.post_vehicles(vehicles))
+            .await
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.observe(
+            "post_vehicles_atomic",
+            self.inner.post_vehicles_atomic(vehicles),
+        )
+        .await
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.observe(
+            "update_vehicle",
+            self.inner.update_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.observe(
+            "upsert_vehicle",
+            self.inner.upsert_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.observe("delete_vehicle", self.inner.delete_vehicle(id))
+            .await
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.observe("soft_delete", self.inner.soft_delete(id))
+            .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.observe("restore", self.inner.restore(id)).await
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.observe("add_tags", self.inner.add_tags(id, tags))
+            .await
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        self.observe("remove_tag", self.inner.remove_tag(id, tag))
+            .await
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.observe("set_status", self.inner.set_status(id, status, if_match))
+            .await
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        self.observe("delete_vehicles", self.inner.delete_vehicles(ids))
+            .await
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        self.observe("unassign_owner", self.inner.unassign_owner(owner_id))
+            .await
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        self.observe("get_history", self.inner.get_history(id))
+            .await
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        self.observe("dump", self.inner.dump()).await
+    }
+
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        self.observe("restore_dump", self.inner.restore_dump(vehicles, mode))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.observe("ping", self.inner.ping()).await
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.observe("health_check", self.inner.health_check())
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), RepoError> {
+        self.observe("flush", self.inner.flush()).await
+    }
+
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        self.inner.snapshot_status()
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        self.inner.capacity()
+    }
+
+    fn read_path(&self) -> Option<ReadPath> {
+        self.inner.read_path()
+    }
+}
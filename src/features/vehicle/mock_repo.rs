@@ -0,0 +1,422 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        model::{
+            CreateVehicleRequest, PageRequest, PageResult, RestoreMode, RestoreReport, Vehicle,
+            VehicleFilter, VehicleId, VehicleRevision, VehicleSearch, VehicleStats, VehicleStatus,
+            VehicleSummary,
+        },
+        persistent_repo::SnapshotStatus,
+        repo::{InMemoryVehicleRepo, ReadPath, RepoError, RepoHealth, StoreCapacity, VehicleRepo},
+    },
+};
+use crate::utils::etag::IfMatch;
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// What the next call of a method does before, or instead of, reaching the store
+#[derive(Debug)]
+enum Step {
+    Fail(RepoError),
+    Delay(Duration),
+}
+
+/// A call the repo received, with its arguments as `Debug` output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedCall {
+    pub method: &'static str,
+    pub args: String,
+}
+
+/// Repo for handler tests that fails or stalls on cue. Steps are queued per method by name,
+/// e.g. `with_failure("get_vehicle", RepoError::Unavailable)` fails the next `get_vehicle` and
+/// `with_delay("post_vehicle", Duration::from_secs(2))` holds up the next `post_vehicle`; calls
+/// without a queued step reach the wrapped in-memory store. Every call is recorded so tests
+/// can assert on what a handler asked of the repo
+#[derive(Default)]
+pub struct MockVehicleRepo {
+    inner: InMemoryVehicleRepo,
+    steps: Mutex<HashMap<&'static str, VecDeque<Step>>>,
+    calls: Mutex<Vec<RecordedCall>>,
+}
+
+impl MockVehicleRepo {
+    /// Answer unscripted calls from `inner`, e.g. one already holding the vehicles a test needs
+    pub fn new(inner: InMemoryVehicleRepo) -> Self {
+        Self {
+            inner,
+            ..Default::default()
+        }
+    }
+
+    /// Fail the next call of `method` that has no earlier step queued with `error`
+    pub fn with_failure(self, method: &'static str, error: RepoError) -> Self {
+        self.fail_next(method, error);
+        self
+    }
+
+    /// Hold up the next call of `method` that has no earlier step queued by `delay`
+    pub fn with_delay(self, method: &'static str, delay: Duration) -> Self {
+        self.delay_next(method, delay);
+        self
+    }
+
+    /// Like `with_failure`, for a repo already handed to the app
+    pub fn fail_next(&self, method: &'static str, error: RepoError) {
+        self.push(method, Step::Fail(error));
+    }
+
+    /// Like `with_delay`, for a repo already handed to the app
+    pub fn delay_next(&self, method: &'static str, delay: Duration) {
+        self.push(method, Step::Delay(delay));
+    }
+
+    /// Every call received so far, oldest first
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// The arguments of every call of `method` received so far, oldest first
+    pub fn calls_to(&self, method: &str) -> Vec<String> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .map(|call| call.args)
+            .collect()
+    }
+
+    fn push(&self, method: &'static str, step: Step) {
+        self.steps
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(method)
+            .or_default()
+            .push_back(step);
+    }
+
+    async fn scripted<T>(
+        &self,
+        method: &'static str,
+        args: String,
+        call: impl Future<Output = Result<T, RepoError>>,
+    ) -> Result<T, RepoError> {
+        self.calls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(RecordedCall { method, args });
+        let step = self
+            .steps
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_mut(method)
+            .and_then(VecDeque::pop_front);
+        match step {
+            Some(Step::Fail(error)) => Err(error),
+            Some(Step::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                call.await
+            }
+            None => call.await,
+        }
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for MockVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.scripted("get_vehicle", format!("{id:?}"), self.inner.get_vehicle(id))
+            .await
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.scripted(
+            "get_vehicle_by_vin",
+            format!("{vin:?}"),
+            self.inner.get_vehicle_by_vin(vin),
+        )
+        .await
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.scripted(
+            "get_vehicle_by_plate",
+            format!("{plate:?}"),
+            self.inner.get_vehicle_by_plate(plate),
+        )
+        .await
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        self.scripted("exists", format!("{id:?}"), self.inner.exists(id))
+            .await
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        self.scripted(
+            "get_vehicles_by_ids",
+            format!("{ids:?}"),
+            self.inner.get_vehicles_by_ids(ids),
+        )
+        .await
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        self.scripted("list", format!("{page:?}"), self.inner.list(page))
+            .await
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        self.scripted(
+            "list_summaries",
+            format!("{page:?}"),
+            self.inner.list_summaries(page),
+        )
+        .await
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        self.scripted(
+            "get_vehicles_after",
+            format!("{filter:?}, {cursor:?}, {limit}"),
+            self.inner.get_vehicles_after(filter, cursor, limit),
+        )
+        .await
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        self.scripted(
+            "search_vehicles",
+            format!("{search:?}, {offset}, {limit}"),
+            self.inner.search_vehicles(search, offset, limit),
+        )
+        .await
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        self.scripted("count", format!("{filter:?}"), self.inner.count(filter))
+            .await
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        self.scripted("stats", format!("{filter:?}"), self.inner.stats(filter))
+            .await
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        self.scripted("manufacturers", String::new(), self.inner.manufacturers())
+            .await
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        self.scripted(
+            "post_vehicle",
+            format!("{vehicle:?}, {allow_duplicate}"),
+            self.inner.post_vehicle(vehicle, allow_duplicate),
+        )
+        .await
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.scripted(
+            "post_vehicles",
+            format!("{vehicles:?}"),
+            self.inner.post_vehicles(vehicles),
+        )
+        .await
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.scripted(
+            "post_vehicles_atomic",
+            format!("{vehicles:?}"),
+            self.inner.post_vehicles_atomic(vehicles),
+        )
+        .await
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.scripted(
+            "update_vehicle",
+            format!("{id:?}, {vehicle:?}, {if_match:?}, {correction}"),
+            self.inner.update_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.scripted(
+            "upsert_vehicle",
+            format!("{id:?}, {vehicle:?}, {if_match:?}, {correction}"),
+            self.inner.upsert_vehicle(id, vehicle, if_match, correction),
+        )
+        .await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.scripted(
+            "delete_vehicle",
+            format!("{id:?}"),
+            self.inner.delete_vehicle(id),
+        )
+        .await
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.scripted("soft_delete", format!("{id:?}"), self.inner.soft_delete(id))
+            .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.scripted("restore", format!("{id:?}"), self.inner.restore(id))
+            .await
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.scripted(
+            "add_tags",
+            format!("{id:?}, {tags:?}"),
+            self.inner.add_tags(id, tags),
+        )
+        .await
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        self.scripted(
+            "remove_tag",
+            format!("{id:?}, {tag:?}"),
+            self.inner.remove_tag(id, tag),
+        )
+        .await
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.scripted(
+            "set_status",
+            format!("{id:?}, {status:?}, {if_match:?}"),
+            self.inner.set_status(id, status, if_match),
+        )
+        .await
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        self.scripted(
+            "delete_vehicles",
+            format!("{ids:?}"),
+            self.inner.delete_vehicles(ids),
+        )
+        .await
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        self.scripted(
+            "unassign_owner",
+            format!("{owner_id:?}"),
+            self.inner.unassign_owner(owner_id),
+        )
+        .await
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        self.scripted("get_history", format!("{id:?}"), self.inner.get_history(id))
+            .await
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        self.scripted("dump", String::new(), self.inner.dump())
+            .await
+    }
+
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        let args = format!("{} vehicles, {mode:?}", vehicles.len());
+        self.scripted(
+            "restore_dump",
+            args,
+            self.inner.restore_dump(vehicles, mode),
+        )
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.scripted("ping", String::new(), self.inner.ping())
+            .await
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.scripted("health_check", String::new(), self.inner.health_check())
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), RepoError> {
+        self.scripted("flush", String::new(), self.inner.flush())
+            .await
+    }
+
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        self.inner.snapshot_status()
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        self.inner.capacity()
+    }
+
+    fn read_path(&self) -> Option<ReadPath> {
+        self.inner.read_path()
+    }
+}
@@ -1,3 +1,32 @@
+pub mod append_log;
+#[cfg(test)]
+mod bench;
+pub mod cached_repo;
+#[cfg(test)]
+mod conformance;
+pub mod dashmap_repo;
+pub mod export;
+pub mod fallback_repo;
 pub mod handler;
+pub mod import;
+pub mod manufacturer;
+pub mod metered_repo;
+#[cfg(any(test, feature = "test-util"))]
+#[allow(dead_code)]
+pub mod mock_repo;
 pub mod model;
+#[cfg(feature = "mongodb")]
+pub mod mongo_repo;
+pub mod persistent_repo;
+#[cfg(feature = "postgres")]
+pub mod postgres_repo;
+#[cfg(feature = "redis")]
+pub mod redis_repo;
 pub mod repo;
+pub mod seed;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_repo;
+// Helpers for tests and seeding, so not every build that enables them uses every setter
+#[cfg(any(test, feature = "test-util"))]
+#[allow(dead_code)]
+pub mod testing;
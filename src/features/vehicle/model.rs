@@ -1,9 +1,35 @@
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    ops::Deref,
+    str::FromStr,
+    sync::Arc,
+};
+use uuid::Uuid;
+use validator::{Validate, ValidateArgs, ValidationError, ValidationErrors};
 
-#[derive(Clone, Debug, Deserialize, Serialize, Validate)]
-pub struct Vehicle {
-    pub id: Option<String>,
+use crate::{
+    features::owner::model::OwnerId,
+    utils::{
+        config::AppConfig,
+        sorting::{SortField, SortKey, Sortable},
+        validator::{
+            Normalise, camel_case, normalise_whitespace, query_params, validate_license_plate,
+            validate_vin,
+        },
+    },
+};
+
+/// Body accepted when creating or replacing a vehicle; server-managed fields are rejected
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[validate(context = AppConfig)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CreateVehicleRequest {
     #[validate(length(
         min = 3,
         max = 25,
@@ -16,11 +42,1739 @@ pub struct Vehicle {
         message = "model must be between 3 and 25 characters"
     ))]
     pub model: String,
-    #[validate(length(min = 4, max = 4, message = "year must be exactly 4 characters"))]
-    pub year: String,
+    #[serde(deserialize_with = "deserialize_year")]
+    #[validate(
+        range(min = MIN_YEAR, max = MAX_YEAR, message = "year must be between 1886 and 2100"),
+        custom(function = "validate_model_year", use_context)
+    )]
+    pub year: u16,
+    #[serde(default)]
+    #[validate(custom(function = "validate_vin"))]
+    pub vin: Option<String>,
+    #[serde(default, alias = "mileage_km")]
+    #[validate(range(max = MAX_MILEAGE_KM, message = "mileageKm must be at most 2000000"))]
+    pub mileage_km: Option<u32>,
+    #[serde(default)]
+    #[validate(custom(function = "validate_color"))]
+    pub color: Option<String>,
+    #[serde(default, alias = "license_plate")]
+    #[validate(custom(function = "validate_license_plate", use_context))]
+    pub license_plate: Option<String>,
+    /// Purchase price, accepted as a JSON number or string
+    #[serde(default)]
+    #[validate(custom(function = "validate_price"))]
+    pub price: Option<Decimal>,
+    #[serde(default, alias = "fuel_type")]
+    pub fuel_type: Option<FuelType>,
+    /// Lowercased and deduplicated when stored
+    #[serde(default)]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Vec<String>,
+    /// Free-form key/value pairs owned by integrators, e.g. ids in external systems
+    #[serde(default)]
+    #[validate(custom(function = "validate_metadata"))]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    #[validate(nested)]
+    pub engine: Option<EngineSpec>,
+    /// Must reference an existing owner, which the handlers check before writing
+    #[serde(default, alias = "owner_id")]
+    pub owner_id: Option<OwnerId>,
+    #[serde(
+        default,
+        alias = "first_registered",
+        deserialize_with = "deserialize_optional_date"
+    )]
+    #[validate(custom(function = "validate_first_registered"))]
+    pub first_registered: Option<NaiveDate>,
+}
+
+impl Normalise for CreateVehicleRequest {
+    fn normalise(&mut self) {
+        normalise_whitespace(&mut self.manufacturer);
+        normalise_whitespace(&mut self.model);
+        self.vin.iter_mut().for_each(normalise_whitespace);
+        self.color.iter_mut().for_each(normalise_whitespace);
+        self.tags.iter_mut().for_each(normalise_whitespace);
+    }
+}
+
+/// The editable fields of a stored vehicle, e.g. as the base for a patch or clone
+impl From<Vehicle> for CreateVehicleRequest {
+    fn from(vehicle: Vehicle) -> Self {
+        Self {
+            // The name as sent, so writes based on it resolve to the same raw and canonical pair
+            manufacturer: vehicle.manufacturer_raw.unwrap_or(vehicle.manufacturer),
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: vehicle.vin,
+            mileage_km: vehicle.mileage_km,
+            color: vehicle.color,
+            license_plate: vehicle.license_plate,
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: vehicle.tags,
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+        }
+    }
+}
+
+/// A vehicle as held by the repo; the store keeps manufacturer and model as interned
+/// `Arc<str>`s and hands out `Vehicle<String>` everywhere else
+#[derive(Clone, Debug)]
+pub struct Vehicle<N = String> {
+    pub id: VehicleId,
+    /// Canonical name, resolved through the manufacturer alias table
+    pub manufacturer: N,
+    /// The name as sent, kept only when it differed from the canonical one
+    pub manufacturer_raw: Option<String>,
+    pub model: N,
+    pub year: u16,
+    /// Stored uppercase and unique across vehicles
+    pub vin: Option<String>,
+    /// Odometer reading; only allowed to go down through a correction
+    pub mileage_km: Option<u32>,
+    /// A lowercase entry of `VEHICLE_COLORS` or a `custom:` value
+    pub color: Option<String>,
+    /// Stored uppercase without whitespace and unique across live vehicles
+    pub license_plate: Option<String>,
+    pub price: Option<Decimal>,
+    pub fuel_type: Option<FuelType>,
+    /// Lowercase and unique, in the order they were added
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub engine: Option<EngineSpec>,
+    pub owner_id: Option<OwnerId>,
+    pub first_registered: Option<NaiveDate>,
+    pub status: VehicleStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Set when the vehicle has been soft deleted
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// When a store with a TTL drops the vehicle
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Bumped by the repo on every mutation
+    pub version: u64,
+}
+
+/// VINs are compared and stored uppercase without surrounding whitespace
+pub fn normalise_vin(vin: Option<String>) -> Option<String> {
+    vin.map(|vin| vin.trim().to_ascii_uppercase())
+}
+
+/// Plates are compared and stored uppercase with all whitespace removed
+pub fn normalise_plate(plate: Option<String>) -> Option<String> {
+    plate.map(|plate| {
+        plate
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_uppercase()
+    })
+}
+
+/// Prices are whole cents at most and never negative
+fn validate_price(price: &Decimal) -> Result<(), ValidationError> {
+    if price.is_sign_negative() && !price.is_zero() {
+        return Err(ValidationError::new("price").with_message("price must not be negative".into()));
+    }
+    if price.normalize().scale() > 2 {
+        return Err(ValidationError::new("price")
+            .with_message("price must have at most 2 decimal places".into()));
+    }
+    Ok(())
+}
+
+pub const MAX_TAGS: usize = 10;
+const MAX_TAG_LENGTH: usize = 30;
+
+/// Tags are compared and stored trimmed and lowercase; the first occurrence of a duplicate wins
+pub fn normalise_tags(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| seen.insert(tag.clone()))
+        .collect()
+}
+
+fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ValidationError::new("tags")
+            .with_message(format!("tags must contain at most {MAX_TAGS} tags").into()));
+    }
+    let invalid: Vec<String> = tags
+        .iter()
+        .enumerate()
+        .filter(|(_, tag)| !(1..=MAX_TAG_LENGTH).contains(&tag.trim().chars().count()))
+        .map(|(index, tag)| format!("tags[{index}] '{tag}'"))
+        .collect();
+    if !invalid.is_empty() {
+        return Err(ValidationError::new("tags").with_message(
+            format!(
+                "tags must be between 1 and {MAX_TAG_LENGTH} characters: {}",
+                invalid.join(", ")
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Engine details, validated as part of the vehicle that carries them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EngineSpec {
+    #[serde(alias = "displacement_cc")]
+    #[validate(range(max = 10_000, message = "displacementCc must be between 0 and 10000"))]
+    pub displacement_cc: u32,
+    #[validate(range(max = 16, message = "cylinders must be between 0 and 16"))]
+    pub cylinders: u8,
+    #[serde(alias = "power_kw")]
+    #[validate(range(min = 1, max = 2000, message = "powerKw must be between 1 and 2000"))]
+    pub power_kw: u16,
+}
+
+pub const MAX_METADATA_KEYS: usize = 20;
+const MAX_METADATA_KEY_LENGTH: usize = 64;
+const MAX_METADATA_VALUE_LENGTH: usize = 256;
+/// Keys with this prefix are reserved for the service itself
+const RESERVED_METADATA_PREFIX: &str = "x-internal-";
+
+fn validate_metadata(metadata: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    let error =
+        |message: String| Err(ValidationError::new("metadata").with_message(message.into()));
+    if metadata.len() > MAX_METADATA_KEYS {
+        return error(format!(
+            "metadata must contain at most {MAX_METADATA_KEYS} keys"
+        ));
+    }
+    for (key, value) in metadata {
+        if key.is_empty() || key.chars().count() > MAX_METADATA_KEY_LENGTH {
+            return error(format!(
+                "metadata key '{key}' must be between 1 and {MAX_METADATA_KEY_LENGTH} characters"
+            ));
+        }
+        if key
+            .get(..RESERVED_METADATA_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(RESERVED_METADATA_PREFIX))
+        {
+            return error(format!(
+                "metadata key '{key}' is reserved, keys may not start with '{RESERVED_METADATA_PREFIX}'"
+            ));
+        }
+        if value.chars().count() > MAX_METADATA_VALUE_LENGTH {
+            return error(format!(
+                "metadata value of '{key}' must be at most {MAX_METADATA_VALUE_LENGTH} characters"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Body of `POST /{id}/tags`; the tags are added to the ones already present
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TagsRequest {
+    #[validate(
+        length(min = 1, message = "tags must contain at least one tag"),
+        custom(function = "validate_tags")
+    )]
+    pub tags: Vec<String>,
+}
+
+impl Normalise for TagsRequest {
+    fn normalise(&mut self) {
+        self.tags.iter_mut().for_each(normalise_whitespace);
+    }
+}
+
+/// Colors offered to clients; anything else has to be sent as `custom:<name>`
+pub const VEHICLE_COLORS: &[&str] = &[
+    "black", "white", "silver", "grey", "red", "blue", "green", "yellow", "orange", "brown",
+    "beige", "gold", "purple",
+];
+pub const CUSTOM_COLOR_PREFIX: &str = "custom:";
+
+/// Canonical colors are stored lowercase, custom ones keep their name after a lowercase prefix
+pub fn normalise_color(color: Option<String>) -> Option<String> {
+    color.map(|color| {
+        let color = color.trim();
+        match strip_custom_prefix(color) {
+            Some(name) => format!("{CUSTOM_COLOR_PREFIX}{}", name.trim()),
+            None => color.to_lowercase(),
+        }
+    })
+}
+
+fn strip_custom_prefix(color: &str) -> Option<&str> {
+    let prefix = color.get(..CUSTOM_COLOR_PREFIX.len())?;
+    prefix
+        .eq_ignore_ascii_case(CUSTOM_COLOR_PREFIX)
+        .then(|| &color[CUSTOM_COLOR_PREFIX.len()..])
+}
+
+fn validate_color(color: &str) -> Result<(), ValidationError> {
+    let color = color.trim();
+    if let Some(name) = strip_custom_prefix(color) {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 30 {
+            return Err(ValidationError::new("color").with_message(
+                "custom color must be between 1 and 30 characters after 'custom:'".into(),
+            ));
+        }
+        return Ok(());
+    }
+    if VEHICLE_COLORS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(color))
+    {
+        return Ok(());
+    }
+    Err(ValidationError::new("color").with_message(
+        format!(
+            "unknown color '{color}', allowed values: {} (or prefix with 'custom:')",
+            VEHICLE_COLORS.join(", ")
+        )
+        .into(),
+    ))
+}
+
+impl<N> Vehicle<N> {
+    /// Strong entity tag for the current version of this vehicle
+    pub fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.id, self.version)
+    }
+
+    /// The same vehicle with manufacturer and model converted, e.g. to or from interned names
+    pub fn map_names<M>(self, mut convert: impl FnMut(N) -> M) -> Vehicle<M> {
+        Vehicle {
+            id: self.id,
+            manufacturer: convert(self.manufacturer),
+            manufacturer_raw: self.manufacturer_raw,
+            model: convert(self.model),
+            year: self.year,
+            vin: self.vin,
+            mileage_km: self.mileage_km,
+            color: self.color,
+            license_plate: self.license_plate,
+            price: self.price,
+            fuel_type: self.fuel_type,
+            tags: self.tags,
+            metadata: self.metadata,
+            engine: self.engine,
+            owner_id: self.owner_id,
+            first_registered: self.first_registered,
+            status: self.status,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            deleted_at: self.deleted_at,
+            expires_at: self.expires_at,
+            version: self.version,
+        }
+    }
 }
 
+impl From<&Vehicle<Arc<str>>> for Vehicle {
+    fn from(vehicle: &Vehicle<Arc<str>>) -> Self {
+        vehicle.clone().map_names(|name| name.to_string())
+    }
+}
+
+/// Lifecycle state of a vehicle; `scrapped` is terminal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleStatus {
+    #[default]
+    Available,
+    InService,
+    Sold,
+    Scrapped,
+}
+
+impl VehicleStatus {
+    #[cfg_attr(
+        not(any(feature = "postgres", feature = "sqlite", feature = "mongodb")),
+        allow(dead_code)
+    )]
+    pub const ALL: &'static [VehicleStatus] = &[
+        VehicleStatus::Available,
+        VehicleStatus::InService,
+        VehicleStatus::Sold,
+        VehicleStatus::Scrapped,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VehicleStatus::Available => "available",
+            VehicleStatus::InService => "in_service",
+            VehicleStatus::Sold => "sold",
+            VehicleStatus::Scrapped => "scrapped",
+        }
+    }
+
+    /// Whether moving from `self` to `to` is allowed; leaving `sold` for anything but
+    /// `scrapped` requires `force`
+    pub fn can_transition(self, to: VehicleStatus, force: bool) -> bool {
+        match (self, to) {
+            (from, to) if from == to => true,
+            (VehicleStatus::Scrapped, _) => false,
+            (VehicleStatus::Sold, VehicleStatus::Scrapped) => true,
+            (VehicleStatus::Sold, _) => force,
+            _ => true,
+        }
+    }
+}
+
+/// Fuel a vehicle runs on; matched case-insensitively on input, new kinds may be added later
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum FuelType {
+    Petrol,
+    Diesel,
+    Electric,
+    Hybrid,
+    Hydrogen,
+}
+
+impl FuelType {
+    pub const ALL: &'static [FuelType] = &[
+        FuelType::Petrol,
+        FuelType::Diesel,
+        FuelType::Electric,
+        FuelType::Hybrid,
+        FuelType::Hydrogen,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FuelType::Petrol => "petrol",
+            FuelType::Diesel => "diesel",
+            FuelType::Electric => "electric",
+            FuelType::Hybrid => "hybrid",
+            FuelType::Hydrogen => "hydrogen",
+        }
+    }
+}
+
+impl FromStr for FuelType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        FuelType::ALL
+            .iter()
+            .copied()
+            .find(|fuel| fuel.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = FuelType::ALL.iter().map(|fuel| fuel.as_str()).collect();
+                format!(
+                    "unknown fuel type '{s}', allowed values: {}",
+                    allowed.join(", ")
+                )
+            })
+    }
+}
+
+impl<'de> Deserialize<'de> for FuelType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Body of `PUT /{id}/status`
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StatusChange {
+    pub status: VehicleStatus,
+}
+
+impl Normalise for StatusChange {}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct StatusParams {
+    /// Allow transitions that are otherwise refused, such as `sold` back to `available`
+    pub force: Option<bool>,
+}
+
+/// Wire representation of a stored vehicle
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleResponse {
+    pub id: VehicleId,
+    pub manufacturer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manufacturer_raw: Option<String>,
+    pub model: String,
+    pub year: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mileage_km: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_plate: Option<String>,
+    /// Serialized as a string so no precision is lost in JavaScript clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuel_type: Option<FuelType>,
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<EngineSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<OwnerId>,
+    /// ISO `YYYY-MM-DD`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_registered: Option<NaiveDate>,
+    pub status: VehicleStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Only present when the store expires vehicles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub version: u64,
+}
+
+impl From<Vehicle> for VehicleResponse {
+    fn from(vehicle: Vehicle) -> Self {
+        Self {
+            id: vehicle.id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw: vehicle.manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: vehicle.vin,
+            mileage_km: vehicle.mileage_km,
+            color: vehicle.color,
+            license_plate: vehicle.license_plate,
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: vehicle.tags,
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: vehicle.status,
+            created_at: vehicle.created_at,
+            updated_at: vehicle.updated_at,
+            deleted_at: vehicle.deleted_at,
+            expires_at: vehicle.expires_at,
+            version: vehicle.version,
+        }
+    }
+}
+
+/// Read back a vehicle stored in its wire form, e.g. by a backend that keeps JSON documents
+impl From<VehicleResponse> for Vehicle {
+    fn from(vehicle: VehicleResponse) -> Self {
+        Self {
+            id: vehicle.id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw: vehicle.manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: vehicle.vin,
+            mileage_km: vehicle.mileage_km,
+            color: vehicle.color,
+            license_plate: vehicle.license_plate,
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: vehicle.tags,
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: vehicle.status,
+            created_at: vehicle.created_at,
+            updated_at: vehicle.updated_at,
+            deleted_at: vehicle.deleted_at,
+            expires_at: vehicle.expires_at,
+            version: vehicle.version,
+        }
+    }
+}
+
+pub const MIN_YEAR: u16 = 1886;
+pub const MAX_YEAR: u16 = 2100;
+
+/// Manufacturers release next year's models early
+pub const DEFAULT_MODEL_YEAR_OFFSET: i32 = 1;
+
+/// Catches typos like `2077` that the static range lets through; the cutoff is the current year
+/// plus the configured `model_year_offset`
+fn validate_model_year(year: u16, config: &AppConfig) -> Result<(), ValidationError> {
+    let cutoff = Utc::now().year() + config.model_year_offset;
+    if i32::from(year) > cutoff {
+        let mut error = ValidationError::new("model_year")
+            .with_message(format!("year cannot be later than the {cutoff} model year").into());
+        error.add_param("cutoff".into(), &cutoff);
+        return Err(error);
+    }
+    Ok(())
+}
+
+pub const MAX_MILEAGE_KM: u32 = 2_000_000;
+
+/// Older clients send the year as a four-digit string, newer ones as a number
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum YearInput {
+    Number(u16),
+    Text(String),
+}
+
+impl YearInput {
+    fn into_year(self) -> Result<u16, String> {
+        match self {
+            YearInput::Number(year) => Ok(year),
+            YearInput::Text(text) => parse_year(&text),
+        }
+    }
+}
+
+/// Parse a four-digit year string; range checks are left to validation
+pub fn parse_year(text: &str) -> Result<u16, String> {
+    let text = text.trim();
+    if text.len() == 4 && text.bytes().all(|b| b.is_ascii_digit()) {
+        return text
+            .parse()
+            .map_err(|_| format!("'{text}' is not a valid year"));
+    }
+    Err(format!("'{text}' is not a four-digit year"))
+}
+
+/// Parse a strict `YYYY-MM-DD` date; range checks are left to validation
+pub fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    let text = text.trim();
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) if text.len() == 10 => Ok(date),
+        _ => Err(format!("'{text}' is not a YYYY-MM-DD date")),
+    }
+}
+
+fn deserialize_optional_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<NaiveDate>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| parse_date(&text))
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+/// Registrations can't predate the first car or lie in the future
+fn validate_first_registered(date: &NaiveDate) -> Result<(), ValidationError> {
+    let earliest = NaiveDate::from_ymd_opt(i32::from(MIN_YEAR), 1, 1).unwrap_or_default();
+    let today = Utc::now().date_naive();
+    if *date < earliest || *date > today {
+        return Err(ValidationError::new("first_registered").with_message(
+            format!("firstRegistered must be between {earliest} and {today}").into(),
+        ));
+    }
+    Ok(())
+}
+
+fn deserialize_year<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    YearInput::deserialize(deserializer)?
+        .into_year()
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_optional_year<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u16>, D::Error> {
+    Option::<YearInput>::deserialize(deserializer)?
+        .map(YearInput::into_year)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+/// Vehicle identifier, serialized as the hyphenated UUID string
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VehicleId(pub Uuid);
+
+impl VehicleId {
+    /// Fresh time-ordered (UUIDv7) id
+    pub fn generate() -> Self {
+        Self(Uuid::now_v7())
+    }
+}
+
+impl fmt::Display for VehicleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for VehicleId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
+/// An id that parsed is valid, this lets it be taken with `ValidatedPath`
+impl Validate for VehicleId {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+/// Segment of `GET /by-vin/{vin}`, checked like a submitted VIN so typos fail before the lookup
+#[derive(Clone, Debug, Deserialize, Validate)]
+pub struct VinPath {
+    #[validate(custom(function = "validate_vin"))]
+    pub vin: String,
+}
+
+/// Segments of `DELETE /{id}/tags/{tag}`
+#[derive(Clone, Debug, Deserialize, Validate)]
+pub struct TagPath {
+    pub id: VehicleId,
+    pub tag: String,
+}
+
+/// Body returned when a vehicle is created: `{ "id": "..." }`
 #[derive(Serialize)]
-pub struct VehicleId {
-    pub id: String,
+#[serde(rename_all = "camelCase")]
+pub struct CreatedVehicle {
+    pub id: VehicleId,
+}
+
+/// The columns list views show, built without cloning the rest of the vehicle
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleSummary {
+    pub id: VehicleId,
+    pub manufacturer: String,
+    pub model: String,
+    pub year: u16,
+}
+
+impl<N: Deref<Target = str>> From<&Vehicle<N>> for VehicleSummary {
+    fn from(vehicle: &Vehicle<N>) -> Self {
+        Self {
+            id: vehicle.id,
+            manufacturer: vehicle.manufacturer.to_string(),
+            model: vehicle.model.to_string(),
+            year: vehicle.year,
+        }
+    }
+}
+
+/// Representation used by the list endpoint
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListView {
+    #[default]
+    Summary,
+    Full,
+}
+
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 500;
+
+#[derive(Clone, Debug, Default, Deserialize, Validate)]
+#[validate(schema(function = "validate_pagination_mode"))]
+pub struct ListParams {
+    #[validate(range(min = 1, max = MAX_PAGE_LIMIT, message = "limit must be between 1 and 500"))]
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// Id of the last vehicle seen; switches the listing to cursor mode (empty starts from the beginning)
+    pub cursor: Option<String>,
+    /// `summary` (the default) or `full`; a `fields` selection always projects the full vehicle
+    pub view: Option<ListView>,
+    /// Comma-separated sort keys (at most three), prefix with `-` for descending (e.g. `manufacturer,-year`)
+    #[validate(custom(function = "validate_sort"))]
+    pub sort: Option<String>,
+}
+
+impl ListParams {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or(0)
+    }
+
+    pub fn sort_keys(&self) -> Vec<SortKey<VehicleSortField>> {
+        self.sort
+            .as_deref()
+            .and_then(|raw| SortKey::parse_list(raw).ok())
+            .unwrap_or_default()
+    }
+
+    /// The offset page these params ask for out of the vehicles matching `filter`
+    pub fn page_request(&self, filter: VehicleFilter) -> PageRequest {
+        PageRequest {
+            filter,
+            offset: self.offset(),
+            limit: self.limit(),
+            sort: self.sort_keys(),
+        }
+    }
+}
+
+/// An offset page of the vehicles matching `filter`, in `sort` order or id order without one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageRequest {
+    pub filter: VehicleFilter,
+    pub offset: usize,
+    pub limit: usize,
+    pub sort: Vec<SortKey<VehicleSortField>>,
+}
+
+/// One page of a listing and how many items matched in total
+#[derive(Clone, Debug)]
+pub struct PageResult<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+impl<T> PageResult<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> PageResult<U> {
+        PageResult {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+        }
+    }
+}
+
+/// Serialized vehicle keys that may be requested through `?fields=`; snake_case names are
+/// accepted too
+pub const VEHICLE_FIELDS: &[&str] = &[
+    "id",
+    "manufacturer",
+    "manufacturerRaw",
+    "model",
+    "year",
+    "vin",
+    "mileageKm",
+    "color",
+    "licensePlate",
+    "price",
+    "fuelType",
+    "tags",
+    "metadata",
+    "engine",
+    "ownerId",
+    "firstRegistered",
+    "status",
+    "createdAt",
+    "updatedAt",
+    "deletedAt",
+    "version",
+];
+
+#[derive(Clone, Debug, Default, Deserialize, Validate)]
+pub struct FieldsParams {
+    /// Comma-separated subset of vehicle fields to return; `id` is always included
+    #[validate(custom(function = "validate_fields"))]
+    pub fields: Option<String>,
+}
+
+impl FieldsParams {
+    /// Serialize a vehicle, keeping only the requested fields
+    pub fn project(&self, vehicle: &Vehicle) -> Value {
+        project_vehicle(vehicle, self.selected().as_ref())
+    }
+
+    pub fn project_all(&self, vehicles: &[Vehicle]) -> Vec<Value> {
+        let selected = self.selected();
+        vehicles
+            .iter()
+            .map(|vehicle| project_vehicle(vehicle, selected.as_ref()))
+            .collect()
+    }
+
+    /// `None` when no fieldset was requested and the full representation applies
+    fn selected(&self) -> Option<HashSet<String>> {
+        let raw = self.fields.as_deref()?;
+        Some(
+            raw.split(',')
+                .map(|field| camel_case(field.trim()))
+                .chain(["id".to_string()])
+                .collect(),
+        )
+    }
+}
+
+fn project_vehicle(vehicle: &Vehicle, selected: Option<&HashSet<String>>) -> Value {
+    let mut value =
+        serde_json::to_value(VehicleResponse::from(vehicle.clone())).unwrap_or_default();
+    if let (Some(selected), Value::Object(map)) = (selected, &mut value) {
+        map.retain(|key, _| selected.contains(key));
+    }
+    value
+}
+
+fn validate_fields(raw: &str) -> Result<(), ValidationError> {
+    let unknown: Vec<&str> = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !VEHICLE_FIELDS.contains(&camel_case(field).as_str()))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(ValidationError::new("fields").with_message(
+        format!(
+            "unknown fields '{}', allowed values: {}",
+            unknown.join("', '"),
+            VEHICLE_FIELDS.join(", ")
+        )
+        .into(),
+    ))
+}
+
+fn validate_pagination_mode(params: &ListParams) -> Result<(), ValidationError> {
+    if params.cursor.is_some() && params.offset.is_some() {
+        return Err(ValidationError::new("pagination_mode")
+            .with_message("cursor and offset cannot be combined".into()));
+    }
+    if params.cursor.is_some() && params.sort.is_some() {
+        return Err(ValidationError::new("pagination_mode").with_message(
+            "cursor pagination is always ordered by id and cannot be sorted".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_sort(raw: &str) -> Result<(), ValidationError> {
+    SortKey::<VehicleSortField>::parse_list(raw).map(|_| ())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VehicleSortField {
+    Manufacturer,
+    Model,
+    Year,
+    Id,
+    CreatedAt,
+    UpdatedAt,
+    Price,
+}
+
+impl SortField for VehicleSortField {
+    const ALLOWED: &'static str = "manufacturer, model, year, id, created_at, updated_at, price";
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "manufacturer" => Some(Self::Manufacturer),
+            "model" => Some(Self::Model),
+            "year" => Some(Self::Year),
+            "id" => Some(Self::Id),
+            "created_at" => Some(Self::CreatedAt),
+            "updated_at" => Some(Self::UpdatedAt),
+            "price" => Some(Self::Price),
+            _ => None,
+        }
+    }
+}
+
+impl<N: Ord> Sortable for Vehicle<N> {
+    type Field = VehicleSortField;
+
+    fn compare_field(&self, other: &Self, field: VehicleSortField) -> Ordering {
+        match field {
+            VehicleSortField::Manufacturer => self.manufacturer.cmp(&other.manufacturer),
+            VehicleSortField::Model => self.model.cmp(&other.model),
+            VehicleSortField::Year => self.year.cmp(&other.year),
+            VehicleSortField::Id => self.id.cmp(&other.id),
+            VehicleSortField::CreatedAt => self.created_at.cmp(&other.created_at),
+            VehicleSortField::UpdatedAt => self.updated_at.cmp(&other.updated_at),
+            VehicleSortField::Price => self.price.cmp(&other.price),
+        }
+    }
+
+    fn is_missing(&self, field: VehicleSortField) -> bool {
+        field == VehicleSortField::Price && self.price.is_none()
+    }
+
+    fn tie_break(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Validate)]
+#[validate(schema(function = "validate_year_range"))]
+pub struct VehicleFilter {
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub year: Option<u16>,
+    /// Inclusive lower bound on the year
+    #[validate(range(min = MIN_YEAR, max = MAX_YEAR, message = "year_min must be between 1886 and 2100"))]
+    pub year_min: Option<u16>,
+    /// Inclusive upper bound on the year
+    #[validate(range(min = MIN_YEAR, max = MAX_YEAR, message = "year_max must be between 1886 and 2100"))]
+    pub year_max: Option<u16>,
+    /// Only vehicles created strictly after this RFC 3339 instant
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only vehicles first registered strictly after this `YYYY-MM-DD` date; vehicles without
+    /// a registration date never match either bound
+    pub registered_after: Option<NaiveDate>,
+    /// Only vehicles first registered strictly before this `YYYY-MM-DD` date
+    pub registered_before: Option<NaiveDate>,
+    /// Inclusive upper bound on the mileage; vehicles without a reading never match
+    pub mileage_max: Option<u32>,
+    /// Inclusive lower bound on engine power; vehicles without engine details never match
+    pub min_power_kw: Option<u16>,
+    pub color: Option<String>,
+    pub fuel_type: Option<FuelType>,
+    /// Repeatable `?tag=` values, all of which must be present; filled in by `with_tags`
+    #[serde(skip)]
+    pub tags: Vec<String>,
+    pub status: Option<VehicleStatus>,
+    pub owner_id: Option<OwnerId>,
+    /// Soft-deleted vehicles are hidden unless this is set
+    pub include_deleted: Option<bool>,
+}
+
+impl VehicleFilter {
+    /// Pick the repeated `tag` parameters out of the raw query pairs, since a struct field
+    /// can't collect them
+    pub fn with_tags(mut self, params: &[(String, String)]) -> Self {
+        self.tags = normalise_tags(
+            params
+                .iter()
+                .filter(|(key, _)| key == "tag")
+                .map(|(_, value)| value.clone())
+                .collect(),
+        );
+        self
+    }
+
+    /// All provided fields must match; strings compare case-insensitively, years numerically
+    pub fn matches<N: Deref<Target = str>>(&self, vehicle: &Vehicle<N>) -> bool {
+        (self.include_deleted.unwrap_or(false) || vehicle.deleted_at.is_none())
+            && self
+                .manufacturer
+                .as_deref()
+                .is_none_or(|m| eq_ignore_case(&vehicle.manufacturer, m))
+            && self
+                .model
+                .as_deref()
+                .is_none_or(|m| eq_ignore_case(&vehicle.model, m))
+            && self.year.is_none_or(|y| vehicle.year == y)
+            && self.year_min.is_none_or(|min| vehicle.year >= min)
+            && self.year_max.is_none_or(|max| vehicle.year <= max)
+            && self
+                .created_after
+                .is_none_or(|after| vehicle.created_at > after)
+            && self
+                .mileage_max
+                .is_none_or(|max| vehicle.mileage_km.is_some_and(|km| km <= max))
+            && self
+                .min_power_kw
+                .is_none_or(|min| vehicle.engine.is_some_and(|engine| engine.power_kw >= min))
+            && self.color.as_deref().is_none_or(|c| {
+                vehicle
+                    .color
+                    .as_deref()
+                    .is_some_and(|color| eq_ignore_case(color, c.trim()))
+            })
+            && self
+                .fuel_type
+                .is_none_or(|fuel| vehicle.fuel_type == Some(fuel))
+            && self.tags.iter().all(|tag| vehicle.tags.contains(tag))
+            && self.status.is_none_or(|status| vehicle.status == status)
+            && self
+                .owner_id
+                .is_none_or(|owner_id| vehicle.owner_id == Some(owner_id))
+            && self
+                .registered_after
+                .is_none_or(|after| vehicle.first_registered.is_some_and(|date| date > after))
+            && self
+                .registered_before
+                .is_none_or(|before| vehicle.first_registered.is_some_and(|date| date < before))
+    }
+}
+
+fn validate_year_range(filter: &VehicleFilter) -> Result<(), ValidationError> {
+    if let (Some(min), Some(max)) = (filter.year_min, filter.year_max)
+        && min > max
+    {
+        return Err(ValidationError::new("year_range").with_message(
+            format!("year_min ({min}) must not be greater than year_max ({max})").into(),
+        ));
+    }
+    Ok(())
+}
+
+fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Validate)]
+pub struct SearchParams {
+    #[serde(default)]
+    #[validate(length(min = 2, message = "q must be at least 2 characters"))]
+    pub q: String,
+    #[validate(range(min = 1, max = MAX_PAGE_LIMIT, message = "limit must be between 1 and 500"))]
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl SearchParams {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Validate)]
+pub struct PageParams {
+    #[validate(range(min = 1, max = MAX_PAGE_LIMIT, message = "limit must be between 1 and 500"))]
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl PageParams {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or(0)
+    }
+}
+
+query_params!(
+    ListParams,
+    FieldsParams,
+    VehicleFilter => ["tag"],
+    SearchParams,
+    PageParams,
+    LookupQuery,
+);
+
+/// Whitespace-separated search tokens, each of which must appear in the manufacturer or model
+#[derive(Clone, Debug)]
+pub struct VehicleSearch {
+    /// Lowercase words that must each appear in the manufacturer or the model
+    pub tokens: Vec<String>,
+}
+
+impl VehicleSearch {
+    pub fn new(query: &str) -> Self {
+        Self {
+            tokens: query.split_whitespace().map(str::to_lowercase).collect(),
+        }
+    }
+
+    pub fn matches<N: Deref<Target = str>>(&self, vehicle: &Vehicle<N>) -> bool {
+        if vehicle.deleted_at.is_some() {
+            return false;
+        }
+        let manufacturer = vehicle.manufacturer.to_lowercase();
+        let model = vehicle.model.to_lowercase();
+        self.tokens
+            .iter()
+            .all(|token| manufacturer.contains(token) || model.contains(token))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CreateParams {
+    /// Skip the manufacturer/model/year uniqueness check
+    pub allow_duplicate: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UpdateParams {
+    /// Accept a mileage lower than the stored one, e.g. to fix a mistyped reading
+    pub correction: Option<bool>,
+}
+
+/// Largest CSV `POST /import` accepts, well above `MAX_BODY_BYTES` since a file holds a fleet
+pub const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Media types the CSV import is accepted as
+pub const CSV_MEDIA_TYPES: &[&str] = &["text/csv"];
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ImportParams {
+    /// Abort the whole import if any row fails
+    pub strict: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportFailure {
+    pub line: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub imported: usize,
+    pub failed: Vec<ImportFailure>,
+}
+
+/// How a dump is restored into a store that already holds vehicles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    /// Add the dumped vehicles next to the stored ones, skipping any that conflict with them
+    #[default]
+    Merge,
+    /// Swap the whole store for the dump at once
+    Replace,
+}
+
+/// A dumped vehicle that was not restored because something else already held its id, VIN
+/// or plate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpConflict {
+    /// Position of the vehicle in the dump
+    pub index: usize,
+    pub id: VehicleId,
+    /// `id`, `vin` or `licensePlate`
+    pub field: &'static str,
+    /// The vehicle holding it, in the store or earlier in the dump
+    pub existing_id: VehicleId,
+}
+
+/// What restoring a dump did
+#[derive(Clone, Debug, Default)]
+pub struct RestoreReport {
+    /// Vehicles put into the store, in dump order
+    pub restored: Vec<VehicleId>,
+    pub conflicts: Vec<DumpConflict>,
+}
+
+/// Most vehicles one batch create may carry
+pub const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BatchCreateRequest {
+    /// Validated one by one by the handler, so a bad vehicle only fails its own entry
+    pub vehicles: Vec<CreateVehicleRequest>,
+}
+
+impl Normalise for BatchCreateRequest {}
+
+/// Only the batch size; deriving would need the vehicles to be serializable for the error
+impl ValidateArgs<'_> for BatchCreateRequest {
+    type Args = ();
+
+    fn validate_with_args(&self, _: ()) -> Result<(), ValidationErrors> {
+        if (1..=MAX_BATCH_SIZE).contains(&self.vehicles.len()) {
+            return Ok(());
+        }
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "vehicles",
+            ValidationError::new("length").with_message(
+                format!("vehicles must contain between 1 and {MAX_BATCH_SIZE} vehicles").into(),
+            ),
+        );
+        Err(errors)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BatchParams {
+    /// Create every vehicle or none of them
+    pub atomic: Option<bool>,
+}
+
+/// What happened to one vehicle of a batch, numbered from 0 in the order sent
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<VehicleId>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchCreateResult {
+    pub created: usize,
+    pub results: Vec<BatchItemResult>,
+    /// Set when error messages past `max_validation_errors` were left out
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl BatchCreateResult {
+    /// Keep the first `max_errors` error messages of the batch, in index order, but always the
+    /// first one of every failed vehicle so none of them looks like it succeeded
+    pub fn new(created: usize, mut results: Vec<BatchItemResult>, max_errors: usize) -> Self {
+        let mut remaining = max_errors;
+        let mut truncated = false;
+        for result in &mut results {
+            let kept = result.errors.len().min(remaining.max(1));
+            if kept < result.errors.len() {
+                result.errors.truncate(kept);
+                truncated = true;
+            }
+            remaining = remaining.saturating_sub(kept);
+        }
+        Self {
+            created,
+            results,
+            truncated,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeleteParams {
+    /// Remove the vehicle outright instead of soft deleting it
+    pub permanent: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BulkDeleteRequest {
+    #[validate(
+        length(min = 1, message = "ids must contain at least one id"),
+        custom(function = "validate_uuid_list")
+    )]
+    pub ids: Vec<String>,
+}
+
+impl Normalise for BulkDeleteRequest {}
+
+impl BulkDeleteRequest {
+    /// Parsed ids; only meaningful once the request has been validated
+    pub fn vehicle_ids(&self) -> Vec<VehicleId> {
+        self.ids.iter().filter_map(|id| id.parse().ok()).collect()
+    }
+}
+
+fn validate_uuid_list(ids: &[String]) -> Result<(), ValidationError> {
+    let invalid: Vec<String> = ids
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| Uuid::parse_str(id).is_err())
+        .map(|(index, id)| format!("ids[{index}] '{id}'"))
+        .collect();
+    if !invalid.is_empty() {
+        return Err(ValidationError::new("uuid")
+            .with_message(format!("invalid UUIDs at {}", invalid.join(", ")).into()));
+    }
+    Ok(())
+}
+
+pub const MAX_LOOKUP_IDS: u64 = 500;
+pub const MAX_QUERY_LOOKUP_IDS: usize = 50;
+
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct LookupRequest {
+    #[validate(
+        length(
+            min = 1,
+            max = MAX_LOOKUP_IDS,
+            message = "ids must contain between 1 and 500 ids"
+        ),
+        custom(function = "validate_uuid_list")
+    )]
+    pub ids: Vec<String>,
+}
+
+impl Normalise for LookupRequest {}
+
+impl LookupRequest {
+    /// Parsed ids in request order with duplicates removed
+    pub fn vehicle_ids(&self) -> Vec<VehicleId> {
+        let mut seen = HashSet::new();
+        self.ids
+            .iter()
+            .filter_map(|id| id.parse::<VehicleId>().ok())
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+}
+
+/// Comma-separated ids for `GET /lookup`; larger sets should use the JSON body variant
+#[derive(Clone, Debug, Default, Deserialize, Validate)]
+pub struct LookupQuery {
+    #[serde(default)]
+    #[validate(custom(function = "validate_lookup_query"))]
+    pub ids: String,
+}
+
+impl From<LookupQuery> for LookupRequest {
+    fn from(query: LookupQuery) -> Self {
+        Self {
+            ids: query
+                .ids
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+fn validate_lookup_query(ids: &str) -> Result<(), ValidationError> {
+    let count = ids.split(',').filter(|id| !id.trim().is_empty()).count();
+    if count == 0 || count > MAX_QUERY_LOOKUP_IDS {
+        return Err(ValidationError::new("length").with_message(
+            format!(
+                "ids must contain between 1 and {MAX_QUERY_LOOKUP_IDS} ids, use POST for larger sets"
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LookupResult {
+    pub items: Vec<VehicleResponse>,
+    pub missing: Vec<VehicleId>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDeleteResult {
+    pub deleted: usize,
+    pub missing: Vec<VehicleId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeType {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+    StatusChanged,
+}
+
+/// Snapshot of a vehicle taken right after a mutation
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleRevision {
+    pub version: u64,
+    pub changed_at: DateTime<Utc>,
+    pub change_type: ChangeType,
+    pub snapshot: VehicleResponse,
+}
+
+/// Counts over a filtered set of vehicles; maps are ordered so the output is deterministic
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleStats {
+    pub total: usize,
+    pub by_manufacturer: BTreeMap<String, usize>,
+    pub by_year: BTreeMap<u16, usize>,
+    /// Mean mileage of the vehicles that have a reading, per manufacturer
+    pub avg_mileage_by_manufacturer: BTreeMap<String, f64>,
+    /// Sum of the known prices per manufacturer
+    pub total_price_by_manufacturer: BTreeMap<String, Decimal>,
+    /// Mean of the known prices per manufacturer, rounded to cents
+    pub avg_price_by_manufacturer: BTreeMap<String, Decimal>,
+    /// Vehicles without a fuel type are left out
+    pub by_fuel_type: BTreeMap<FuelType, usize>,
+}
+
+/// Body of `GET /manufacturers`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleManufacturers {
+    pub manufacturers: Vec<String>,
+}
+
+/// Body of `GET /colors`, so UIs can offer the palette without hardcoding it
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleColors {
+    pub colors: &'static [&'static str],
+    pub custom_prefix: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleCount {
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Validate)]
+#[validate(context = AppConfig, schema(function = "validate_patch_not_empty"))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct VehiclePatch {
+    #[validate(length(
+        min = 3,
+        max = 25,
+        message = "manufacturer must be between 3 and 25 characters"
+    ))]
+    pub manufacturer: Option<String>,
+    #[validate(length(
+        min = 3,
+        max = 25,
+        message = "model must be between 3 and 25 characters"
+    ))]
+    pub model: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_year")]
+    #[validate(
+        range(min = MIN_YEAR, max = MAX_YEAR, message = "year must be between 1886 and 2100"),
+        custom(function = "validate_model_year", use_context)
+    )]
+    pub year: Option<u16>,
+    #[validate(custom(function = "validate_vin"))]
+    pub vin: Option<String>,
+    #[serde(alias = "mileage_km")]
+    #[validate(range(max = MAX_MILEAGE_KM, message = "mileageKm must be at most 2000000"))]
+    pub mileage_km: Option<u32>,
+    #[validate(custom(function = "validate_color"))]
+    pub color: Option<String>,
+    #[serde(alias = "license_plate")]
+    #[validate(custom(function = "validate_license_plate", use_context))]
+    pub license_plate: Option<String>,
+    #[validate(custom(function = "validate_price"))]
+    pub price: Option<Decimal>,
+    #[serde(alias = "fuel_type")]
+    pub fuel_type: Option<FuelType>,
+    /// Replaces the current tags
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<Vec<String>>,
+    /// Merged into the current metadata, overwriting keys that already exist
+    #[validate(custom(function = "validate_metadata"))]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// Replaces the current engine details as a whole
+    #[validate(nested)]
+    pub engine: Option<EngineSpec>,
+    #[serde(alias = "owner_id")]
+    pub owner_id: Option<OwnerId>,
+    #[serde(
+        default,
+        alias = "first_registered",
+        deserialize_with = "deserialize_optional_date"
+    )]
+    #[validate(custom(function = "validate_first_registered"))]
+    pub first_registered: Option<NaiveDate>,
+}
+
+impl VehiclePatch {
+    /// Overwrite only the fields present in the patch
+    pub fn apply(self, base: CreateVehicleRequest) -> CreateVehicleRequest {
+        CreateVehicleRequest {
+            manufacturer: self.manufacturer.unwrap_or(base.manufacturer),
+            model: self.model.unwrap_or(base.model),
+            year: self.year.unwrap_or(base.year),
+            vin: self.vin.or(base.vin),
+            mileage_km: self.mileage_km.or(base.mileage_km),
+            color: self.color.or(base.color),
+            license_plate: self.license_plate.or(base.license_plate),
+            price: self.price.or(base.price),
+            fuel_type: self.fuel_type.or(base.fuel_type),
+            tags: self.tags.unwrap_or(base.tags),
+            metadata: match self.metadata {
+                Some(metadata) => base.metadata.into_iter().chain(metadata).collect(),
+                None => base.metadata,
+            },
+            engine: self.engine.or(base.engine),
+            owner_id: self.owner_id.or(base.owner_id),
+            first_registered: self.first_registered.or(base.first_registered),
+        }
+    }
+}
+
+impl Normalise for VehiclePatch {
+    fn normalise(&mut self) {
+        self.manufacturer.iter_mut().for_each(normalise_whitespace);
+        self.model.iter_mut().for_each(normalise_whitespace);
+        self.vin.iter_mut().for_each(normalise_whitespace);
+        self.color.iter_mut().for_each(normalise_whitespace);
+        self.tags
+            .iter_mut()
+            .flatten()
+            .for_each(normalise_whitespace);
+    }
+}
+
+fn validate_patch_not_empty(patch: &VehiclePatch) -> Result<(), ValidationError> {
+    if patch.manufacturer.is_none()
+        && patch.model.is_none()
+        && patch.year.is_none()
+        && patch.vin.is_none()
+        && patch.mileage_km.is_none()
+        && patch.color.is_none()
+        && patch.license_plate.is_none()
+        && patch.price.is_none()
+        && patch.fuel_type.is_none()
+        && patch.tags.is_none()
+        && patch.metadata.is_none()
+        && patch.engine.is_none()
+        && patch.owner_id.is_none()
+        && patch.first_registered.is_none()
+    {
+        return Err(ValidationError::new("empty_patch").with_message(
+            "patch must contain at least one of manufacturer, model, year, vin, mileageKm, color, \
+             licensePlate, price, fuelType, tags, metadata, engine, ownerId or firstRegistered"
+                .into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(model_year_offset: i32) -> AppConfig {
+        AppConfig {
+            model_year_offset,
+            ..AppConfig::default()
+        }
+    }
+
+    fn made_in(year: u16) -> CreateVehicleRequest {
+        CreateVehicleRequest {
+            year,
+            ..Vehicle::builder().request()
+        }
+    }
+
+    #[test]
+    fn model_year_cutoff_is_pinned_by_the_configured_offset() {
+        let this_year = Utc::now().year();
+        let next_year = made_in(this_year as u16 + 1);
+
+        let errors = next_year.validate_with_args(&config(0)).unwrap_err();
+        let error = &errors.field_errors()["year"][0];
+        assert_eq!(error.code, "model_year");
+        assert_eq!(error.params["cutoff"], this_year);
+        assert_eq!(
+            error.message.as_deref(),
+            Some(format!("year cannot be later than the {this_year} model year").as_str())
+        );
+
+        assert!(next_year.validate_with_args(&config(1)).is_ok());
+        assert!(
+            made_in(this_year as u16 + 3)
+                .validate_with_args(&config(3))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn model_year_cutoff_composes_with_the_year_range() {
+        let errors = made_in(2101).validate_with_args(&config(1)).unwrap_err();
+        let codes: Vec<&str> = errors.field_errors()["year"]
+            .iter()
+            .map(|error| error.code.as_ref())
+            .collect();
+        assert_eq!(codes, ["range", "model_year"]);
+
+        let patch = VehiclePatch {
+            year: Some(Utc::now().year() as u16 + 2),
+            ..serde_json::from_value(serde_json::json!({ "model": "Yaris" })).unwrap()
+        };
+        assert!(patch.validate_with_args(&config(1)).is_err());
+        assert!(patch.validate_with_args(&config(2)).is_ok());
+    }
+
+    fn with_year(year: serde_json::Value) -> Result<CreateVehicleRequest, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "manufacturer": "Toyota",
+            "model": "Corolla",
+            "year": year,
+        }))
+    }
+
+    #[test]
+    fn year_is_read_from_a_number_or_a_four_digit_string() {
+        assert_eq!(with_year(2020.into()).unwrap().year, 2020);
+        assert_eq!(with_year("2020".into()).unwrap().year, 2020);
+        assert_eq!(with_year(" 1999 ".into()).unwrap().year, 1999);
+
+        for text in ["abcd", "20", "20201", "2020.0", ""] {
+            let error = with_year(text.into()).unwrap_err().to_string();
+            assert!(error.contains("four-digit year"), "{text}: {error}");
+        }
+        assert!(with_year(2020.5.into()).is_err());
+        assert!(with_year((-2020).into()).is_err());
+    }
+
+    #[test]
+    fn years_outside_1886_to_2100_fail_the_range_rule() {
+        for year in [
+            serde_json::json!(1885),
+            serde_json::json!("0999"),
+            serde_json::json!(2101),
+        ] {
+            let vehicle = with_year(year.clone()).unwrap();
+
+            let errors = vehicle.validate_with_args(&config(100)).unwrap_err();
+
+            let error = &errors.field_errors()["year"][0];
+            assert_eq!(error.code, "range", "{year}");
+            assert_eq!(error.params["min"], 1886, "{year}");
+            assert_eq!(error.params["max"], 2100, "{year}");
+        }
+        for year in [1886, 2100] {
+            let vehicle = with_year(year.into()).unwrap();
+            assert!(vehicle.validate_with_args(&config(100)).is_ok(), "{year}");
+        }
+    }
+
+    #[test]
+    fn license_plate_follows_the_configured_pattern() {
+        let plated = CreateVehicleRequest {
+            license_plate: Some("ab 123 cd".to_string()),
+            ..Vehicle::builder().request()
+        };
+        assert!(plated.validate_with_args(&config(1)).is_ok());
+
+        let dutch = AppConfig {
+            plate_pattern: regex::Regex::new("^[A-Z]{2}-[0-9]{3}-[A-Z]$").unwrap(),
+            ..config(1)
+        };
+        let errors = plated.validate_with_args(&dutch).unwrap_err();
+        let error = &errors.field_errors()["license_plate"][0];
+        assert_eq!(error.code, "license_plate");
+        assert_eq!(error.params["pattern"], "^[A-Z]{2}-[0-9]{3}-[A-Z]$");
+    }
+
+    #[test]
+    fn batch_result_caps_errors_but_keeps_one_per_failed_vehicle() {
+        let failed = |index| BatchItemResult {
+            index,
+            id: None,
+            errors: vec![format!("{index}: first"), format!("{index}: second")],
+        };
+        let result = BatchCreateResult::new(0, (0..3).map(failed).collect(), 3);
+
+        let kept: Vec<&[String]> = result.results.iter().map(|r| &r.errors[..]).collect();
+        assert_eq!(
+            kept,
+            [&["0: first", "0: second"][..], &["1: first"], &["2: first"]]
+        );
+        assert!(result.truncated);
+
+        let result = BatchCreateResult::new(0, (0..3).map(failed).collect(), 6);
+        assert!(result.results.iter().all(|r| r.errors.len() == 2));
+        assert!(!result.truncated);
+    }
 }
@@ -0,0 +1,1028 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        manufacturer::ManufacturerAliases,
+        model::{
+            ChangeType, CreateVehicleRequest, EngineSpec, FuelType, MAX_TAGS, PageRequest,
+            PageResult, Vehicle, VehicleFilter, VehicleId, VehicleRevision, VehicleSearch,
+            VehicleSortField, VehicleStats, VehicleStatus, VehicleSummary, normalise_color,
+            normalise_plate, normalise_tags, normalise_vin,
+        },
+        repo::{RepoError, VehicleRepo, check_mileage, check_not_deleted, collect_stats},
+    },
+};
+use crate::utils::{
+    etag::IfMatch,
+    sorting::{Direction, SortKey},
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    Client, Collection, Database, IndexModel,
+    bson::{Bson, DateTime, Decimal128, Document, Uuid, doc},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, IndexOptions},
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Conditional writes that lose to another writer are retried this many times before giving up
+const MAX_ATTEMPTS: usize = 5;
+
+/// Server code for a unique index violation
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Vehicle repo backed by a MongoDB database, for deployments that already run one.
+///
+/// Vehicles live in the `vehicles` collection with their id as `_id`, revisions in
+/// `vehicle_revisions`. Indexes are created on connect; unique ones on the VIN and on the plate
+/// of live vehicles back the key checks. MongoDB only offers transactions on replica sets, so
+/// writes are instead conditional on the version they read and are retried when another writer
+/// got in between. Filters, sorting and pagination become query documents; case-insensitive
+/// matching uses lowercased key fields written alongside each vehicle.
+pub struct MongoVehicleRepo {
+    database: Database,
+    vehicles: Collection<VehicleDocument>,
+    revisions: Collection<RevisionDocument>,
+    history_limit: usize,
+    aliases: ManufacturerAliases,
+}
+
+/// One document of `vehicles`; the key fields only exist for queries and indexes
+#[derive(Deserialize, Serialize)]
+struct VehicleDocument {
+    #[serde(rename = "_id")]
+    id: Uuid,
+    manufacturer: String,
+    manufacturer_raw: Option<String>,
+    model: String,
+    year: i32,
+    vin: Option<String>,
+    mileage_km: Option<i64>,
+    color: Option<String>,
+    license_plate: Option<String>,
+    price: Option<Decimal128>,
+    fuel_type: Option<String>,
+    tags: Vec<String>,
+    metadata: BTreeMap<String, String>,
+    engine: Option<EngineSpec>,
+    owner_id: Option<Uuid>,
+    /// `YYYY-MM-DD`, which orders like the date itself
+    first_registered: Option<String>,
+    status: String,
+    created_at: DateTime,
+    updated_at: DateTime,
+    deleted_at: Option<DateTime>,
+    version: i64,
+    manufacturer_key: String,
+    model_key: String,
+    color_key: Option<String>,
+    /// The plate while the vehicle is live, so the unique index skips deleted vehicles
+    live_plate: Option<String>,
+    /// Lets vehicles without a price sort last in either direction
+    has_price: bool,
+}
+
+/// One document of `vehicle_revisions`
+#[derive(Deserialize, Serialize)]
+struct RevisionDocument {
+    vehicle_id: Uuid,
+    version: i64,
+    revision: VehicleRevision,
+}
+
+fn bson_uuid(id: uuid::Uuid) -> Uuid {
+    Uuid::from_bytes(id.into_bytes())
+}
+
+/// MongoDB keeps milliseconds, so finer precision is dropped on the way in
+fn bson_time(time: chrono::DateTime<Utc>) -> DateTime {
+    DateTime::from_millis(time.timestamp_millis())
+}
+
+fn chrono_time(time: DateTime) -> chrono::DateTime<Utc> {
+    chrono::DateTime::from_timestamp_millis(time.timestamp_millis()).unwrap_or_default()
+}
+
+impl From<&Vehicle> for VehicleDocument {
+    fn from(vehicle: &Vehicle) -> Self {
+        Self {
+            id: bson_uuid(vehicle.id.0),
+            manufacturer: vehicle.manufacturer.clone(),
+            manufacturer_raw: vehicle.manufacturer_raw.clone(),
+            model: vehicle.model.clone(),
+            year: vehicle.year.into(),
+            vin: vehicle.vin.clone(),
+            mileage_km: vehicle.mileage_km.map(i64::from),
+            color: vehicle.color.clone(),
+            license_plate: vehicle.license_plate.clone(),
+            // Every Decimal fits in a Decimal128
+            price: vehicle
+                .price
+                .and_then(|price| price.to_string().parse().ok()),
+            fuel_type: vehicle.fuel_type.map(|fuel| fuel.as_str().to_string()),
+            tags: vehicle.tags.clone(),
+            metadata: vehicle.metadata.clone(),
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id.map(|owner| bson_uuid(owner.0)),
+            first_registered: vehicle.first_registered.map(|date| date.to_string()),
+            status: vehicle.status.as_str().to_string(),
+            created_at: bson_time(vehicle.created_at),
+            updated_at: bson_time(vehicle.updated_at),
+            deleted_at: vehicle.deleted_at.map(bson_time),
+            version: vehicle.version as i64,
+            manufacturer_key: vehicle.manufacturer.to_lowercase(),
+            model_key: vehicle.model.to_lowercase(),
+            color_key: vehicle.color.as_deref().map(str::to_lowercase),
+            live_plate: vehicle
+                .license_plate
+                .clone()
+                .filter(|_| vehicle.deleted_at.is_none()),
+            has_price: vehicle.price.is_some(),
+        }
+    }
+}
+
+impl TryFrom<VehicleDocument> for Vehicle {
+    type Error = RepoError;
+
+    fn try_from(document: VehicleDocument) -> Result<Self, RepoError> {
+        let id = uuid::Uuid::from_bytes(document.id.bytes());
+        let corrupt =
+            |field: &str| RepoError::Backend(format!("vehicle {id} has an invalid {field}"));
+        Ok(Vehicle {
+            id: VehicleId(id),
+            manufacturer: document.manufacturer,
+            manufacturer_raw: document.manufacturer_raw,
+            model: document.model,
+            year: u16::try_from(document.year).map_err(|_| corrupt("year"))?,
+            vin: document.vin,
+            mileage_km: document
+                .mileage_km
+                .map(u32::try_from)
+                .transpose()
+                .map_err(|_| corrupt("mileage_km"))?,
+            color: document.color,
+            license_plate: document.license_plate,
+            price: document
+                .price
+                .map(|price| {
+                    // Very small values come back in scientific notation
+                    let price = price.to_string();
+                    price
+                        .parse::<Decimal>()
+                        .or_else(|_| Decimal::from_scientific(&price))
+                })
+                .transpose()
+                .map_err(|_| corrupt("price"))?,
+            fuel_type: document
+                .fuel_type
+                .map(|fuel| fuel.parse::<FuelType>())
+                .transpose()
+                .map_err(|_| corrupt("fuel_type"))?,
+            tags: document.tags,
+            metadata: document.metadata,
+            engine: document.engine,
+            owner_id: document
+                .owner_id
+                .map(|owner| OwnerId(uuid::Uuid::from_bytes(owner.bytes()))),
+            first_registered: document
+                .first_registered
+                .map(|date| date.parse::<NaiveDate>())
+                .transpose()
+                .map_err(|_| corrupt("first_registered"))?,
+            status: VehicleStatus::ALL
+                .iter()
+                .copied()
+                .find(|status| status.as_str() == document.status)
+                .ok_or_else(|| corrupt("status"))?,
+            created_at: chrono_time(document.created_at),
+            updated_at: chrono_time(document.updated_at),
+            deleted_at: document.deleted_at.map(chrono_time),
+            expires_at: None,
+            version: u64::try_from(document.version).map_err(|_| corrupt("version"))?,
+        })
+    }
+}
+
+/// MongoDB takes counts as `i64`; anything larger means "no limit"
+fn mongo_count(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+/// The query document for `filter`, mirroring `VehicleFilter::matches`
+fn filter_document(filter: &VehicleFilter) -> Document {
+    let mut query = Document::new();
+    if !filter.include_deleted.unwrap_or(false) {
+        query.insert("deleted_at", Bson::Null);
+    }
+    if let Some(manufacturer) = &filter.manufacturer {
+        query.insert("manufacturer_key", manufacturer.to_lowercase());
+    }
+    if let Some(model) = &filter.model {
+        query.insert("model_key", model.to_lowercase());
+    }
+    let mut year = Document::new();
+    if let Some(exact) = filter.year {
+        year.insert("$eq", i32::from(exact));
+    }
+    if let Some(min) = filter.year_min {
+        year.insert("$gte", i32::from(min));
+    }
+    if let Some(max) = filter.year_max {
+        year.insert("$lte", i32::from(max));
+    }
+    if !year.is_empty() {
+        query.insert("year", year);
+    }
+    if let Some(after) = filter.created_after {
+        query.insert("created_at", doc! { "$gt": bson_time(after) });
+    }
+    // Comparisons only match values of the same type, so missing readings never match
+    if let Some(max) = filter.mileage_max {
+        query.insert("mileage_km", doc! { "$lte": i64::from(max) });
+    }
+    if let Some(min) = filter.min_power_kw {
+        query.insert("engine.powerKw", doc! { "$gte": i32::from(min) });
+    }
+    if let Some(color) = &filter.color {
+        query.insert("color_key", color.trim().to_lowercase());
+    }
+    if let Some(fuel_type) = filter.fuel_type {
+        query.insert("fuel_type", fuel_type.as_str());
+    }
+    if !filter.tags.is_empty() {
+        query.insert("tags", doc! { "$all": &filter.tags });
+    }
+    if let Some(status) = filter.status {
+        query.insert("status", status.as_str());
+    }
+    if let Some(owner_id) = filter.owner_id {
+        query.insert("owner_id", bson_uuid(owner_id.0));
+    }
+    let mut registered = Document::new();
+    if let Some(after) = filter.registered_after {
+        registered.insert("$gt", after.to_string());
+    }
+    if let Some(before) = filter.registered_before {
+        registered.insert("$lt", before.to_string());
+    }
+    if !registered.is_empty() {
+        query.insert("first_registered", registered);
+    }
+    query
+}
+
+/// The sort document for `sort`, mirroring `sorting::compare`: vehicles without a price go
+/// last in either direction and ties fall back to the id
+fn sort_document(sort: &[SortKey<VehicleSortField>]) -> Document {
+    let mut order = Document::new();
+    for key in sort {
+        let field = match key.field {
+            VehicleSortField::Manufacturer => "manufacturer",
+            VehicleSortField::Model => "model",
+            VehicleSortField::Year => "year",
+            VehicleSortField::Id => "_id",
+            VehicleSortField::CreatedAt => "created_at",
+            VehicleSortField::UpdatedAt => "updated_at",
+            VehicleSortField::Price => {
+                order.insert("has_price", -1);
+                "price"
+            }
+        };
+        let direction = match key.direction {
+            Direction::Ascending => 1,
+            Direction::Descending => -1,
+        };
+        order.insert(field, direction);
+    }
+    if !order.contains_key("_id") {
+        order.insert("_id", 1);
+    }
+    order
+}
+
+/// Whether `error` is a unique index violation
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(failure)) if failure.code == DUPLICATE_KEY
+    )
+}
+
+impl MongoVehicleRepo {
+    /// Connect to the deployment at `uri` and create the indexes, keeping up to `history_limit`
+    /// revisions per vehicle; 0 disables history. Uses the database named in the URI, or
+    /// `vehicles` when it names none
+    pub async fn connect(uri: &str, history_limit: usize) -> Result<Self, mongodb::error::Error> {
+        let mut options = ClientOptions::parse(uri).await?;
+        options.server_selection_timeout = Some(Duration::from_secs(5));
+        let client = Client::with_options(options)?;
+        let database = client
+            .default_database()
+            .unwrap_or_else(|| client.database("vehicles"));
+        let vehicles = database.collection::<VehicleDocument>("vehicles");
+        let revisions = database.collection::<RevisionDocument>("vehicle_revisions");
+
+        let unique_where = |filter: Document| {
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(filter)
+                .build()
+        };
+        vehicles
+            .create_indexes([
+                IndexModel::builder()
+                    .keys(doc! { "manufacturer": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "manufacturer_key": 1, "model_key": 1, "year": 1 })
+                    .build(),
+                IndexModel::builder().keys(doc! { "owner_id": 1 }).build(),
+                IndexModel::builder()
+                    .keys(doc! { "vin": 1 })
+                    .options(unique_where(doc! { "vin": { "$type": "string" } }))
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "live_plate": 1 })
+                    .options(unique_where(doc! { "live_plate": { "$type": "string" } }))
+                    .build(),
+            ])
+            .await?;
+        revisions
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "vehicle_id": 1, "version": -1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await?;
+
+        Ok(Self {
+            database,
+            vehicles,
+            revisions,
+            history_limit,
+            aliases: ManufacturerAliases::default(),
+        })
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    /// `filter` with its manufacturer resolved through the aliases like stored values
+    fn canonical_filter(&self, filter: &VehicleFilter) -> VehicleFilter {
+        VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        }
+    }
+
+    async fn load(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.vehicles
+            .find_one(doc! { "_id": bson_uuid(id.0) })
+            .await?
+            .map(Vehicle::try_from)
+            .transpose()
+    }
+
+    /// The vehicles matching `query` in `order`, skipping `offset` and keeping up to `limit`
+    async fn fetch(
+        &self,
+        query: Document,
+        order: Document,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Vehicle>, RepoError> {
+        // A limit of 0 means "no limit" to MongoDB
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        self.vehicles
+            .find(query)
+            .sort(order)
+            .skip(offset as u64)
+            .limit(mongo_count(limit))
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter()
+            .map(Vehicle::try_from)
+            .collect()
+    }
+
+    /// The error for a unique index violation by `vehicle`, or `None` when the holder is gone
+    /// again or the clash was on the id, so the write should be retried
+    async fn conflict(&self, vehicle: &Vehicle) -> Result<Option<RepoError>, RepoError> {
+        let id = bson_uuid(vehicle.id.0);
+        if let Some(vin) = &vehicle.vin
+            && let Some(holder) = self
+                .vehicles
+                .find_one(doc! { "vin": vin, "_id": { "$ne": id } })
+                .await?
+        {
+            return Ok(Some(RepoError::VinTaken {
+                existing_id: VehicleId(uuid::Uuid::from_bytes(holder.id.bytes())),
+            }));
+        }
+        if vehicle.deleted_at.is_none()
+            && let Some(plate) = &vehicle.license_plate
+            && let Some(holder) = self
+                .vehicles
+                .find_one(doc! { "live_plate": plate, "_id": { "$ne": id } })
+                .await?
+        {
+            return Ok(Some(RepoError::PlateTaken {
+                existing_id: VehicleId(uuid::Uuid::from_bytes(holder.id.bytes())),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Insert `vehicle`, or replace it if it is still at `read_version`, and record the change;
+    /// false means another writer got there first and the caller should read again and retry
+    async fn store(
+        &self,
+        vehicle: &Vehicle,
+        read_version: Option<u64>,
+        change_type: ChangeType,
+    ) -> Result<bool, RepoError> {
+        let document = VehicleDocument::from(vehicle);
+        let written = match read_version {
+            None => self.vehicles.insert_one(&document).await.map(|_| true),
+            Some(version) => self
+                .vehicles
+                .replace_one(
+                    doc! { "_id": document.id, "version": version as i64 },
+                    &document,
+                )
+                .await
+                .map(|result| result.matched_count == 1),
+        };
+        match written {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(e) if is_duplicate_key(&e) => {
+                return match self.conflict(vehicle).await? {
+                    Some(conflict) => Err(conflict),
+                    None => Ok(false),
+                };
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.record(vehicle, change_type).await?;
+
+        Ok(true)
+    }
+
+    /// Snapshot `vehicle` into its revision history, dropping revisions past the limit
+    async fn record(&self, vehicle: &Vehicle, change_type: ChangeType) -> Result<(), RepoError> {
+        if self.history_limit == 0 {
+            return Ok(());
+        }
+        let vehicle_id = bson_uuid(vehicle.id.0);
+        let version = vehicle.version as i64;
+        let revision = RevisionDocument {
+            vehicle_id,
+            version,
+            revision: VehicleRevision {
+                version: vehicle.version,
+                changed_at: Utc::now(),
+                change_type,
+                snapshot: vehicle.clone().into(),
+            },
+        };
+        self.revisions
+            .replace_one(
+                doc! { "vehicle_id": vehicle_id, "version": version },
+                &revision,
+            )
+            .upsert(true)
+            .await?;
+        // Every change bumps the version by one, so the oldest kept revision is easy to find
+        self.revisions
+            .delete_many(doc! {
+                "vehicle_id": vehicle_id,
+                "version": { "$lte": version - mongo_count(self.history_limit) },
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Let `change` edit the stored vehicle and write it back, retrying when another writer got
+    /// in between; `change` returns the kind of change made, or `None` to leave it untouched
+    async fn modify(
+        &self,
+        id: VehicleId,
+        change: impl Fn(&mut Vehicle) -> Result<Option<ChangeType>, RepoError> + Send + Sync,
+    ) -> Result<Option<Vehicle>, RepoError> {
+        for _ in 0..MAX_ATTEMPTS {
+            let Some(mut vehicle) = self.load(id).await? else {
+                return Ok(None);
+            };
+            let Some(change_type) = change(&mut vehicle)? else {
+                return Ok(Some(vehicle));
+            };
+            let read_version = vehicle.version;
+            vehicle.updated_at = Utc::now();
+            vehicle.version += 1;
+            if self
+                .store(&vehicle, Some(read_version), change_type)
+                .await?
+            {
+                return Ok(Some(vehicle));
+            }
+        }
+        Err(RepoError::Unavailable)
+    }
+
+    /// Replace the vehicle with this id, creating it when `create` is set; the flag is true
+    /// when it was created
+    async fn write(
+        &self,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+        create: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let manufacturer_raw = self.canonicalise(&mut vehicle);
+        for _ in 0..MAX_ATTEMPTS {
+            let existing = self.load(id).await?;
+            if existing.is_none() && !create {
+                return Err(RepoError::NotFound);
+            }
+            check_not_deleted(existing.as_ref())?;
+            // If-Match can only be satisfied by a vehicle that already exists
+            if let Some(if_match) = if_match
+                && !existing
+                    .as_ref()
+                    .is_some_and(|v| if_match.matches(&v.etag()))
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: existing.map(|v| v.version),
+                });
+            }
+            check_mileage(existing.as_ref(), vehicle.mileage_km, correction)?;
+            let now = Utc::now();
+            let stored = Vehicle {
+                id,
+                manufacturer: vehicle.manufacturer.clone(),
+                manufacturer_raw: manufacturer_raw.clone(),
+                model: vehicle.model.clone(),
+                year: vehicle.year,
+                vin: normalise_vin(vehicle.vin.clone()),
+                mileage_km: vehicle.mileage_km,
+                color: normalise_color(vehicle.color.clone()),
+                license_plate: normalise_plate(vehicle.license_plate.clone()),
+                price: vehicle.price,
+                fuel_type: vehicle.fuel_type,
+                tags: normalise_tags(vehicle.tags.clone()),
+                metadata: vehicle.metadata.clone(),
+                engine: vehicle.engine,
+                owner_id: vehicle.owner_id,
+                first_registered: vehicle.first_registered,
+                status: existing
+                    .as_ref()
+                    .map_or_else(VehicleStatus::default, |v| v.status),
+                created_at: existing.as_ref().map_or(now, |v| v.created_at),
+                updated_at: now,
+                deleted_at: existing.as_ref().and_then(|v| v.deleted_at),
+                expires_at: None,
+                version: existing.as_ref().map_or(1, |v| v.version + 1),
+            };
+            let read_version = existing.as_ref().map(|v| v.version);
+            let change_type = if existing.is_none() {
+                ChangeType::Created
+            } else {
+                ChangeType::Updated
+            };
+            if self.store(&stored, read_version, change_type).await? {
+                return Ok((stored, existing.is_none()));
+            }
+        }
+        Err(RepoError::Unavailable)
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for MongoVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        Ok(self.load(id).await?.filter(|v| v.deleted_at.is_none()))
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.vehicles
+            .find_one(doc! { "vin": vin.trim().to_ascii_uppercase(), "deleted_at": Bson::Null })
+            .await?
+            .map(Vehicle::try_from)
+            .transpose()
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let Some(plate) = normalise_plate(Some(plate.to_string())) else {
+            return Ok(None);
+        };
+        self.vehicles
+            .find_one(doc! { "live_plate": plate })
+            .await?
+            .map(Vehicle::try_from)
+            .transpose()
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        let count = self
+            .vehicles
+            .count_documents(doc! { "_id": bson_uuid(id.0), "deleted_at": Bson::Null })
+            .limit(1)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        if ids.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let wanted: Vec<Uuid> = ids.iter().map(|id| bson_uuid(id.0)).collect();
+        let stored = self
+            .fetch(
+                doc! { "_id": { "$in": wanted }, "deleted_at": Bson::Null },
+                doc! { "_id": 1 },
+                0,
+                ids.len(),
+            )
+            .await?;
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for id in ids {
+            match stored.iter().find(|v| v.id == *id) {
+                Some(vehicle) => found.push(vehicle.clone()),
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let query = filter_document(&self.canonical_filter(&page.filter));
+        // Without a transaction the total and the page may straddle a concurrent write
+        let total = self.vehicles.count_documents(query.clone()).await?;
+        let items = self
+            .fetch(query, sort_document(&page.sort), page.offset, page.limit)
+            .await?;
+
+        Ok(PageResult {
+            items,
+            total: total as usize,
+        })
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        Ok(self.list(page).await?.map(|v| VehicleSummary::from(&v)))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let mut query = filter_document(&self.canonical_filter(filter));
+        if let Some(cursor) = cursor {
+            query.insert("_id", doc! { "$gt": bson_uuid(cursor.0) });
+        }
+        // One document past the page tells whether there is a next one
+        let mut items = self
+            .fetch(query, doc! { "_id": 1 }, 0, limit.saturating_add(1))
+            .await?;
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|v| v.id)
+        } else {
+            None
+        };
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let mut query = doc! { "deleted_at": Bson::Null };
+        if !search.tokens.is_empty() {
+            let tokens: Vec<Document> = search
+                .tokens
+                .iter()
+                .map(|token| {
+                    let pattern = regex::escape(token);
+                    doc! { "$or": [
+                        { "manufacturer_key": { "$regex": &pattern } },
+                        { "model_key": { "$regex": &pattern } },
+                    ] }
+                })
+                .collect();
+            query.insert("$and", tokens);
+        }
+        let total = self.vehicles.count_documents(query.clone()).await?;
+        let items = self.fetch(query, doc! { "_id": 1 }, offset, limit).await?;
+
+        Ok((items, total as usize))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let everything = VehicleFilter::default();
+        let filter = self.canonical_filter(filter.unwrap_or(&everything));
+        let count = self
+            .vehicles
+            .count_documents(filter_document(&filter))
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        let query = filter_document(&self.canonical_filter(filter));
+        let matching = self.fetch(query, doc! { "_id": 1 }, 0, usize::MAX).await?;
+
+        Ok(collect_stats(&matching))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let mut manufacturers: Vec<String> = self
+            .vehicles
+            .distinct("manufacturer", doc! { "deleted_at": Bson::Null })
+            .await?
+            .into_iter()
+            .filter_map(|manufacturer| match manufacturer {
+                Bson::String(manufacturer) => Some(manufacturer),
+                _ => None,
+            })
+            .collect();
+        manufacturers.sort();
+
+        Ok(manufacturers)
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        // Best-effort: two concurrent creates of the same vehicle can both pass this check
+        if !allow_duplicate {
+            let manufacturer = self.aliases.canonical(&vehicle.manufacturer);
+            let existing = self
+                .vehicles
+                .find_one(doc! {
+                    "deleted_at": Bson::Null,
+                    "manufacturer_key": manufacturer.to_lowercase(),
+                    "model_key": vehicle.model.to_lowercase(),
+                    "year": i32::from(vehicle.year),
+                })
+                .sort(doc! { "_id": 1 })
+                .await?;
+            if let Some(existing) = existing {
+                return Err(RepoError::Duplicate {
+                    existing_id: VehicleId(uuid::Uuid::from_bytes(existing.id.bytes())),
+                });
+            }
+        }
+        let id = VehicleId::generate();
+        self.write(id, vehicle, None, false, true).await?;
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            match self.write(id, vehicle, None, false, true).await {
+                Ok(_) => ids.push(id),
+                Err(RepoError::VinTaken { .. } | RepoError::PlateTaken { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.write(id, vehicle, if_match, correction, false)
+            .await
+            .map(|(updated, _)| updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.write(id, vehicle, if_match, correction, true).await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let Some(removed) = self
+            .vehicles
+            .find_one_and_delete(doc! { "_id": bson_uuid(id.0) })
+            .await?
+        else {
+            return Ok(None);
+        };
+        self.revisions
+            .delete_many(doc! { "vehicle_id": bson_uuid(id.0) })
+            .await?;
+
+        Vehicle::try_from(removed).map(Some)
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.modify(id, |vehicle| {
+            // Keep the original timestamp when deleting an already deleted vehicle
+            if vehicle.deleted_at.is_some() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = Some(Utc::now());
+            Ok(Some(ChangeType::Deleted))
+        })
+        .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            if vehicle.deleted_at.is_none() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = None;
+            Ok(Some(ChangeType::Restored))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            let merged = normalise_tags(vehicle.tags.iter().chain(&tags).cloned().collect());
+            if merged.len() == vehicle.tags.len() {
+                return Ok(None);
+            }
+            if merged.len() > MAX_TAGS {
+                return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+            }
+            vehicle.tags = merged;
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let tag = tag.trim().to_lowercase();
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if !vehicle.tags.contains(&tag) {
+                return Ok(None);
+            }
+            vehicle.tags.retain(|t| *t != tag);
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if let Some(if_match) = if_match
+                && !if_match.matches(&vehicle.etag())
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: Some(vehicle.version),
+                });
+            }
+            if vehicle.status == status {
+                return Ok(None);
+            }
+            vehicle.status = status;
+            Ok(Some(ChangeType::StatusChanged))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            match self.delete_vehicle(*id).await? {
+                Some(_) => deleted += 1,
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let assigned = self
+            .fetch(
+                doc! { "owner_id": bson_uuid(owner_id.0) },
+                doc! { "_id": 1 },
+                0,
+                usize::MAX,
+            )
+            .await?;
+        let mut unassigned = 0;
+        for vehicle in assigned {
+            // The owner may have changed since the scan, so check again on every attempt
+            let changed = self
+                .modify(vehicle.id, |vehicle| {
+                    if vehicle.owner_id != Some(owner_id) {
+                        return Ok(None);
+                    }
+                    vehicle.owner_id = None;
+                    Ok(Some(ChangeType::Updated))
+                })
+                .await?;
+            if changed.is_some_and(|v| v.owner_id.is_none()) {
+                unassigned += 1;
+            }
+        }
+
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        if !self.exists(id).await? {
+            return Ok(None);
+        }
+        let revisions: Vec<RevisionDocument> = self
+            .revisions
+            .find(doc! { "vehicle_id": bson_uuid(id.0) })
+            .sort(doc! { "version": -1 })
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(Some(
+            revisions
+                .into_iter()
+                .map(|revision| revision.revision)
+                .collect(),
+        ))
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        self.database.run_command(doc! { "ping": 1 }).await?;
+
+        Ok(())
+    }
+}
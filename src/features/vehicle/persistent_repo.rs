@@ -0,0 +1,832 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        append_log::{AppendLog, LogEntry, Replayed},
+        model::{
+            CreateVehicleRequest, PageRequest, PageResult, RestoreMode, RestoreReport, Vehicle,
+            VehicleFilter, VehicleId, VehicleResponse, VehicleRevision, VehicleSearch,
+            VehicleStats, VehicleStatus, VehicleSummary,
+        },
+        repo::{InMemoryVehicleRepo, RepoError, RepoHealth, StoreCapacity, VehicleRepo},
+    },
+};
+use crate::utils::etag::IfMatch;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    ffi::OsString,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Mutex, watch},
+    time::MissedTickBehavior,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Schema version written with every record; bump it and add a step to `MIGRATIONS` whenever
+/// the stored shape of a vehicle changes
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a record from version `n + 1` to `n + 2`
+const MIGRATIONS: &[fn(&mut Value)] = &[v1_to_v2];
+
+/// Why a snapshot could not be loaded
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("snapshot is corrupt: {0}")]
+    Corrupt(String),
+
+    /// Written by a newer build; loading it anyway would drop whatever that build added
+    #[error(
+        "vehicle {id} has schema version {version}, this build reads versions 1 to {SCHEMA_VERSION}"
+    )]
+    UnsupportedVersion { id: String, version: u32 },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Version 1 records predate timestamps, status, tags and metadata: vehicles count as created
+/// when their UUIDv7 id was minted, unchanged since, available and untagged
+fn v1_to_v2(record: &mut Value) {
+    let Some(fields) = record.as_object_mut() else {
+        return;
+    };
+    let minted = fields
+        .get("id")
+        .and_then(Value::as_str)
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .and_then(|id| id.get_timestamp())
+        .and_then(|timestamp| {
+            let (seconds, nanos) = timestamp.to_unix();
+            DateTime::<Utc>::from_timestamp(i64::try_from(seconds).ok()?, nanos)
+        })
+        .unwrap_or_else(Utc::now);
+    fields
+        .entry("createdAt")
+        .or_insert_with(|| minted.to_rfc3339().into());
+    fields
+        .entry("updatedAt")
+        .or_insert_with(|| minted.to_rfc3339().into());
+    fields
+        .entry("status")
+        .or_insert_with(|| VehicleStatus::default().as_str().into());
+    fields
+        .entry("tags")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    fields
+        .entry("metadata")
+        .or_insert_with(|| Value::Object(Default::default()));
+}
+
+/// Bring a record written at schema version `from` up to date and read it back
+pub fn migrate(mut record: Value, from: u32) -> Result<Vehicle, SnapshotError> {
+    let id = record
+        .get("id")
+        .and_then(Value::as_str)
+        .unwrap_or("<missing id>")
+        .to_string();
+    if from == 0 || from > SCHEMA_VERSION {
+        return Err(SnapshotError::UnsupportedVersion { id, version: from });
+    }
+    for step in &MIGRATIONS[from as usize - 1..] {
+        step(&mut record);
+    }
+    serde_json::from_value::<VehicleResponse>(record)
+        .map(Vehicle::from)
+        .map_err(|e| SnapshotError::Corrupt(format!("vehicle {id}: {e}")))
+}
+
+/// A vehicle as written to the snapshot and the append log
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleRecord {
+    schema_version: u32,
+    #[serde(flatten)]
+    vehicle: VehicleResponse,
+}
+
+impl From<Vehicle> for VehicleRecord {
+    fn from(vehicle: Vehicle) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            vehicle: vehicle.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+    vehicles: Vec<VehicleRecord>,
+}
+
+/// A snapshot as read back, before each record is migrated
+#[derive(Deserialize)]
+struct StoredSnapshot {
+    vehicles: Vec<Value>,
+}
+
+/// The vehicles in a snapshot file, each migrated to the current schema
+fn parse_snapshot(bytes: &[u8]) -> Result<Vec<Vehicle>, SnapshotError> {
+    let snapshot: StoredSnapshot =
+        serde_json::from_slice(bytes).map_err(|e| SnapshotError::Corrupt(e.to_string()))?;
+    snapshot.vehicles.into_iter().map(parse_record).collect()
+}
+
+/// A stored vehicle record migrated to the current schema
+pub fn parse_record(record: Value) -> Result<Vehicle, SnapshotError> {
+    // Version 1 records predate the field
+    let version = match record.get("schemaVersion") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| SnapshotError::Corrupt(format!("invalid schemaVersion {version}")))?,
+    };
+    migrate(record, version)
+}
+
+/// `path` with `suffix` appended to its file name
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Replace `path` with `contents` so readers only ever see the old or the new file whole
+async fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temporary = with_suffix(path, ".tmp");
+    let mut file = tokio::fs::File::create(&temporary).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&temporary, path).await
+}
+
+/// How the most recent snapshot write went, for the readiness probe
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotStatus {
+    /// When a snapshot was last attempted, `None` before the first one
+    pub last_attempt: Option<DateTime<Utc>>,
+    /// Why that attempt failed, `None` if it was written
+    pub last_error: Option<String>,
+    /// Whether there are changes the snapshot file doesn't have yet
+    pub dirty: bool,
+}
+
+/// What a successful change did to the store, for the append log
+enum Change {
+    Inserted(VehicleId),
+    Updated(VehicleId),
+    Deleted(VehicleId),
+}
+
+/// In-memory vehicle repo that survives restarts by keeping a JSON snapshot on disk.
+///
+/// The snapshot at `DATA_FILE` is loaded on startup and rewritten, through a temporary file
+/// and a rename, after every successful change, or only by `run_snapshots` when writes are
+/// deferred. A snapshot that can't be parsed is moved aside instead of stopping the service.
+/// With an append log, every change is also logged and replayed over the snapshot on startup,
+/// so deferred writes only lose what wasn't synced yet. Revision history is not persisted and
+/// starts over after a restart, and neither are evictions nor expiry sweeps.
+pub struct PersistentVehicleRepo {
+    inner: InMemoryVehicleRepo,
+    path: PathBuf,
+    /// Held for the whole of every change so the log has them in the order they were applied
+    log: Option<Mutex<AppendLog>>,
+    /// Write a snapshot after every change instead of leaving it to `run_snapshots`
+    write_through: bool,
+    /// Set by every change and cleared when a snapshot is taken
+    dirty: AtomicBool,
+    /// Held while writing so an older snapshot can't overwrite a newer one
+    flush_lock: Mutex<()>,
+    status: std::sync::Mutex<SnapshotStatus>,
+}
+
+impl PersistentVehicleRepo {
+    /// Wrap `inner`, filling it from the snapshot at `path` if there is one. Fails on I/O
+    /// errors and on records from a newer schema version, which must not be thrown away
+    pub async fn open(
+        inner: InMemoryVehicleRepo,
+        path: impl Into<PathBuf>,
+    ) -> Result<Self, SnapshotError> {
+        let path = path.into();
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => match parse_snapshot(&bytes) {
+                Ok(vehicles) => {
+                    info!(
+                        "Loaded {} vehicles from snapshot {}",
+                        vehicles.len(),
+                        path.display()
+                    );
+                    inner.load_vehicles(vehicles);
+                }
+                Err(SnapshotError::Corrupt(reason)) => {
+                    let aside = with_suffix(
+                        &path,
+                        &format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+                    );
+                    tokio::fs::rename(&path, &aside).await?;
+                    warn!(
+                        "Snapshot {} is corrupt ({}), moved it to {} and starting empty",
+                        path.display(),
+                        reason,
+                        aside.display()
+                    );
+                }
+                Err(e) => return Err(e),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("No snapshot at {}, starting empty", path.display());
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            inner,
+            path,
+            log: None,
+            write_through: true,
+            dirty: AtomicBool::new(false),
+            flush_lock: Mutex::new(()),
+            status: Default::default(),
+        })
+    }
+
+    /// Log every change next to the snapshot, first replaying what an earlier run logged since
+    /// its last snapshot; segments are closed once they reach `max_segment_bytes`
+    pub async fn with_append_log(self, max_segment_bytes: u64) -> Result<Self, SnapshotError> {
+        let (log, replayed) = AppendLog::open(&self.path, max_segment_bytes).await?;
+        if !replayed.is_empty() {
+            // The snapshot doesn't have these yet, and the segments holding them stay until it does
+            self.dirty.store(true, Ordering::Release);
+        }
+        for entry in replayed {
+            match entry {
+                Replayed::Put(vehicle) => self.inner.load_vehicles([*vehicle]),
+                Replayed::Delete(id) => self.inner.unload_vehicle(id),
+            }
+        }
+        Ok(Self {
+            log: Some(Mutex::new(log)),
+            ..self
+        })
+    }
+
+    /// Leave snapshots to `run_snapshots` instead of writing one after every change
+    pub fn with_deferred_writes(self) -> Self {
+        Self {
+            write_through: false,
+            ..self
+        }
+    }
+
+    /// Write a snapshot every `interval` while there are unsaved changes, and a final one once
+    /// `shutdown` fires
+    pub async fn run_snapshots(
+        self: Arc<Self>,
+        interval: Duration,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately and there is nothing to write yet
+        ticks.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    if let Err(e) = self.snapshot().await {
+                        error!("Failed to persist vehicle snapshot: {:?}", e);
+                    }
+                }
+                _ = shutdown.changed() => break,
+            }
+        }
+        info!("Writing final vehicle snapshot");
+        if let Err(e) = self.snapshot().await {
+            error!("Failed to persist vehicle snapshot: {:?}", e);
+        }
+    }
+
+    /// Write a snapshot if anything changed since the last one, returning whether it did
+    pub async fn snapshot(&self) -> Result<bool, RepoError> {
+        let _guard = self.flush_lock.lock().await;
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(false);
+        }
+        let written = self.persist().await;
+        // Keep the changes pending so the next attempt writes them
+        if written.is_err() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        let mut status = self
+            .status
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        status.last_attempt = Some(Utc::now());
+        status.last_error = written.as_ref().err().map(|e| format!("{e:?}"));
+
+        written.map(|()| true)
+    }
+
+    /// Sync the append log every `interval`, and a last time once `shutdown` fires
+    pub async fn run_log_sync(
+        self: Arc<Self>,
+        interval: Duration,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticks.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = self.sync_log().await {
+                error!("Failed to sync the append log: {:?}", e);
+            }
+        }
+        if let Err(e) = self.sync_log().await {
+            error!("Failed to sync the append log: {:?}", e);
+        }
+    }
+
+    /// Get everything appended to the log so far onto the disk
+    async fn sync_log(&self) -> Result<(), RepoError> {
+        let Some(log) = &self.log else {
+            return Ok(());
+        };
+        log.lock()
+            .await
+            .sync()
+            .await
+            .map_err(|e| RepoError::Backend(format!("failed to sync append log: {e}")))
+    }
+
+    /// Write the current contents of the store to the snapshot file, then drop the log segments
+    /// it makes redundant
+    async fn persist(&self) -> Result<(), RepoError> {
+        // Read under the log so every change is either in this snapshot or in a segment it keeps
+        let (vehicles, covered) = match &self.log {
+            None => (self.inner.vehicles(), None),
+            Some(log) => {
+                let mut log = log.lock().await;
+                let covered = log.checkpoint().await.map_err(|e| {
+                    RepoError::Backend(format!("failed to close append log segment: {e}"))
+                })?;
+                (self.inner.vehicles(), Some(covered))
+            }
+        };
+        let snapshot = Snapshot {
+            vehicles: vehicles.into_iter().map(VehicleRecord::from).collect(),
+        };
+        let contents =
+            serde_json::to_vec(&snapshot).map_err(|e| RepoError::Backend(e.to_string()))?;
+        write_atomically(&self.path, &contents).await.map_err(|e| {
+            RepoError::Backend(format!(
+                "failed to write snapshot {}: {e}",
+                self.path.display()
+            ))
+        })?;
+
+        // Segments left behind are only replayed again, which ends in the same state
+        if let (Some(log), Some(covered)) = (&self.log, covered)
+            && let Err(e) = log.lock().await.remove_before(covered).await
+        {
+            warn!(
+                "Failed to remove append log segments covered by the snapshot: {}",
+                e
+            );
+        }
+        Ok(())
+    }
+
+    /// Run `call`, logging the changes `changes` says it made while still holding the log so
+    /// no other change can be logged between it and them, then note them for the snapshot
+    async fn logged<T>(
+        &self,
+        call: impl Future<Output = Result<T, RepoError>>,
+        changes: impl FnOnce(&T) -> Vec<Change>,
+    ) -> Result<T, RepoError> {
+        let Some(log) = &self.log else {
+            return self.persisted(call.await).await;
+        };
+        let mut log = log.lock().await;
+        let result = call.await;
+        if let Ok(value) = &result {
+            let at = Utc::now();
+            for change in changes(value) {
+                // The vehicle as the change left it; one that has already expired needs no entry
+                let entry = match change {
+                    Change::Inserted(id) => match self.inner.stored_vehicle(id) {
+                        Some(vehicle) => LogEntry::Insert {
+                            at,
+                            vehicle: vehicle.into(),
+                        },
+                        None => continue,
+                    },
+                    Change::Updated(id) => match self.inner.stored_vehicle(id) {
+                        Some(vehicle) => LogEntry::Update {
+                            at,
+                            vehicle: vehicle.into(),
+                        },
+                        None => continue,
+                    },
+                    Change::Deleted(id) => LogEntry::Delete { at, id },
+                };
+                if let Err(e) = log.append(&entry).await {
+                    error!("Failed to append to the append log: {}", e);
+                }
+            }
+        }
+        drop(log);
+        self.persisted(result).await
+    }
+
+    /// Pass `result` through, noting the change it reports if it succeeded and writing a
+    /// snapshot unless writes are deferred. The change is already live in memory, so a failed
+    /// write is logged rather than returned and the next snapshot tries again
+    async fn persisted<T>(&self, result: Result<T, RepoError>) -> Result<T, RepoError> {
+        if result.is_ok() {
+            self.dirty.store(true, Ordering::Release);
+            if self.write_through
+                && let Err(e) = self.snapshot().await
+            {
+                error!("Failed to persist vehicle snapshot: {:?}", e);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for PersistentVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.inner.get_vehicle(id).await
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.inner.get_vehicle_by_vin(vin).await
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        self.inner.get_vehicle_by_plate(plate).await
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        self.inner.exists(id).await
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        self.inner.get_vehicles_by_ids(ids).await
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        self.inner.list(page).await
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        self.inner.list_summaries(page).await
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        self.inner.get_vehicles_after(filter, cursor, limit).await
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        self.inner.search_vehicles(search, offset, limit).await
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        self.inner.count(filter).await
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        self.inner.stats(filter).await
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        self.inner.manufacturers().await
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        self.logged(self.inner.post_vehicle(vehicle, allow_duplicate), |id| {
+            vec![Change::Inserted(*id)]
+        })
+        .await
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.logged(self.inner.post_vehicles(vehicles), |ids| {
+            ids.iter().copied().map(Change::Inserted).collect()
+        })
+        .await
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        self.logged(self.inner.post_vehicles_atomic(vehicles), |ids| {
+            ids.iter().copied().map(Change::Inserted).collect()
+        })
+        .await
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.logged(
+            self.inner.update_vehicle(id, vehicle, if_match, correction),
+            |updated| vec![Change::Updated(updated.id)],
+        )
+        .await
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.logged(
+            self.inner.upsert_vehicle(id, vehicle, if_match, correction),
+            |(written, created)| match created {
+                true => vec![Change::Inserted(written.id)],
+                false => vec![Change::Updated(written.id)],
+            },
+        )
+        .await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.logged(self.inner.delete_vehicle(id), |removed| {
+            removed.iter().map(|v| Change::Deleted(v.id)).collect()
+        })
+        .await
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.logged(self.inner.soft_delete(id), |deleted| {
+            deleted.iter().map(|v| Change::Updated(v.id)).collect()
+        })
+        .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.logged(self.inner.restore(id), |restored| {
+            vec![Change::Updated(restored.id)]
+        })
+        .await
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.logged(self.inner.add_tags(id, tags), |tagged| {
+            vec![Change::Updated(tagged.id)]
+        })
+        .await
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        self.logged(self.inner.remove_tag(id, tag), |untagged| {
+            vec![Change::Updated(untagged.id)]
+        })
+        .await
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.logged(self.inner.set_status(id, status, if_match), |changed| {
+            vec![Change::Updated(changed.id)]
+        })
+        .await
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        self.logged(self.inner.delete_vehicles(ids), |(_, missing)| {
+            ids.iter()
+                .filter(|id| !missing.contains(id))
+                .copied()
+                .map(Change::Deleted)
+                .collect()
+        })
+        .await
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        // The store only reports how many it changed, so note which ones first
+        let unassign = async {
+            let owned = self
+                .inner
+                .list(PageRequest {
+                    filter: VehicleFilter {
+                        owner_id: Some(owner_id),
+                        include_deleted: Some(true),
+                        ..Default::default()
+                    },
+                    limit: usize::MAX,
+                    ..Default::default()
+                })
+                .await?;
+            let changed = self.inner.unassign_owner(owner_id).await?;
+            Ok((changed, owned.items))
+        };
+        self.logged(unassign, |(_, owned)| {
+            owned.iter().map(|v| Change::Updated(v.id)).collect()
+        })
+        .await
+        .map(|(changed, _)| changed)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        self.inner.get_history(id).await
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        self.inner.dump().await
+    }
+
+    /// A replace isn't logged vehicle by vehicle, since replaying older segments would bring
+    /// the replaced vehicles back; a snapshot is written straight away instead, deferred writes
+    /// or not, which drops those segments. A crash before it lands restarts on the old store
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        if mode == RestoreMode::Merge {
+            return self
+                .logged(self.inner.restore_dump(vehicles, mode), |report| {
+                    report
+                        .restored
+                        .iter()
+                        .copied()
+                        .map(Change::Inserted)
+                        .collect()
+                })
+                .await;
+        }
+        let report = self.inner.restore_dump(vehicles, mode).await?;
+        if report.conflicts.is_empty() {
+            self.dirty.store(true, Ordering::Release);
+            if let Err(e) = self.snapshot().await {
+                error!("Failed to persist vehicle snapshot: {:?}", e);
+            }
+        }
+        Ok(report)
+    }
+
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.inner.health_check().await
+    }
+
+    async fn flush(&self) -> Result<(), RepoError> {
+        self.sync_log().await?;
+        self.snapshot().await.map(|_| ())
+    }
+
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        let status = self
+            .status
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Some(SnapshotStatus {
+            dirty: self.dirty.load(Ordering::Acquire),
+            ..status.clone()
+        })
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        self.inner.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_FIXTURE: &str = include_str!("fixtures/vehicles_v1.json");
+
+    fn v1_records() -> Vec<Value> {
+        serde_json::from_str(V1_FIXTURE).expect("fixture is valid JSON")
+    }
+
+    #[test]
+    fn v1_fixture_migrates_to_current() {
+        let vehicles = v1_records()
+            .into_iter()
+            .map(parse_record)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("v1 records migrate");
+
+        let corolla = &vehicles[0];
+        assert_eq!(
+            corolla.id.to_string(),
+            "018c2b5e-93a0-7d4e-9b1f-2a6c8e0f4d31"
+        );
+        assert_eq!(corolla.manufacturer, "Toyota");
+        assert_eq!(corolla.vin.as_deref(), Some("1HGCM82633A004352"));
+        assert_eq!(corolla.version, 3);
+        assert_eq!(
+            corolla.created_at.to_rfc3339(),
+            "2023-12-02T16:31:07.680+00:00"
+        );
+        assert_eq!(corolla.updated_at, corolla.created_at);
+        assert_eq!(corolla.status, VehicleStatus::Available);
+        assert!(corolla.tags.is_empty());
+        assert!(corolla.metadata.is_empty());
+
+        let golf = &vehicles[1];
+        assert_eq!(golf.vin, None);
+        assert_eq!(
+            golf.created_at.to_rfc3339(),
+            "2023-12-02T16:33:49.694+00:00"
+        );
+    }
+
+    #[test]
+    fn current_record_round_trips() {
+        let vehicle = parse_record(v1_records().remove(0)).unwrap();
+        let record = serde_json::to_value(VehicleRecord::from(vehicle.clone())).unwrap();
+        assert_eq!(record["schemaVersion"], SCHEMA_VERSION);
+
+        let read = parse_record(record).unwrap();
+        assert_eq!(read.id, vehicle.id);
+        assert_eq!(read.created_at, vehicle.created_at);
+        assert_eq!(read.status, vehicle.status);
+        assert_eq!(read.version, vehicle.version);
+    }
+
+    #[test]
+    fn future_version_is_rejected_with_the_id() {
+        let mut record = v1_records().remove(1);
+        record["schemaVersion"] = (SCHEMA_VERSION + 1).into();
+
+        let error = parse_record(record).unwrap_err();
+        assert!(matches!(
+            error,
+            SnapshotError::UnsupportedVersion { ref id, version }
+                if id == "018c2b61-0c7e-7f21-a3d5-6b8e9c1a2f47" && version == SCHEMA_VERSION + 1
+        ));
+        assert!(
+            error
+                .to_string()
+                .contains("018c2b61-0c7e-7f21-a3d5-6b8e9c1a2f47")
+        );
+    }
+
+    #[test]
+    fn version_zero_is_rejected() {
+        let error = migrate(v1_records().remove(0), 0).unwrap_err();
+        assert!(matches!(
+            error,
+            SnapshotError::UnsupportedVersion { version: 0, .. }
+        ));
+    }
+}
@@ -0,0 +1,868 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        manufacturer::ManufacturerAliases,
+        model::{
+            ChangeType, CreateVehicleRequest, EngineSpec, FuelType, MAX_TAGS, PageRequest,
+            PageResult, Vehicle, VehicleFilter, VehicleId, VehicleRevision, VehicleSearch,
+            VehicleStats, VehicleStatus, VehicleSummary, normalise_color, normalise_plate,
+            normalise_tags, normalise_vin,
+        },
+        repo::{RepoError, VehicleRepo, check_mileage, check_not_deleted, collect_stats, identity},
+    },
+};
+use crate::utils::{etag::IfMatch, sorting};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgConnection, PgPool, postgres::PgPoolOptions, types::Json};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::Duration,
+};
+use uuid::Uuid;
+
+/// Vehicle repo backed by the Postgres tables in `migrations/`, applied on connect.
+///
+/// Every write runs in a transaction that locks the vehicle row first, so If-Match and mileage
+/// checks can't race. VIN and live-plate uniqueness are enforced by unique indexes, with a
+/// lookup beforehand so conflicts can name the vehicle holding the key. Filters, sorting,
+/// search and stats are evaluated in Rust over the fetched rows, exactly like the in-memory
+/// backends; only soft-deleted vehicles and cursors are filtered in SQL.
+pub struct PostgresVehicleRepo {
+    pool: PgPool,
+    history_limit: usize,
+    aliases: ManufacturerAliases,
+}
+
+/// One row of `vehicles`; the columns mirror `Vehicle` in database types
+#[derive(FromRow)]
+struct VehicleRow {
+    id: Uuid,
+    manufacturer: String,
+    manufacturer_raw: Option<String>,
+    model: String,
+    year: i16,
+    vin: Option<String>,
+    mileage_km: Option<i64>,
+    color: Option<String>,
+    license_plate: Option<String>,
+    price: Option<Decimal>,
+    fuel_type: Option<String>,
+    tags: Vec<String>,
+    metadata: Json<BTreeMap<String, String>>,
+    engine: Option<Json<EngineSpec>>,
+    owner_id: Option<Uuid>,
+    first_registered: Option<NaiveDate>,
+    status: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    version: i64,
+}
+
+impl TryFrom<VehicleRow> for Vehicle {
+    type Error = RepoError;
+
+    fn try_from(row: VehicleRow) -> Result<Self, RepoError> {
+        let id = row.id;
+        let corrupt =
+            |column: &str| RepoError::Backend(format!("vehicle {id} has an invalid {column}"));
+        Ok(Vehicle {
+            id: VehicleId(row.id),
+            manufacturer: row.manufacturer,
+            manufacturer_raw: row.manufacturer_raw,
+            model: row.model,
+            year: u16::try_from(row.year).map_err(|_| corrupt("year"))?,
+            vin: row.vin,
+            mileage_km: row
+                .mileage_km
+                .map(u32::try_from)
+                .transpose()
+                .map_err(|_| corrupt("mileage_km"))?,
+            color: row.color,
+            license_plate: row.license_plate,
+            price: row.price,
+            fuel_type: row
+                .fuel_type
+                .map(|fuel| fuel.parse::<FuelType>())
+                .transpose()
+                .map_err(|_| corrupt("fuel_type"))?,
+            tags: row.tags,
+            metadata: row.metadata.0,
+            engine: row.engine.map(|engine| engine.0),
+            owner_id: row.owner_id.map(OwnerId),
+            first_registered: row.first_registered,
+            status: VehicleStatus::ALL
+                .iter()
+                .copied()
+                .find(|status| status.as_str() == row.status)
+                .ok_or_else(|| corrupt("status"))?,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            expires_at: None,
+            version: u64::try_from(row.version).map_err(|_| corrupt("version"))?,
+        })
+    }
+}
+
+/// The vehicle with this id, locked until the transaction on `conn` ends
+async fn lock(conn: &mut PgConnection, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+    sqlx::query_as::<_, VehicleRow>("SELECT * FROM vehicles WHERE id = $1 FOR UPDATE")
+        .bind(id.0)
+        .fetch_optional(conn)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+}
+
+/// Insert `vehicle`, or overwrite the row with its id
+async fn save(conn: &mut PgConnection, vehicle: &Vehicle) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO vehicles (id, manufacturer, manufacturer_raw, model, year, vin, mileage_km, \
+         color, license_plate, price, fuel_type, tags, metadata, engine, owner_id, \
+         first_registered, status, created_at, updated_at, deleted_at, version) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, \
+         $18, $19, $20, $21) \
+         ON CONFLICT (id) DO UPDATE SET manufacturer = EXCLUDED.manufacturer, \
+         manufacturer_raw = EXCLUDED.manufacturer_raw, model = EXCLUDED.model, \
+         year = EXCLUDED.year, vin = EXCLUDED.vin, mileage_km = EXCLUDED.mileage_km, \
+         color = EXCLUDED.color, license_plate = EXCLUDED.license_plate, \
+         price = EXCLUDED.price, fuel_type = EXCLUDED.fuel_type, tags = EXCLUDED.tags, \
+         metadata = EXCLUDED.metadata, engine = EXCLUDED.engine, owner_id = EXCLUDED.owner_id, \
+         first_registered = EXCLUDED.first_registered, status = EXCLUDED.status, \
+         created_at = EXCLUDED.created_at, updated_at = EXCLUDED.updated_at, \
+         deleted_at = EXCLUDED.deleted_at, version = EXCLUDED.version",
+    )
+    .bind(vehicle.id.0)
+    .bind(&vehicle.manufacturer)
+    .bind(&vehicle.manufacturer_raw)
+    .bind(&vehicle.model)
+    .bind(vehicle.year as i16)
+    .bind(&vehicle.vin)
+    .bind(vehicle.mileage_km.map(i64::from))
+    .bind(&vehicle.color)
+    .bind(&vehicle.license_plate)
+    .bind(vehicle.price)
+    .bind(vehicle.fuel_type.map(FuelType::as_str))
+    .bind(&vehicle.tags)
+    .bind(Json(&vehicle.metadata))
+    .bind(vehicle.engine.map(Json))
+    .bind(vehicle.owner_id.map(|owner| owner.0))
+    .bind(vehicle.first_registered)
+    .bind(vehicle.status.as_str())
+    .bind(vehicle.created_at)
+    .bind(vehicle.updated_at)
+    .bind(vehicle.deleted_at)
+    .bind(vehicle.version as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Another vehicle holding `vin`, soft-deleted ones included
+async fn vin_holder(
+    conn: &mut PgConnection,
+    vin: &str,
+    id: VehicleId,
+) -> Result<Option<VehicleId>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM vehicles WHERE vin = $1 AND id <> $2")
+        .bind(vin)
+        .bind(id.0)
+        .fetch_optional(conn)
+        .await
+        .map(|holder| holder.map(VehicleId))
+}
+
+/// Another live vehicle holding `plate`
+async fn plate_holder(
+    conn: &mut PgConnection,
+    plate: &str,
+    id: VehicleId,
+) -> Result<Option<VehicleId>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT id FROM vehicles WHERE license_plate = $1 AND deleted_at IS NULL AND id <> $2",
+    )
+    .bind(plate)
+    .bind(id.0)
+    .fetch_optional(conn)
+    .await
+    .map(|holder| holder.map(VehicleId))
+}
+
+/// Fail when the VIN or, for live vehicles, the plate of `vehicle` belongs to another vehicle
+async fn check_keys(conn: &mut PgConnection, vehicle: &Vehicle) -> Result<(), RepoError> {
+    if let Some(vin) = &vehicle.vin
+        && let Some(existing_id) = vin_holder(conn, vin, vehicle.id).await?
+    {
+        return Err(RepoError::VinTaken { existing_id });
+    }
+    if vehicle.deleted_at.is_none()
+        && let Some(plate) = &vehicle.license_plate
+        && let Some(existing_id) = plate_holder(conn, plate, vehicle.id).await?
+    {
+        return Err(RepoError::PlateTaken { existing_id });
+    }
+    Ok(())
+}
+
+impl PostgresVehicleRepo {
+    /// Connect to `database_url` and apply pending migrations, keeping up to `history_limit`
+    /// revisions per vehicle; 0 disables history
+    pub async fn connect(database_url: &str, history_limit: usize) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            // Fail requests and readiness probes quickly while the database is unreachable
+            .acquire_timeout(Duration::from_secs(5))
+            .connect(database_url)
+            .await?;
+        sqlx::migrate!("migrations/postgres").run(&pool).await?;
+
+        Ok(Self {
+            pool,
+            history_limit,
+            aliases: ManufacturerAliases::default(),
+        })
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    /// Snapshot `vehicle` into its revision history, dropping revisions past the limit
+    async fn record(
+        &self,
+        conn: &mut PgConnection,
+        vehicle: &Vehicle,
+        change_type: ChangeType,
+    ) -> Result<(), RepoError> {
+        if self.history_limit == 0 {
+            return Ok(());
+        }
+        let revision = VehicleRevision {
+            version: vehicle.version,
+            changed_at: Utc::now(),
+            change_type,
+            snapshot: vehicle.clone().into(),
+        };
+        sqlx::query(
+            "INSERT INTO vehicle_revisions (vehicle_id, version, revision) VALUES ($1, $2, $3) \
+             ON CONFLICT (vehicle_id, version) DO UPDATE SET revision = EXCLUDED.revision",
+        )
+        .bind(vehicle.id.0)
+        .bind(vehicle.version as i64)
+        .bind(Json(&revision))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            "DELETE FROM vehicle_revisions WHERE vehicle_id = $1 AND version NOT IN \
+             (SELECT version FROM vehicle_revisions WHERE vehicle_id = $1 \
+             ORDER BY version DESC LIMIT $2)",
+        )
+        .bind(vehicle.id.0)
+        .bind(self.history_limit as i64)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Save `vehicle` after checking its keys and record the change
+    async fn store(
+        &self,
+        conn: &mut PgConnection,
+        vehicle: &Vehicle,
+        change_type: ChangeType,
+    ) -> Result<(), RepoError> {
+        check_keys(conn, vehicle).await?;
+        if let Err(error) = save(conn, vehicle).await {
+            return Err(self.conflict(error, vehicle).await);
+        }
+        self.record(conn, vehicle, change_type).await
+    }
+
+    /// Turn a unique violation lost to a concurrent writer into the conflict it stands for
+    async fn conflict(&self, error: sqlx::Error, vehicle: &Vehicle) -> RepoError {
+        let constraint = error
+            .as_database_error()
+            .and_then(|e| e.constraint())
+            .map(str::to_owned);
+        // The failed statement aborted the transaction, so look the holder up on a fresh one
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => return e.into(),
+        };
+        let holder = match (constraint.as_deref(), &vehicle.vin, &vehicle.license_plate) {
+            (Some("vehicles_vin_key"), Some(vin), _) => vin_holder(&mut conn, vin, vehicle.id)
+                .await
+                .map(|holder| holder.map(|existing_id| RepoError::VinTaken { existing_id })),
+            (Some("vehicles_live_plate_key"), _, Some(plate)) => {
+                plate_holder(&mut conn, plate, vehicle.id)
+                    .await
+                    .map(|holder| holder.map(|existing_id| RepoError::PlateTaken { existing_id }))
+            }
+            _ => Ok(None),
+        };
+        match holder {
+            Ok(Some(conflict)) => conflict,
+            Ok(None) => error.into(),
+            Err(e) => e.into(),
+        }
+    }
+
+    /// Lock the vehicle, let `change` edit it and save the result; `change` returns the kind of
+    /// change made, or `None` to leave the vehicle untouched
+    async fn modify(
+        &self,
+        id: VehicleId,
+        change: impl FnOnce(&mut Vehicle) -> Result<Option<ChangeType>, RepoError> + Send,
+    ) -> Result<Option<Vehicle>, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let Some(mut vehicle) = lock(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        let Some(change_type) = change(&mut vehicle)? else {
+            return Ok(Some(vehicle));
+        };
+        vehicle.updated_at = Utc::now();
+        vehicle.version += 1;
+        self.store(&mut tx, &vehicle, change_type).await?;
+        tx.commit().await?;
+
+        Ok(Some(vehicle))
+    }
+
+    /// Replace the vehicle with this id, creating it when `create` is set; the flag is true
+    /// when it was created
+    async fn write(
+        &self,
+        conn: &mut PgConnection,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+        create: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let manufacturer_raw = self.canonicalise(&mut vehicle);
+        let existing = lock(conn, id).await?;
+        if existing.is_none() && !create {
+            return Err(RepoError::NotFound);
+        }
+        check_not_deleted(existing.as_ref())?;
+        // If-Match can only be satisfied by a vehicle that already exists
+        if let Some(if_match) = if_match
+            && !existing
+                .as_ref()
+                .is_some_and(|v| if_match.matches(&v.etag()))
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: existing.map(|v| v.version),
+            });
+        }
+        check_mileage(existing.as_ref(), vehicle.mileage_km, correction)?;
+        let created = existing.is_none();
+        let now = Utc::now();
+        let stored = Vehicle {
+            id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: normalise_vin(vehicle.vin),
+            mileage_km: vehicle.mileage_km,
+            color: normalise_color(vehicle.color),
+            license_plate: normalise_plate(vehicle.license_plate),
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: normalise_tags(vehicle.tags),
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: existing
+                .as_ref()
+                .map_or_else(VehicleStatus::default, |v| v.status),
+            created_at: existing.as_ref().map_or(now, |v| v.created_at),
+            updated_at: now,
+            deleted_at: existing.as_ref().and_then(|v| v.deleted_at),
+            expires_at: None,
+            version: existing.as_ref().map_or(1, |v| v.version + 1),
+        };
+        let change_type = if created {
+            ChangeType::Created
+        } else {
+            ChangeType::Updated
+        };
+        self.store(conn, &stored, change_type).await?;
+
+        Ok((stored, created))
+    }
+
+    /// Vehicles matching `filter` past `cursor`, in id order
+    async fn matching(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+    ) -> Result<Vec<Vehicle>, RepoError> {
+        // Filters name manufacturers the way clients do, so resolve aliases like stored values
+        let filter = VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        };
+        let rows = sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE ($1 OR deleted_at IS NULL) \
+             AND ($2::uuid IS NULL OR id > $2) ORDER BY id",
+        )
+        .bind(filter.include_deleted.unwrap_or(false))
+        .bind(cursor.map(|id| id.0))
+        .fetch_all(&self.pool)
+        .await?;
+        let mut matching = Vec::new();
+        for row in rows {
+            let vehicle = Vehicle::try_from(row)?;
+            if filter.matches(&vehicle) {
+                matching.push(vehicle);
+            }
+        }
+
+        Ok(matching)
+    }
+
+    /// First live vehicle sharing the manufacturer/model/year of `vehicle`
+    async fn find_duplicate(
+        &self,
+        vehicle: &CreateVehicleRequest,
+    ) -> Result<Option<VehicleId>, RepoError> {
+        let manufacturer = self.aliases.canonical(&vehicle.manufacturer);
+        let (manufacturer, model, year) = identity(&manufacturer, &vehicle.model, vehicle.year);
+        let existing = sqlx::query_scalar(
+            "SELECT id FROM vehicles WHERE deleted_at IS NULL AND lower(manufacturer) = $1 \
+             AND lower(model) = $2 AND year = $3 ORDER BY id LIMIT 1",
+        )
+        .bind(manufacturer)
+        .bind(model)
+        .bind(year as i16)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(existing.map(VehicleId))
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for PostgresVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(id.0)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE vin = $1 AND deleted_at IS NULL",
+        )
+        .bind(vin.trim().to_ascii_uppercase())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let Some(plate) = normalise_plate(Some(plate.to_string())) else {
+            return Ok(None);
+        };
+        sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE license_plate = $1 AND deleted_at IS NULL",
+        )
+        .bind(plate)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        let exists = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM vehicles WHERE id = $1 AND deleted_at IS NULL)",
+        )
+        .bind(id.0)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        let uuids: Vec<Uuid> = ids.iter().map(|id| id.0).collect();
+        let rows = sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE id = ANY($1) AND deleted_at IS NULL",
+        )
+        .bind(uuids)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut stored = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let vehicle = Vehicle::try_from(row)?;
+            stored.insert(vehicle.id, vehicle);
+        }
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for id in ids {
+            match stored.get(id) {
+                Some(vehicle) => found.push(vehicle.clone()),
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let mut matching = self.matching(&page.filter, None).await?;
+        let total = matching.len();
+
+        matching.sort_by(|a, b| sorting::compare(&page.sort, a, b));
+        let items = matching
+            .into_iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .collect();
+
+        Ok(PageResult { items, total })
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        Ok(self.list(page).await?.map(|v| VehicleSummary::from(&v)))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let mut range = self.matching(filter, cursor).await?.into_iter();
+        let items: Vec<Vehicle> = range.by_ref().take(limit).collect();
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = match range.next() {
+            Some(_) => items.last().map(|v| v.id),
+            None => None,
+        };
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let rows = sqlx::query_as::<_, VehicleRow>("SELECT * FROM vehicles ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut matching = Vec::new();
+        for row in rows {
+            let vehicle = Vehicle::try_from(row)?;
+            if search.matches(&vehicle) {
+                matching.push(vehicle);
+            }
+        }
+        let total = matching.len();
+        let items = matching.into_iter().skip(offset).take(limit).collect();
+
+        Ok((items, total))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let everything = VehicleFilter::default();
+        let filter = filter.unwrap_or(&everything);
+        Ok(self.matching(filter, None).await?.len() as u64)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        Ok(collect_stats(&self.matching(filter, None).await?))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let manufacturers: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT manufacturer FROM vehicles WHERE deleted_at IS NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Sorted here so the order doesn't depend on the database collation
+        Ok(manufacturers
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        if !allow_duplicate && let Some(existing_id) = self.find_duplicate(&vehicle).await? {
+            return Err(RepoError::Duplicate { existing_id });
+        }
+        let id = VehicleId::generate();
+        let mut tx = self.pool.begin().await?;
+        self.write(&mut tx, id, vehicle, None, false, true).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            match self.write(&mut tx, id, vehicle, None, false, true).await {
+                Ok(_) => ids.push(id),
+                Err(RepoError::VinTaken { .. } | RepoError::PlateTaken { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        // Any failure drops the transaction, rolling back the vehicles written before it
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            self.write(&mut tx, id, vehicle, None, false, true).await?;
+            ids.push(id);
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let (updated, _) = self
+            .write(&mut tx, id, vehicle, if_match, correction, false)
+            .await?;
+        tx.commit().await?;
+
+        Ok(updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let written = self
+            .write(&mut tx, id, vehicle, if_match, correction, true)
+            .await?;
+        tx.commit().await?;
+
+        Ok(written)
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        // Revisions go with the vehicle through the foreign key
+        sqlx::query_as::<_, VehicleRow>("DELETE FROM vehicles WHERE id = $1 RETURNING *")
+            .bind(id.0)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(Vehicle::try_from)
+            .transpose()
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.modify(id, |vehicle| {
+            // Keep the original timestamp when deleting an already deleted vehicle
+            if vehicle.deleted_at.is_some() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = Some(Utc::now());
+            Ok(Some(ChangeType::Deleted))
+        })
+        .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            if vehicle.deleted_at.is_none() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = None;
+            Ok(Some(ChangeType::Restored))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            let merged = normalise_tags(vehicle.tags.iter().cloned().chain(tags).collect());
+            if merged.len() == vehicle.tags.len() {
+                return Ok(None);
+            }
+            if merged.len() > MAX_TAGS {
+                return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+            }
+            vehicle.tags = merged;
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let tag = tag.trim().to_lowercase();
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if !vehicle.tags.contains(&tag) {
+                return Ok(None);
+            }
+            vehicle.tags.retain(|t| *t != tag);
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if let Some(if_match) = if_match
+                && !if_match.matches(&vehicle.etag())
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: Some(vehicle.version),
+                });
+            }
+            if vehicle.status == status {
+                return Ok(None);
+            }
+            vehicle.status = status;
+            Ok(Some(ChangeType::StatusChanged))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            let removed = sqlx::query("DELETE FROM vehicles WHERE id = $1")
+                .bind(id.0)
+                .execute(&mut *tx)
+                .await?;
+            match removed.rows_affected() {
+                0 => missing.push(*id),
+                _ => deleted += 1,
+            }
+        }
+        tx.commit().await?;
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE owner_id = $1 ORDER BY id FOR UPDATE",
+        )
+        .bind(owner_id.0)
+        .fetch_all(&mut *tx)
+        .await?;
+        let now = Utc::now();
+        let unassigned = rows.len();
+        for row in rows {
+            let mut vehicle = Vehicle::try_from(row)?;
+            vehicle.owner_id = None;
+            vehicle.updated_at = now;
+            vehicle.version += 1;
+            // Only the owner changed, so the VIN and plate checks can be skipped
+            save(&mut tx, &vehicle).await?;
+            self.record(&mut tx, &vehicle, ChangeType::Updated).await?;
+        }
+        tx.commit().await?;
+
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        if !self.exists(id).await? {
+            return Ok(None);
+        }
+        let revisions: Vec<Json<VehicleRevision>> = sqlx::query_scalar(
+            "SELECT revision FROM vehicle_revisions WHERE vehicle_id = $1 ORDER BY version DESC",
+        )
+        .bind(id.0)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(
+            revisions.into_iter().map(|revision| revision.0).collect(),
+        ))
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+}
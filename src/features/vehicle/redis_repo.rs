@@ -0,0 +1,800 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        manufacturer::ManufacturerAliases,
+        model::{
+            ChangeType, CreateVehicleRequest, MAX_TAGS, PageRequest, PageResult, Vehicle,
+            VehicleFilter, VehicleId, VehicleResponse, VehicleRevision, VehicleSearch,
+            VehicleStats, VehicleStatus, VehicleSummary, normalise_color, normalise_plate,
+            normalise_tags, normalise_vin,
+        },
+        repo::{RepoError, VehicleRepo, check_mileage, check_not_deleted, collect_stats, identity},
+    },
+};
+use crate::utils::{etag::IfMatch, sorting};
+use async_trait::async_trait;
+use chrono::Utc;
+use redis::{Client, RedisError, Script, aio::ConnectionManager};
+use std::{collections::BTreeSet, time::Duration};
+
+/// Set of every stored vehicle id, used for listing
+const IDS_KEY: &str = "vehicles:ids";
+
+/// Conditional writes that lose to another writer are retried this many times before giving up
+const MAX_ATTEMPTS: usize = 5;
+
+/// Replace a vehicle if it still holds the JSON it was read with, claiming its VIN and plate and
+/// giving up the ones it no longer holds.
+///
+/// KEYS: vehicle, id set, history, new VIN, new plate, old VIN, old plate (index keys are empty
+/// when unused). ARGV: expected JSON (empty when absent), new JSON, TTL in seconds (0 for none),
+/// id, revision JSON (empty to skip), history limit.
+const WRITE_SCRIPT: &str = r"
+if (redis.call('GET', KEYS[1]) or '') ~= ARGV[1] then
+    return {'changed'}
+end
+local ttl = tonumber(ARGV[3])
+local function put(key, value)
+    if ttl > 0 then
+        redis.call('SET', key, value, 'EX', ttl)
+    else
+        redis.call('SET', key, value)
+    end
+end
+-- An index entry only counts while the vehicle it points at is still stored
+local function holder(key)
+    if key == '' then
+        return false
+    end
+    local id = redis.call('GET', key)
+    if id and id ~= ARGV[4] and redis.call('EXISTS', 'vehicle:' .. id) == 1 then
+        return id
+    end
+    return false
+end
+local vin_holder = holder(KEYS[4])
+if vin_holder then
+    return {'vin', vin_holder}
+end
+local plate_holder = holder(KEYS[5])
+if plate_holder then
+    return {'plate', plate_holder}
+end
+for i = 6, 7 do
+    if KEYS[i] ~= '' and KEYS[i] ~= KEYS[4] and KEYS[i] ~= KEYS[5]
+        and redis.call('GET', KEYS[i]) == ARGV[4] then
+        redis.call('DEL', KEYS[i])
+    end
+end
+put(KEYS[1], ARGV[2])
+for i = 4, 5 do
+    if KEYS[i] ~= '' then
+        put(KEYS[i], ARGV[4])
+    end
+end
+redis.call('SADD', KEYS[2], ARGV[4])
+if ARGV[5] ~= '' then
+    redis.call('LPUSH', KEYS[3], ARGV[5])
+    redis.call('LTRIM', KEYS[3], 0, tonumber(ARGV[6]) - 1)
+    if ttl > 0 then
+        redis.call('EXPIRE', KEYS[3], ttl)
+    end
+end
+return {'ok'}
+";
+
+/// Remove a vehicle if it still holds the JSON it was read with, along with its history and the
+/// index entries pointing at it.
+///
+/// KEYS: vehicle, id set, history, VIN, plate. ARGV: expected JSON, id.
+const DELETE_SCRIPT: &str = r"
+if (redis.call('GET', KEYS[1]) or '') ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1], KEYS[3])
+redis.call('SREM', KEYS[2], ARGV[2])
+for i = 4, 5 do
+    if KEYS[i] ~= '' and redis.call('GET', KEYS[i]) == ARGV[2] then
+        redis.call('DEL', KEYS[i])
+    end
+end
+return 1
+";
+
+/// Drop ids whose vehicle has expired from the id set. KEYS: id set. ARGV: ids.
+const PRUNE_SCRIPT: &str = r"
+for _, id in ipairs(ARGV) do
+    if redis.call('EXISTS', 'vehicle:' .. id) == 0 then
+        redis.call('SREM', KEYS[1], id)
+    end
+end
+return 0
+";
+
+/// Vehicle repo shared by every replica through Redis.
+///
+/// Each vehicle is a JSON document under `vehicle:{id}`, listed through the `vehicles:ids` set,
+/// with `vehicle:vin:*` and `vehicle:plate:*` pointing back at their holders and revisions in
+/// the `vehicle:{id}:history` list. Writes are Lua scripts that only apply if the vehicle is
+/// unchanged since it was read and are retried otherwise, so replicas can't overwrite each
+/// other. Listings, filters, search and stats load every vehicle and evaluate in the service;
+/// duplicate detection is best-effort, as with the `dashmap` backend.
+///
+/// With a TTL every key expires that long after the vehicle's last write; expired ids are
+/// pruned from the set when a listing finds them.
+pub struct RedisVehicleRepo {
+    conn: ConnectionManager,
+    write_script: Script,
+    delete_script: Script,
+    prune_script: Script,
+    ttl: Option<Duration>,
+    history_limit: usize,
+    aliases: ManufacturerAliases,
+}
+
+fn vehicle_key(id: VehicleId) -> String {
+    format!("vehicle:{id}")
+}
+
+fn history_key(id: VehicleId) -> String {
+    format!("vehicle:{id}:history")
+}
+
+fn vin_key(vin: Option<&str>) -> String {
+    vin.map(|vin| format!("vehicle:vin:{vin}"))
+        .unwrap_or_default()
+}
+
+fn plate_key(plate: Option<&str>) -> String {
+    plate
+        .map(|plate| format!("vehicle:plate:{plate}"))
+        .unwrap_or_default()
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<String, RepoError> {
+    serde_json::to_string(value).map_err(|e| RepoError::Backend(e.to_string()))
+}
+
+fn decode(raw: &str) -> Result<Vehicle, RepoError> {
+    serde_json::from_str::<VehicleResponse>(raw)
+        .map(Vehicle::from)
+        .map_err(|e| RepoError::Backend(format!("corrupt vehicle record: {e}")))
+}
+
+/// A vehicle together with the exact JSON it was read from, for conditional writes
+type Stored = (String, Vehicle);
+
+impl RedisVehicleRepo {
+    /// Connect to the server at `redis_url`, keeping up to `history_limit` revisions per
+    /// vehicle; 0 disables history. The connection reconnects by itself after failures
+    pub async fn connect(redis_url: &str, history_limit: usize) -> Result<Self, RedisError> {
+        let conn = ConnectionManager::new(Client::open(redis_url)?).await?;
+
+        Ok(Self {
+            conn,
+            write_script: Script::new(WRITE_SCRIPT),
+            delete_script: Script::new(DELETE_SCRIPT),
+            prune_script: Script::new(PRUNE_SCRIPT),
+            ttl: None,
+            history_limit,
+            aliases: ManufacturerAliases::default(),
+        })
+    }
+
+    /// Let every key expire `ttl` after the vehicle's last write
+    pub fn with_ttl(self, ttl: Option<Duration>) -> Self {
+        Self { ttl, ..self }
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    async fn load(&self, id: VehicleId) -> Result<Option<Stored>, RepoError> {
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(vehicle_key(id))
+            .query_async(&mut self.conn.clone())
+            .await?;
+        raw.map(|raw| decode(&raw).map(|vehicle| (raw, vehicle)))
+            .transpose()
+    }
+
+    /// Every stored vehicle, in id order
+    async fn all(&self) -> Result<Vec<Vehicle>, RepoError> {
+        let mut conn = self.conn.clone();
+        let ids: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(IDS_KEY)
+            .query_async(&mut conn)
+            .await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids.iter().map(|id| format!("vehicle:{id}")).collect();
+        let raws: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let mut vehicles = Vec::with_capacity(raws.len());
+        let mut expired = Vec::new();
+        for (id, raw) in ids.iter().zip(raws) {
+            match raw {
+                Some(raw) => vehicles.push(decode(&raw)?),
+                None => expired.push(id),
+            }
+        }
+        if !expired.is_empty() {
+            self.prune_script
+                .key(IDS_KEY)
+                .arg(expired)
+                .invoke_async::<()>(&mut conn)
+                .await?;
+        }
+        vehicles.sort_by_key(|v| v.id);
+
+        Ok(vehicles)
+    }
+
+    /// Vehicles matching `filter`, in id order
+    async fn matching(&self, filter: &VehicleFilter) -> Result<Vec<Vehicle>, RepoError> {
+        // Filters name manufacturers the way clients do, so resolve aliases like stored values
+        let filter = VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        };
+        let mut vehicles = self.all().await?;
+        vehicles.retain(|v| filter.matches(v));
+
+        Ok(vehicles)
+    }
+
+    /// Write `new` over `old` (`None` when creating) unless another writer got there first;
+    /// false means the caller should read again and retry
+    async fn commit(
+        &self,
+        old: Option<&Stored>,
+        new: &Vehicle,
+        change_type: ChangeType,
+    ) -> Result<bool, RepoError> {
+        let revision = if self.history_limit == 0 {
+            String::new()
+        } else {
+            encode(&VehicleRevision {
+                version: new.version,
+                changed_at: Utc::now(),
+                change_type,
+                snapshot: new.clone().into(),
+            })?
+        };
+        let old_vehicle = old.map(|(_, vehicle)| vehicle);
+        let live_plate = new
+            .license_plate
+            .as_deref()
+            .filter(|_| new.deleted_at.is_none());
+        let outcome: Vec<String> = self
+            .write_script
+            .key(vehicle_key(new.id))
+            .key(IDS_KEY)
+            .key(history_key(new.id))
+            .key(vin_key(new.vin.as_deref()))
+            .key(plate_key(live_plate))
+            .key(vin_key(old_vehicle.and_then(|v| v.vin.as_deref())))
+            .key(plate_key(
+                old_vehicle.and_then(|v| v.license_plate.as_deref()),
+            ))
+            .arg(old.map(|(raw, _)| raw.as_str()).unwrap_or_default())
+            .arg(encode(&VehicleResponse::from(new.clone()))?)
+            .arg(self.ttl.map_or(0, |ttl| ttl.as_secs()))
+            .arg(new.id.to_string())
+            .arg(revision)
+            .arg(self.history_limit)
+            .invoke_async(&mut self.conn.clone())
+            .await?;
+        let holder = |raw: Option<&String>| {
+            raw.and_then(|raw| raw.parse::<VehicleId>().ok())
+                .ok_or_else(|| RepoError::Backend(format!("unexpected write result {outcome:?}")))
+        };
+        match outcome.first().map(String::as_str) {
+            Some("ok") => Ok(true),
+            Some("changed") => Ok(false),
+            Some("vin") => Err(RepoError::VinTaken {
+                existing_id: holder(outcome.get(1))?,
+            }),
+            Some("plate") => Err(RepoError::PlateTaken {
+                existing_id: holder(outcome.get(1))?,
+            }),
+            _ => Err(RepoError::Backend(format!(
+                "unexpected write result {outcome:?}"
+            ))),
+        }
+    }
+
+    /// Let `change` edit the stored vehicle and write it back, retrying when another writer got
+    /// in between; `change` returns the kind of change made, or `None` to leave it untouched
+    async fn modify(
+        &self,
+        id: VehicleId,
+        change: impl Fn(&mut Vehicle) -> Result<Option<ChangeType>, RepoError> + Send,
+    ) -> Result<Option<Vehicle>, RepoError> {
+        for _ in 0..MAX_ATTEMPTS {
+            let Some(stored) = self.load(id).await? else {
+                return Ok(None);
+            };
+            let mut vehicle = stored.1.clone();
+            let Some(change_type) = change(&mut vehicle)? else {
+                return Ok(Some(vehicle));
+            };
+            vehicle.updated_at = Utc::now();
+            vehicle.version += 1;
+            if self.commit(Some(&stored), &vehicle, change_type).await? {
+                return Ok(Some(vehicle));
+            }
+        }
+        Err(RepoError::Unavailable)
+    }
+
+    /// Replace the vehicle with this id, creating it when `create` is set; the flag is true
+    /// when it was created
+    async fn write(
+        &self,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+        create: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let manufacturer_raw = self.canonicalise(&mut vehicle);
+        for _ in 0..MAX_ATTEMPTS {
+            let existing = self.load(id).await?;
+            let current = existing.as_ref().map(|(_, v)| v);
+            if current.is_none() && !create {
+                return Err(RepoError::NotFound);
+            }
+            check_not_deleted(current)?;
+            // If-Match can only be satisfied by a vehicle that already exists
+            if let Some(if_match) = if_match
+                && !current.is_some_and(|v| if_match.matches(&v.etag()))
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: current.map(|v| v.version),
+                });
+            }
+            check_mileage(current, vehicle.mileage_km, correction)?;
+            let now = Utc::now();
+            let stored = Vehicle {
+                id,
+                manufacturer: vehicle.manufacturer.clone(),
+                manufacturer_raw: manufacturer_raw.clone(),
+                model: vehicle.model.clone(),
+                year: vehicle.year,
+                vin: normalise_vin(vehicle.vin.clone()),
+                mileage_km: vehicle.mileage_km,
+                color: normalise_color(vehicle.color.clone()),
+                license_plate: normalise_plate(vehicle.license_plate.clone()),
+                price: vehicle.price,
+                fuel_type: vehicle.fuel_type,
+                tags: normalise_tags(vehicle.tags.clone()),
+                metadata: vehicle.metadata.clone(),
+                engine: vehicle.engine,
+                owner_id: vehicle.owner_id,
+                first_registered: vehicle.first_registered,
+                status: current.map_or_else(VehicleStatus::default, |v| v.status),
+                created_at: current.map_or(now, |v| v.created_at),
+                updated_at: now,
+                deleted_at: current.and_then(|v| v.deleted_at),
+                expires_at: None,
+                version: current.map_or(1, |v| v.version + 1),
+            };
+            let change_type = if current.is_none() {
+                ChangeType::Created
+            } else {
+                ChangeType::Updated
+            };
+            if self.commit(existing.as_ref(), &stored, change_type).await? {
+                return Ok((stored, current.is_none()));
+            }
+        }
+        Err(RepoError::Unavailable)
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for RedisVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        Ok(self
+            .load(id)
+            .await?
+            .map(|(_, vehicle)| vehicle)
+            .filter(|v| v.deleted_at.is_none()))
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        let vin = vin.trim().to_ascii_uppercase();
+        let holder: Option<String> = redis::cmd("GET")
+            .arg(vin_key(Some(&vin)))
+            .query_async(&mut self.conn.clone())
+            .await?;
+        let Some(id) = holder.and_then(|id| id.parse().ok()) else {
+            return Ok(None);
+        };
+        // The entry may outlive its vehicle when keys expire
+        Ok(self
+            .get_vehicle(id)
+            .await?
+            .filter(|v| v.vin.as_deref() == Some(vin.as_str())))
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let Some(plate) = normalise_plate(Some(plate.to_string())) else {
+            return Ok(None);
+        };
+        let holder: Option<String> = redis::cmd("GET")
+            .arg(plate_key(Some(&plate)))
+            .query_async(&mut self.conn.clone())
+            .await?;
+        let Some(id) = holder.and_then(|id| id.parse().ok()) else {
+            return Ok(None);
+        };
+        Ok(self.get_vehicle(id).await?.filter(|v| {
+            v.deleted_at.is_none() && v.license_plate.as_deref() == Some(plate.as_str())
+        }))
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        Ok(self.get_vehicle(id).await?.is_some())
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        if ids.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let keys: Vec<String> = ids.iter().copied().map(vehicle_key).collect();
+        let raws: Vec<Option<String>> = redis::cmd("MGET")
+            .arg(&keys)
+            .query_async(&mut self.conn.clone())
+            .await?;
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for (id, raw) in ids.iter().zip(raws) {
+            match raw.map(|raw| decode(&raw)).transpose()? {
+                Some(vehicle) if vehicle.deleted_at.is_none() => found.push(vehicle),
+                _ => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let mut matching = self.matching(&page.filter).await?;
+        let total = matching.len();
+
+        matching.sort_by(|a, b| sorting::compare(&page.sort, a, b));
+        let items = matching
+            .into_iter()
+            .skip(page.offset)
+            .take(page.limit)
+            .collect();
+
+        Ok(PageResult { items, total })
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        Ok(self.list(page).await?.map(|v| VehicleSummary::from(&v)))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let mut range = self
+            .matching(filter)
+            .await?
+            .into_iter()
+            .filter(|v| cursor.is_none_or(|cursor| v.id > cursor));
+        let items: Vec<Vehicle> = range.by_ref().take(limit).collect();
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = match range.next() {
+            Some(_) => items.last().map(|v| v.id),
+            None => None,
+        };
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let mut matching = self.all().await?;
+        matching.retain(|v| search.matches(v));
+        let total = matching.len();
+        let items = matching.into_iter().skip(offset).take(limit).collect();
+
+        Ok((items, total))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let everything = VehicleFilter::default();
+        let filter = filter.unwrap_or(&everything);
+        Ok(self.matching(filter).await?.len() as u64)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        Ok(collect_stats(&self.matching(filter).await?))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let manufacturers: BTreeSet<String> = self
+            .all()
+            .await?
+            .into_iter()
+            .filter(|v| v.deleted_at.is_none())
+            .map(|v| v.manufacturer)
+            .collect();
+
+        Ok(manufacturers.into_iter().collect())
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        if !allow_duplicate {
+            let manufacturer = self.aliases.canonical(&vehicle.manufacturer);
+            let key = identity(&manufacturer, &vehicle.model, vehicle.year);
+            let duplicate = self.all().await?.into_iter().find(|v| {
+                v.deleted_at.is_none() && identity(&v.manufacturer, &v.model, v.year) == key
+            });
+            if let Some(existing) = duplicate {
+                return Err(RepoError::Duplicate {
+                    existing_id: existing.id,
+                });
+            }
+        }
+        let id = VehicleId::generate();
+        self.write(id, vehicle, None, false, true).await?;
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            match self.write(id, vehicle, None, false, true).await {
+                Ok(_) => ids.push(id),
+                Err(RepoError::VinTaken { .. } | RepoError::PlateTaken { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        self.write(id, vehicle, if_match, correction, false)
+            .await
+            .map(|(updated, _)| updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        self.write(id, vehicle, if_match, correction, true).await
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        for _ in 0..MAX_ATTEMPTS {
+            let Some((raw, vehicle)) = self.load(id).await? else {
+                return Ok(None);
+            };
+            let deleted: bool = self
+                .delete_script
+                .key(vehicle_key(id))
+                .key(IDS_KEY)
+                .key(history_key(id))
+                .key(vin_key(vehicle.vin.as_deref()))
+                .key(plate_key(vehicle.license_plate.as_deref()))
+                .arg(raw)
+                .arg(id.to_string())
+                .invoke_async(&mut self.conn.clone())
+                .await?;
+            if deleted {
+                return Ok(Some(vehicle));
+            }
+        }
+        Err(RepoError::Unavailable)
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.modify(id, |vehicle| {
+            // Keep the original timestamp when deleting an already deleted vehicle
+            if vehicle.deleted_at.is_some() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = Some(Utc::now());
+            Ok(Some(ChangeType::Deleted))
+        })
+        .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            if vehicle.deleted_at.is_none() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = None;
+            Ok(Some(ChangeType::Restored))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            let merged = normalise_tags(vehicle.tags.iter().chain(&tags).cloned().collect());
+            if merged.len() == vehicle.tags.len() {
+                return Ok(None);
+            }
+            if merged.len() > MAX_TAGS {
+                return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+            }
+            vehicle.tags = merged;
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let tag = tag.trim().to_lowercase();
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if !vehicle.tags.contains(&tag) {
+                return Ok(None);
+            }
+            vehicle.tags.retain(|t| *t != tag);
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if let Some(if_match) = if_match
+                && !if_match.matches(&vehicle.etag())
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: Some(vehicle.version),
+                });
+            }
+            if vehicle.status == status {
+                return Ok(None);
+            }
+            vehicle.status = status;
+            Ok(Some(ChangeType::StatusChanged))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            match self.delete_vehicle(*id).await? {
+                Some(_) => deleted += 1,
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let assigned: Vec<VehicleId> = self
+            .all()
+            .await?
+            .into_iter()
+            .filter(|v| v.owner_id == Some(owner_id))
+            .map(|v| v.id)
+            .collect();
+        let mut unassigned = 0;
+        for id in assigned {
+            // The owner may have changed since the scan, so check again on every attempt
+            let changed = self
+                .modify(id, |vehicle| {
+                    if vehicle.owner_id != Some(owner_id) {
+                        return Ok(None);
+                    }
+                    vehicle.owner_id = None;
+                    Ok(Some(ChangeType::Updated))
+                })
+                .await?;
+            if changed.is_some_and(|v| v.owner_id.is_none()) {
+                unassigned += 1;
+            }
+        }
+
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        let (raw, revisions): (Option<String>, Vec<String>) = redis::pipe()
+            .cmd("GET")
+            .arg(vehicle_key(id))
+            .cmd("LRANGE")
+            .arg(history_key(id))
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut self.conn.clone())
+            .await?;
+        let live = raw.map(|raw| decode(&raw)).transpose()?;
+        if live.is_none_or(|v| v.deleted_at.is_some()) {
+            return Ok(None);
+        }
+        // Newest first, since revisions are pushed onto the head of the list
+        let revisions = revisions
+            .iter()
+            .map(|raw| {
+                serde_json::from_str(raw)
+                    .map_err(|e| RepoError::Backend(format!("corrupt vehicle revision: {e}")))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(revisions))
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        redis::cmd("PING")
+            .query_async::<String>(&mut self.conn.clone())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,42 +1,2038 @@
-use crate::features::vehicle::model::{Vehicle, VehicleId};
+use crate::{
+    features::{
+        owner::model::OwnerId,
+        vehicle::manufacturer::ManufacturerAliases,
+        vehicle::model::{
+            ChangeType, CreateVehicleRequest, DumpConflict, MAX_TAGS, PageRequest, PageResult,
+            RestoreMode, RestoreReport, Vehicle, VehicleFilter, VehicleId, VehicleRevision,
+            VehicleSearch, VehicleStats, VehicleStatus, VehicleSummary, normalise_color,
+            normalise_plate, normalise_tags, normalise_vin,
+        },
+        vehicle::persistent_repo::SnapshotStatus,
+    },
+    utils::{etag::IfMatch, sorting},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures_util::{Stream, stream};
+use rust_decimal::Decimal;
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    ops::{Bound, Deref},
+    str::FromStr,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+    time::Duration,
 };
-use uuid::Uuid;
+use tokio::{sync::watch, time::MissedTickBehavior};
+use tracing::{debug, info, warn};
 
+#[async_trait]
 pub trait VehicleRepo: Sync + Send {
-    async fn get_vehicle(&self, id: Uuid) -> Option<Vehicle>;
-    async fn get_vehicles(&self) -> Vec<Vehicle>;
-    async fn post_vehicle(&self, vehicle: Vehicle) -> Option<VehicleId>;
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError>;
+    /// Look a vehicle up by VIN, ignoring case
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError>;
+    /// Look up the live vehicle holding a plate, ignoring case and whitespace
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError>;
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError>;
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError>;
+    /// One offset page of the matching vehicles plus their total count
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError>;
+    /// Same page as `list`, but only the summary columns are copied out of the store
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError>;
+    /// Up to `limit` matching vehicles past `cursor` in id order, plus the cursor to continue
+    /// from when there are more; see `vehicle_chunks` for walking all of them
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError>;
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError>;
+    /// How many vehicles match `filter`, or how many live ones there are without one
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError>;
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError>;
+    /// Distinct canonical manufacturers of live vehicles, sorted
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError>;
+    /// Create a vehicle, rejecting duplicates of a live manufacturer/model/year unless allowed
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError>;
+    /// Insert every vehicle under a single lock, skipping duplicate checks; vehicles whose VIN
+    /// or license plate is already taken are left out
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError>;
+    /// Insert every vehicle or none of them, skipping duplicate checks like `post_vehicles`; a
+    /// VIN or plate already taken, by the store or earlier in the batch, fails the whole batch.
+    /// Backends without transactions insert one by one and delete what they inserted when one
+    /// fails, so concurrent readers may briefly see part of the batch
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            match self.post_vehicle(vehicle, true).await {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    for id in ids {
+                        if let Err(undo) = self.delete_vehicle(id).await {
+                            warn!("Failed to undo batch insert of vehicle {}: {:?}", id, undo);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(ids)
+    }
+    /// Replace the editable fields; a lower mileage is rejected unless `correction` is set
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError>;
+    /// Update the vehicle with this id or create it; the flag is true when it was created
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError>;
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError>;
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError>;
+    /// Undo a soft delete, unless a live vehicle has claimed the license plate meanwhile
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError>;
+    /// Add tags on top of the existing ones, keeping the total within `MAX_TAGS`
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError>;
+    /// Remove a tag; a no-op when the vehicle doesn't carry it
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError>;
+    /// Set the lifecycle status; a no-op when the status is unchanged
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError>;
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError>;
+    /// Clear the owner on every vehicle assigned to it, soft-deleted ones included, returning
+    /// how many were changed
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError>;
+    /// Recorded revisions newest-first, or `None` if the vehicle does not exist or is soft-deleted
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError>;
+    /// Every stored vehicle in id order, soft-deleted ones included
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        let filter = VehicleFilter {
+            include_deleted: Some(true),
+            ..Default::default()
+        };
+        let mut vehicles = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (chunk, next_cursor) = self
+                .get_vehicles_after(&filter, cursor, DUMP_CHUNK_SIZE)
+                .await?;
+            vehicles.extend(chunk);
+            match next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(vehicles),
+            }
+        }
+    }
+    /// Put dumped vehicles back as they were, ids, versions and timestamps included, without
+    /// history. `Merge` leaves out and reports the ones whose id, VIN or live plate is taken;
+    /// `Replace` swaps the whole store for the dump in one go, or changes nothing and reports
+    /// the conflicts when the dump clashes with itself. A dump that won't fit is refused whole
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        let _ = (vehicles, mode);
+        Err(RepoError::Unsupported("restoring a dump"))
+    }
+    /// Check the backend can serve requests, for the readiness probe
+    async fn ping(&self) -> Result<(), RepoError> {
+        Ok(())
+    }
+    /// Check the backend can serve requests and report what it can about itself; backends
+    /// that only know whether they are reachable ping
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        self.ping().await?;
+        Ok(RepoHealth::default())
+    }
+    /// Write out anything not yet persisted, called once on shutdown
+    async fn flush(&self) -> Result<(), RepoError> {
+        Ok(())
+    }
+    /// How persisting the store to disk is going, for backends that do
+    fn snapshot_status(&self) -> Option<SnapshotStatus> {
+        None
+    }
+    /// How full the store is, for backends with a vehicle limit
+    fn capacity(&self) -> Option<StoreCapacity> {
+        None
+    }
+    /// Which store is answering reads, for backends with a fallback
+    fn read_path(&self) -> Option<ReadPath> {
+        None
+    }
+}
+
+/// Vehicles copied out per `get_vehicles_after` call by the default `dump`
+const DUMP_CHUNK_SIZE: usize = 500;
+
+/// Every vehicle matching `filter` past `after`, in id order, `chunk_size` at a time. Each chunk
+/// is its own `get_vehicles_after` call, so the in-memory store's lock is only held while one
+/// chunk is copied out and writes go ahead between chunks; memory stays at one chunk however
+/// many vehicles there are. A vehicle written mid-walk shows up if its id is still ahead of
+/// the walk. The stream ends after yielding an error
+pub fn vehicle_chunks(
+    repo: Arc<dyn VehicleRepo>,
+    filter: VehicleFilter,
+    after: Option<VehicleId>,
+    chunk_size: usize,
+) -> impl Stream<Item = Result<Vec<Vehicle>, RepoError>> + Send + 'static {
+    stream::unfold(Some(after), move |cursor| {
+        let repo = repo.clone();
+        let filter = filter.clone();
+        async move {
+            let cursor = cursor?;
+            match repo.get_vehicles_after(&filter, cursor, chunk_size).await {
+                Ok((vehicles, _)) if vehicles.is_empty() => None,
+                Ok((vehicles, next_cursor)) => Some((Ok(vehicles), next_cursor.map(Some))),
+                Err(e) => Some((Err(e), None)),
+            }
+        }
+    })
+}
+
+/// Vehicles held against the configured limit, soft-deleted ones included
+#[derive(Clone, Copy, Debug)]
+pub struct StoreCapacity {
+    pub used: usize,
+    pub limit: usize,
+    pub policy: CapacityPolicy,
+}
+
+/// What a full store does with a new vehicle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CapacityPolicy {
+    /// Refuse it with `RepoError::CapacityExceeded`
+    #[default]
+    Reject,
+    /// Make room by removing the oldest vehicles, by id, along with their history
+    EvictOldest,
+}
+
+impl CapacityPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CapacityPolicy::Reject => "reject",
+            CapacityPolicy::EvictOldest => "evict_oldest",
+        }
+    }
+}
+
+impl FromStr for CapacityPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(CapacityPolicy::Reject),
+            "evict_oldest" => Ok(CapacityPolicy::EvictOldest),
+            other => Err(format!(
+                "unknown capacity policy '{other}', allowed values: reject, evict_oldest"
+            )),
+        }
+    }
+}
+
+/// What a backend's health check found, for the readiness probe
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepoHealth {
+    /// Vehicles held, soft-deleted ones included, for backends that know without a query
+    pub vehicles: Option<usize>,
+}
+
+/// The store a backend with a fallback is currently reading from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadPath {
+    Primary,
+    /// The fallback copy, while the primary is unavailable
+    Secondary,
+}
+
+impl ReadPath {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadPath::Primary => "primary",
+            ReadPath::Secondary => "secondary",
+        }
+    }
+}
+
+/// Why a repo call did not succeed
+#[derive(Debug, PartialEq, Eq)]
+pub enum RepoError {
+    NotFound,
+    VersionMismatch {
+        current_version: Option<u64>,
+    },
+    Duplicate {
+        existing_id: VehicleId,
+    },
+    VinTaken {
+        existing_id: VehicleId,
+    },
+    PlateTaken {
+        existing_id: VehicleId,
+    },
+    TooManyTags {
+        limit: usize,
+    },
+    MileageDecrease {
+        current_mileage_km: u32,
+    },
+    /// The store already holds its maximum number of vehicles
+    CapacityExceeded {
+        limit: usize,
+    },
+    /// The backend failed in a way retrying won't fix, e.g. a corrupt record
+    Backend(String),
+    /// The backend can't be reached right now; retrying later may succeed
+    #[cfg_attr(
+        not(any(
+            feature = "postgres",
+            feature = "sqlite",
+            feature = "redis",
+            feature = "mongodb"
+        )),
+        allow(dead_code)
+    )]
+    Unavailable,
+    /// The backend doesn't implement this operation
+    Unsupported(&'static str),
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl From<sqlx::Error> for RepoError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+                RepoError::Unavailable
+            }
+            other => RepoError::Backend(other.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "mongodb")]
+impl From<mongodb::error::Error> for RepoError {
+    fn from(error: mongodb::error::Error) -> Self {
+        use mongodb::error::ErrorKind;
+
+        match error.kind.as_ref() {
+            ErrorKind::ServerSelection { .. }
+            | ErrorKind::Io(_)
+            | ErrorKind::ConnectionPoolCleared { .. } => RepoError::Unavailable,
+            _ => RepoError::Backend(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for RepoError {
+    fn from(error: redis::RedisError) -> Self {
+        if error.is_io_error()
+            || error.is_connection_dropped()
+            || error.is_connection_refusal()
+            || error.is_timeout()
+        {
+            RepoError::Unavailable
+        } else {
+            RepoError::Backend(error.to_string())
+        }
+    }
+}
+
+/// Normalised manufacturer/model/year used for duplicate detection
+pub type VehicleIdentity = (String, String, u16);
+
+/// Vehicles as held by the store, sharing one allocation per distinct manufacturer and model
+pub type StoredVehicle = Vehicle<Arc<str>>;
+
+/// Interned manufacturer and model names; a name is dropped as soon as no stored vehicle uses it
+#[derive(Default)]
+struct NameTable(HashSet<Arc<str>>);
+
+impl NameTable {
+    fn intern(&mut self, name: String) -> Arc<str> {
+        if let Some(interned) = self.0.get(name.as_str()) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = name.into();
+        self.0.insert(interned.clone());
+        interned
+    }
+
+    /// Hand back a stored vehicle's share of `name`, forgetting the name when it was the last one
+    fn release(&mut self, name: Arc<str>) -> String {
+        let owned = name.to_string();
+        drop(name);
+        if self
+            .0
+            .get(owned.as_str())
+            .is_some_and(|interned| Arc::strong_count(interned) == 1)
+        {
+            self.0.remove(owned.as_str());
+        }
+        owned
+    }
+}
+
+/// Counts and averages over `vehicles`, grouped the way the stats endpoint reports them
+pub fn collect_stats<'a, N: Deref<Target = str> + 'a>(
+    vehicles: impl IntoIterator<Item = &'a Vehicle<N>>,
+) -> VehicleStats {
+    let mut stats = VehicleStats::default();
+    let mut mileage: HashMap<&str, (u64, u64)> = HashMap::new();
+    let mut prices: HashMap<&str, (Decimal, u32)> = HashMap::new();
+    for vehicle in vehicles {
+        stats.total += 1;
+        *stats
+            .by_manufacturer
+            .entry(vehicle.manufacturer.to_string())
+            .or_default() += 1;
+        *stats.by_year.entry(vehicle.year).or_default() += 1;
+        if let Some(fuel_type) = vehicle.fuel_type {
+            *stats.by_fuel_type.entry(fuel_type).or_default() += 1;
+        }
+        if let Some(km) = vehicle.mileage_km {
+            let (sum, count) = mileage.entry(&*vehicle.manufacturer).or_default();
+            *sum += u64::from(km);
+            *count += 1;
+        }
+        if let Some(price) = vehicle.price {
+            let (sum, count) = prices.entry(&*vehicle.manufacturer).or_default();
+            *sum += price;
+            *count += 1;
+        }
+    }
+    stats.avg_mileage_by_manufacturer = mileage
+        .into_iter()
+        .map(|(manufacturer, (sum, count))| (manufacturer.to_string(), sum as f64 / count as f64))
+        .collect();
+    for (manufacturer, (sum, count)) in prices {
+        stats.avg_price_by_manufacturer.insert(
+            manufacturer.to_string(),
+            (sum / Decimal::from(count)).round_dp(2),
+        );
+        stats
+            .total_price_by_manufacturer
+            .insert(manufacturer.to_string(), sum);
+    }
+
+    stats
+}
+
+/// A soft-deleted vehicle can be restored but not written to, so to a write it isn't there
+pub fn check_not_deleted<N>(stored: Option<&Vehicle<N>>) -> Result<(), RepoError> {
+    match stored {
+        Some(vehicle) if vehicle.deleted_at.is_some() => Err(RepoError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Odometers only go forward, so a lower reading than the stored one needs a correction
+pub fn check_mileage<N>(
+    stored: Option<&Vehicle<N>>,
+    mileage_km: Option<u32>,
+    correction: bool,
+) -> Result<(), RepoError> {
+    match (stored.and_then(|v| v.mileage_km), mileage_km) {
+        (Some(current), Some(new)) if new < current && !correction => {
+            Err(RepoError::MileageDecrease {
+                current_mileage_km: current,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Dumped vehicles that can't be restored, because an earlier one in the dump or, through
+/// `taken`, the store already holds their id, VIN or live plate. A vehicle left out doesn't
+/// claim anything, so it never causes a conflict further down
+pub fn dump_conflicts(
+    vehicles: &[Vehicle],
+    taken: impl Fn(&Vehicle) -> Option<(&'static str, VehicleId)>,
+) -> Vec<DumpConflict> {
+    let mut ids = HashSet::new();
+    let mut vins = HashMap::new();
+    let mut plates = HashMap::new();
+    let mut conflicts = Vec::new();
+    for (index, vehicle) in vehicles.iter().enumerate() {
+        // Soft-deleted vehicles may share a plate, like in the store
+        let plate = vehicle
+            .license_plate
+            .as_deref()
+            .filter(|_| vehicle.deleted_at.is_none());
+        let conflict = taken(vehicle).or_else(|| {
+            if ids.contains(&vehicle.id) {
+                return Some(("id", vehicle.id));
+            }
+            if let Some(existing_id) = vehicle.vin.as_deref().and_then(|vin| vins.get(vin)) {
+                return Some(("vin", *existing_id));
+            }
+            plate
+                .and_then(|plate| plates.get(plate))
+                .map(|existing_id| ("licensePlate", *existing_id))
+        });
+        match conflict {
+            Some((field, existing_id)) => conflicts.push(DumpConflict {
+                index,
+                id: vehicle.id,
+                field,
+                existing_id,
+            }),
+            None => {
+                ids.insert(vehicle.id);
+                if let Some(vin) = vehicle.vin.as_deref() {
+                    vins.insert(vin, vehicle.id);
+                }
+                if let Some(plate) = plate {
+                    plates.insert(plate, vehicle.id);
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+pub fn identity(manufacturer: &str, model: &str, year: u16) -> VehicleIdentity {
+    let normalise = |value: &str| value.trim().to_lowercase();
+    (normalise(manufacturer), normalise(model), year)
+}
+
+/// Vehicles plus the secondary indexes kept in sync with them under a single lock
+#[derive(Default)]
+pub struct VehicleStore {
+    pub vehicles: BTreeMap<VehicleId, StoredVehicle>,
+    identities: HashMap<VehicleIdentity, BTreeSet<VehicleId>>,
+    vins: HashMap<String, VehicleId>,
+    /// Soft-deleted vehicles may share a plate, so several ids can hold the same one
+    plates: HashMap<String, BTreeSet<VehicleId>>,
+    /// Ordered so tag filters can walk candidates in id order, e.g. for cursor pagination
+    tags: HashMap<String, BTreeSet<VehicleId>>,
+    /// Keyed by the lowercased canonical manufacturer, ordered like `tags`
+    manufacturers: HashMap<String, BTreeSet<VehicleId>>,
+    /// Vehicles that expire, soonest first
+    expiries: BTreeSet<(DateTime<Utc>, VehicleId)>,
+    history: HashMap<VehicleId, VecDeque<VehicleRevision>>,
+    history_limit: usize,
+    /// How long after its creation a vehicle expires, if ever
+    ttl: Option<TimeDelta>,
+    /// Most vehicles held at once, if limited
+    capacity: Option<usize>,
+    capacity_policy: CapacityPolicy,
+    aliases: ManufacturerAliases,
+    names: NameTable,
+}
+
+impl VehicleStore {
+    /// A store without vehicles, configured like this one
+    fn emptied(&self) -> VehicleStore {
+        VehicleStore {
+            history_limit: self.history_limit,
+            ttl: self.ttl,
+            capacity: self.capacity,
+            capacity_policy: self.capacity_policy,
+            aliases: self.aliases.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Another vehicle, by id, VIN or live plate, already holding what `vehicle` needs
+    fn find_holder(&self, vehicle: &Vehicle) -> Option<(&'static str, VehicleId)> {
+        if self.vehicles.contains_key(&vehicle.id) {
+            return Some(("id", vehicle.id));
+        }
+        if let Some(owner) = self.find_vin_owner(vehicle.vin.as_deref(), None) {
+            return Some(("vin", owner));
+        }
+        if vehicle.deleted_at.is_some() {
+            return None;
+        }
+        self.find_plate_owner(vehicle.license_plate.as_deref(), None)
+            .map(|owner| ("licensePlate", owner))
+    }
+
+    fn insert(&mut self, id: VehicleId, mut vehicle: Vehicle) -> Option<Vehicle> {
+        let previous = self.unlink(&id);
+        vehicle.expires_at = self.ttl.map(|ttl| vehicle.created_at + ttl);
+        if let Some(expires_at) = vehicle.expires_at {
+            self.expiries.insert((expires_at, id));
+        }
+        self.identities
+            .entry(identity(
+                &vehicle.manufacturer,
+                &vehicle.model,
+                vehicle.year,
+            ))
+            .or_default()
+            .insert(id);
+        self.manufacturers
+            .entry(vehicle.manufacturer.to_lowercase())
+            .or_default()
+            .insert(id);
+        if let Some(vin) = &vehicle.vin {
+            self.vins.insert(vin.clone(), id);
+        }
+        if let Some(plate) = &vehicle.license_plate {
+            self.plates.entry(plate.clone()).or_default().insert(id);
+        }
+        for tag in &vehicle.tags {
+            self.tags.entry(tag.clone()).or_default().insert(id);
+        }
+        let vehicle = vehicle.map_names(|name| self.names.intern(name));
+        self.vehicles.insert(id, vehicle);
+        previous
+    }
+
+    /// Snapshot the current state of `id` into its bounded revision history
+    fn record(&mut self, id: VehicleId, change_type: ChangeType) {
+        if self.history_limit == 0 {
+            return;
+        }
+        let Some(vehicle) = self.vehicles.get(&id) else {
+            return;
+        };
+        let revisions = self.history.entry(id).or_default();
+        if revisions.len() == self.history_limit {
+            revisions.pop_front();
+        }
+        revisions.push_back(VehicleRevision {
+            version: vehicle.version,
+            changed_at: Utc::now(),
+            change_type,
+            snapshot: Vehicle::from(vehicle).into(),
+        });
+    }
+
+    /// Remove a vehicle for good, including its history
+    fn remove(&mut self, id: &VehicleId) -> Option<Vehicle> {
+        self.history.remove(id);
+        self.unlink(id)
+    }
+
+    fn unlink(&mut self, id: &VehicleId) -> Option<Vehicle> {
+        let removed = self.vehicles.remove(id)?;
+        if let Some(expires_at) = removed.expires_at {
+            self.expiries.remove(&(expires_at, *id));
+        }
+        let key = identity(&removed.manufacturer, &removed.model, removed.year);
+        if let Some(ids) = self.identities.get_mut(&key) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.identities.remove(&key);
+            }
+        }
+        let manufacturer = removed.manufacturer.to_lowercase();
+        if let Some(ids) = self.manufacturers.get_mut(&manufacturer) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.manufacturers.remove(&manufacturer);
+            }
+        }
+        if let Some(vin) = &removed.vin
+            && self.vins.get(vin) == Some(id)
+        {
+            self.vins.remove(vin);
+        }
+        if let Some(plate) = &removed.license_plate
+            && let Some(ids) = self.plates.get_mut(plate)
+        {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.plates.remove(plate);
+            }
+        }
+        for tag in &removed.tags {
+            if let Some(ids) = self.tags.get_mut(tag) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+        Some(removed.map_names(|name| self.names.release(name)))
+    }
+
+    /// Rebuild every secondary index from the vehicles and report the first one that has
+    /// drifted, for tests driving the store through random sequences of writes
+    #[cfg(any(test, feature = "test-util"))]
+    #[allow(dead_code)]
+    pub fn verify_indexes(&self) -> Result<(), String> {
+        fn bucket<K: std::hash::Hash + Eq>(
+            index: &mut HashMap<K, BTreeSet<VehicleId>>,
+            key: K,
+            id: VehicleId,
+        ) {
+            index.entry(key).or_default().insert(id);
+        }
+
+        let mut identities = HashMap::new();
+        let mut manufacturers = HashMap::new();
+        let mut plates = HashMap::new();
+        let mut tags = HashMap::new();
+        for (id, vehicle) in &self.vehicles {
+            let key = identity(&vehicle.manufacturer, &vehicle.model, vehicle.year);
+            bucket(&mut identities, key, *id);
+            bucket(&mut manufacturers, vehicle.manufacturer.to_lowercase(), *id);
+            if let Some(plate) = &vehicle.license_plate {
+                bucket(&mut plates, plate.clone(), *id);
+            }
+            for tag in &vehicle.tags {
+                bucket(&mut tags, tag.clone(), *id);
+            }
+        }
+        if identities != self.identities {
+            return Err("identity index is out of sync".to_string());
+        }
+        if manufacturers != self.manufacturers {
+            return Err("manufacturer index is out of sync".to_string());
+        }
+        if plates != self.plates {
+            return Err("plate index is out of sync".to_string());
+        }
+        if tags != self.tags {
+            return Err("tag index is out of sync".to_string());
+        }
+        for (vin, id) in &self.vins {
+            if self.vehicles.get(id).and_then(|v| v.vin.as_ref()) != Some(vin) {
+                return Err(format!(
+                    "vin index points {vin} at {id}, which doesn't hold it"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// `id` unless it has expired and is only waiting for the next sweep, soft-deleted or not
+    fn unexpired_vehicle(&self, id: &VehicleId) -> Option<&StoredVehicle> {
+        let now = Utc::now();
+        self.vehicles
+            .get(id)
+            .filter(|v| v.expires_at.is_none_or(|at| at > now))
+    }
+
+    /// `id` unless it is soft-deleted or has expired; only restoring reaches past this
+    fn live(&self, id: &VehicleId) -> Option<&StoredVehicle> {
+        self.unexpired_vehicle(id)
+            .filter(|v| v.deleted_at.is_none())
+    }
+
+    /// Every vehicle that hasn't expired, in id order
+    fn unexpired(&self) -> impl Iterator<Item = &StoredVehicle> {
+        let now = Utc::now();
+        self.vehicles
+            .values()
+            .filter(move |v| v.expires_at.is_none_or(|at| at > now))
+    }
+
+    /// Remove every vehicle whose expiry has passed, returning how many there were
+    fn sweep_expired(&mut self) -> usize {
+        let now = Utc::now();
+        let mut removed = 0;
+        while let Some(&(expires_at, id)) = self.expiries.first()
+            && expires_at <= now
+        {
+            self.expiries.pop_first();
+            self.remove(&id);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Refuse `incoming` new vehicles that can't fit, now or, under `EvictOldest`, even after
+    /// evicting everything else
+    fn check_capacity(&self, incoming: usize) -> Result<(), RepoError> {
+        let Some(limit) = self.capacity else {
+            return Ok(());
+        };
+        let needed = match self.capacity_policy {
+            CapacityPolicy::Reject => self.vehicles.len() + incoming,
+            CapacityPolicy::EvictOldest => incoming,
+        };
+        if needed > limit {
+            return Err(RepoError::CapacityExceeded { limit });
+        }
+        Ok(())
+    }
+
+    /// Under `EvictOldest`, remove the oldest vehicles until `incoming` more fit
+    fn evict_for(&mut self, incoming: usize) {
+        let Some(limit) = self.capacity else {
+            return;
+        };
+        if self.capacity_policy != CapacityPolicy::EvictOldest {
+            return;
+        }
+        while self.vehicles.len() + incoming > limit {
+            let Some(&oldest) = self.vehicles.keys().next() else {
+                return;
+            };
+            self.remove(&oldest);
+            info!(
+                "Evicted vehicle {} to stay within the capacity of {} vehicles",
+                oldest, limit
+            );
+        }
+    }
+
+    /// Another vehicle than `id` (soft-deleted ones included) already holding `vin`
+    fn find_vin_owner(&self, vin: Option<&str>, id: Option<VehicleId>) -> Option<VehicleId> {
+        let owner = *self.vins.get(vin?)?;
+        (Some(owner) != id).then_some(owner)
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    /// Vehicles matching `filter` in id order, starting after `cursor`; when a manufacturer or
+    /// tags are requested only the ids in the smallest of their index buckets are visited
+    /// instead of every vehicle
+    fn matching<'a>(
+        &'a self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+    ) -> Box<dyn Iterator<Item = &'a StoredVehicle> + 'a> {
+        // Filters name manufacturers the way clients do, so resolve aliases like stored values
+        let filter = VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        };
+        let lower = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        let rarest = filter
+            .manufacturer
+            .iter()
+            .map(|m| self.manufacturers.get(&m.to_lowercase()))
+            .chain(filter.tags.iter().map(|tag| self.tags.get(tag)))
+            .min_by_key(|ids| ids.map_or(0, BTreeSet::len));
+        // Expired vehicles waiting for the next sweep are already gone as far as readers know
+        let now = Utc::now();
+        let keep =
+            move |v: &&StoredVehicle| v.expires_at.is_none_or(|at| at > now) && filter.matches(v);
+        // No bucket to narrow by means a full scan; a requested but empty one means no matches
+        let Some(rarest) = rarest else {
+            return Box::new(
+                self.vehicles
+                    .range((lower, Bound::Unbounded))
+                    .map(|(_, v)| v)
+                    .filter(keep),
+            );
+        };
+        match rarest {
+            Some(ids) => Box::new(
+                ids.range((lower, Bound::Unbounded))
+                    .filter_map(|id| self.vehicles.get(id))
+                    .filter(keep),
+            ),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    /// The vehicles on `page` and the total matching, borrowed so callers only copy the window
+    /// out of the store. Without a sort the matches already come in id order and only the
+    /// window is buffered; with one, only the vehicles up to the end of the window are sorted
+    fn page(&self, page: &PageRequest) -> PageResult<&StoredVehicle> {
+        let matching = self.matching(&page.filter, None);
+        if page.sort.is_empty() {
+            let mut items = Vec::new();
+            let mut total = 0;
+            for vehicle in matching {
+                if total >= page.offset && items.len() < page.limit {
+                    items.push(vehicle);
+                }
+                total += 1;
+            }
+            return PageResult { items, total };
+        }
+
+        let mut items: Vec<&StoredVehicle> = matching.collect();
+        let total = items.len();
+        let end = page.offset.saturating_add(page.limit).min(total);
+        let compare = |a: &&StoredVehicle, b: &&StoredVehicle| sorting::compare(&page.sort, *a, *b);
+        if end < total && end > 0 {
+            items.select_nth_unstable_by(end - 1, compare);
+            items.truncate(end);
+        }
+        items.sort_by(compare);
+        items.drain(..page.offset.min(items.len()));
+        items.truncate(page.limit);
+        PageResult { items, total }
+    }
+
+    /// A live vehicle other than `id` holding `plate`
+    fn find_plate_owner(&self, plate: Option<&str>, id: Option<VehicleId>) -> Option<VehicleId> {
+        self.plates
+            .get(plate?)?
+            .iter()
+            .copied()
+            .filter(|owner| Some(*owner) != id)
+            .find(|owner| {
+                self.vehicles
+                    .get(owner)
+                    .is_some_and(|v| v.deleted_at.is_none())
+            })
+    }
+
+    /// First live vehicle sharing the manufacturer/model/year of `vehicle`
+    fn find_duplicate(&self, vehicle: &CreateVehicleRequest) -> Option<VehicleId> {
+        self.identities
+            .get(&identity(
+                &vehicle.manufacturer,
+                &vehicle.model,
+                vehicle.year,
+            ))?
+            .iter()
+            .copied()
+            .find(|id| {
+                self.vehicles
+                    .get(id)
+                    .is_some_and(|v| v.deleted_at.is_none())
+            })
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct InMemoryVehicleRepo {
-    pub map: Arc<Mutex<HashMap<Uuid, Vehicle>>>,
+    /// Reads share the lock and only writes take it exclusively; no guard outlives a call,
+    /// so none is ever held across an await point
+    pub store: Arc<RwLock<VehicleStore>>,
 }
 
+impl InMemoryVehicleRepo {
+    /// Keep up to `history_limit` revisions per vehicle; 0 disables history
+    pub fn with_history_limit(history_limit: usize) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(VehicleStore {
+                history_limit,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        self.write().aliases = aliases;
+        self
+    }
+
+    /// Hold at most `limit` vehicles, handling new ones past it according to `policy`
+    pub fn with_capacity(self, limit: Option<usize>, policy: CapacityPolicy) -> Self {
+        {
+            let mut store = self.write();
+            store.capacity = limit;
+            store.capacity_policy = policy;
+        }
+        self
+    }
+
+    /// Shared access to the store. A writer that panicked poisons the lock, but one failed
+    /// request shouldn't take every later one down with it, so the poison is cleared instead
+    fn read(&self) -> RwLockReadGuard<'_, VehicleStore> {
+        self.store.read().unwrap_or_else(|poisoned| {
+            warn!("Vehicle store lock was poisoned by a panicking writer, recovering");
+            self.store.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// Every stored vehicle in id order, soft-deleted ones included and expired ones not
+    pub fn vehicles(&self) -> Vec<Vehicle> {
+        self.read().unexpired().map(Vehicle::from).collect()
+    }
+
+    /// The vehicle with this id, soft-deleted or not, for wrappers that log or mirror every
+    /// change to it
+    pub fn stored_vehicle(&self, id: VehicleId) -> Option<Vehicle> {
+        self.read().unexpired_vehicle(&id).map(Vehicle::from)
+    }
+
+    /// Put previously stored vehicles back as they were, e.g. from a snapshot; their VINs and
+    /// plates are trusted to be unique already
+    pub fn load_vehicles(&self, vehicles: impl IntoIterator<Item = Vehicle>) {
+        let mut store = self.write();
+        for vehicle in vehicles {
+            store.insert(vehicle.id, vehicle);
+        }
+    }
+
+    /// Take a vehicle back out as if it had never been loaded, e.g. replaying a logged delete
+    pub fn unload_vehicle(&self, id: VehicleId) {
+        self.lock().remove(&id);
+    }
+
+    /// Exclusive access to the store, recovering from a poisoned lock like `read`
+    fn lock(&self) -> RwLockWriteGuard<'_, VehicleStore> {
+        self.store.write().unwrap_or_else(|poisoned| {
+            warn!("Vehicle store lock was poisoned by a panicking writer, recovering");
+            self.store.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// Exclusive access with expired vehicles swept first, so they neither count towards the
+    /// capacity nor hold on to their VIN or plate until the next scheduled sweep
+    fn write(&self) -> RwLockWriteGuard<'_, VehicleStore> {
+        let mut store = self.lock();
+        let removed = store.sweep_expired();
+        if removed > 0 {
+            debug!("Removed {} expired vehicles before a write", removed);
+        }
+        store
+    }
+
+    /// Drop every vehicle created more than `ttl` ago; `None` keeps them indefinitely
+    pub fn with_ttl(self, ttl: Option<Duration>) -> Self {
+        self.lock().ttl = ttl.map(|ttl| TimeDelta::from_std(ttl).unwrap_or(TimeDelta::MAX));
+        self
+    }
+
+    /// Remove the vehicles that have expired now, returning how many there were
+    pub fn sweep_expired(&self) -> usize {
+        let removed = self.lock().sweep_expired();
+        if removed > 0 {
+            info!("Expiry sweep removed {} vehicles", removed);
+        }
+        removed
+    }
+
+    /// Sweep expired vehicles every `interval` until `shutdown` flips
+    pub async fn run_expiry_sweeps(self, interval: Duration, mut shutdown: watch::Receiver<bool>) {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately and there is nothing to expire yet
+        ticks.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    self.sweep_expired();
+                }
+                _ = shutdown.changed() => break,
+            }
+        }
+    }
+}
+
+#[async_trait]
 impl VehicleRepo for InMemoryVehicleRepo {
-    async fn get_vehicle(&self, id: Uuid) -> Option<Vehicle> {
-        self.map.lock().unwrap().get(&id).cloned()
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        Ok(self.read().live(&id).map(Vehicle::from))
     }
 
-    async fn get_vehicles(&self) -> Vec<Vehicle> {
-        self.map.lock().unwrap().values().cloned().collect()
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        let store = self.read();
+        let Some(id) = store.vins.get(&vin.trim().to_ascii_uppercase()) else {
+            return Ok(None);
+        };
+        Ok(store.live(id).map(Vehicle::from))
     }
 
-    async fn post_vehicle(&self, vehicle: Vehicle) -> Option<VehicleId> {
-        let id = Uuid::now_v7();
-        self.map.lock().unwrap().insert(
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let store = self.read();
+        let plate = normalise_plate(Some(plate.to_string()));
+        let Some(id) = store.find_plate_owner(plate.as_deref(), None) else {
+            return Ok(None);
+        };
+        Ok(store.live(&id).map(Vehicle::from))
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        Ok(self.read().live(&id).is_some())
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        let store = self.read();
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for id in ids {
+            match store.live(id) {
+                Some(vehicle) => found.push(vehicle.into()),
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let store = self.read();
+        Ok(store.page(&page).map(Vehicle::from))
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        let store = self.read();
+        Ok(store.page(&page).map(VehicleSummary::from))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let store = self.read();
+        let mut range = store.matching(filter, cursor);
+        let page: Vec<&StoredVehicle> = range.by_ref().take(limit).collect();
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = match range.next() {
+            Some(_) => page.last().map(|v| v.id),
+            None => None,
+        };
+        let items = page.into_iter().map(Vehicle::from).collect();
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let store = self.read();
+        let matching: Vec<&StoredVehicle> =
+            store.unexpired().filter(|v| search.matches(v)).collect();
+        let total = matching.len();
+        let items = matching
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(Vehicle::from)
+            .collect();
+
+        Ok((items, total))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let everything = VehicleFilter::default();
+        let filter = filter.unwrap_or(&everything);
+        Ok(self.read().matching(filter, None).count() as u64)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        Ok(collect_stats(self.read().matching(filter, None)))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let store = self.read();
+        let manufacturers: BTreeSet<&str> = store
+            .unexpired()
+            .filter(|v| v.deleted_at.is_none())
+            .map(|v| &*v.manufacturer)
+            .collect();
+
+        Ok(manufacturers.into_iter().map(String::from).collect())
+    }
+
+    async fn post_vehicle(
+        &self,
+        mut vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        let mut store = self.write();
+        let manufacturer_raw = store.canonicalise(&mut vehicle);
+        if !allow_duplicate && let Some(existing_id) = store.find_duplicate(&vehicle) {
+            return Err(RepoError::Duplicate { existing_id });
+        }
+        let vin = normalise_vin(vehicle.vin);
+        if let Some(existing_id) = store.find_vin_owner(vin.as_deref(), None) {
+            return Err(RepoError::VinTaken { existing_id });
+        }
+        let license_plate = normalise_plate(vehicle.license_plate);
+        if let Some(existing_id) = store.find_plate_owner(license_plate.as_deref(), None) {
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        store.check_capacity(1)?;
+        store.evict_for(1);
+        let id = VehicleId::generate();
+        let now = Utc::now();
+        store.insert(
             id,
             Vehicle {
-                id: Some(id.to_string()),
+                id,
                 manufacturer: vehicle.manufacturer,
+                manufacturer_raw,
                 model: vehicle.model,
                 year: vehicle.year,
+                vin,
+                mileage_km: vehicle.mileage_km,
+                color: normalise_color(vehicle.color),
+                license_plate,
+                price: vehicle.price,
+                fuel_type: vehicle.fuel_type,
+                tags: normalise_tags(vehicle.tags),
+                metadata: vehicle.metadata,
+                engine: vehicle.engine,
+                owner_id: vehicle.owner_id,
+                first_registered: vehicle.first_registered,
+                status: VehicleStatus::default(),
+                created_at: now,
+                updated_at: now,
+                deleted_at: None,
+                expires_at: None,
+                version: 1,
             },
         );
+        store.record(id, ChangeType::Created);
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut store = self.write();
+        // Checked for the whole batch up front, so it is never left half inserted
+        store.check_capacity(vehicles.len())?;
+        let now = Utc::now();
+        let ids = vehicles
+            .into_iter()
+            .filter_map(|mut vehicle| {
+                let manufacturer_raw = store.canonicalise(&mut vehicle);
+                let vin = normalise_vin(vehicle.vin);
+                let license_plate = normalise_plate(vehicle.license_plate);
+                if store.find_vin_owner(vin.as_deref(), None).is_some()
+                    || store
+                        .find_plate_owner(license_plate.as_deref(), None)
+                        .is_some()
+                {
+                    return None;
+                }
+                store.evict_for(1);
+                let id = VehicleId::generate();
+                store.insert(
+                    id,
+                    Vehicle {
+                        id,
+                        manufacturer: vehicle.manufacturer,
+                        manufacturer_raw,
+                        model: vehicle.model,
+                        year: vehicle.year,
+                        vin,
+                        mileage_km: vehicle.mileage_km,
+                        color: normalise_color(vehicle.color),
+                        license_plate,
+                        price: vehicle.price,
+                        fuel_type: vehicle.fuel_type,
+                        tags: normalise_tags(vehicle.tags),
+                        metadata: vehicle.metadata,
+                        engine: vehicle.engine,
+                        owner_id: vehicle.owner_id,
+                        first_registered: vehicle.first_registered,
+                        status: VehicleStatus::default(),
+                        created_at: now,
+                        updated_at: now,
+                        deleted_at: None,
+                        expires_at: None,
+                        version: 1,
+                    },
+                );
+                store.record(id, ChangeType::Created);
+                Some(id)
+            })
+            .collect();
+
+        Ok(ids)
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut store = self.write();
+        store.check_capacity(vehicles.len())?;
+        // Every conflict is found before the first insert, so a failure leaves nothing behind
+        let mut vins = HashMap::new();
+        let mut plates = HashMap::new();
+        let mut prepared = Vec::with_capacity(vehicles.len());
+        for mut vehicle in vehicles {
+            let id = VehicleId::generate();
+            let manufacturer_raw = store.canonicalise(&mut vehicle);
+            let vin = normalise_vin(vehicle.vin.take());
+            let license_plate = normalise_plate(vehicle.license_plate.take());
+            if let Some(vin) = &vin
+                && let Some(existing_id) = store
+                    .find_vin_owner(Some(vin), None)
+                    .or_else(|| vins.insert(vin.clone(), id))
+            {
+                return Err(RepoError::VinTaken { existing_id });
+            }
+            if let Some(plate) = &license_plate
+                && let Some(existing_id) = store
+                    .find_plate_owner(Some(plate), None)
+                    .or_else(|| plates.insert(plate.clone(), id))
+            {
+                return Err(RepoError::PlateTaken { existing_id });
+            }
+            prepared.push((id, vehicle, manufacturer_raw, vin, license_plate));
+        }
+
+        let now = Utc::now();
+        let mut ids = Vec::with_capacity(prepared.len());
+        for (id, vehicle, manufacturer_raw, vin, license_plate) in prepared {
+            store.evict_for(1);
+            store.insert(
+                id,
+                Vehicle {
+                    id,
+                    manufacturer: vehicle.manufacturer,
+                    manufacturer_raw,
+                    model: vehicle.model,
+                    year: vehicle.year,
+                    vin,
+                    mileage_km: vehicle.mileage_km,
+                    color: normalise_color(vehicle.color),
+                    license_plate,
+                    price: vehicle.price,
+                    fuel_type: vehicle.fuel_type,
+                    tags: normalise_tags(vehicle.tags),
+                    metadata: vehicle.metadata,
+                    engine: vehicle.engine,
+                    owner_id: vehicle.owner_id,
+                    first_registered: vehicle.first_registered,
+                    status: VehicleStatus::default(),
+                    created_at: now,
+                    updated_at: now,
+                    deleted_at: None,
+                    expires_at: None,
+                    version: 1,
+                },
+            );
+            store.record(id, ChangeType::Created);
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        let mut store = self.write();
+        let manufacturer_raw = store.canonicalise(&mut vehicle);
+        let stored = store.live(&id).ok_or(RepoError::NotFound)?;
+        if let Some(if_match) = if_match
+            && !if_match.matches(&stored.etag())
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: Some(stored.version),
+            });
+        }
+        check_mileage(Some(stored), vehicle.mileage_km, correction)?;
+        let updated = Vehicle {
+            id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: normalise_vin(vehicle.vin),
+            mileage_km: vehicle.mileage_km,
+            color: normalise_color(vehicle.color),
+            license_plate: normalise_plate(vehicle.license_plate),
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: normalise_tags(vehicle.tags),
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: stored.status,
+            created_at: stored.created_at,
+            updated_at: Utc::now(),
+            deleted_at: stored.deleted_at,
+            expires_at: stored.expires_at,
+            version: stored.version + 1,
+        };
+        if let Some(existing_id) = store.find_vin_owner(updated.vin.as_deref(), Some(id)) {
+            return Err(RepoError::VinTaken { existing_id });
+        }
+        if let Some(existing_id) =
+            store.find_plate_owner(updated.license_plate.as_deref(), Some(id))
+        {
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        store.insert(id, updated.clone());
+        store.record(id, ChangeType::Updated);
+
+        Ok(updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let mut store = self.write();
+        let manufacturer_raw = store.canonicalise(&mut vehicle);
+        let existing = store.vehicles.get(&id);
+        check_not_deleted(existing)?;
+        // If-Match can only be satisfied by a vehicle that already exists
+        if let Some(if_match) = if_match
+            && !existing.is_some_and(|v| if_match.matches(&v.etag()))
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: existing.map(|v| v.version),
+            });
+        }
+        check_mileage(existing, vehicle.mileage_km, correction)?;
+        let created = existing.is_none();
+        let now = Utc::now();
+        let stored = Vehicle {
+            id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: normalise_vin(vehicle.vin),
+            mileage_km: vehicle.mileage_km,
+            color: normalise_color(vehicle.color),
+            license_plate: normalise_plate(vehicle.license_plate),
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: normalise_tags(vehicle.tags),
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: existing.map_or_else(VehicleStatus::default, |v| v.status),
+            created_at: existing.map_or(now, |v| v.created_at),
+            updated_at: now,
+            deleted_at: existing.and_then(|v| v.deleted_at),
+            expires_at: None,
+            version: existing.map_or(1, |v| v.version + 1),
+        };
+        if let Some(existing_id) = store.find_vin_owner(stored.vin.as_deref(), Some(id)) {
+            return Err(RepoError::VinTaken { existing_id });
+        }
+        if let Some(existing_id) = store.find_plate_owner(stored.license_plate.as_deref(), Some(id))
+        {
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        if created {
+            store.check_capacity(1)?;
+            store.evict_for(1);
+        }
+        store.insert(id, stored.clone());
+        let change_type = if created {
+            ChangeType::Created
+        } else {
+            ChangeType::Updated
+        };
+        store.record(id, change_type);
+
+        Ok((stored, created))
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        Ok(self.write().remove(&id))
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let mut store = self.write();
+        let Some(stored) = store.vehicles.get_mut(&id) else {
+            return Ok(None);
+        };
+        // Keep the original timestamp when deleting an already deleted vehicle
+        if stored.deleted_at.is_some() {
+            return Ok(Some(Vehicle::from(&*stored)));
+        }
+        let now = Utc::now();
+        stored.deleted_at = Some(now);
+        stored.updated_at = now;
+        stored.version += 1;
+        let deleted = Vehicle::from(&*stored);
+        store.record(id, ChangeType::Deleted);
+
+        Ok(Some(deleted))
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        let mut store = self.write();
+        let stored = store.vehicles.get(&id).ok_or(RepoError::NotFound)?;
+        if stored.deleted_at.is_none() {
+            return Ok(Vehicle::from(stored));
+        }
+        if let Some(existing_id) = store.find_plate_owner(stored.license_plate.as_deref(), Some(id))
+        {
+            return Err(RepoError::PlateTaken { existing_id });
+        }
+        let stored = store.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        stored.deleted_at = None;
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let restored = Vehicle::from(&*stored);
+        store.record(id, ChangeType::Restored);
+
+        Ok(restored)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        let mut store = self.write();
+        let stored = store.live(&id).ok_or(RepoError::NotFound)?;
+        let merged = normalise_tags(stored.tags.iter().cloned().chain(tags).collect());
+        if merged.len() == stored.tags.len() {
+            return Ok(Vehicle::from(stored));
+        }
+        if merged.len() > MAX_TAGS {
+            return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+        }
+        let updated = Vehicle {
+            tags: merged,
+            updated_at: Utc::now(),
+            version: stored.version + 1,
+            ..Vehicle::from(stored)
+        };
+        store.insert(id, updated.clone());
+        store.record(id, ChangeType::Updated);
+
+        Ok(updated)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let mut store = self.write();
+        let stored = store.live(&id).ok_or(RepoError::NotFound)?;
+        let tag = tag.trim().to_lowercase();
+        if !stored.tags.contains(&tag) {
+            return Ok(Vehicle::from(stored));
+        }
+        let updated = Vehicle {
+            tags: stored.tags.iter().filter(|t| **t != tag).cloned().collect(),
+            updated_at: Utc::now(),
+            version: stored.version + 1,
+            ..Vehicle::from(stored)
+        };
+        store.insert(id, updated.clone());
+        store.record(id, ChangeType::Updated);
+
+        Ok(updated)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        let mut store = self.write();
+        store.live(&id).ok_or(RepoError::NotFound)?;
+        let stored = store.vehicles.get_mut(&id).ok_or(RepoError::NotFound)?;
+        if let Some(if_match) = if_match
+            && !if_match.matches(&stored.etag())
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: Some(stored.version),
+            });
+        }
+        if stored.status == status {
+            return Ok(Vehicle::from(&*stored));
+        }
+        stored.status = status;
+        stored.updated_at = Utc::now();
+        stored.version += 1;
+        let updated = Vehicle::from(&*stored);
+        store.record(id, ChangeType::StatusChanged);
+
+        Ok(updated)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let mut store = self.write();
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            match store.remove(id) {
+                Some(_) => deleted += 1,
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let mut store = self.write();
+        let now = Utc::now();
+        let ids: Vec<VehicleId> = store
+            .vehicles
+            .values_mut()
+            .filter(|v| v.owner_id == Some(owner_id))
+            .map(|v| {
+                v.owner_id = None;
+                v.updated_at = now;
+                v.version += 1;
+                v.id
+            })
+            .collect();
+        for id in &ids {
+            store.record(*id, ChangeType::Updated);
+        }
+
+        Ok(ids.len())
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        let store = self.read();
+        if store.live(&id).is_none() {
+            return Ok(None);
+        }
+        let revisions = store
+            .history
+            .get(&id)
+            .map(|revisions| revisions.iter().rev().cloned().collect())
+            .unwrap_or_default();
+
+        Ok(Some(revisions))
+    }
+
+    async fn dump(&self) -> Result<Vec<Vehicle>, RepoError> {
+        Ok(self.vehicles())
+    }
+
+    /// A replacement store is built beside the current one and swapped in under the write
+    /// lock, so readers see either the old vehicles or the dumped ones, never a mix
+    async fn restore_dump(
+        &self,
+        vehicles: Vec<Vehicle>,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, RepoError> {
+        match mode {
+            RestoreMode::Merge => {
+                let mut store = self.write();
+                let conflicts = dump_conflicts(&vehicles, |vehicle| store.find_holder(vehicle));
+                let skipped: HashSet<usize> = conflicts.iter().map(|c| c.index).collect();
+                let incoming = vehicles.len() - skipped.len();
+                store.check_capacity(incoming)?;
+                store.evict_for(incoming);
+                let mut restored = Vec::with_capacity(incoming);
+                for (index, vehicle) in vehicles.into_iter().enumerate() {
+                    if !skipped.contains(&index) {
+                        restored.push(vehicle.id);
+                        store.insert(vehicle.id, vehicle);
+                    }
+                }
+                Ok(RestoreReport {
+                    restored,
+                    conflicts,
+                })
+            }
+            RestoreMode::Replace => {
+                let conflicts = dump_conflicts(&vehicles, |_| None);
+                if !conflicts.is_empty() {
+                    return Ok(RestoreReport {
+                        restored: Vec::new(),
+                        conflicts,
+                    });
+                }
+                let mut replacement = self.read().emptied();
+                if let Some(limit) = replacement.capacity
+                    && vehicles.len() > limit
+                {
+                    return Err(RepoError::CapacityExceeded { limit });
+                }
+                let restored = vehicles.iter().map(|vehicle| vehicle.id).collect();
+                for vehicle in vehicles {
+                    replacement.insert(vehicle.id, vehicle);
+                }
+                *self.lock() = replacement;
+                Ok(RestoreReport {
+                    restored,
+                    conflicts: Vec::new(),
+                })
+            }
+        }
+    }
+
+    /// Waits for the lock without blocking the runtime, so a writer stuck holding it makes the
+    /// check time out rather than hang the thread
+    async fn health_check(&self) -> Result<RepoHealth, RepoError> {
+        loop {
+            let vehicles = match self.store.try_read() {
+                Ok(store) => Some(store.vehicles.len()),
+                Err(TryLockError::Poisoned(_)) => Some(self.read().vehicles.len()),
+                Err(TryLockError::WouldBlock) => None,
+            };
+            match vehicles {
+                Some(vehicles) => {
+                    return Ok(RepoHealth {
+                        vehicles: Some(vehicles),
+                    });
+                }
+                None => tokio::time::sleep(Duration::from_millis(1)).await,
+            }
+        }
+    }
+
+    fn capacity(&self) -> Option<StoreCapacity> {
+        let store = self.read();
+        store.capacity.map(|limit| StoreCapacity {
+            used: store.vehicles.len(),
+            limit,
+            policy: store.capacity_policy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::vehicle::testing::{SplitMix64, VehicleBuilder};
+
+    fn manufacturer(filter: &str) -> VehicleFilter {
+        VehicleFilter {
+            manufacturer: Some(filter.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Readers and writers share the store from many worker threads at once; every task has to
+    /// finish, readers must only ever see whole vehicles, and the indexes must match the
+    /// vehicles once the dust settles
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn concurrent_readers_and_writers_leave_the_indexes_consistent() {
+        const WRITERS: usize = 8;
+        const READERS: usize = 8;
+        const WRITES: usize = 50;
+        let repo = Arc::new(InMemoryVehicleRepo::default());
+        let seeded = repo
+            .post_vehicles((0..100).map(|_| Vehicle::builder().request()).collect())
+            .await
+            .unwrap();
+
+        let mut tasks = Vec::new();
+        for _ in 0..WRITERS {
+            let repo = repo.clone();
+            tasks.push(tokio::spawn(async move {
+                for write in 0..WRITES {
+                    let vehicle = Vehicle::builder().manufacturer("Ford").request();
+                    let id = repo.post_vehicle(vehicle, true).await.unwrap();
+                    let renamed = Vehicle::builder().manufacturer("Volkswagen").request();
+                    repo.update_vehicle(id, renamed, None, false).await.unwrap();
+                    if write % 5 == 0 {
+                        repo.delete_vehicle(id).await.unwrap();
+                    } else if write % 3 == 0 {
+                        repo.soft_delete(id).await.unwrap();
+                    }
+                }
+            }));
+        }
+        for _ in 0..READERS {
+            let repo = repo.clone();
+            let seeded = seeded.clone();
+            tasks.push(tokio::spawn(async move {
+                for read in 0..WRITES {
+                    let id = seeded[read % seeded.len()];
+                    assert!(repo.get_vehicle(id).await.unwrap().is_some());
+                    let page = repo
+                        .list(PageRequest {
+                            filter: manufacturer("Volkswagen"),
+                            limit: 20,
+                            ..Default::default()
+                        })
+                        .await
+                        .unwrap();
+                    assert!(page.items.iter().all(|v| v.manufacturer == "Volkswagen"));
+                    assert!(page.items.len() <= page.total);
+                    let fords = repo.count(Some(&manufacturer("Ford"))).await.unwrap();
+                    assert!(fords <= WRITERS as u64);
+                }
+            }));
+        }
+        let finished = tokio::time::timeout(Duration::from_secs(30), async {
+            for task in tasks {
+                task.await.expect("task doesn't panic");
+            }
+        })
+        .await;
+
+        assert!(finished.is_ok(), "tasks deadlocked");
+        repo.read().verify_indexes().unwrap();
+        // Per writer: every fifth vehicle is removed, every third of the rest soft-deleted
+        let removed = WRITES.div_ceil(5);
+        let soft_deleted = (0..WRITES).filter(|w| w % 5 != 0 && w % 3 == 0).count();
+        let live = WRITERS * (WRITES - removed - soft_deleted);
+        assert_eq!(
+            repo.count(Some(&manufacturer("Volkswagen"))).await.unwrap(),
+            live as u64
+        );
+        assert_eq!(repo.count(Some(&manufacturer("Ford"))).await.unwrap(), 0);
+        assert_eq!(
+            repo.vehicles().len(),
+            seeded.len() + WRITERS * (WRITES - removed)
+        );
+    }
+
+    /// An export of 50,000 vehicles walks them a chunk at a time while writers keep going: no
+    /// chunk outgrows its size, every vehicle left untouched is exported exactly once, and
+    /// neither side waits on the other for long
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn exporting_fifty_thousand_vehicles_streams_them_past_concurrent_writes() {
+        use futures_util::StreamExt;
+        const SEEDED: u64 = 50_000;
+        const CHUNK: usize = 500;
+        let repo = Arc::new(InMemoryVehicleRepo::default());
+        repo.load_vehicles((1..=SEEDED).map(|seed| VehicleBuilder::random(seed).build()));
+        // Every hundredth seeded vehicle goes mid-export, from the end the walk reaches last
+        let deleted: Vec<VehicleId> = (1..=SEEDED)
+            .rev()
+            .step_by(100)
+            .map(|seed| VehicleId(uuid::Uuid::from_u64_pair(0, seed)))
+            .collect();
+
+        let writer = {
+            let (repo, deleted) = (repo.clone(), deleted.clone());
+            tokio::spawn(async move {
+                for (write, id) in deleted.into_iter().enumerate() {
+                    repo.delete_vehicle(id).await.unwrap();
+                    if write % 2 == 0 {
+                        let vehicle = Vehicle::builder().manufacturer("Ford").request();
+                        repo.post_vehicle(vehicle, true).await.unwrap();
+                    }
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+        let export = async {
+            let mut exported = HashSet::new();
+            let mut largest_chunk = 0;
+            let repo: Arc<dyn VehicleRepo> = repo.clone();
+            let mut chunks = Box::pin(vehicle_chunks(repo, VehicleFilter::default(), None, CHUNK));
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.unwrap();
+                largest_chunk = largest_chunk.max(chunk.len());
+                for vehicle in chunk {
+                    assert!(exported.insert(vehicle.id), "{} exported twice", vehicle.id);
+                }
+            }
+            (exported, largest_chunk)
+        };
+        let finished = tokio::time::timeout(Duration::from_secs(60), async {
+            let (exported, largest_chunk) = export.await;
+            writer.await.expect("writer doesn't panic");
+            (exported, largest_chunk)
+        })
+        .await;
+
+        let (exported, largest_chunk) = finished.expect("export and writes deadlocked");
+        assert_eq!(largest_chunk, CHUNK);
+        let untouched = (1..=SEEDED)
+            .map(|seed| VehicleId(uuid::Uuid::from_u64_pair(0, seed)))
+            .filter(|id| !deleted.contains(id));
+        for id in untouched {
+            assert!(exported.contains(&id), "{id} missing from the export");
+        }
+        let posted = deleted.len().div_ceil(2);
+        assert_eq!(
+            repo.vehicles().len(),
+            SEEDED as usize - deleted.len() + posted
+        );
+        repo.read().verify_indexes().unwrap();
+    }
+
+    /// A thread that panics while holding the lock poisons it; the repo has to clear that and
+    /// keep answering reads and writes instead of failing every later request
+    #[tokio::test]
+    async fn a_poisoned_lock_doesnt_stop_the_repo_serving() {
+        let repo = InMemoryVehicleRepo::default();
+        let id = repo
+            .post_vehicle(Vehicle::builder().request(), true)
+            .await
+            .unwrap();
+
+        let store = repo.store.clone();
+        let panicked = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _guard = store.write().unwrap();
+                    panic!("writer fails while holding the lock");
+                })
+                .join()
+        });
+        assert!(panicked.is_err());
+        assert!(repo.store.is_poisoned());
+
+        assert!(repo.get_vehicle(id).await.unwrap().is_some());
+        assert!(!repo.store.is_poisoned());
+        let second = repo
+            .post_vehicle(Vehicle::builder().model("Yaris").request(), true)
+            .await
+            .unwrap();
+        assert!(repo.get_vehicle(second).await.unwrap().is_some());
+        assert_eq!(repo.count(None).await.unwrap(), 2);
+        assert!(repo.health_check().await.is_ok());
+    }
+
+    /// Drive the store through a long random mix of writes, checking after every step that
+    /// the indexes still match the vehicles and that filtered reads agree with a full scan
+    #[tokio::test]
+    async fn random_writes_keep_every_index_in_step() {
+        const TAGS: &[&str] = &["fleet", "lease", "demo"];
+        let repo = InMemoryVehicleRepo::default();
+        let mut rng = SplitMix64(69);
+        let mut ids: Vec<VehicleId> = Vec::new();
+
+        for step in 0..2_000u64 {
+            let random = |rng: &mut SplitMix64| {
+                let mut vehicle = VehicleBuilder::random(rng.next());
+                if rng.below(2) == 0 {
+                    vehicle = vehicle.license_plate(format!("P{}", rng.below(40)));
+                }
+                if rng.below(3) == 0 {
+                    vehicle = vehicle.tag(TAGS[rng.below(TAGS.len())]);
+                }
+                vehicle.request()
+            };
+            let target = match ids.is_empty() {
+                true => None,
+                false => Some(ids[rng.below(ids.len())]),
+            };
+            // Plate clashes, missing and soft-deleted targets are expected along the way
+            let _ = match (rng.below(7), target) {
+                (0 | 1, _) | (_, None) => {
+                    let created = repo.post_vehicle(random(&mut rng), true).await;
+                    created.map(|id| ids.push(id))
+                }
+                (2, Some(id)) => {
+                    let vehicle = random(&mut rng);
+                    repo.update_vehicle(id, vehicle, None, true).await.map(drop)
+                }
+                (3, Some(id)) => repo.soft_delete(id).await.map(drop),
+                (4, Some(id)) => repo.restore(id).await.map(drop),
+                (5, Some(id)) => {
+                    let tag = TAGS[rng.below(TAGS.len())].to_string();
+                    repo.add_tags(id, vec![tag]).await.map(drop)
+                }
+                (_, Some(id)) => repo.delete_vehicle(id).await.map(drop),
+            };
+
+            let store = repo.read();
+            if let Err(drift) = store.verify_indexes() {
+                panic!("step {step}: {drift}");
+            }
+        }
+
+        let all = repo.vehicles();
+        for name in ["Toyota", "Ford", "Tesla"] {
+            let scanned = all
+                .iter()
+                .filter(|v| v.deleted_at.is_none() && v.manufacturer == name)
+                .count();
+            let counted = repo.count(Some(&manufacturer(name))).await.unwrap();
+            assert_eq!(counted, scanned as u64, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_full_store_rejects_the_vehicle_past_its_capacity() {
+        let repo = InMemoryVehicleRepo::default().with_capacity(Some(3), CapacityPolicy::Reject);
+        let vehicle = || Vehicle::builder().request();
+        let two = repo
+            .post_vehicles(vec![vehicle(), vehicle()])
+            .await
+            .unwrap();
+
+        let batch = repo.post_vehicles(vec![vehicle(), vehicle()]).await;
+        assert!(matches!(
+            batch,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+        assert_eq!(
+            repo.vehicles().len(),
+            2,
+            "a batch that won't fit adds nothing"
+        );
+        let third = repo.post_vehicle(vehicle(), true).await.unwrap();
+        let fourth = repo.post_vehicle(vehicle(), true).await;
+        assert!(matches!(
+            fourth,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+
+        let kept: Vec<VehicleId> = repo.vehicles().iter().map(|v| v.id).collect();
+        assert_eq!(kept, [two[0], two[1], third]);
+        let capacity = repo.capacity().unwrap();
+        assert_eq!((capacity.used, capacity.limit), (3, 3));
+    }
+
+    #[tokio::test]
+    async fn a_full_store_evicts_its_oldest_vehicle_for_the_next() {
+        let repo =
+            InMemoryVehicleRepo::default().with_capacity(Some(3), CapacityPolicy::EvictOldest);
+        let vehicle = || Vehicle::builder().request();
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(repo.post_vehicle(vehicle(), true).await.unwrap());
+        }
+        assert_eq!(repo.vehicles().len(), 3, "nothing is evicted at the limit");
+
+        let fourth = repo.post_vehicle(vehicle(), true).await.unwrap();
 
-        Some(VehicleId { id: id.to_string() })
+        let kept: Vec<VehicleId> = repo.vehicles().iter().map(|v| v.id).collect();
+        assert_eq!(kept, [ids[1], ids[2], fourth]);
+        assert!(repo.get_history(ids[0]).await.unwrap().is_none());
+        let too_many = repo
+            .post_vehicles((0..4).map(|_| vehicle()).collect())
+            .await;
+        assert!(matches!(
+            too_many,
+            Err(RepoError::CapacityExceeded { limit: 3 })
+        ));
+        repo.read().verify_indexes().unwrap();
     }
 }
@@ -0,0 +1,152 @@
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::{
+    features::vehicle::{
+        import::validation_messages, model::CreateVehicleRequest, repo::VehicleRepo,
+    },
+    utils::{
+        config::AppConfig,
+        validator::{Normalise, ValidateWithConfig},
+    },
+};
+
+/// Where the vehicles loaded on startup come from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeedSource {
+    /// A JSON array of vehicles in the create body's shape
+    File(String),
+    /// The built-in demo fleet
+    Demo,
+}
+
+impl SeedSource {
+    /// `--seed <path>` on the command line, then `SEED_FILE`, then `SEED=demo`; any other
+    /// `SEED` value is an error so a typo doesn't silently start an empty service
+    pub fn resolve(seed_file: Option<&str>, seed: Option<&str>) -> Result<Option<Self>, String> {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if let Some(path) = arg.strip_prefix("--seed=") {
+                return Ok(Some(Self::File(path.to_string())));
+            }
+            if arg == "--seed" {
+                return match args.next() {
+                    Some(path) => Ok(Some(Self::File(path))),
+                    None => Err("--seed requires a file path".to_string()),
+                };
+            }
+        }
+        if let Some(path) = seed_file {
+            return Ok(Some(Self::File(path.to_string())));
+        }
+        match seed {
+            None | Some("") => Ok(None),
+            Some("demo") => Ok(Some(Self::Demo)),
+            Some(other) => Err(format!("unknown SEED '{other}', expected 'demo'")),
+        }
+    }
+}
+
+/// A seed entry left out, numbered from 1 in the order of the file
+#[derive(Debug)]
+pub struct SeedFailure {
+    pub index: usize,
+    pub errors: Vec<String>,
+}
+
+/// Ten vehicles with fixed plates, so a store that survives restarts skips them the second time
+fn demo_fleet() -> Value {
+    json!([
+        { "manufacturer": "Toyota", "model": "Corolla", "year": 2019, "mileageKm": 48_200, "color": "white", "licensePlate": "DEMO-001", "price": "14950.00", "fuelType": "petrol", "tags": ["demo"] },
+        { "manufacturer": "Toyota", "model": "Prius", "year": 2021, "mileageKm": 22_750, "color": "silver", "licensePlate": "DEMO-002", "price": "23400.00", "fuelType": "hybrid", "tags": ["demo"] },
+        { "manufacturer": "Volkswagen", "model": "Golf", "year": 2017, "mileageKm": 91_300, "color": "blue", "licensePlate": "DEMO-003", "price": "11200.00", "fuelType": "diesel", "tags": ["demo"] },
+        { "manufacturer": "Tesla", "model": "Model 3", "year": 2022, "mileageKm": 18_400, "color": "red", "licensePlate": "DEMO-004", "price": "36900.00", "fuelType": "electric", "tags": ["demo"] },
+        { "manufacturer": "Ford", "model": "Focus", "year": 2015, "mileageKm": 124_600, "color": "black", "licensePlate": "DEMO-005", "price": "6800.00", "fuelType": "petrol", "tags": ["demo"] },
+        { "manufacturer": "BMW", "model": "320d", "year": 2020, "mileageKm": 56_900, "color": "grey", "licensePlate": "DEMO-006", "price": "31500.00", "fuelType": "diesel", "tags": ["demo"] },
+        { "manufacturer": "Honda", "model": "Civic", "year": 2018, "mileageKm": 67_100, "color": "white", "licensePlate": "DEMO-007", "price": "13750.00", "fuelType": "petrol", "tags": ["demo"] },
+        { "manufacturer": "Hyundai", "model": "Nexo", "year": 2023, "mileageKm": 9_800, "color": "green", "licensePlate": "DEMO-008", "price": "52000.00", "fuelType": "hydrogen", "tags": ["demo"] },
+        { "manufacturer": "Renault", "model": "Zoe", "year": 2020, "mileageKm": 34_500, "color": "blue", "licensePlate": "DEMO-009", "price": "15900.00", "fuelType": "electric", "tags": ["demo"] },
+        { "manufacturer": "Volvo", "model": "XC60", "year": 2019, "mileageKm": 78_300, "color": "black", "licensePlate": "DEMO-010", "price": "28400.00", "fuelType": "hybrid", "tags": ["demo"] }
+    ])
+}
+
+/// Normalise and validate every entry of a JSON array, splitting it like the CSV import does;
+/// entries naming an owner are refused as owners aren't seeded and would be left dangling
+pub fn parse_seed(
+    value: Value,
+    config: &AppConfig,
+) -> Result<(Vec<CreateVehicleRequest>, Vec<SeedFailure>), String> {
+    let Value::Array(entries) = value else {
+        return Err("expected a JSON array of vehicles".to_string());
+    };
+
+    let mut vehicles = Vec::new();
+    let mut failed = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let vehicle = serde_json::from_value::<CreateVehicleRequest>(entry)
+            .map_err(|e| vec![e.to_string()])
+            .and_then(|mut vehicle| {
+                vehicle.normalise();
+                vehicle
+                    .validate_with_config(config)
+                    .map_err(|e| validation_messages(&e))?;
+                match vehicle.owner_id {
+                    Some(_) => Err(vec!["ownerId: owners can't be seeded".to_string()]),
+                    None => Ok(vehicle),
+                }
+            });
+        match vehicle {
+            Ok(vehicle) => vehicles.push(vehicle),
+            Err(errors) => failed.push(SeedFailure {
+                index: index + 1,
+                errors,
+            }),
+        }
+    }
+    Ok((vehicles, failed))
+}
+
+/// Load the seed vehicles into the repo through the batch insert and log what happened to them;
+/// only an unreadable source is an error, individual bad entries are logged and skipped
+pub async fn seed_vehicles(
+    repo: &dyn VehicleRepo,
+    source: &SeedSource,
+    config: &AppConfig,
+) -> Result<(), String> {
+    let value = match source {
+        SeedSource::Demo => demo_fleet(),
+        SeedSource::File(path) => {
+            let body = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("failed to read {path}: {e}"))?;
+            serde_json::from_str(&body).map_err(|e| format!("failed to parse {path}: {e}"))?
+        }
+    };
+    let (vehicles, failed) = parse_seed(value, config)?;
+
+    for failure in &failed {
+        warn!(
+            "Seed vehicle #{} failed validation: {}",
+            failure.index,
+            failure.errors.join("; ")
+        );
+    }
+    let valid = vehicles.len();
+    let loaded = repo
+        .post_vehicles(vehicles)
+        .await
+        .map_err(|e| format!("failed to insert seed vehicles: {e:?}"))?
+        .len();
+
+    info!(
+        "Seeded {} vehicles from {}, {} failed validation, {} skipped as their VIN or plate was taken",
+        loaded,
+        match source {
+            SeedSource::Demo => "the demo fleet",
+            SeedSource::File(path) => path,
+        },
+        failed.len(),
+        valid - loaded
+    );
+    Ok(())
+}
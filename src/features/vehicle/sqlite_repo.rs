@@ -0,0 +1,951 @@
+use crate::features::{
+    owner::model::OwnerId,
+    vehicle::{
+        manufacturer::ManufacturerAliases,
+        model::{
+            ChangeType, CreateVehicleRequest, EngineSpec, FuelType, MAX_TAGS, PageRequest,
+            PageResult, Vehicle, VehicleFilter, VehicleId, VehicleRevision, VehicleSearch,
+            VehicleSortField, VehicleStats, VehicleStatus, VehicleSummary, normalise_color,
+            normalise_plate, normalise_tags, normalise_vin,
+        },
+        repo::{RepoError, VehicleRepo, check_mileage, check_not_deleted, collect_stats},
+    },
+};
+use crate::utils::{
+    etag::IfMatch,
+    sorting::{Direction, SortKey},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::{
+    FromRow, QueryBuilder, Sqlite, SqliteConnection, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    types::Json,
+};
+use std::{collections::BTreeMap, time::Duration};
+use uuid::Uuid;
+
+/// Vehicle repo backed by a single SQLite file, for one-node deployments that should survive
+/// restarts.
+///
+/// The schema in `migrations/sqlite/` is applied on connect. The file runs in WAL mode, so
+/// reads never wait for the writer. Writes take the write lock up front with `BEGIN IMMEDIATE`
+/// and queue behind each other through the busy timeout instead of failing on lock upgrades.
+/// Filters, sorting, pagination and search run in SQL; case-insensitive matching uses
+/// lowercased key columns written alongside each vehicle, since SQLite only folds ASCII case.
+pub struct SqliteVehicleRepo {
+    pool: SqlitePool,
+    history_limit: usize,
+    aliases: ManufacturerAliases,
+}
+
+/// One row of `vehicles`; the key columns are write-only and not read back
+#[derive(FromRow)]
+struct VehicleRow {
+    id: Uuid,
+    manufacturer: String,
+    manufacturer_raw: Option<String>,
+    model: String,
+    year: u16,
+    vin: Option<String>,
+    mileage_km: Option<u32>,
+    color: Option<String>,
+    license_plate: Option<String>,
+    price: Option<String>,
+    fuel_type: Option<String>,
+    tags: Json<Vec<String>>,
+    metadata: Json<BTreeMap<String, String>>,
+    engine: Option<Json<EngineSpec>>,
+    owner_id: Option<Uuid>,
+    first_registered: Option<NaiveDate>,
+    status: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    version: i64,
+}
+
+impl TryFrom<VehicleRow> for Vehicle {
+    type Error = RepoError;
+
+    fn try_from(row: VehicleRow) -> Result<Self, RepoError> {
+        let id = row.id;
+        let corrupt =
+            |column: &str| RepoError::Backend(format!("vehicle {id} has an invalid {column}"));
+        Ok(Vehicle {
+            id: VehicleId(row.id),
+            manufacturer: row.manufacturer,
+            manufacturer_raw: row.manufacturer_raw,
+            model: row.model,
+            year: row.year,
+            vin: row.vin,
+            mileage_km: row.mileage_km,
+            color: row.color,
+            license_plate: row.license_plate,
+            price: row
+                .price
+                .map(|price| price.parse::<Decimal>())
+                .transpose()
+                .map_err(|_| corrupt("price"))?,
+            fuel_type: row
+                .fuel_type
+                .map(|fuel| fuel.parse::<FuelType>())
+                .transpose()
+                .map_err(|_| corrupt("fuel_type"))?,
+            tags: row.tags.0,
+            metadata: row.metadata.0,
+            engine: row.engine.map(|engine| engine.0),
+            owner_id: row.owner_id.map(OwnerId),
+            first_registered: row.first_registered,
+            status: VehicleStatus::ALL
+                .iter()
+                .copied()
+                .find(|status| status.as_str() == row.status)
+                .ok_or_else(|| corrupt("status"))?,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            expires_at: None,
+            version: u64::try_from(row.version).map_err(|_| corrupt("version"))?,
+        })
+    }
+}
+
+/// SQLite counts rows in `i64`; anything larger means "no limit"
+fn sql_count(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+/// Append the conditions of `filter` to a query ending in `WHERE <condition>`, mirroring
+/// `VehicleFilter::matches`
+fn push_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &VehicleFilter) {
+    if !filter.include_deleted.unwrap_or(false) {
+        query.push(" AND deleted_at IS NULL");
+    }
+    if let Some(manufacturer) = &filter.manufacturer {
+        query
+            .push(" AND manufacturer_key = ")
+            .push_bind(manufacturer.to_lowercase());
+    }
+    if let Some(model) = &filter.model {
+        query
+            .push(" AND model_key = ")
+            .push_bind(model.to_lowercase());
+    }
+    if let Some(year) = filter.year {
+        query.push(" AND year = ").push_bind(year);
+    }
+    if let Some(min) = filter.year_min {
+        query.push(" AND year >= ").push_bind(min);
+    }
+    if let Some(max) = filter.year_max {
+        query.push(" AND year <= ").push_bind(max);
+    }
+    if let Some(after) = filter.created_after {
+        query.push(" AND created_at > ").push_bind(after);
+    }
+    if let Some(max) = filter.mileage_max {
+        query.push(" AND mileage_km <= ").push_bind(max);
+    }
+    if let Some(min) = filter.min_power_kw {
+        query
+            .push(" AND json_extract(engine, '$.powerKw') >= ")
+            .push_bind(min);
+    }
+    if let Some(color) = &filter.color {
+        query
+            .push(" AND color_key = ")
+            .push_bind(color.trim().to_lowercase());
+    }
+    if let Some(fuel_type) = filter.fuel_type {
+        query
+            .push(" AND fuel_type = ")
+            .push_bind(fuel_type.as_str());
+    }
+    for tag in &filter.tags {
+        query
+            .push(" AND EXISTS (SELECT 1 FROM json_each(vehicles.tags) WHERE value = ")
+            .push_bind(tag.clone())
+            .push(")");
+    }
+    if let Some(status) = filter.status {
+        query.push(" AND status = ").push_bind(status.as_str());
+    }
+    if let Some(owner_id) = filter.owner_id {
+        query.push(" AND owner_id = ").push_bind(owner_id.0);
+    }
+    if let Some(after) = filter.registered_after {
+        query.push(" AND first_registered > ").push_bind(after);
+    }
+    if let Some(before) = filter.registered_before {
+        query.push(" AND first_registered < ").push_bind(before);
+    }
+}
+
+/// Append an ORDER BY for `sort`, mirroring `sorting::compare`: vehicles without a price go
+/// last in either direction and ties fall back to the id
+fn push_order(query: &mut QueryBuilder<'_, Sqlite>, sort: &[SortKey<VehicleSortField>]) {
+    query.push(" ORDER BY ");
+    for key in sort {
+        let column = match key.field {
+            VehicleSortField::Manufacturer => "manufacturer",
+            VehicleSortField::Model => "model",
+            VehicleSortField::Year => "year",
+            VehicleSortField::Id => "id",
+            VehicleSortField::CreatedAt => "created_at",
+            VehicleSortField::UpdatedAt => "updated_at",
+            VehicleSortField::Price => {
+                query.push("price IS NULL, ");
+                "CAST(price AS REAL)"
+            }
+        };
+        let direction = match key.direction {
+            Direction::Ascending => "ASC",
+            Direction::Descending => "DESC",
+        };
+        query.push(format_args!("{column} {direction}, "));
+    }
+    query.push("id");
+}
+
+/// Fetch the rows selected by `query` as vehicles
+async fn fetch_vehicles(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    conn: &mut SqliteConnection,
+) -> Result<Vec<Vehicle>, RepoError> {
+    query
+        .build_query_as::<VehicleRow>()
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(Vehicle::try_from)
+        .collect()
+}
+
+/// The vehicle with this id
+async fn load(conn: &mut SqliteConnection, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+    sqlx::query_as::<_, VehicleRow>("SELECT * FROM vehicles WHERE id = ?")
+        .bind(id.0)
+        .fetch_optional(conn)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+}
+
+/// Insert `vehicle`, or overwrite the row with its id
+async fn save(conn: &mut SqliteConnection, vehicle: &Vehicle) -> Result<(), RepoError> {
+    sqlx::query(
+        "INSERT INTO vehicles (id, manufacturer, manufacturer_raw, model, year, vin, mileage_km, \
+         color, license_plate, price, fuel_type, tags, metadata, engine, owner_id, \
+         first_registered, status, created_at, updated_at, deleted_at, version, \
+         manufacturer_key, model_key, color_key) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (id) DO UPDATE SET manufacturer = excluded.manufacturer, \
+         manufacturer_raw = excluded.manufacturer_raw, model = excluded.model, \
+         year = excluded.year, vin = excluded.vin, mileage_km = excluded.mileage_km, \
+         color = excluded.color, license_plate = excluded.license_plate, \
+         price = excluded.price, fuel_type = excluded.fuel_type, tags = excluded.tags, \
+         metadata = excluded.metadata, engine = excluded.engine, owner_id = excluded.owner_id, \
+         first_registered = excluded.first_registered, status = excluded.status, \
+         created_at = excluded.created_at, updated_at = excluded.updated_at, \
+         deleted_at = excluded.deleted_at, version = excluded.version, \
+         manufacturer_key = excluded.manufacturer_key, model_key = excluded.model_key, \
+         color_key = excluded.color_key",
+    )
+    .bind(vehicle.id.0)
+    .bind(&vehicle.manufacturer)
+    .bind(&vehicle.manufacturer_raw)
+    .bind(&vehicle.model)
+    .bind(vehicle.year)
+    .bind(&vehicle.vin)
+    .bind(vehicle.mileage_km)
+    .bind(&vehicle.color)
+    .bind(&vehicle.license_plate)
+    .bind(vehicle.price.map(|price| price.to_string()))
+    .bind(vehicle.fuel_type.map(FuelType::as_str))
+    .bind(Json(&vehicle.tags))
+    .bind(Json(&vehicle.metadata))
+    .bind(vehicle.engine.map(Json))
+    .bind(vehicle.owner_id.map(|owner| owner.0))
+    .bind(vehicle.first_registered)
+    .bind(vehicle.status.as_str())
+    .bind(vehicle.created_at)
+    .bind(vehicle.updated_at)
+    .bind(vehicle.deleted_at)
+    .bind(vehicle.version as i64)
+    .bind(vehicle.manufacturer.to_lowercase())
+    .bind(vehicle.model.to_lowercase())
+    .bind(vehicle.color.as_deref().map(str::to_lowercase))
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Fail when the VIN or, for live vehicles, the plate of `vehicle` belongs to another vehicle
+async fn check_keys(conn: &mut SqliteConnection, vehicle: &Vehicle) -> Result<(), RepoError> {
+    if let Some(vin) = &vehicle.vin {
+        let holder: Option<Uuid> =
+            sqlx::query_scalar("SELECT id FROM vehicles WHERE vin = ? AND id <> ?")
+                .bind(vin)
+                .bind(vehicle.id.0)
+                .fetch_optional(&mut *conn)
+                .await?;
+        if let Some(existing_id) = holder {
+            return Err(RepoError::VinTaken {
+                existing_id: VehicleId(existing_id),
+            });
+        }
+    }
+    if vehicle.deleted_at.is_none()
+        && let Some(plate) = &vehicle.license_plate
+    {
+        let holder: Option<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM vehicles WHERE license_plate = ? AND deleted_at IS NULL AND id <> ?",
+        )
+        .bind(plate)
+        .bind(vehicle.id.0)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(existing_id) = holder {
+            return Err(RepoError::PlateTaken {
+                existing_id: VehicleId(existing_id),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl SqliteVehicleRepo {
+    /// Open the database file at `path`, creating it if missing, and apply pending migrations,
+    /// keeping up to `history_limit` revisions per vehicle; 0 disables history
+    pub async fn connect(path: &str, history_limit: usize) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = SqlitePoolOptions::new()
+            .acquire_timeout(Duration::from_secs(5))
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("migrations/sqlite").run(&pool).await?;
+
+        Ok(Self {
+            pool,
+            history_limit,
+            aliases: ManufacturerAliases::default(),
+        })
+    }
+
+    /// Resolve manufacturer names through `aliases` instead of the built-in table alone
+    pub fn with_manufacturer_aliases(self, aliases: ManufacturerAliases) -> Self {
+        Self { aliases, ..self }
+    }
+
+    /// Store the canonical manufacturer on `vehicle`, returning the name as sent when it differed
+    fn canonicalise(&self, vehicle: &mut CreateVehicleRequest) -> Option<String> {
+        let canonical = self.aliases.canonical(&vehicle.manufacturer);
+        (canonical != vehicle.manufacturer)
+            .then(|| std::mem::replace(&mut vehicle.manufacturer, canonical))
+    }
+
+    /// `filter` with its manufacturer resolved through the aliases like stored values
+    fn canonical_filter(&self, filter: &VehicleFilter) -> VehicleFilter {
+        VehicleFilter {
+            manufacturer: filter
+                .manufacturer
+                .as_deref()
+                .map(|m| self.aliases.canonical(m.trim())),
+            ..filter.clone()
+        }
+    }
+
+    /// Snapshot `vehicle` into its revision history, dropping revisions past the limit
+    async fn record(
+        &self,
+        conn: &mut SqliteConnection,
+        vehicle: &Vehicle,
+        change_type: ChangeType,
+    ) -> Result<(), RepoError> {
+        if self.history_limit == 0 {
+            return Ok(());
+        }
+        let revision = VehicleRevision {
+            version: vehicle.version,
+            changed_at: Utc::now(),
+            change_type,
+            snapshot: vehicle.clone().into(),
+        };
+        sqlx::query(
+            "INSERT INTO vehicle_revisions (vehicle_id, version, revision) VALUES (?, ?, ?) \
+             ON CONFLICT (vehicle_id, version) DO UPDATE SET revision = excluded.revision",
+        )
+        .bind(vehicle.id.0)
+        .bind(vehicle.version as i64)
+        .bind(Json(&revision))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query(
+            "DELETE FROM vehicle_revisions WHERE vehicle_id = ?1 AND version NOT IN \
+             (SELECT version FROM vehicle_revisions WHERE vehicle_id = ?1 \
+             ORDER BY version DESC LIMIT ?2)",
+        )
+        .bind(vehicle.id.0)
+        .bind(sql_count(self.history_limit))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Save `vehicle` after checking its keys and record the change
+    async fn store(
+        &self,
+        conn: &mut SqliteConnection,
+        vehicle: &Vehicle,
+        change_type: ChangeType,
+    ) -> Result<(), RepoError> {
+        check_keys(conn, vehicle).await?;
+        save(conn, vehicle).await?;
+        self.record(conn, vehicle, change_type).await
+    }
+
+    /// Load the vehicle under the write lock, let `change` edit it and save the result;
+    /// `change` returns the kind of change made, or `None` to leave the vehicle untouched
+    async fn modify(
+        &self,
+        id: VehicleId,
+        change: impl FnOnce(&mut Vehicle) -> Result<Option<ChangeType>, RepoError> + Send,
+    ) -> Result<Option<Vehicle>, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let Some(mut vehicle) = load(&mut tx, id).await? else {
+            return Ok(None);
+        };
+        let Some(change_type) = change(&mut vehicle)? else {
+            return Ok(Some(vehicle));
+        };
+        vehicle.updated_at = Utc::now();
+        vehicle.version += 1;
+        self.store(&mut tx, &vehicle, change_type).await?;
+        tx.commit().await?;
+
+        Ok(Some(vehicle))
+    }
+
+    /// Replace the vehicle with this id, creating it when `create` is set; the flag is true
+    /// when it was created
+    async fn write(
+        &self,
+        conn: &mut SqliteConnection,
+        id: VehicleId,
+        mut vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+        create: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let manufacturer_raw = self.canonicalise(&mut vehicle);
+        let existing = load(conn, id).await?;
+        if existing.is_none() && !create {
+            return Err(RepoError::NotFound);
+        }
+        check_not_deleted(existing.as_ref())?;
+        // If-Match can only be satisfied by a vehicle that already exists
+        if let Some(if_match) = if_match
+            && !existing
+                .as_ref()
+                .is_some_and(|v| if_match.matches(&v.etag()))
+        {
+            return Err(RepoError::VersionMismatch {
+                current_version: existing.map(|v| v.version),
+            });
+        }
+        check_mileage(existing.as_ref(), vehicle.mileage_km, correction)?;
+        let created = existing.is_none();
+        let now = Utc::now();
+        let stored = Vehicle {
+            id,
+            manufacturer: vehicle.manufacturer,
+            manufacturer_raw,
+            model: vehicle.model,
+            year: vehicle.year,
+            vin: normalise_vin(vehicle.vin),
+            mileage_km: vehicle.mileage_km,
+            color: normalise_color(vehicle.color),
+            license_plate: normalise_plate(vehicle.license_plate),
+            price: vehicle.price,
+            fuel_type: vehicle.fuel_type,
+            tags: normalise_tags(vehicle.tags),
+            metadata: vehicle.metadata,
+            engine: vehicle.engine,
+            owner_id: vehicle.owner_id,
+            first_registered: vehicle.first_registered,
+            status: existing
+                .as_ref()
+                .map_or_else(VehicleStatus::default, |v| v.status),
+            created_at: existing.as_ref().map_or(now, |v| v.created_at),
+            updated_at: now,
+            deleted_at: existing.as_ref().and_then(|v| v.deleted_at),
+            expires_at: None,
+            version: existing.as_ref().map_or(1, |v| v.version + 1),
+        };
+        let change_type = if created {
+            ChangeType::Created
+        } else {
+            ChangeType::Updated
+        };
+        self.store(conn, &stored, change_type).await?;
+
+        Ok((stored, created))
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for SqliteVehicleRepo {
+    async fn get_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        let mut conn = self.pool.acquire().await?;
+        Ok(load(&mut conn, id)
+            .await?
+            .filter(|v| v.deleted_at.is_none()))
+    }
+
+    async fn get_vehicle_by_vin(&self, vin: &str) -> Result<Option<Vehicle>, RepoError> {
+        sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE vin = ? AND deleted_at IS NULL",
+        )
+        .bind(vin.trim().to_ascii_uppercase())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+    }
+
+    async fn get_vehicle_by_plate(&self, plate: &str) -> Result<Option<Vehicle>, RepoError> {
+        let Some(plate) = normalise_plate(Some(plate.to_string())) else {
+            return Ok(None);
+        };
+        sqlx::query_as::<_, VehicleRow>(
+            "SELECT * FROM vehicles WHERE license_plate = ? AND deleted_at IS NULL",
+        )
+        .bind(plate)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Vehicle::try_from)
+        .transpose()
+    }
+
+    async fn exists(&self, id: VehicleId) -> Result<bool, RepoError> {
+        let exists = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM vehicles WHERE id = ? AND deleted_at IS NULL)",
+        )
+        .bind(id.0)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn get_vehicles_by_ids(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(Vec<Vehicle>, Vec<VehicleId>), RepoError> {
+        if ids.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id.0);
+        }
+        query.push(") AND deleted_at IS NULL");
+        let mut conn = self.pool.acquire().await?;
+        let stored = fetch_vehicles(&mut query, &mut conn).await?;
+        let mut found = Vec::with_capacity(ids.len());
+        let mut missing = Vec::new();
+        for id in ids {
+            match stored.iter().find(|v| v.id == *id) {
+                Some(vehicle) => found.push(vehicle.clone()),
+                None => missing.push(*id),
+            }
+        }
+
+        Ok((found, missing))
+    }
+
+    async fn list(&self, page: PageRequest) -> Result<PageResult<Vehicle>, RepoError> {
+        let filter = self.canonical_filter(&page.filter);
+        // One read transaction, so the page and the total come from the same snapshot
+        let mut tx = self.pool.begin().await?;
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM vehicles WHERE 1 = 1");
+        push_filter(&mut count, &filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&mut *tx).await?;
+
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles WHERE 1 = 1");
+        push_filter(&mut query, &filter);
+        push_order(&mut query, &page.sort);
+        query
+            .push(" LIMIT ")
+            .push_bind(sql_count(page.limit))
+            .push(" OFFSET ")
+            .push_bind(sql_count(page.offset));
+        let items = fetch_vehicles(&mut query, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(PageResult {
+            items,
+            total: total as usize,
+        })
+    }
+
+    async fn list_summaries(
+        &self,
+        page: PageRequest,
+    ) -> Result<PageResult<VehicleSummary>, RepoError> {
+        Ok(self.list(page).await?.map(|v| VehicleSummary::from(&v)))
+    }
+
+    async fn get_vehicles_after(
+        &self,
+        filter: &VehicleFilter,
+        cursor: Option<VehicleId>,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, Option<VehicleId>), RepoError> {
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles WHERE 1 = 1");
+        push_filter(&mut query, &self.canonical_filter(filter));
+        if let Some(cursor) = cursor {
+            query.push(" AND id > ").push_bind(cursor.0);
+        }
+        // One row past the page tells whether there is a next one
+        query
+            .push(" ORDER BY id LIMIT ")
+            .push_bind(sql_count(limit.saturating_add(1)));
+        let mut conn = self.pool.acquire().await?;
+        let mut items = fetch_vehicles(&mut query, &mut conn).await?;
+
+        // Only hand out a cursor when there is at least one more vehicle past this page
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|v| v.id)
+        } else {
+            None
+        };
+
+        Ok((items, next_cursor))
+    }
+
+    async fn search_vehicles(
+        &self,
+        search: &VehicleSearch,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<Vehicle>, usize), RepoError> {
+        let push_search = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query.push(" WHERE deleted_at IS NULL");
+            for token in &search.tokens {
+                query
+                    .push(" AND (instr(manufacturer_key, ")
+                    .push_bind(token.clone())
+                    .push(") > 0 OR instr(model_key, ")
+                    .push_bind(token.clone())
+                    .push(") > 0)");
+            }
+        };
+        let mut tx = self.pool.begin().await?;
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM vehicles");
+        push_search(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(&mut *tx).await?;
+
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles");
+        push_search(&mut query);
+        query
+            .push(" ORDER BY id LIMIT ")
+            .push_bind(sql_count(limit))
+            .push(" OFFSET ")
+            .push_bind(sql_count(offset));
+        let items = fetch_vehicles(&mut query, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok((items, total as usize))
+    }
+
+    async fn count(&self, filter: Option<&VehicleFilter>) -> Result<u64, RepoError> {
+        let everything = VehicleFilter::default();
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM vehicles WHERE 1 = 1");
+        push_filter(&mut query, &self.canonical_filter(filter.unwrap_or(&everything)));
+        let count: i64 = query.build_query_scalar().fetch_one(&self.pool).await?;
+
+        Ok(count as u64)
+    }
+
+    async fn stats(&self, filter: &VehicleFilter) -> Result<VehicleStats, RepoError> {
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles WHERE 1 = 1");
+        push_filter(&mut query, &self.canonical_filter(filter));
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(collect_stats(&fetch_vehicles(&mut query, &mut conn).await?))
+    }
+
+    async fn manufacturers(&self) -> Result<Vec<String>, RepoError> {
+        let manufacturers = sqlx::query_scalar(
+            "SELECT DISTINCT manufacturer FROM vehicles WHERE deleted_at IS NULL \
+             ORDER BY manufacturer",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(manufacturers)
+    }
+
+    async fn post_vehicle(
+        &self,
+        vehicle: CreateVehicleRequest,
+        allow_duplicate: bool,
+    ) -> Result<VehicleId, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        if !allow_duplicate {
+            let manufacturer = self.aliases.canonical(&vehicle.manufacturer);
+            let existing: Option<Uuid> = sqlx::query_scalar(
+                "SELECT id FROM vehicles WHERE deleted_at IS NULL AND manufacturer_key = ? \
+                 AND model_key = ? AND year = ? ORDER BY id LIMIT 1",
+            )
+            .bind(manufacturer.to_lowercase())
+            .bind(vehicle.model.to_lowercase())
+            .bind(vehicle.year)
+            .fetch_optional(&mut *tx)
+            .await?;
+            if let Some(existing_id) = existing {
+                return Err(RepoError::Duplicate {
+                    existing_id: VehicleId(existing_id),
+                });
+            }
+        }
+        let id = VehicleId::generate();
+        self.write(&mut tx, id, vehicle, None, false, true).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn post_vehicles(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            match self.write(&mut tx, id, vehicle, None, false, true).await {
+                Ok(_) => ids.push(id),
+                Err(RepoError::VinTaken { .. } | RepoError::PlateTaken { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    async fn post_vehicles_atomic(
+        &self,
+        vehicles: Vec<CreateVehicleRequest>,
+    ) -> Result<Vec<VehicleId>, RepoError> {
+        // Any failure drops the transaction, rolling back the vehicles written before it
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut ids = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            let id = VehicleId::generate();
+            self.write(&mut tx, id, vehicle, None, false, true).await?;
+            ids.push(id);
+        }
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<Vehicle, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let (updated, _) = self
+            .write(&mut tx, id, vehicle, if_match, correction, false)
+            .await?;
+        tx.commit().await?;
+
+        Ok(updated)
+    }
+
+    async fn upsert_vehicle(
+        &self,
+        id: VehicleId,
+        vehicle: CreateVehicleRequest,
+        if_match: Option<&IfMatch>,
+        correction: bool,
+    ) -> Result<(Vehicle, bool), RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let written = self
+            .write(&mut tx, id, vehicle, if_match, correction, true)
+            .await?;
+        tx.commit().await?;
+
+        Ok(written)
+    }
+
+    async fn delete_vehicle(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        // Revisions go with the vehicle through the foreign key
+        sqlx::query_as::<_, VehicleRow>("DELETE FROM vehicles WHERE id = ? RETURNING *")
+            .bind(id.0)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(Vehicle::try_from)
+            .transpose()
+    }
+
+    async fn soft_delete(&self, id: VehicleId) -> Result<Option<Vehicle>, RepoError> {
+        self.modify(id, |vehicle| {
+            // Keep the original timestamp when deleting an already deleted vehicle
+            if vehicle.deleted_at.is_some() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = Some(Utc::now());
+            Ok(Some(ChangeType::Deleted))
+        })
+        .await
+    }
+
+    async fn restore(&self, id: VehicleId) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            if vehicle.deleted_at.is_none() {
+                return Ok(None);
+            }
+            vehicle.deleted_at = None;
+            Ok(Some(ChangeType::Restored))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn add_tags(&self, id: VehicleId, tags: Vec<String>) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            let merged = normalise_tags(vehicle.tags.iter().cloned().chain(tags).collect());
+            if merged.len() == vehicle.tags.len() {
+                return Ok(None);
+            }
+            if merged.len() > MAX_TAGS {
+                return Err(RepoError::TooManyTags { limit: MAX_TAGS });
+            }
+            vehicle.tags = merged;
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn remove_tag(&self, id: VehicleId, tag: &str) -> Result<Vehicle, RepoError> {
+        let tag = tag.trim().to_lowercase();
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if !vehicle.tags.contains(&tag) {
+                return Ok(None);
+            }
+            vehicle.tags.retain(|t| *t != tag);
+            Ok(Some(ChangeType::Updated))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn set_status(
+        &self,
+        id: VehicleId,
+        status: VehicleStatus,
+        if_match: Option<&IfMatch>,
+    ) -> Result<Vehicle, RepoError> {
+        self.modify(id, |vehicle| {
+            check_not_deleted(Some(&*vehicle))?;
+            if let Some(if_match) = if_match
+                && !if_match.matches(&vehicle.etag())
+            {
+                return Err(RepoError::VersionMismatch {
+                    current_version: Some(vehicle.version),
+                });
+            }
+            if vehicle.status == status {
+                return Ok(None);
+            }
+            vehicle.status = status;
+            Ok(Some(ChangeType::StatusChanged))
+        })
+        .await?
+        .ok_or(RepoError::NotFound)
+    }
+
+    async fn delete_vehicles(
+        &self,
+        ids: &[VehicleId],
+    ) -> Result<(usize, Vec<VehicleId>), RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut missing = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            let removed = sqlx::query("DELETE FROM vehicles WHERE id = ?")
+                .bind(id.0)
+                .execute(&mut *tx)
+                .await?;
+            match removed.rows_affected() {
+                0 => missing.push(*id),
+                _ => deleted += 1,
+            }
+        }
+        tx.commit().await?;
+
+        Ok((deleted, missing))
+    }
+
+    async fn unassign_owner(&self, owner_id: OwnerId) -> Result<usize, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let mut query = QueryBuilder::new("SELECT * FROM vehicles WHERE owner_id = ");
+        query.push_bind(owner_id.0).push(" ORDER BY id");
+        let vehicles = fetch_vehicles(&mut query, &mut tx).await?;
+        let now = Utc::now();
+        let unassigned = vehicles.len();
+        for mut vehicle in vehicles {
+            vehicle.owner_id = None;
+            vehicle.updated_at = now;
+            vehicle.version += 1;
+            // Only the owner changed, so the VIN and plate checks can be skipped
+            save(&mut tx, &vehicle).await?;
+            self.record(&mut tx, &vehicle, ChangeType::Updated).await?;
+        }
+        tx.commit().await?;
+
+        Ok(unassigned)
+    }
+
+    async fn get_history(&self, id: VehicleId) -> Result<Option<Vec<VehicleRevision>>, RepoError> {
+        if !self.exists(id).await? {
+            return Ok(None);
+        }
+        let revisions: Vec<Json<VehicleRevision>> = sqlx::query_scalar(
+            "SELECT revision FROM vehicle_revisions WHERE vehicle_id = ? ORDER BY version DESC",
+        )
+        .bind(id.0)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(
+            revisions.into_iter().map(|revision| revision.0).collect(),
+        ))
+    }
+
+    async fn ping(&self) -> Result<(), RepoError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+use crate::{
+    features::{
+        owner::model::OwnerId,
+        vehicle::model::{
+            CreateVehicleRequest, EngineSpec, FuelType, VEHICLE_COLORS, Vehicle, VehicleId,
+            VehicleStatus, normalise_color, normalise_plate, normalise_tags, normalise_vin,
+        },
+    },
+    utils::{config::AppConfig, validator::ValidateWithConfig},
+};
+
+/// Settings built vehicles are validated under, read once rather than per vehicle
+static CONFIG: LazyLock<AppConfig> = LazyLock::new(AppConfig::default);
+
+/// Manufacturers and models `VehicleBuilder::random` picks from
+const RANDOM_MODELS: &[(&str, &[&str])] = &[
+    ("Toyota", &["Corolla", "Yaris", "RAV4", "Prius"]),
+    ("Volkswagen", &["Golf", "Polo", "Passat", "Tiguan"]),
+    ("Ford", &["Focus", "Fiesta", "Mustang", "Kuga"]),
+    ("BMW", &["320i", "530e", "iX3"]),
+    ("Tesla", &["Model 3", "Model Y"]),
+];
+
+/// Fluent construction of valid vehicles for tests and seeding, so new fields only need a
+/// default here instead of touching every literal
+#[derive(Clone, Debug)]
+pub struct VehicleBuilder {
+    id: Option<VehicleId>,
+    request: CreateVehicleRequest,
+    status: VehicleStatus,
+}
+
+impl Vehicle {
+    pub fn builder() -> VehicleBuilder {
+        VehicleBuilder::default()
+    }
+}
+
+impl Default for VehicleBuilder {
+    fn default() -> Self {
+        Self {
+            id: None,
+            request: CreateVehicleRequest {
+                manufacturer: "Toyota".to_string(),
+                model: "Corolla".to_string(),
+                year: 2020,
+                vin: None,
+                mileage_km: None,
+                color: None,
+                license_plate: None,
+                price: None,
+                fuel_type: None,
+                tags: Vec::new(),
+                metadata: Default::default(),
+                engine: None,
+                owner_id: None,
+                first_registered: None,
+            },
+            status: VehicleStatus::default(),
+        }
+    }
+}
+
+impl VehicleBuilder {
+    /// A valid vehicle derived from `seed` alone, so seeded data and benchmarks are reproducible;
+    /// VIN and plate are left empty as they have to be unique
+    pub fn random(seed: u64) -> Self {
+        let mut rng = SplitMix64(seed);
+        let (manufacturer, models) = RANDOM_MODELS[rng.below(RANDOM_MODELS.len())];
+        let model = models[rng.below(models.len())];
+        Self::default()
+            .id(VehicleId(Uuid::from_u64_pair(0, seed)))
+            .manufacturer(manufacturer)
+            .model(model)
+            .year(1990 + rng.below(35) as u16)
+            .mileage_km(rng.below(300_000) as u32)
+            .color(VEHICLE_COLORS[rng.below(VEHICLE_COLORS.len())])
+            .fuel_type(FuelType::ALL[rng.below(FuelType::ALL.len())])
+            .price(Decimal::new(500_000 + rng.below(9_500_000) as i64, 2))
+    }
+
+    pub fn id(mut self, id: VehicleId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn manufacturer(mut self, manufacturer: impl Into<String>) -> Self {
+        self.request.manufacturer = manufacturer.into();
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.request.model = model.into();
+        self
+    }
+
+    pub fn year(mut self, year: u16) -> Self {
+        self.request.year = year;
+        self
+    }
+
+    pub fn vin(mut self, vin: impl Into<String>) -> Self {
+        self.request.vin = Some(vin.into());
+        self
+    }
+
+    pub fn mileage_km(mut self, mileage_km: u32) -> Self {
+        self.request.mileage_km = Some(mileage_km);
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.request.color = Some(color.into());
+        self
+    }
+
+    pub fn license_plate(mut self, license_plate: impl Into<String>) -> Self {
+        self.request.license_plate = Some(license_plate.into());
+        self
+    }
+
+    pub fn price(mut self, price: Decimal) -> Self {
+        self.request.price = Some(price);
+        self
+    }
+
+    pub fn fuel_type(mut self, fuel_type: FuelType) -> Self {
+        self.request.fuel_type = Some(fuel_type);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.request.tags.push(tag.into());
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.request.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn engine(mut self, engine: EngineSpec) -> Self {
+        self.request.engine = Some(engine);
+        self
+    }
+
+    pub fn owner_id(mut self, owner_id: OwnerId) -> Self {
+        self.request.owner_id = Some(owner_id);
+        self
+    }
+
+    pub fn first_registered(mut self, date: NaiveDate) -> Self {
+        self.request.first_registered = Some(date);
+        self
+    }
+
+    pub fn status(mut self, status: VehicleStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The create request, validated under the default config, e.g. to seed a repo through
+    /// `post_vehicle`
+    pub fn request(self) -> CreateVehicleRequest {
+        if let Err(errors) = self.request.validate_with_config(&CONFIG) {
+            panic!("VehicleBuilder produced an invalid vehicle: {errors}");
+        }
+        self.request
+    }
+
+    /// A stored vehicle at version 1, normalised the way the repo would store it
+    pub fn build(self) -> Vehicle {
+        let (id, status) = (self.id.unwrap_or_else(VehicleId::generate), self.status);
+        let request = self.request();
+        let now = Utc::now();
+        Vehicle {
+            id,
+            manufacturer: request.manufacturer,
+            manufacturer_raw: None,
+            model: request.model,
+            year: request.year,
+            vin: normalise_vin(request.vin),
+            mileage_km: request.mileage_km,
+            color: normalise_color(request.color),
+            license_plate: normalise_plate(request.license_plate),
+            price: request.price,
+            fuel_type: request.fuel_type,
+            tags: normalise_tags(request.tags),
+            metadata: request.metadata,
+            engine: request.engine,
+            owner_id: request.owner_id,
+            first_registered: request.first_registered,
+            status,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            expires_at: None,
+            version: 1,
+        }
+    }
+}
+
+/// Tiny deterministic generator, enough to spread seeded vehicles without pulling in `rand`
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform enough in `0..bound` for test data
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
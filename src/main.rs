@@ -1,21 +1,85 @@
 mod features;
 mod middlewares;
 mod routes;
+#[cfg(test)]
+mod test_app;
 mod utils;
 
+#[cfg(feature = "mongodb")]
+use crate::features::vehicle::mongo_repo::MongoVehicleRepo;
+#[cfg(feature = "postgres")]
+use crate::features::vehicle::postgres_repo::PostgresVehicleRepo;
+#[cfg(feature = "redis")]
+use crate::features::vehicle::redis_repo::RedisVehicleRepo;
+#[cfg(feature = "sqlite")]
+use crate::features::vehicle::sqlite_repo::SqliteVehicleRepo;
 use crate::{
-    features::vehicle::repo::InMemoryVehicleRepo,
-    middlewares::tracing::{metrics_middleware, tracing_middleware},
+    features::{
+        owner::repo::InMemoryOwnerRepo,
+        vehicle::{
+            cached_repo::CachedRepo,
+            dashmap_repo::DashMapVehicleRepo,
+            fallback_repo::FallbackRepo,
+            metered_repo::MeteredRepo,
+            persistent_repo::PersistentVehicleRepo,
+            repo::{InMemoryVehicleRepo, VehicleRepo},
+            seed::{SeedSource, seed_vehicles},
+        },
+    },
+    middlewares::{
+        degraded::degraded_middleware,
+        method_not_allowed::method_not_allowed_middleware,
+        panic::{install_panic_hook, panic_response},
+        problem::problem_middleware,
+        tracing::{metrics_middleware, tracing_middleware},
+    },
     routes::routes,
-    utils::opentelemetry::init_telemetry,
+    utils::{config::AppConfig, metrics::init_metrics, opentelemetry::init_telemetry},
 };
-use axum::middleware;
-use tokio::net::TcpListener;
+use axum::{
+    ServiceExt,
+    extract::{DefaultBodyLimit, FromRef, Request},
+    middleware,
+    response::Response,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::{convert::Infallible, sync::Arc, time::Duration};
+use tokio::{net::TcpListener, sync::watch};
+use tower::{Layer, Service};
+use tower_http::catch_panic::CatchPanicLayer;
 use tracing::{error, info, warn};
 
 #[derive(Clone)]
-pub struct AppState<T> {
-    vehicle_repo: T,
+pub struct AppState {
+    vehicle_repo: Arc<dyn VehicleRepo>,
+    owner_repo: InMemoryOwnerRepo,
+    config: AppConfig,
+    /// Renders `/metrics`; `None` if the recorder couldn't be installed
+    metrics: Option<PrometheusHandle>,
+}
+
+impl FromRef<AppState> for AppConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+/// The routes behind every middleware layer, as served
+fn app(
+    state: AppState,
+) -> impl Service<Request, Response = Response, Error = Infallible, Future: Send> + Clone + Send + 'static
+{
+    let max_body_bytes = state.config.max_body_bytes;
+    let app = routes()
+        .layer(CatchPanicLayer::custom(panic_response))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn(problem_middleware))
+        .layer(middleware::from_fn(degraded_middleware))
+        .layer(middleware::from_fn(tracing_middleware))
+        .layer(middleware::from_fn(metrics_middleware))
+        .with_state(state);
+    // Wraps the whole router (not each route) so it sees the Allow header axum adds on 405
+    middleware::from_fn(method_not_allowed_middleware).layer(app)
 }
 
 #[tokio::main]
@@ -32,14 +96,205 @@ async fn main() {
             None
         }
     };
+    install_panic_hook();
 
-    let vehicle_repo = InMemoryVehicleRepo::default();
+    let metrics = match init_metrics() {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            warn!(
+                "Failed to install the metrics recorder, /metrics is unavailable: {}",
+                e
+            );
+            None
+        }
+    };
 
-    // Build the application with middleware layers
-    let app = routes()
-        .layer(middleware::from_fn(tracing_middleware))
-        .layer(middleware::from_fn(metrics_middleware))
-        .with_state(AppState { vehicle_repo });
+    let config = AppConfig::default();
+    // The snapshotting repo and its interval, when snapshots are written in the background
+    let mut snapshotter = None;
+    // The in-memory repo and how often to sweep it, when vehicles expire
+    let mut sweeper = None;
+    // The repo owning the append log and how often to sync it, when there is one
+    let mut log_syncer = None;
+    let vehicle_repo: Arc<dyn VehicleRepo> = match config.repo_backend.as_str() {
+        "memory" => {
+            let repo = InMemoryVehicleRepo::with_history_limit(config.history_limit)
+                .with_manufacturer_aliases(config.manufacturer_aliases.clone())
+                .with_capacity(config.max_vehicles, config.capacity_policy)
+                .with_ttl(config.vehicle_ttl_seconds.map(Duration::from_secs));
+            if let Some(seconds) = config.vehicle_ttl_seconds {
+                // Reads already skip expired vehicles, so the sweep only has to free memory
+                sweeper = Some((repo.clone(), Duration::from_secs(seconds.min(60))));
+            }
+            match config.data_file.as_deref() {
+                None => Arc::new(repo),
+                Some(data_file) => {
+                    let mut repo = match PersistentVehicleRepo::open(repo, data_file).await {
+                        Ok(repo) => repo,
+                        Err(e) => {
+                            error!("Failed to load vehicle snapshot {}: {}", data_file, e);
+                            std::process::exit(1);
+                        }
+                    };
+                    if config.write_ahead_log {
+                        repo = match repo.with_append_log(config.wal_segment_bytes).await {
+                            Ok(repo) => repo,
+                            Err(e) => {
+                                error!("Failed to replay the append log of {}: {}", data_file, e);
+                                std::process::exit(1);
+                            }
+                        };
+                    }
+                    if config.snapshot_interval_seconds.is_some() {
+                        repo = repo.with_deferred_writes();
+                    }
+                    let repo = Arc::new(repo);
+                    if let Some(seconds) = config.snapshot_interval_seconds {
+                        snapshotter = Some((repo.clone(), Duration::from_secs(seconds)));
+                    }
+                    if config.write_ahead_log {
+                        let interval = Duration::from_millis(config.wal_sync_interval_ms);
+                        log_syncer = Some((repo.clone(), interval));
+                    }
+                    repo
+                }
+            }
+        }
+        "dashmap" => Arc::new(
+            DashMapVehicleRepo::with_history_limit(config.history_limit)
+                .with_shards(config.repo_shards)
+                .with_manufacturer_aliases(config.manufacturer_aliases.clone())
+                .with_capacity(config.max_vehicles, config.capacity_policy),
+        ),
+        #[cfg(feature = "postgres")]
+        "postgres" => {
+            let Some(database_url) = config.database_url.as_deref() else {
+                error!("REPO_BACKEND=postgres requires DATABASE_URL");
+                std::process::exit(1);
+            };
+            match PostgresVehicleRepo::connect(database_url, config.history_limit).await {
+                Ok(repo) => {
+                    Arc::new(repo.with_manufacturer_aliases(config.manufacturer_aliases.clone()))
+                }
+                Err(e) => {
+                    error!("Failed to connect to Postgres: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            match SqliteVehicleRepo::connect(&config.sqlite_path, config.history_limit).await {
+                Ok(repo) => {
+                    Arc::new(repo.with_manufacturer_aliases(config.manufacturer_aliases.clone()))
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to open SQLite database {}: {}",
+                        config.sqlite_path, e
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        #[cfg(feature = "redis")]
+        "redis" => {
+            let Some(redis_url) = config.redis_url.as_deref() else {
+                error!("REPO_BACKEND=redis requires REDIS_URL");
+                std::process::exit(1);
+            };
+            match RedisVehicleRepo::connect(redis_url, config.history_limit).await {
+                Ok(repo) => Arc::new(
+                    repo.with_ttl(config.vehicle_ttl_seconds.map(Duration::from_secs))
+                        .with_manufacturer_aliases(config.manufacturer_aliases.clone()),
+                ),
+                Err(e) => {
+                    error!("Failed to connect to Redis: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        #[cfg(feature = "mongodb")]
+        "mongodb" => {
+            let Some(mongodb_uri) = config.mongodb_uri.as_deref() else {
+                error!("REPO_BACKEND=mongodb requires MONGODB_URI");
+                std::process::exit(1);
+            };
+            match MongoVehicleRepo::connect(mongodb_uri, config.history_limit).await {
+                Ok(repo) => {
+                    Arc::new(repo.with_manufacturer_aliases(config.manufacturer_aliases.clone()))
+                }
+                Err(e) => {
+                    error!("Failed to connect to MongoDB: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        other => {
+            error!(
+                "Unknown REPO_BACKEND '{}', expected 'memory', 'dashmap', or with the matching feature 'postgres', 'sqlite', 'redis' or 'mongodb'",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let vehicle_repo: Arc<dyn VehicleRepo> = if config.read_fallback {
+        let repo = FallbackRepo::new(
+            vehicle_repo,
+            InMemoryVehicleRepo::with_history_limit(0)
+                .with_manufacturer_aliases(config.manufacturer_aliases.clone()),
+        );
+        // A backend that is down on startup leaves the copy to fill up from later writes
+        match repo.warm().await {
+            Ok(copied) => info!("Copied {} vehicles into the read fallback", copied),
+            Err(e) => warn!(
+                "Failed to fill the read fallback, starting it empty: {:?}",
+                e
+            ),
+        }
+        Arc::new(repo)
+    } else {
+        vehicle_repo
+    };
+    let vehicle_repo: Arc<dyn VehicleRepo> = match config.vehicle_cache_entries {
+        None => vehicle_repo,
+        Some(entries) => Arc::new(
+            CachedRepo::new(
+                vehicle_repo,
+                entries,
+                Duration::from_secs(config.vehicle_cache_ttl_seconds),
+            )
+            .with_list_ttl(
+                config
+                    .vehicle_cache_list_ttl_seconds
+                    .map(Duration::from_secs),
+            ),
+        ),
+    };
+    let vehicle_repo: Arc<dyn VehicleRepo> = Arc::new(MeteredRepo::new(vehicle_repo));
+
+    // Seed before the listener exists, so no request sees a half-loaded store
+    match SeedSource::resolve(config.seed_file.as_deref(), config.seed.as_deref()) {
+        Ok(None) => {}
+        Ok(Some(source)) => {
+            if let Err(e) = seed_vehicles(vehicle_repo.as_ref(), &source, &config).await {
+                error!("Failed to seed vehicles: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            error!("Invalid seed configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let app = app(AppState {
+        vehicle_repo: vehicle_repo.clone(),
+        owner_repo: InMemoryOwnerRepo::default(),
+        config,
+        metrics,
+    });
 
     let listener = match TcpListener::bind("0.0.0.0:8000").await {
         Ok(listener) => listener,
@@ -55,9 +310,18 @@ async fn main() {
     );
     info!("Health check available at: http://0.0.0.0:8000/health");
     info!("Vehicles API available at: http://0.0.0.0:8000/api/v1/vehicles");
+    info!("Owners API available at: http://0.0.0.0:8000/api/v1/owners");
 
     // Set up graceful shutdown
-    let server = axum::serve(listener, app);
+    let server = axum::serve(listener, ServiceExt::<Request>::into_make_service(app));
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let sweeps = sweeper.map(|(repo, interval)| {
+        tokio::spawn(repo.run_expiry_sweeps(interval, shutdown_rx.clone()))
+    });
+    let log_syncs = log_syncer
+        .map(|(repo, interval)| tokio::spawn(repo.run_log_sync(interval, shutdown_rx.clone())));
+    let snapshots =
+        snapshotter.map(|(repo, interval)| tokio::spawn(repo.run_snapshots(interval, shutdown_rx)));
 
     tokio::select! {
         result = server => {
@@ -70,6 +334,29 @@ async fn main() {
         }
     }
 
+    // The snapshot task stops on the same signal as the server, writing a final snapshot
+    let _ = shutdown_tx.send(true);
+    if let Some(snapshots) = snapshots
+        && let Err(e) = snapshots.await
+    {
+        error!("Snapshot task failed: {}", e);
+    }
+    if let Some(sweeps) = sweeps
+        && let Err(e) = sweeps.await
+    {
+        error!("Expiry sweep task failed: {}", e);
+    }
+    if let Some(log_syncs) = log_syncs
+        && let Err(e) = log_syncs.await
+    {
+        error!("Append log sync task failed: {}", e);
+    }
+
+    // Persist whatever the last requests changed before the process goes away
+    if let Err(e) = vehicle_repo.flush().await {
+        error!("Failed to flush vehicle store: {:?}", e);
+    }
+
     // Cleanup telemetry resources
     if let Some(guard) = _telemetry_guard {
         info!("Shutting down telemetry...");
@@ -0,0 +1,20 @@
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+
+use crate::features::vehicle::fallback_repo::track_degraded;
+
+/// Marks a response built from a fallback copy rather than the primary store
+#[derive(Clone, Copy, Debug)]
+pub struct Degraded;
+
+/// Flags responses whose reads fell back to a secondary store, with a `Degraded` extension
+/// for outer layers and an `x-degraded: true` header for clients
+pub async fn degraded_middleware(request: Request, next: Next) -> Response {
+    let (mut response, degraded) = track_degraded(next.run(request)).await;
+    if degraded {
+        response.extensions_mut().insert(Degraded);
+        response
+            .headers_mut()
+            .insert("x-degraded", HeaderValue::from_static("true"));
+    }
+    response
+}
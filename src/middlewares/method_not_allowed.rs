@@ -0,0 +1,132 @@
+use axum::{
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::problem::{ErrorCode, Problem};
+
+/// Replace axum's empty 405 body with a problem listing the methods from the `Allow` header
+pub async fn method_not_allowed_middleware(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+
+    let (parts, _) = response.into_parts();
+    let allowed: Vec<&str> = parts
+        .headers
+        .get(header::ALLOW)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|method| !method.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Runs outside the tracing middleware, so the request id comes from its response header
+    let mut problem = Problem::from_code(ErrorCode::MethodNotAllowed)
+        .with_detail(format!("{} only allows {}", path, allowed.join(", ")))
+        .with_instance(path)
+        .with("allowed", &allowed);
+    if let Some(request_id) = parts
+        .headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+    {
+        problem = problem.with("request_id", request_id);
+    }
+
+    // Keep the original headers (Allow, x-request-id, ...) and only swap the body
+    let mut response = problem.into_response();
+    for (name, value) in &parts.headers {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{features::vehicle::mock_repo::MockVehicleRepo, test_app::TestApp};
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{Method, StatusCode, header},
+    };
+    use std::sync::Arc;
+
+    const ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    #[tokio::test]
+    async fn every_route_answers_an_unsupported_verb_with_its_allowed_methods() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+        let cases = [
+            (Method::PUT, "/health".to_string(), "GET,HEAD"),
+            (Method::POST, "/health/live".to_string(), "GET,HEAD"),
+            (Method::DELETE, "/health/ready".to_string(), "GET,HEAD"),
+            (
+                Method::PUT,
+                "/api/v1/vehicles".to_string(),
+                "POST,GET,HEAD,DELETE",
+            ),
+            (Method::GET, "/api/v1/vehicles/batch".to_string(), "POST"),
+            (
+                Method::DELETE,
+                "/api/v1/vehicles/stats".to_string(),
+                "GET,HEAD",
+            ),
+            (
+                Method::PUT,
+                "/api/v1/vehicles/lookup".to_string(),
+                "GET,HEAD,POST",
+            ),
+            (
+                Method::POST,
+                format!("/api/v1/vehicles/{ID}"),
+                "GET,HEAD,PUT,PATCH,DELETE",
+            ),
+            (
+                Method::GET,
+                format!("/api/v1/vehicles/{ID}/restore"),
+                "POST",
+            ),
+            (Method::POST, format!("/api/v1/vehicles/{ID}/status"), "PUT"),
+        ];
+
+        for (method, uri, allow) in cases {
+            let request = Request::builder()
+                .method(&method)
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+
+            let response = app.send(request).await;
+
+            assert_eq!(
+                response.status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {uri}"
+            );
+            assert_eq!(response.headers[header::ALLOW], allow, "{method} {uri}");
+            assert_eq!(
+                response.headers[header::CONTENT_TYPE],
+                "application/problem+json"
+            );
+            let problem = response.json();
+            assert_eq!(problem["code"], "method_not_allowed", "{method} {uri}");
+            let allowed: Vec<&str> = allow.split(',').collect();
+            assert_eq!(
+                problem["allowed"],
+                serde_json::json!(allowed),
+                "{method} {uri}"
+            );
+            assert_eq!(problem["instance"], uri);
+        }
+    }
+}
@@ -1 +1,5 @@
+pub mod degraded;
+pub mod method_not_allowed;
+pub mod panic;
+pub mod problem;
 pub mod tracing;
@@ -0,0 +1,72 @@
+use std::{any::Any, backtrace::Backtrace, panic};
+
+use anyhow::anyhow;
+use axum::response::{IntoResponse, Response};
+use metrics::counter;
+use tracing::error;
+
+use crate::utils::validator::ServerError;
+
+/// Route panics through tracing with their backtrace, so they land in the JSON logs inside the
+/// span of the request that panicked rather than as plain text on stderr
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().map(ToString::to_string).unwrap_or_default();
+        error!(
+            panic = payload_message(info.payload()),
+            location,
+            backtrace = %Backtrace::force_capture(),
+            "Panicked"
+        );
+    }));
+}
+
+/// Responder for `CatchPanicLayer`: the hook has already logged the panic, the client gets a
+/// `500` problem instead of a dropped connection and the server keeps serving
+pub fn panic_response(payload: Box<dyn Any + Send + 'static>) -> Response {
+    counter!("panics_total").increment(1);
+    ServerError::Internal(anyhow!("handler panicked: {}", payload_message(&*payload)))
+        .into_response()
+}
+
+/// `panic!` payloads are a `&str` or a `String` unless a value was passed to `panic_any`
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{features::vehicle::mock_repo::MockVehicleRepo, test_app::TestApp};
+    use axum::http::{StatusCode, header};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn a_panicking_handler_answers_a_500_problem_and_the_app_keeps_serving() {
+        let app = TestApp::with_repo(Arc::new(MockVehicleRepo::default()));
+
+        let response = app.get("/test/panic").await;
+
+        assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let problem = response.json();
+        assert_eq!(problem["code"], "internal_error");
+        assert_eq!(problem["instance"], "/test/panic");
+        assert_eq!(
+            problem["request_id"],
+            response.headers["x-request-id"].to_str().unwrap()
+        );
+        assert!(
+            !problem.to_string().contains("test handler panicked"),
+            "the panic message stays in the logs"
+        );
+
+        assert_eq!(app.get("/health").await.status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,47 @@
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::problem::Problem;
+
+/// Largest plain-text error body read back into a problem's `detail`
+const DETAIL_LIMIT: usize = 16 * 1024;
+
+/// Turn the plain-text or empty error responses that never pass through `ServerError`, such as
+/// axum's own extractor rejections for a malformed id or query string, into problem details.
+/// Error responses with a JSON body, e.g. a refused batch listing its results, are left alone
+pub async fn problem_middleware(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let plain = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.starts_with("text/plain"));
+    if !plain || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let detail = to_bytes(body, DETAIL_LIMIT)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+    let mut problem = Problem::new(status);
+    if !detail.is_empty() {
+        problem = problem.with_detail(detail);
+    }
+
+    let mut response = problem.into_response();
+    for (name, value) in &parts.headers {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+    *response.extensions_mut() = parts.extensions;
+    response
+}
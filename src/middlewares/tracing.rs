@@ -1,10 +1,50 @@
-use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use axum::{
+    extract::Request,
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
 use std::time::Instant;
 use tracing::{Instrument, info_span};
 use uuid::Uuid;
 
+use crate::utils::{i18n::Locale, response::RequestContext};
+
+/// Why a request failed, left on the response by `ServerError` so the request's span records
+/// it as `error` next to the status code
+#[derive(Clone, Debug)]
+pub struct FailedWith(pub String);
+
+/// The request being handled, for responses built without access to it
+struct RequestScope {
+    id: String,
+    path: String,
+    locale: Locale,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestScope;
+}
+
+/// The id the tracing middleware gave the current request, if it runs under one
+pub fn current_request_id() -> Option<String> {
+    REQUEST.try_with(|request| request.id.clone()).ok()
+}
+
+/// The path of the current request, if it runs under the tracing middleware
+pub fn current_request_path() -> Option<String> {
+    REQUEST.try_with(|request| request.path.clone()).ok()
+}
+
+/// The language picked from the current request's `Accept-Language`, English outside one
+pub fn current_locale() -> Locale {
+    REQUEST
+        .try_with(|request| request.locale)
+        .unwrap_or_default()
+}
+
 /// Tracing middleware that adds request tracking and timing
-pub async fn tracing_middleware(request: Request, next: Next) -> Response {
+pub async fn tracing_middleware(mut request: Request, next: Next) -> Response {
     let start = Instant::now();
     let method = request.method().clone();
     let uri = request.uri().clone();
@@ -17,6 +57,12 @@ pub async fn tracing_middleware(request: Request, next: Next) -> Response {
         .map(|s| s.to_string())
         .unwrap_or_else(|| Uuid::new_v4().to_string());
 
+    // Expose the request ID and start time to handlers
+    request.extensions_mut().insert(RequestContext {
+        request_id: request_id.clone(),
+        started_at: start,
+    });
+
     // Create span for this request
     let span = info_span!(
         "http_request",
@@ -25,10 +71,21 @@ pub async fn tracing_middleware(request: Request, next: Next) -> Response {
         request_id = %request_id,
         status_code = tracing::field::Empty,
         duration_ms = tracing::field::Empty,
+        error = tracing::field::Empty,
     );
 
+    let scope = RequestScope {
+        id: request_id.clone(),
+        path: uri.path().to_string(),
+        locale: request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Locale::from_accept_language)
+            .unwrap_or_default(),
+    };
     async move {
-        let response = next.run(request).await;
+        let response = REQUEST.scope(scope, next.run(request)).await;
 
         let duration = start.elapsed();
         let status_code = response.status().as_u16();
@@ -36,6 +93,9 @@ pub async fn tracing_middleware(request: Request, next: Next) -> Response {
         // Record span fields within the span context
         tracing::Span::current().record("status_code", status_code);
         tracing::Span::current().record("duration_ms", duration.as_millis() as u64);
+        if let Some(FailedWith(error)) = response.extensions().get() {
+            tracing::Span::current().record("error", error.as_str());
+        }
 
         // Add request ID to response headers
         let mut response = response;
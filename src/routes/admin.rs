@@ -0,0 +1,19 @@
+use crate::{
+    AppState,
+    features::admin::{
+        handler::{export_dump, import_dump},
+        model::DUMP_BODY_LIMIT,
+    },
+};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    routing::{get, post},
+};
+
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/export", get(export_dump))
+        .route("/import", post(import_dump))
+        .layer(DefaultBodyLimit::max(DUMP_BODY_LIMIT))
+}
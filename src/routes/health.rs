@@ -1,6 +1,12 @@
-use axum::{http::StatusCode, Json};
+use axum::{extract::State, http::StatusCode, Json};
 use serde_json::{json, Value};
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::AppState;
+
+/// Longest the readiness probe waits on the vehicle store before reporting it unhealthy
+const REPO_HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Health check endpoint for monitoring and load balancer probes
 pub async fn health_check() -> Result<Json<Value>, StatusCode> {
@@ -15,25 +21,83 @@ pub async fn health_check() -> Result<Json<Value>, StatusCode> {
 }
 
 /// Readiness check for Kubernetes readiness probes
-pub async fn readiness_check() -> Result<Json<Value>, StatusCode> {
+pub async fn readiness_check(
+    State(state): State<AppState>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
     info!("Readiness check requested");
     
-    // In a real application, you would check:
-    // - Database connectivity
-    // - External service dependencies
-    // - Required configuration presence
+    // Not ready while the vehicle store can't serve requests, e.g. the database is down or hung
+    let started = Instant::now();
+    let health = match tokio::time::timeout(REPO_HEALTH_TIMEOUT, state.vehicle_repo.health_check()).await {
+        Ok(Ok(health)) => health,
+        Ok(Err(e)) => {
+            warn!("Readiness check failed, vehicle store unhealthy: {:?}", e);
+            return Err(not_ready(format!("{:?}", e)));
+        }
+        Err(_) => {
+            warn!("Readiness check failed, vehicle store did not answer within {:?}", REPO_HEALTH_TIMEOUT);
+            return Err(not_ready(format!("no answer within {}ms", REPO_HEALTH_TIMEOUT.as_millis())));
+        }
+    };
+    
+    let mut checks = json!({
+        "database": {
+            "status": "ok",
+            "vehicles": health.vehicles,
+            "latency_ms": started.elapsed().as_millis() as u64
+        },
+        "external_apis": "ok"
+    });
+    // Reported but not failed on, so operators can spot persistence falling behind
+    if let Some(snapshot) = state.vehicle_repo.snapshot_status() {
+        checks["snapshot"] = json!({
+            "last_attempt": snapshot.last_attempt.map(|at| at.to_rfc3339()),
+            "outcome": match (&snapshot.last_attempt, &snapshot.last_error) {
+                (None, _) => "none",
+                (Some(_), None) => "ok",
+                (Some(_), Some(_)) => "failed"
+            },
+            "error": snapshot.last_error,
+            "pending_changes": snapshot.dirty
+        });
+    }
+    if let Some(capacity) = state.vehicle_repo.capacity() {
+        checks["capacity"] = json!({
+            "used": capacity.used,
+            "limit": capacity.limit,
+            "policy": capacity.policy.as_str()
+        });
+    }
+    if let Some(read_path) = state.vehicle_repo.read_path() {
+        checks["read_path"] = json!(read_path.as_str());
+    }
     
     Ok(Json(json!({
         "status": "ready",
         "service": "vehicle-manager-axum",
-        "checks": {
-            "database": "ok",
-            "external_apis": "ok"
-        },
+        "checks": checks,
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
 }
 
+/// The 503 answered while the vehicle store is unhealthy, saying why
+fn not_ready(error: String) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "status": "not_ready",
+            "service": "vehicle-manager-axum",
+            "checks": {
+                "database": {
+                    "status": "error",
+                    "error": error
+                }
+            },
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    )
+}
+
 /// Liveness probe for Kubernetes liveness checks
 pub async fn liveness_check() -> Result<Json<Value>, StatusCode> {
     info!("Liveness check requested");
@@ -43,4 +107,45 @@ pub async fn liveness_check() -> Result<Json<Value>, StatusCode> {
         "service": "vehicle-manager-axum",
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::{
+        features::vehicle::{mock_repo::MockVehicleRepo, repo::RepoError},
+        test_app::TestApp,
+    };
+    use axum::http::StatusCode;
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn readiness_answers_503_while_the_store_is_unavailable() {
+        let repo = Arc::new(
+            MockVehicleRepo::default().with_failure("health_check", RepoError::Unavailable),
+        );
+        let app = TestApp::with_repo(repo.clone());
+
+        let response = app.get("/health/ready").await;
+
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.json();
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"]["database"]["error"], "Unavailable");
+        assert_eq!(repo.calls_to("health_check").len(), 1);
+
+        assert_eq!(app.get("/health/ready").await.status, StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn readiness_gives_up_on_a_store_that_doesnt_answer_in_time() {
+        let repo = MockVehicleRepo::default().with_delay("health_check", Duration::from_secs(3));
+        let app = TestApp::with_repo(Arc::new(repo));
+
+        let response = app.get("/health/ready").await;
+
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.json()["checks"]["database"]["error"],
+            "no answer within 2000ms"
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::{AppState, utils::validator::ServerError};
+
+/// Prometheus scrape endpoint
+pub async fn metrics(State(state): State<AppState>) -> Result<Response, ServerError> {
+    match &state.metrics {
+        Some(handle) => Ok((
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response()),
+        None => Err(ServerError::Unavailable(
+            "the metrics recorder is not installed".to_string(),
+        )),
+    }
+}
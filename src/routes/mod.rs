@@ -1,24 +1,59 @@
+pub mod admin;
 pub mod health;
+pub mod metrics;
+pub mod owner;
 pub mod vehicle;
 
+use crate::utils::problem::{ErrorCode, Problem};
 use crate::{
     AppState,
-    features::vehicle::repo::InMemoryVehicleRepo,
     routes::{
+        admin::admin_routes,
         health::{health_check, liveness_check, readiness_check},
+        metrics::metrics,
+        owner::owner_routes,
         vehicle::vehicle_routes,
     },
 };
-use axum::{Router, routing::get};
+use axum::{
+    Router,
+    http::{Method, Uri},
+    routing::get,
+};
+use tracing::warn;
+
+/// Unknown paths anywhere in the tree, nested routers included, answer a `404` problem that
+/// names the path, so a typo can't be mistaken for a missing resource or a proxy error
+async fn not_found(method: Method, uri: Uri) -> Problem {
+    warn!("No route matches {} {}", method, uri.path());
+    Problem::from_code(ErrorCode::NotFound).with_detail(format!("no route matches {}", uri.path()))
+}
 
-pub fn routes() -> Router<AppState<InMemoryVehicleRepo>> {
+pub fn routes() -> Router<AppState> {
     let health_routes = Router::new()
         .route("/", get(health_check))
         .route("/live", get(liveness_check))
         .route("/ready", get(readiness_check));
 
-    Router::new()
+    let router = Router::new()
         .nest("/health", health_routes)
+        .route("/metrics", get(metrics))
         // API v1 routes
-        .nest("/api/v1", Router::new().nest("/vehicles", vehicle_routes()))
+        .nest(
+            "/api/v1",
+            Router::new()
+                .nest("/vehicles", vehicle_routes())
+                .nest("/owners", owner_routes())
+                .nest("/admin", admin_routes()),
+        )
+        .fallback(not_found);
+    #[cfg(test)]
+    let router = router.route("/test/panic", get(panicking));
+    router
+}
+
+/// Lets tests drive a handler panic through the whole stack
+#[cfg(test)]
+async fn panicking() -> &'static str {
+    panic!("test handler panicked")
 }
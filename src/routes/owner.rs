@@ -0,0 +1,14 @@
+use crate::{
+    AppState,
+    features::owner::handler::{
+        delete_owner, get_owner, get_owner_vehicles, get_owners, post_owner, put_owner,
+    },
+};
+use axum::{Router, routing::get};
+
+pub fn owner_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_owners).post(post_owner))
+        .route("/{id}", get(get_owner).put(put_owner).delete(delete_owner))
+        .route("/{id}/vehicles", get(get_owner_vehicles))
+}
@@ -1,17 +1,58 @@
 use crate::{
     AppState,
     features::vehicle::{
-        handler::{get_vehicle, get_vehicles, post_vehicle},
-        repo::InMemoryVehicleRepo,
+        handler::{
+            add_vehicle_tags, clone_vehicle, count_vehicles, delete_vehicle, delete_vehicles,
+            export_vehicles, get_vehicle, get_vehicle_by_plate, get_vehicle_by_vin,
+            get_vehicle_colors, get_vehicle_history, get_vehicle_manufacturers, get_vehicle_stats,
+            get_vehicles, head_vehicle, import_vehicles, lookup_vehicles_body,
+            lookup_vehicles_query, patch_vehicle, post_vehicle, post_vehicles_batch, put_vehicle,
+            put_vehicle_status, remove_vehicle_tag, restore_vehicle, search_vehicles,
+        },
+        model::IMPORT_BODY_LIMIT,
     },
 };
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
 };
 
-pub fn vehicle_routes() -> Router<AppState<InMemoryVehicleRepo>> {
+pub fn vehicle_routes() -> Router<AppState> {
     Router::new()
-        .route("/", post(post_vehicle).get(get_vehicles))
-        .route("/{id}", get(get_vehicle))
+        .route(
+            "/",
+            post(post_vehicle).get(get_vehicles).delete(delete_vehicles),
+        )
+        .route("/batch", post(post_vehicles_batch))
+        .route("/by-plate/{plate}", get(get_vehicle_by_plate))
+        .route("/by-vin/{vin}", get(get_vehicle_by_vin))
+        .route("/colors", get(get_vehicle_colors))
+        .route("/count", get(count_vehicles))
+        .route("/export", get(export_vehicles))
+        .route(
+            "/import",
+            post(import_vehicles).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .route(
+            "/lookup",
+            get(lookup_vehicles_query).post(lookup_vehicles_body),
+        )
+        .route("/manufacturers", get(get_vehicle_manufacturers))
+        .route("/search", get(search_vehicles))
+        .route("/stats", get(get_vehicle_stats))
+        .route(
+            "/{id}",
+            get(get_vehicle)
+                .head(head_vehicle)
+                .put(put_vehicle)
+                .patch(patch_vehicle)
+                .delete(delete_vehicle),
+        )
+        .route("/{id}/clone", post(clone_vehicle))
+        .route("/{id}/history", get(get_vehicle_history))
+        .route("/{id}/restore", post(restore_vehicle))
+        .route("/{id}/status", put(put_vehicle_status))
+        .route("/{id}/tags", post(add_vehicle_tags))
+        .route("/{id}/tags/{tag}", delete(remove_vehicle_tag))
 }
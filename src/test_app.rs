@@ -0,0 +1,90 @@
+//! Drives the app as served, middleware included, without binding a listener
+
+use crate::{
+    AppState, app,
+    features::{owner::repo::InMemoryOwnerRepo, vehicle::repo::VehicleRepo},
+    utils::config::AppConfig,
+};
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::Request,
+    http::{HeaderMap, Method, StatusCode, header},
+    response::Response,
+};
+use serde_json::Value;
+use std::{convert::Infallible, sync::Arc};
+use tower::{ServiceExt, util::BoxCloneService};
+
+pub struct TestApp {
+    service: BoxCloneService<Request, Response, Infallible>,
+}
+
+impl TestApp {
+    pub fn with_repo(vehicle_repo: Arc<dyn VehicleRepo>) -> Self {
+        Self::with_config(vehicle_repo, AppConfig::default())
+    }
+
+    pub fn with_config(vehicle_repo: Arc<dyn VehicleRepo>, config: AppConfig) -> Self {
+        let service = app(AppState {
+            vehicle_repo,
+            owner_repo: InMemoryOwnerRepo::default(),
+            config,
+            metrics: None,
+        });
+        Self {
+            service: BoxCloneService::new(service),
+        }
+    }
+
+    pub async fn send(&self, request: Request) -> TestResponse {
+        let response = self
+            .service
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap_or_else(|never| match never {});
+        let (parts, body) = response.into_parts();
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body: to_bytes(body, usize::MAX).await.expect("body is readable"),
+        }
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        let request = Request::builder()
+            .uri(uri)
+            .body(Body::empty())
+            .expect("request is valid");
+        self.send(request).await
+    }
+
+    /// `body` sent as `application/json`
+    pub async fn json(&self, method: Method, uri: &str, body: &Value) -> TestResponse {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request is valid");
+        self.send(request).await
+    }
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "{} body is not JSON ({e}): {}",
+                self.status,
+                String::from_utf8_lossy(&self.body)
+            )
+        })
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    features::vehicle::{
+        manufacturer::ManufacturerAliases, model::DEFAULT_MODEL_YEAR_OFFSET, repo::CapacityPolicy,
+    },
+    utils::validator::DEFAULT_PLATE_PATTERN,
+};
+use regex::Regex;
+use tracing::warn;
+
+/// Application settings read from the environment
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Require `If-Match` on updates instead of falling back to last-write-wins
+    pub strict_concurrency: bool,
+    /// Revisions kept per vehicle for the history endpoint; 0 disables history
+    pub history_limit: usize,
+    /// Drop unknown fields in request bodies instead of rejecting them
+    pub lenient_json: bool,
+    /// Ignore unknown query parameters instead of rejecting them
+    pub lenient_query: bool,
+    /// Log a redacted copy of request bodies `ValidatedPayload` rejects, at debug level
+    pub log_rejected_bodies: bool,
+    /// Fields masked in logged bodies: `vin` and `license_plate` plus the comma-separated
+    /// names in `REDACT_FIELDS`, matched in snake_case or camelCase
+    pub redact_fields: Vec<String>,
+    /// Most validation errors reported in one response, from `MAX_VALIDATION_ERRORS`; clients
+    /// fixing a payload rarely need more, and the response stays small
+    pub max_validation_errors: usize,
+    /// Largest request body accepted, in bytes; the CSV and dump imports allow more
+    pub max_body_bytes: usize,
+    /// Model years accepted past the current calendar year
+    pub model_year_offset: i32,
+    /// Format license plates must match once uppercased without whitespace, from `PLATE_REGEX`;
+    /// an invalid pattern is logged and the default used instead
+    pub plate_pattern: Regex,
+    /// Built-in manufacturer aliases plus the `alias=Canonical` pairs in `MANUFACTURER_ALIASES`
+    pub manufacturer_aliases: ManufacturerAliases,
+    /// Storage behind the vehicle API: `memory` (one lock around the store), `dashmap` or,
+    /// with the matching feature, `postgres`, `sqlite`, `redis` or `mongodb`
+    pub repo_backend: String,
+    /// Shards the `dashmap` backend splits its maps into, rounded up to a power of two;
+    /// defaults to the number of CPUs
+    pub repo_shards: usize,
+    /// Most vehicles the `memory` backend holds at once; unset or 0 means no limit
+    pub max_vehicles: Option<usize>,
+    /// What the `memory` backend does with a new vehicle once `MAX_VEHICLES` is reached
+    pub capacity_policy: CapacityPolicy,
+    /// Snapshot file that keeps the `memory` backend's vehicles across restarts
+    pub data_file: Option<String>,
+    /// Write the `DATA_FILE` snapshot at this interval, when there are changes, instead of
+    /// after every change
+    pub snapshot_interval_seconds: Option<u64>,
+    /// Also log every change next to `DATA_FILE` and replay the log on startup
+    pub write_ahead_log: bool,
+    /// How often the append log is synced to disk, bounding what a crash can lose
+    pub wal_sync_interval_ms: u64,
+    /// Size at which the append log moves on to a new segment file
+    pub wal_segment_bytes: u64,
+    /// JSON array of vehicles inserted on startup, overridden by `--seed <path>`
+    pub seed_file: Option<String>,
+    /// `demo` inserts the built-in demo fleet on startup when no seed file is given
+    pub seed: Option<String>,
+    /// Connection string for `REPO_BACKEND=postgres`
+    #[cfg(feature = "postgres")]
+    pub database_url: Option<String>,
+    /// Database file for `REPO_BACKEND=sqlite`, created if missing
+    #[cfg(feature = "sqlite")]
+    pub sqlite_path: String,
+    /// Server for `REPO_BACKEND=redis`
+    #[cfg(feature = "redis")]
+    pub redis_url: Option<String>,
+    /// Seconds after which a vehicle expires, counted from its creation under
+    /// `REPO_BACKEND=memory` and from its last write under `REPO_BACKEND=redis`; unset or 0
+    /// keeps vehicles until deleted
+    pub vehicle_ttl_seconds: Option<u64>,
+    /// Connection string for `REPO_BACKEND=mongodb`
+    #[cfg(feature = "mongodb")]
+    pub mongodb_uri: Option<String>,
+    /// Most vehicles kept in the read-through cache in front of any backend; unset or 0
+    /// disables the cache
+    pub vehicle_cache_entries: Option<usize>,
+    /// How long a cached vehicle is served before it is fetched again
+    pub vehicle_cache_ttl_seconds: u64,
+    /// How long the first page of a listing is served from the cache; unset or 0 always asks
+    /// the backend
+    pub vehicle_cache_list_ttl_seconds: Option<u64>,
+    /// Keep an in-memory copy of the backend's vehicles and answer reads from it while the
+    /// backend is unavailable
+    pub read_fallback: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            strict_concurrency: std::env::var("STRICT_CONCURRENCY")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+            history_limit: std::env::var("VEHICLE_HISTORY_LIMIT")
+                .map(|v| v.parse().unwrap_or(20))
+                .unwrap_or(20),
+            lenient_json: std::env::var("LENIENT_JSON")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+            lenient_query: std::env::var("LENIENT_QUERY")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+            log_rejected_bodies: std::env::var("LOG_REJECTED_BODIES")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+            redact_fields: ["vin", "license_plate"]
+                .into_iter()
+                .map(String::from)
+                .chain(
+                    std::env::var("REDACT_FIELDS")
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|field| !field.is_empty())
+                        .map(String::from),
+                )
+                .collect(),
+            max_validation_errors: std::env::var("MAX_VALIDATION_ERRORS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&limit| limit > 0)
+                .unwrap_or(100),
+            max_body_bytes: std::env::var("MAX_BODY_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&bytes| bytes > 0)
+                .unwrap_or(1024 * 1024),
+            model_year_offset: std::env::var("MODEL_YEAR_OFFSET")
+                .map(|v| v.parse().unwrap_or(DEFAULT_MODEL_YEAR_OFFSET))
+                .unwrap_or(DEFAULT_MODEL_YEAR_OFFSET),
+            plate_pattern: std::env::var("PLATE_REGEX")
+                .ok()
+                .and_then(|pattern| {
+                    Regex::new(&pattern)
+                        .inspect_err(|e| {
+                            warn!(
+                                "Invalid PLATE_REGEX '{}', using the default: {}",
+                                pattern, e
+                            )
+                        })
+                        .ok()
+                })
+                .unwrap_or_else(|| {
+                    Regex::new(DEFAULT_PLATE_PATTERN).expect("default plate pattern is valid")
+                }),
+            manufacturer_aliases: ManufacturerAliases::default()
+                .extend_from(&std::env::var("MANUFACTURER_ALIASES").unwrap_or_default()),
+            repo_backend: std::env::var("REPO_BACKEND").unwrap_or_else(|_| "memory".to_string()),
+            repo_shards: std::env::var("REPO_SHARDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&shards| shards > 0)
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from)),
+            max_vehicles: std::env::var("MAX_VEHICLES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&limit| limit > 0),
+            capacity_policy: std::env::var("CAPACITY_POLICY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            data_file: std::env::var("DATA_FILE").ok(),
+            snapshot_interval_seconds: std::env::var("SNAPSHOT_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&seconds| seconds > 0),
+            write_ahead_log: std::env::var("WRITE_AHEAD_LOG")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+            wal_sync_interval_ms: std::env::var("WAL_SYNC_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&ms| ms > 0)
+                .unwrap_or(1000),
+            wal_segment_bytes: std::env::var("WAL_SEGMENT_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&bytes| bytes > 0)
+                .unwrap_or(16 * 1024 * 1024),
+            seed_file: std::env::var("SEED_FILE").ok(),
+            seed: std::env::var("SEED").ok(),
+            #[cfg(feature = "postgres")]
+            database_url: std::env::var("DATABASE_URL").ok(),
+            #[cfg(feature = "sqlite")]
+            sqlite_path: std::env::var("SQLITE_PATH").unwrap_or_else(|_| "vehicles.db".to_string()),
+            #[cfg(feature = "redis")]
+            redis_url: std::env::var("REDIS_URL").ok(),
+            vehicle_ttl_seconds: std::env::var("VEHICLE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&seconds| seconds > 0),
+            #[cfg(feature = "mongodb")]
+            mongodb_uri: std::env::var("MONGODB_URI").ok(),
+            vehicle_cache_entries: std::env::var("VEHICLE_CACHE_ENTRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&entries| entries > 0),
+            vehicle_cache_ttl_seconds: std::env::var("VEHICLE_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&seconds| seconds > 0)
+                .unwrap_or(60),
+            vehicle_cache_list_ttl_seconds: std::env::var("VEHICLE_CACHE_LIST_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&seconds| seconds > 0),
+            read_fallback: std::env::var("READ_FALLBACK")
+                .map(|v| v.parse().unwrap_or(false))
+                .unwrap_or(false),
+        }
+    }
+}
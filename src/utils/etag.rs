@@ -0,0 +1,41 @@
+use axum::http::{HeaderMap, header};
+
+/// Check an `If-None-Match` header against the current entity tag using weak comparison
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').map(str::trim).any(|candidate| {
+                candidate == "*"
+                    || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+            })
+        })
+}
+
+/// Parsed `If-Match` precondition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IfMatch {
+    Any,
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(header::IF_MATCH)?.to_str().ok()?.trim();
+        if value == "*" {
+            return Some(Self::Any);
+        }
+        Some(Self::Tags(
+            value.split(',').map(|tag| tag.trim().to_string()).collect(),
+        ))
+    }
+
+    /// Strong comparison, so weak tags never satisfy the precondition
+    pub fn matches(&self, etag: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| !tag.starts_with("W/") && tag == etag),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use serde_json::{Map, Value};
+
+/// Languages validation messages are available in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    /// The supported language the client prefers most by `Accept-Language`, with `q` weights
+    /// honoured and regional variants like `de-AT` matched by language; anything else is `En`
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut ranges: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((quality, tag))
+            })
+            .collect();
+        // Stable, so ties keep the client's order
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges
+            .into_iter()
+            .find_map(|(_, tag)| Self::from_tag(tag))
+            .unwrap_or_default()
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split('-').next().unwrap_or_default();
+        if language.eq_ignore_ascii_case("en") {
+            Some(Locale::En)
+        } else if language.eq_ignore_ascii_case("de") {
+            Some(Locale::De)
+        } else {
+            None
+        }
+    }
+
+    /// The `Content-Language` value for messages in this locale
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+}
+
+/// Message templates by validation code; `{field}` is the failing field's name and any other
+/// `{param}` one of the error's params. A code can have several templates for different sets
+/// of params, the first whose params are all present is used
+fn templates(locale: Locale, code: &str) -> &'static [&'static str] {
+    match (locale, code) {
+        (Locale::En, "length") => &[
+            "{field} must be exactly {equal} characters",
+            "{field} must be between {min} and {max} characters",
+            "{field} must be at least {min} characters",
+            "{field} must be at most {max} characters",
+        ],
+        (Locale::En, "range") => &[
+            "{field} must be between {min} and {max}",
+            "{field} must be at least {min}",
+            "{field} must be at most {max}",
+        ],
+        (Locale::En, "email") => &["{field} must be a valid email address"],
+        (Locale::En, "model_year") => &["year cannot be later than the {cutoff} model year"],
+        (Locale::De, "length") => &[
+            "{field} muss genau {equal} Zeichen lang sein",
+            "{field} muss zwischen {min} und {max} Zeichen lang sein",
+            "{field} muss mindestens {min} Zeichen lang sein",
+            "{field} darf höchstens {max} Zeichen lang sein",
+        ],
+        (Locale::De, "range") => &[
+            "{field} muss zwischen {min} und {max} liegen",
+            "{field} muss mindestens {min} sein",
+            "{field} darf höchstens {max} sein",
+        ],
+        (Locale::De, "email") => &["{field} muss eine gültige E-Mail-Adresse sein"],
+        (Locale::De, "vin") => &[
+            "{field} ist keine gültige Fahrzeug-Identifizierungsnummer (17 Zeichen mit gültiger Prüfziffer)",
+        ],
+        (Locale::De, "license_plate") => &["{field} muss dem Muster {pattern} entsprechen"],
+        (Locale::De, "model_year") => &["year darf nicht nach dem Modelljahr {cutoff} liegen"],
+        (Locale::De, "year_range") => &["year_min darf nicht größer als year_max sein"],
+        (Locale::De, "first_registered") => &["{field} liegt außerhalb des zulässigen Zeitraums"],
+        (Locale::De, "price") => {
+            &["{field} muss ein nicht negativer Betrag mit höchstens 2 Nachkommastellen sein"]
+        }
+        (Locale::De, "tags") => &["{field} enthält zu viele oder ungültige Tags"],
+        (Locale::De, "color") => {
+            &["{field} ist keine bekannte Farbe (eigene Farben mit dem Präfix 'custom:')"]
+        }
+        (Locale::De, "metadata") => &["{field} überschreitet die Grenzen für Metadaten"],
+        (Locale::De, "sort") => &["{field} ist keine gültige Sortierung"],
+        (Locale::De, "fields") => &["{field} nennt unbekannte Felder"],
+        (Locale::De, "pagination_mode") => {
+            &["cursor-Paginierung lässt sich weder mit offset noch mit sort kombinieren"]
+        }
+        (Locale::De, "uuid") => &["{field} enthält ungültige UUIDs"],
+        (Locale::De, "empty_patch") => &["der Patch muss mindestens ein Feld ändern"],
+        _ => &[],
+    }
+}
+
+/// `detail` of the validation-failed problem
+pub fn validation_failed(locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => "the request has fields that failed validation",
+        Locale::De => "die Anfrage enthält Felder, die die Validierung nicht bestanden haben",
+    }
+}
+
+/// Render the message for `code` in `locale`, if the catalog has a template whose params are
+/// all given
+pub fn message(
+    locale: Locale,
+    code: &str,
+    field: &str,
+    params: &Map<String, Value>,
+) -> Option<String> {
+    templates(locale, code)
+        .iter()
+        .find_map(|template| render(template, field, params))
+}
+
+fn render(template: &str, field: &str, params: &Map<String, Value>) -> Option<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        rendered.push_str(&rest[..start]);
+        match &rest[start + 1..end] {
+            "field" => rendered.push_str(field),
+            name => match params.get(name)? {
+                Value::String(value) => rendered.push_str(value),
+                value => rendered.push_str(&value.to_string()),
+            },
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Some(rendered)
+}
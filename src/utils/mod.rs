@@ -1,2 +1,10 @@
+pub mod config;
+pub mod etag;
+pub mod i18n;
+pub mod metrics;
 pub mod opentelemetry;
+pub mod problem;
+pub mod redact;
+pub mod response;
+pub mod sorting;
 pub mod validator;
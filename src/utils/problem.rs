@@ -0,0 +1,236 @@
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::middlewares::tracing::{current_request_id, current_request_path};
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Stable, coarse error class carried as `code` by every problem, so clients can branch on it
+/// instead of on titles or details; `type` narrows it down where one code covers several cases
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    /// A path segment, such as a vehicle id, that doesn't parse
+    InvalidPathParameter,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    ValidationFailed,
+    PreconditionRequired,
+    RateLimited,
+    InternalError,
+    NotImplemented,
+    Unavailable,
+    InsufficientStorage,
+}
+
+impl ErrorCode {
+    pub const ALL: &[ErrorCode] = &[
+        ErrorCode::BadRequest,
+        ErrorCode::InvalidPathParameter,
+        ErrorCode::NotFound,
+        ErrorCode::MethodNotAllowed,
+        ErrorCode::Conflict,
+        ErrorCode::PreconditionFailed,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::UnsupportedMediaType,
+        ErrorCode::ValidationFailed,
+        ErrorCode::PreconditionRequired,
+        ErrorCode::RateLimited,
+        ErrorCode::InternalError,
+        ErrorCode::NotImplemented,
+        ErrorCode::Unavailable,
+        ErrorCode::InsufficientStorage,
+    ];
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::InvalidPathParameter => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::InsufficientStorage => StatusCode::INSUFFICIENT_STORAGE,
+        }
+    }
+
+    /// The first code with this status, so `bad_request` for any 400; statuses without one of
+    /// their own fall back to `bad_request` or `internal_error` by class, e.g. for errors
+    /// rewritten by the problem middleware
+    pub fn from_status(status: StatusCode) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|code| code.status() == status)
+            .unwrap_or(if status.is_server_error() {
+                ErrorCode::InternalError
+            } else {
+                ErrorCode::BadRequest
+            })
+    }
+}
+
+/// An RFC 7807 problem details body. `instance` is the request path and the request id is
+/// carried as the `request_id` extension member, both filled in from the tracing middleware;
+/// `request_id` is null for problems built outside it rather than a made-up id
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// `about:blank` when the status says it all, otherwise `/problems/<slug>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    /// A problem of type `about:blank`, titled after `status` and carrying its `ErrorCode`
+    pub fn new(status: StatusCode) -> Self {
+        let mut extensions = Map::new();
+        extensions.insert("request_id".to_string(), current_request_id().into());
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            code: ErrorCode::from_status(status),
+            detail: None,
+            instance: current_request_path(),
+            extensions,
+        }
+    }
+
+    /// A problem of type `about:blank` with `code`'s status
+    pub fn from_code(code: ErrorCode) -> Self {
+        Self {
+            code,
+            ..Self::new(code.status())
+        }
+    }
+
+    /// Name a more specific problem type than the status, e.g. `vin-conflict`
+    pub fn with_type(self, slug: &str) -> Self {
+        Self {
+            problem_type: format!("/problems/{slug}"),
+            ..self
+        }
+    }
+
+    pub fn with_detail(self, detail: impl Into<String>) -> Self {
+        Self {
+            detail: Some(detail.into()),
+            ..self
+        }
+    }
+
+    /// For responses built outside the tracing middleware, which can't fill it in
+    pub fn with_instance(self, instance: impl Into<String>) -> Self {
+        Self {
+            instance: Some(instance.into()),
+            ..self
+        }
+    }
+
+    /// Add an extension member; values that fail to serialise are left out
+    pub fn with(mut self, member: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.extensions.insert(member.to_string(), value);
+        }
+        self
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where each code sits in `ALL` and the status it answers with. There is no wildcard arm,
+    /// so a new code doesn't compile until it is listed here, and then fails until it is in `ALL`
+    fn expected(code: ErrorCode) -> (usize, StatusCode) {
+        match code {
+            ErrorCode::BadRequest => (0, StatusCode::BAD_REQUEST),
+            ErrorCode::InvalidPathParameter => (1, StatusCode::BAD_REQUEST),
+            ErrorCode::NotFound => (2, StatusCode::NOT_FOUND),
+            ErrorCode::MethodNotAllowed => (3, StatusCode::METHOD_NOT_ALLOWED),
+            ErrorCode::Conflict => (4, StatusCode::CONFLICT),
+            ErrorCode::PreconditionFailed => (5, StatusCode::PRECONDITION_FAILED),
+            ErrorCode::PayloadTooLarge => (6, StatusCode::PAYLOAD_TOO_LARGE),
+            ErrorCode::UnsupportedMediaType => (7, StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ErrorCode::ValidationFailed => (8, StatusCode::UNPROCESSABLE_ENTITY),
+            ErrorCode::PreconditionRequired => (9, StatusCode::PRECONDITION_REQUIRED),
+            ErrorCode::RateLimited => (10, StatusCode::TOO_MANY_REQUESTS),
+            ErrorCode::InternalError => (11, StatusCode::INTERNAL_SERVER_ERROR),
+            ErrorCode::NotImplemented => (12, StatusCode::NOT_IMPLEMENTED),
+            ErrorCode::Unavailable => (13, StatusCode::SERVICE_UNAVAILABLE),
+            ErrorCode::InsufficientStorage => (14, StatusCode::INSUFFICIENT_STORAGE),
+        }
+    }
+
+    #[test]
+    fn every_code_is_listed_once_with_its_status() {
+        assert_eq!(ErrorCode::ALL.len(), 15);
+        for (index, &code) in ErrorCode::ALL.iter().enumerate() {
+            assert_eq!(expected(code), (index, code.status()), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn statuses_map_back_to_the_first_code_answering_them() {
+        for &code in ErrorCode::ALL {
+            let found = ErrorCode::from_status(code.status());
+            assert_eq!(found.status(), code.status(), "{code:?}");
+            assert!(expected(found).0 <= expected(code).0, "{code:?}");
+        }
+        assert_eq!(
+            ErrorCode::from_status(StatusCode::IM_A_TEAPOT),
+            ErrorCode::BadRequest
+        );
+        assert_eq!(
+            ErrorCode::from_status(StatusCode::BAD_GATEWAY),
+            ErrorCode::InternalError
+        );
+    }
+
+    #[test]
+    fn problems_keep_the_code_they_are_built_from() {
+        for &code in ErrorCode::ALL {
+            let problem = Problem::from_code(code);
+            assert_eq!(problem.code, code);
+            assert_eq!(problem.status, code.status().as_u16());
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::sync::LazyLock;
+
+/// Longest copy of a rejected body written to the logs, in bytes
+pub const LOGGED_BODY_LIMIT: usize = 4 * 1024;
+
+/// Stands in for the value of a sensitive field
+const MASK: &str = "***";
+
+/// A quoted key, the colon after it and the value that follows, quoted or bare
+static KEY_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""([^"\\]*)"(\s*:\s*)("(?:[^"\\]|\\.)*"?|[^,}\]\s]*)"#)
+        .expect("key-value pattern is valid")
+});
+
+/// `license_plate`, `licensePlate` and `LICENSE_PLATE` all name the same field
+fn field_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// A body fit for the logs: every value under one of `fields`, at any depth, replaced by
+/// `"***"` and the result cut to `LOGGED_BODY_LIMIT`; the flag says whether it was cut.
+/// JSON is redacted field by field, form bodies pair by pair, and anything else that can't be
+/// parsed, such as malformed JSON, by masking what follows a quoted sensitive key
+pub fn redacted_body(body: &[u8], fields: &[String]) -> (String, bool) {
+    let keys: Vec<String> = fields.iter().map(|field| field_key(field)).collect();
+    let text = String::from_utf8_lossy(body);
+    let mut redacted = match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value, &keys);
+            value.to_string()
+        }
+        Err(_) if !text.trim_start().starts_with(['{', '[']) && text.contains('=') => {
+            redact_form(&text, &keys)
+        }
+        Err(_) => redact_text(&text, &keys),
+    };
+    let truncated = redacted.len() > LOGGED_BODY_LIMIT;
+    if truncated {
+        let mut end = LOGGED_BODY_LIMIT;
+        while !redacted.is_char_boundary(end) {
+            end -= 1;
+        }
+        redacted.truncate(end);
+    }
+    (redacted, truncated)
+}
+
+fn redact_value(value: &mut Value, keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                match keys.contains(&field_key(name)) {
+                    true => *value = Value::String(MASK.to_string()),
+                    false => redact_value(value, keys),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_value(item, keys)),
+        _ => {}
+    }
+}
+
+fn redact_form(body: &str, keys: &[String]) -> String {
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if keys.contains(&field_key(name)) => format!("{name}={MASK}"),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Best effort for text that isn't valid JSON: the value after `"key":`, quoted or bare, is
+/// masked whatever the key's spelling
+fn redact_text(body: &str, keys: &[String]) -> String {
+    KEY_VALUE
+        .replace_all(body, |captures: &Captures| {
+            match keys.contains(&field_key(&captures[1])) {
+                true => format!("\"{}\"{}\"{MASK}\"", &captures[1], &captures[2]),
+                false => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
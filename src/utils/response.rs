@@ -0,0 +1,120 @@
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::{convert::Infallible, time::Instant};
+
+/// Per-request data stashed in the request extensions by the tracing middleware
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub started_at: Instant,
+}
+
+impl RequestContext {
+    pub fn took_ms(&self) -> u64 {
+        self.started_at.elapsed().as_millis() as u64
+    }
+}
+
+impl<S> FromRequestParts<S> for RequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Fall back to the raw header when the middleware isn't installed (e.g. in isolation)
+        Ok(parts
+            .extensions
+            .get::<RequestContext>()
+            .cloned()
+            .unwrap_or_else(|| RequestContext {
+                request_id: parts
+                    .headers
+                    .get("x-request-id")
+                    .and_then(|h| h.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string(),
+                started_at: Instant::now(),
+            }))
+    }
+}
+
+/// Envelope for single-resource responses: `{ "data": {...} }`
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T> {
+    pub data: T,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn new(data: T) -> Self {
+        Self { data }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiResponse<T> {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ListMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    pub request_id: String,
+    pub took_ms: u64,
+}
+
+/// Envelope for collection responses: `{ "data": [...], "meta": {...} }`
+#[derive(Debug, Serialize)]
+pub struct ApiListResponse<T> {
+    pub data: Vec<T>,
+    pub meta: ListMeta,
+}
+
+impl<T> ApiListResponse<T> {
+    pub fn new(data: Vec<T>, ctx: &RequestContext) -> Self {
+        Self {
+            data,
+            meta: ListMeta {
+                request_id: ctx.request_id.clone(),
+                took_ms: ctx.took_ms(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn total(mut self, total: usize) -> Self {
+        self.meta.total = Some(total);
+        self
+    }
+
+    pub fn page(mut self, limit: usize, offset: usize) -> Self {
+        self.meta.limit = Some(limit);
+        self.meta.offset = Some(offset);
+        self
+    }
+
+    pub fn cursor(mut self, limit: usize, next_cursor: Option<String>) -> Self {
+        self.meta.limit = Some(limit);
+        self.meta.next_cursor = next_cursor;
+        self
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiListResponse<T> {
+    fn into_response(self) -> Response {
+        Json(self).into_response()
+    }
+}
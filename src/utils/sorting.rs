@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+
+use validator::ValidationError;
+
+/// Upper bound on the number of keys accepted in a single `sort` parameter
+pub const MAX_SORT_KEYS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+impl Direction {
+    pub fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            Direction::Ascending => ordering,
+            Direction::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// A resource field that can appear in a `sort` parameter
+pub trait SortField: Copy + PartialEq + Sized {
+    /// Comma-separated list of accepted names, used in error messages
+    const ALLOWED: &'static str;
+
+    fn parse(name: &str) -> Option<Self>;
+}
+
+/// A resource that can be ordered by its sort fields
+pub trait Sortable {
+    type Field: SortField;
+
+    fn compare_field(&self, other: &Self, field: Self::Field) -> Ordering;
+
+    /// Whether the item has no value for `field`; such items sort last in either direction
+    fn is_missing(&self, _field: Self::Field) -> bool {
+        false
+    }
+
+    /// Final ordering applied when every requested key compares equal
+    fn tie_break(&self, other: &Self) -> Ordering;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortKey<F> {
+    pub field: F,
+    pub direction: Direction,
+}
+
+impl<F: SortField> SortKey<F> {
+    /// Parse `field,-field,...` into ordered keys, prefix `-` meaning descending
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>, ValidationError> {
+        let mut keys: Vec<Self> = Vec::new();
+        for key in raw.split(',').map(str::trim) {
+            let (direction, name) = match key.strip_prefix('-') {
+                Some(name) => (Direction::Descending, name),
+                None => (Direction::Ascending, key),
+            };
+            let field = F::parse(name).ok_or_else(|| {
+                sort_error(format!(
+                    "unknown sort field '{name}', allowed values: {}",
+                    F::ALLOWED
+                ))
+            })?;
+            if keys.iter().any(|k| k.field == field) {
+                return Err(sort_error(format!("duplicate sort field '{name}'")));
+            }
+            keys.push(SortKey { field, direction });
+        }
+        if keys.len() > MAX_SORT_KEYS {
+            return Err(sort_error(format!(
+                "at most {MAX_SORT_KEYS} sort fields are allowed"
+            )));
+        }
+        Ok(keys)
+    }
+}
+
+fn sort_error(message: String) -> ValidationError {
+    ValidationError::new("sort").with_message(message.into())
+}
+
+/// Compare two items key by key, falling back to the item's tie-breaker
+pub fn compare<T: Sortable>(keys: &[SortKey<T::Field>], a: &T, b: &T) -> Ordering {
+    keys.iter()
+        .map(
+            |key| match (a.is_missing(key.field), b.is_missing(key.field)) {
+                (false, false) => key.direction.apply(a.compare_field(b, key.field)),
+                (a_missing, b_missing) => a_missing.cmp(&b_missing),
+            },
+        )
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.tie_break(b))
+}
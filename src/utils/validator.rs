@@ -1,48 +1,1254 @@
+use anyhow::anyhow;
 use axum::{
-    Json,
-    extract::{FromRequest, Request, rejection::JsonRejection},
-    http::StatusCode,
+    Form, Json,
+    body::{Body, Bytes},
+    extract::{
+        FromRef, FromRequest, FromRequestParts, OptionalFromRequest, Query, RawPathParams, Request,
+        path::ErrorKind,
+        rejection::{
+            BytesRejection, FailedToBufferBody, FormRejection, JsonRejection, PathRejection,
+            QueryRejection, StringRejection,
+        },
+    },
+    http::{HeaderMap, HeaderValue, StatusCode, header, request::Parts},
     response::{IntoResponse, Response},
 };
-use serde::de::DeserializeOwned;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, DeserializeOwned, Visitor},
+};
+use serde_json::{Map, Value, error::Category, json};
+use serde_path_to_error::{Path, Segment};
+use std::borrow::Cow;
 use thiserror::Error;
-use validator::{Validate, ValidationErrors};
+use tracing::{debug, error};
+use validator::{Validate, ValidateArgs, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::{
+    features::vehicle::{
+        model::{BatchCreateResult, ImportFailure},
+        repo::RepoError,
+    },
+    middlewares::tracing::{FailedWith, current_locale, current_request_id},
+    utils::{
+        config::AppConfig,
+        i18n::{self, Locale},
+        problem::{ErrorCode, Problem},
+        redact::redacted_body,
+    },
+};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ValidatedPayload<T>(pub T);
 
+/// Query string counterpart of `ValidatedPayload`: deserialized with `Query`, then validated
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedQuery<T>(pub T);
+
+/// Path counterpart of `ValidatedPayload`: deserialized with `Path`, then validated
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedPath<T>(pub T);
+
 #[derive(Debug, Error)]
 pub enum ServerError {
-    #[error(transparent)]
-    ValidationError(#[from] ValidationErrors),
+    /// Failed rules, of which at most `max_errors` are reported
+    #[error("{errors}")]
+    ValidationError {
+        errors: ValidationErrors,
+        max_errors: usize,
+    },
 
     #[error(transparent)]
     AxumJsonRejection(#[from] JsonRejection),
+
+    #[error(transparent)]
+    AxumQueryRejection(#[from] QueryRejection),
+
+    /// Query keys none of the endpoint's parameters use, so a typo doesn't silently widen
+    /// a listing
+    #[error("{}", unknown_query_message(.unknown))]
+    UnknownQueryParams { unknown: Vec<UnknownParam> },
+
+    /// Well-formed JSON that doesn't fit the expected shape, as opposed to data that fails
+    /// validation
+    #[error("{message}")]
+    InvalidBody {
+        kind: &'static str,
+        field: Option<String>,
+        message: String,
+    },
+
+    /// A path parameter that doesn't parse, naming the segment when it can be told apart
+    #[error("{message}")]
+    InvalidPath {
+        segment: Option<String>,
+        value: Option<String>,
+        message: String,
+    },
+
+    #[error("the request body must be sent as {}", accepted.join(" or "))]
+    UnsupportedMediaType { accepted: &'static [&'static str] },
+
+    #[error("the request body is larger than the {limit} byte limit")]
+    PayloadTooLarge { limit: usize },
+
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+
+    #[error("record {index} is invalid: {reason}")]
+    InvalidRecord { index: usize, reason: String },
+
+    /// `id` is whatever the resource was looked up by, when there was one
+    #[error("{}", match id {
+        Some(id) => format!("{resource} {id} not found"),
+        None => format!("{resource} not found"),
+    })]
+    NotFound {
+        resource: &'static str,
+        id: Option<String>,
+    },
+
+    #[error("a vehicle with the same manufacturer, model and year already exists")]
+    Duplicate { existing_id: String },
+
+    /// The request clashes with the state of the resource
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("{message}")]
+    UnknownField { field: String, message: String },
+
+    #[error("a vehicle with this VIN already exists")]
+    VinConflict { existing_id: String },
+
+    #[error("a vehicle with this license plate already exists")]
+    PlateConflict { existing_id: String },
+
+    /// An atomic batch with vehicles that failed validation; nothing was created
+    #[error("{failed} of {} vehicles failed validation, none were created", .report.results.len())]
+    BatchRejected {
+        failed: usize,
+        report: BatchCreateResult,
+    },
+
+    /// A strict CSV import with rows that failed; nothing was imported
+    #[error("{} rows failed, none were imported", .failed.len())]
+    ImportRejected { failed: Vec<ImportFailure> },
+
+    #[error("a vehicle can carry at most {limit} tags")]
+    TooManyTags { limit: usize },
+
+    #[error("cannot change status from {current_status} to {requested_status}")]
+    InvalidTransition {
+        current_status: String,
+        requested_status: String,
+    },
+
+    #[error(
+        "mileageKm cannot be lower than the current reading of {current_mileage_km} km, odometers don't go backwards (pass ?correction=true to fix a bad reading)"
+    )]
+    MileageDecrease { current_mileage_km: u32 },
+
+    #[error("the vehicle store is full, it holds at most {limit} vehicles")]
+    CapacityExceeded { limit: usize },
+
+    #[error("owner {owner_id} does not exist")]
+    UnknownOwner { owner_id: String },
+
+    #[error(
+        "owner is still assigned to {vehicle_count} vehicles (pass ?unassign=true to unassign them)"
+    )]
+    OwnerInUse { vehicle_count: u64 },
+
+    #[error("If-Match precondition failed")]
+    PreconditionFailed { current_version: Option<u64> },
+
+    #[error("If-Match header is required for updates")]
+    PreconditionRequired,
+
+    #[error("internal server error")]
+    Internal(anyhow::Error),
+
+    /// Something the request needs can't be reached right now; retrying later may succeed
+    #[error("{0}")]
+    Unavailable(String),
+
+    #[error("{0} is not supported by this vehicle store")]
+    Unsupported(&'static str),
 }
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
+        let problem = match self {
+            ServerError::ValidationError {
+                ref errors,
+                max_errors,
+            } => {
+                let locale = current_locale();
+                let (params, truncated) = invalid_params(errors, locale, max_errors);
+                let problem = Problem::from_code(ErrorCode::ValidationFailed)
+                    .with_type("validation-failed")
+                    .with_detail(i18n::validation_failed(locale))
+                    .with("invalid-params", params);
+                match truncated {
+                    true => problem.with("truncated", true),
+                    false => problem,
+                }
+            }
+            ServerError::AxumJsonRejection(ref rejection) => {
+                let (status, kind) = match rejection {
+                    JsonRejection::JsonSyntaxError(_) => (StatusCode::BAD_REQUEST, "syntax"),
+                    JsonRejection::JsonDataError(_) => (
+                        StatusCode::BAD_REQUEST,
+                        data_error_kind(&rejection.body_text()),
+                    ),
+                    _ => (rejection.status(), "unreadable"),
+                };
+                let problem = Problem::new(status)
+                    .with_type("invalid-body")
+                    .with_detail(rejection.body_text())
+                    .with("kind", kind);
+                match json_error(rejection) {
+                    Some((error, path)) => problem
+                        .with("category", json_error_category(error))
+                        .with("line", error.line())
+                        .with("column", error.column())
+                        .with("field", path),
+                    None => problem,
+                }
+            }
+            ServerError::AxumQueryRejection(ref rejection) => {
+                Problem::from_code(ErrorCode::BadRequest)
+                    .with_type("invalid-query")
+                    .with_detail(rejection.body_text())
+            }
+            ServerError::UnknownQueryParams { ref unknown } => {
+                Problem::from_code(ErrorCode::BadRequest)
+                    .with_type("unknown-query-parameter")
+                    .with_detail(self.to_string())
+                    .with("parameters", unknown)
+            }
+            ServerError::InvalidBody {
+                kind, ref field, ..
+            } => {
+                let problem = Problem::from_code(ErrorCode::BadRequest)
+                    .with_type("invalid-body")
+                    .with_detail(self.to_string())
+                    .with("kind", kind)
+                    .with("field", field);
+                // Parsed JSON that doesn't fit, classified like the rejections above
+                match kind {
+                    "missing_field" | "type_mismatch" => problem.with("category", "data"),
+                    _ => problem,
+                }
+            }
+            ServerError::InvalidPath {
+                ref segment,
+                ref value,
+                ..
+            } => Problem::from_code(ErrorCode::InvalidPathParameter)
+                .with_type("invalid-path")
+                .with_detail(self.to_string())
+                .with("segment", segment)
+                .with("value", value),
+            ServerError::UnsupportedMediaType { accepted } => {
+                Problem::from_code(ErrorCode::UnsupportedMediaType)
+                    .with_type("unsupported-media-type")
+                    .with_detail(self.to_string())
+                    .with("accepted", accepted)
+            }
+            ServerError::PayloadTooLarge { limit } => {
+                Problem::from_code(ErrorCode::PayloadTooLarge)
+                    .with_type("payload-too-large")
+                    .with_detail(self.to_string())
+                    .with("limit", limit)
+            }
+            ServerError::InvalidCursor(_) => Problem::from_code(ErrorCode::BadRequest)
+                .with_type("invalid-cursor")
+                .with_detail(self.to_string()),
+            ServerError::InvalidRecord { index, .. } => Problem::from_code(ErrorCode::BadRequest)
+                .with_type("invalid-record")
+                .with_detail(self.to_string())
+                .with("index", index),
+            ServerError::UnknownField { ref field, .. } => {
+                Problem::from_code(ErrorCode::BadRequest)
+                    .with_type("unknown-field")
+                    .with_detail(self.to_string())
+                    .with("field", field)
+            }
+            ServerError::NotFound {
+                resource, ref id, ..
+            } => Problem::from_code(ErrorCode::NotFound)
+                .with_detail(self.to_string())
+                .with("resource", resource)
+                .with("id", id),
+            ServerError::Duplicate { ref existing_id } => Problem::from_code(ErrorCode::Conflict)
+                .with_type("duplicate-vehicle")
+                .with_detail(self.to_string())
+                .with("existing_id", existing_id),
+            ServerError::Conflict(_) => {
+                Problem::from_code(ErrorCode::Conflict).with_detail(self.to_string())
+            }
+            ServerError::VinConflict { ref existing_id } => Problem::from_code(ErrorCode::Conflict)
+                .with_type("vin-conflict")
+                .with_detail(self.to_string())
+                .with("existing_id", existing_id),
+            ServerError::PlateConflict { ref existing_id } => {
+                Problem::from_code(ErrorCode::Conflict)
+                    .with_type("plate-conflict")
+                    .with_detail(self.to_string())
+                    .with("existing_id", existing_id)
+            }
+            ServerError::InvalidTransition {
+                ref current_status,
+                ref requested_status,
+            } => Problem::from_code(ErrorCode::Conflict)
+                .with_type("invalid-transition")
+                .with_detail(self.to_string())
+                .with("current_status", current_status)
+                .with("requested_status", requested_status),
+            ServerError::BatchRejected { ref report, .. } => {
+                let problem = Problem::from_code(ErrorCode::ValidationFailed)
+                    .with_type("batch-rejected")
+                    .with_detail(self.to_string())
+                    .with("results", &report.results);
+                match report.truncated {
+                    true => problem.with("truncated", true),
+                    false => problem,
+                }
+            }
+            ServerError::ImportRejected { ref failed } => Problem::from_code(ErrorCode::BadRequest)
+                .with_type("import-rejected")
+                .with_detail(self.to_string())
+                .with("failed", failed),
+            ServerError::TooManyTags { limit } => Problem::from_code(ErrorCode::ValidationFailed)
+                .with_type("too-many-tags")
+                .with_detail(self.to_string())
+                .with("limit", limit),
+            ServerError::MileageDecrease { current_mileage_km } => {
+                Problem::from_code(ErrorCode::ValidationFailed)
+                    .with_type("mileage-decrease")
+                    .with_detail(self.to_string())
+                    .with("current_mileage_km", current_mileage_km)
+            }
+            ServerError::CapacityExceeded { limit } => {
+                Problem::from_code(ErrorCode::InsufficientStorage)
+                    .with_type("capacity-exceeded")
+                    .with_detail(self.to_string())
+                    .with("limit", limit)
+            }
+            ServerError::UnknownOwner { ref owner_id } => {
+                Problem::from_code(ErrorCode::ValidationFailed)
+                    .with_type("unknown-owner")
+                    .with_detail(self.to_string())
+                    .with("owner_id", owner_id)
+            }
+            ServerError::OwnerInUse { vehicle_count } => Problem::from_code(ErrorCode::Conflict)
+                .with_type("owner-in-use")
+                .with_detail(self.to_string())
+                .with("vehicle_count", vehicle_count),
+            ServerError::PreconditionFailed { current_version } => {
+                Problem::from_code(ErrorCode::PreconditionFailed)
+                    .with_detail(self.to_string())
+                    .with("current_version", current_version)
+            }
+            ServerError::PreconditionRequired => {
+                Problem::from_code(ErrorCode::PreconditionRequired).with_detail(self.to_string())
+            }
+            // The details stay in the logs, clients only learn that something broke
+            ServerError::Internal(_) => {
+                Problem::from_code(ErrorCode::InternalError).with_detail(self.to_string())
+            }
+            ServerError::Unavailable(_) => {
+                Problem::from_code(ErrorCode::Unavailable).with_detail(self.to_string())
+            }
+            ServerError::Unsupported(_) => {
+                Problem::from_code(ErrorCode::NotImplemented).with_detail(self.to_string())
+            }
+        };
+        self.log(problem.code.status());
+        let failure = match &self {
+            ServerError::Internal(error) => format!("{error:#}"),
+            error => error.to_string(),
+        };
+        let mut response = problem.into_response();
+        response.extensions_mut().insert(FailedWith(failure));
+        if let ServerError::ValidationError { .. } = self {
+            response.headers_mut().insert(
+                header::CONTENT_LANGUAGE,
+                HeaderValue::from_static(current_locale().tag()),
+            );
+        }
+        response
+    }
+}
+
+impl ServerError {
+    /// Failed validation, reported up to the deployment's `max_validation_errors`
+    pub fn validation(errors: ValidationErrors, config: &AppConfig) -> Self {
+        ServerError::ValidationError {
+            errors,
+            max_errors: config.max_validation_errors,
+        }
+    }
+
+    pub fn not_found(resource: &'static str, id: impl ToString) -> Self {
+        ServerError::NotFound {
+            resource,
+            id: Some(id.to_string()),
+        }
+    }
+
+    /// Server faults are logged as errors, with their cause chain, in the span of the request
+    /// that hit them; client errors are routine and only logged at debug
+    fn log(&self, status: StatusCode) {
         match self {
-            ServerError::ValidationError(_) => {
-                let message = format!("Input Validation Error: [{self}]").replace("\n", ",");
-                (StatusCode::BAD_REQUEST, message).into_response()
+            ServerError::Internal(error) => error!("Internal server error: {:#}", error),
+            error if status.is_server_error() => error!("Request failed: {}", error),
+            error => debug!("Request rejected: {}", error),
+        }
+    }
+}
+
+impl From<RepoError> for ServerError {
+    fn from(error: RepoError) -> Self {
+        match error {
+            RepoError::NotFound => ServerError::NotFound {
+                resource: "vehicle",
+                id: None,
+            },
+            RepoError::VersionMismatch { current_version } => {
+                ServerError::PreconditionFailed { current_version }
+            }
+            RepoError::Duplicate { existing_id } => ServerError::Duplicate {
+                existing_id: existing_id.to_string(),
+            },
+            RepoError::VinTaken { existing_id } => ServerError::VinConflict {
+                existing_id: existing_id.to_string(),
+            },
+            RepoError::PlateTaken { existing_id } => ServerError::PlateConflict {
+                existing_id: existing_id.to_string(),
+            },
+            RepoError::TooManyTags { limit } => ServerError::TooManyTags { limit },
+            RepoError::MileageDecrease { current_mileage_km } => {
+                ServerError::MileageDecrease { current_mileage_km }
             }
-            _ => (StatusCode::BAD_REQUEST, self.into_response()).into_response(),
+            RepoError::CapacityExceeded { limit } => ServerError::CapacityExceeded { limit },
+            RepoError::Backend(message) => ServerError::Internal(anyhow!(message)),
+            RepoError::Unavailable => {
+                ServerError::Unavailable("the vehicle store can't be reached right now".to_string())
+            }
+            RepoError::Unsupported(operation) => ServerError::Unsupported(operation),
         }
     }
 }
 
+/// The `invalid-params` of a validation problem: one entry per failure, as in RFC 7807's
+/// example, named by the field's path with nested structs and lists flattened into dotted paths
+/// like `engine.cylinders` or `items[0].name`, sorted by name and capped at `max_errors`; the
+/// flag says whether any were left out. The rejected value itself is left out of the params.
+/// `reason` is in `locale`: English keeps the message the rule was written with, other
+/// languages come from the catalog and fall back to English for codes it doesn't cover;
+/// `code` and `params` stay as they are for clients that translate on their own
+fn invalid_params(
+    errors: &ValidationErrors,
+    locale: Locale,
+    max_errors: usize,
+) -> (Vec<Value>, bool) {
+    let mut params: Vec<Value> = field_errors(errors)
+        .into_iter()
+        .map(|(path, error)| {
+            let values: Map<String, Value> = error
+                .params
+                .iter()
+                .filter(|(name, _)| *name != "value")
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect();
+            let authored = || error.message.as_ref().map(|message| message.to_string());
+            let catalog = |locale| i18n::message(locale, &error.code, &path, &values);
+            let reason = match locale {
+                Locale::En => authored().or_else(|| catalog(Locale::En)),
+                _ => catalog(locale).or_else(authored),
+            }
+            .unwrap_or_else(|| error.code.to_string());
+            json!({
+                "name": path,
+                "reason": reason,
+                "code": error.code,
+                "params": values,
+            })
+        })
+        .collect();
+    params.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    let truncated = params.len() > max_errors;
+    params.truncate(max_errors);
+    (params, truncated)
+}
+
+/// Every failed rule with the path to its field, nested structs dotted (`engine.cylinders`)
+/// and list entries indexed (`[3].manufacturer`, `tags[2]`)
+pub fn field_errors(errors: &ValidationErrors) -> Vec<(String, &ValidationError)> {
+    fn walk<'a>(
+        errors: &'a ValidationErrors,
+        prefix: &str,
+        found: &mut Vec<(String, &'a ValidationError)>,
+    ) {
+        for (field, kind) in errors.errors() {
+            let path = match prefix {
+                "" => field.to_string(),
+                prefix => format!("{prefix}.{field}"),
+            };
+            match kind {
+                ValidationErrorsKind::Field(errors) => {
+                    found.extend(errors.iter().map(|error| (path.clone(), error)));
+                }
+                ValidationErrorsKind::Struct(nested) => walk(nested, &path, found),
+                ValidationErrorsKind::List(items) => {
+                    for (index, nested) in items {
+                        walk(nested, &format!("{path}[{index}]"), found);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(errors, "", &mut found);
+    found
+}
+
+/// Cleans up a payload before it is validated, so rules such as lengths apply to the value that
+/// ends up stored; payloads without free-text fields keep the no-op default
+pub trait Normalise {
+    fn normalise(&mut self) {}
+}
+
+/// What a payload's derived `ValidateArgs` takes, built from the deployment's settings: `()` for
+/// plain payloads, the config itself for those declared `#[validate(context = AppConfig)]`
+pub trait FromConfig<'a> {
+    fn from_config(config: &'a AppConfig) -> Self;
+}
+
+impl FromConfig<'_> for () {
+    fn from_config(_: &AppConfig) -> Self {}
+}
+
+impl<'a> FromConfig<'a> for &'a AppConfig {
+    fn from_config(config: &'a AppConfig) -> Self {
+        config
+    }
+}
+
+/// Validation under the deployment's settings, so rules like the model-year cutoff follow
+/// `AppConfig` instead of process-wide state and tests can pin them
+pub trait ValidateWithConfig {
+    fn validate_with_config(&self, config: &AppConfig) -> Result<(), ValidationErrors>;
+}
+
+impl<T> ValidateWithConfig for T
+where
+    T: for<'a> ValidateArgs<'a, Args: FromConfig<'a>>,
+{
+    fn validate_with_config(&self, config: &AppConfig) -> Result<(), ValidationErrors> {
+        self.validate_with_args(FromConfig::from_config(config))
+    }
+}
+
+/// Trim leading/trailing whitespace and collapse internal runs of it into a single space
+pub fn normalise_whitespace(value: &mut String) {
+    *value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+}
+
 impl<T, S> FromRequest<S> for ValidatedPayload<T>
 where
-    T: DeserializeOwned + Validate,
+    T: DeserializeOwned + ValidateWithConfig + Normalise,
     S: Send + Sync,
-    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    AppConfig: FromRef<S>,
 {
     type Rejection = ServerError;
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
-        let Json(value) = Json::<T>::from_request(req, state).await?;
-        value.validate()?;
-        Ok(ValidatedPayload(value))
+        Ok(ValidatedPayload(read_payload(req, state).await?))
+    }
+}
+
+/// Allows `Option<ValidatedPayload<T>>`: `None` when the request carries no `Content-Type`
+impl<T, S> OptionalFromRequest<S> for ValidatedPayload<T>
+where
+    T: DeserializeOwned + ValidateWithConfig + Normalise,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if !req.headers().contains_key(header::CONTENT_TYPE) {
+            return Ok(None);
+        }
+        Ok(Some(ValidatedPayload(read_payload(req, state).await?)))
+    }
+}
+
+/// `parse_payload`, keeping a copy of the body to log redacted if it is refused when
+/// `LOG_REJECTED_BODIES` is enabled
+async fn read_payload<T, S>(mut req: Request, state: &S) -> Result<T, ServerError>
+where
+    T: DeserializeOwned + ValidateWithConfig + Normalise,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    let config = AppConfig::from_ref(state);
+    if !config.log_rejected_bodies {
+        return parse_payload(req, state).await;
+    }
+    // Buffered under the same extensions, so the route's body limit still applies
+    let mut buffered = Request::new(std::mem::take(req.body_mut()));
+    *buffered.extensions_mut() = req.extensions().clone();
+    let body = <Bytes as FromRequest<S>>::from_request(buffered, state)
+        .await
+        .map_err(|rejection| json_rejection(rejection.into(), config.max_body_bytes))?;
+    *req.body_mut() = Body::from(body.clone());
+
+    let result = parse_payload(req, state).await;
+    if let Err(error) = &result {
+        let (redacted, truncated) = redacted_body(&body, &config.redact_fields);
+        debug!(
+            request_id = current_request_id(),
+            body = redacted,
+            truncated,
+            "Rejected request body: {}",
+            error
+        );
+    }
+    result
+}
+
+/// Read a body as JSON or, for clients that can only post forms, as a URL-encoded form, then
+/// normalise and validate it the same way whichever it came as
+async fn parse_payload<T, S>(req: Request, state: &S) -> Result<T, ServerError>
+where
+    T: DeserializeOwned + ValidateWithConfig + Normalise,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    let config = AppConfig::from_ref(state);
+    let limit = config.max_body_bytes;
+    let mut value: T = match has_media_type(req.headers(), FORM_MEDIA_TYPES) {
+        // Form fields are all strings, so they go straight to `T` rather than through `Value`;
+        // lenient mode doesn't apply to them
+        true => {
+            let Form(value) = <Form<T> as FromRequest<S>>::from_request(req, state)
+                .await
+                .map_err(|rejection| form_rejection(rejection, limit))?;
+            value
+        }
+        false => {
+            let lenient = lenient_requested(&req, state);
+            let Json(value) = <Json<Value> as FromRequest<S>>::from_request(req, state)
+                .await
+                .map_err(|rejection| match rejection {
+                    JsonRejection::MissingJsonContentType(_) => ServerError::UnsupportedMediaType {
+                        accepted: PAYLOAD_MEDIA_TYPES,
+                    },
+                    rejection => json_rejection(rejection, limit),
+                })?;
+            deserialize_fields(value, lenient)?
+        }
+    };
+    value.normalise();
+    value
+        .validate_with_config(&config)
+        .map_err(|errors| ServerError::validation(camel_case_keys(errors), &config))?;
+    Ok(value)
+}
+
+/// Media types JSON bodies are accepted as; `application/*+json` types such as
+/// `application/merge-patch+json` are let through too
+pub const JSON_MEDIA_TYPES: &[&str] = &["application/json"];
+
+/// Media types form bodies are accepted as
+pub const FORM_MEDIA_TYPES: &[&str] = &["application/x-www-form-urlencoded"];
+
+/// Media types `ValidatedPayload` reads, JSON or a URL-encoded form
+pub const PAYLOAD_MEDIA_TYPES: &[&str] = &["application/json", "application/x-www-form-urlencoded"];
+
+/// Whether the `Content-Type` is one of `accepted`, ignoring parameters like `charset=utf-8`
+pub fn has_media_type(headers: &HeaderMap, accepted: &[&str]) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| {
+            accepted
+                .iter()
+                .any(|accepted| media_type.trim().eq_ignore_ascii_case(accepted))
+        })
+}
+
+/// A JSON body over the route's size limit answers `413` naming `limit`, and one sent without
+/// a JSON `Content-Type` answers `415` listing the accepted types, as opposed to a body that
+/// was read but isn't valid JSON
+pub fn json_rejection(rejection: JsonRejection, limit: usize) -> ServerError {
+    match rejection {
+        JsonRejection::MissingJsonContentType(_) => ServerError::UnsupportedMediaType {
+            accepted: JSON_MEDIA_TYPES,
+        },
+        JsonRejection::BytesRejection(BytesRejection::FailedToBufferBody(
+            FailedToBufferBody::LengthLimitError(_),
+        )) => ServerError::PayloadTooLarge { limit },
+        rejection => rejection.into(),
+    }
+}
+
+/// Same as `json_rejection` for bodies taken as text
+pub fn string_rejection(rejection: StringRejection, limit: usize) -> ServerError {
+    match rejection {
+        StringRejection::FailedToBufferBody(FailedToBufferBody::LengthLimitError(_)) => {
+            ServerError::PayloadTooLarge { limit }
+        }
+        rejection => ServerError::InvalidBody {
+            kind: "unreadable",
+            field: None,
+            message: rejection.body_text(),
+        },
+    }
+}
+
+/// Same as `json_rejection` for form bodies; fields that don't deserialize are reported like
+/// their JSON counterparts, named when serde names them
+pub fn form_rejection(rejection: FormRejection, limit: usize) -> ServerError {
+    match rejection {
+        FormRejection::InvalidFormContentType(_) => ServerError::UnsupportedMediaType {
+            accepted: PAYLOAD_MEDIA_TYPES,
+        },
+        FormRejection::BytesRejection(BytesRejection::FailedToBufferBody(
+            FailedToBufferBody::LengthLimitError(_),
+        )) => ServerError::PayloadTooLarge { limit },
+        FormRejection::FailedToDeserializeForm(_)
+        | FormRejection::FailedToDeserializeFormBody(_) => {
+            let message = rejection.body_text();
+            // `Failed to deserialize form body: engine: ...`, the path left out at the top level
+            let error = message
+                .split_once(": ")
+                .map_or(message.as_str(), |(_, error)| error);
+            let (path, error) = match error.split_once(": ") {
+                Some((path, error)) if !path.contains(' ') => (Some(path), error),
+                _ => (None, error),
+            };
+            if error.starts_with("unknown field") {
+                return ServerError::UnknownField {
+                    field: path.unwrap_or_default().to_string(),
+                    message: error.to_string(),
+                };
+            }
+            let kind = data_error_kind(error);
+            let missing = (kind == "missing_field")
+                .then(|| error.split('`').nth(1))
+                .flatten();
+            let field = match (path, missing) {
+                (None, missing) => missing.map(String::from),
+                (Some(path), None) => Some(path.to_string()),
+                (Some(path), Some(name)) => Some(format!("{path}.{name}")),
+            };
+            ServerError::InvalidBody {
+                kind,
+                field,
+                message: error.to_string(),
+            }
+        }
+        rejection => ServerError::InvalidBody {
+            kind: "unreadable",
+            field: None,
+            message: rejection.body_text(),
+        },
+    }
+}
+
+/// Query strings `ValidatedQuery` extracts; a tuple reads several structs from the same query
+/// string, so a key one of them uses is not unknown to the others
+pub trait QueryParams: Sized {
+    /// Keys the query understands, anything else is refused
+    fn known_keys(keys: &mut Vec<&'static str>);
+
+    fn from_query(parts: &Parts, config: &AppConfig) -> Result<Self, ServerError>;
+}
+
+/// Implement `QueryParams` for query structs, optionally with keys read outside serde (like the
+/// repeated `tag` picked out of the raw pairs)
+macro_rules! query_params {
+    ($($ty:ty $(=> [$($extra:literal),*])?),* $(,)?) => {$(
+        impl $crate::utils::validator::QueryParams for $ty {
+            fn known_keys(keys: &mut Vec<&'static str>) {
+                keys.extend($crate::utils::validator::struct_fields::<Self>());
+                $(keys.extend([$($extra),*]);)?
+            }
+
+            fn from_query(
+                parts: &axum::http::request::Parts,
+                config: &$crate::utils::config::AppConfig,
+            ) -> Result<Self, $crate::utils::validator::ServerError> {
+                $crate::utils::validator::parse_query(parts, config)
+            }
+        }
+    )*};
+}
+pub(crate) use query_params;
+
+macro_rules! tuple_query_params {
+    ($($name:ident),+) => {
+        impl<$($name: QueryParams),+> QueryParams for ($($name,)+) {
+            fn known_keys(keys: &mut Vec<&'static str>) {
+                $($name::known_keys(keys);)+
+            }
+
+            fn from_query(parts: &Parts, config: &AppConfig) -> Result<Self, ServerError> {
+                Ok(($($name::from_query(parts, config)?,)+))
+            }
+        }
+    };
+}
+tuple_query_params!(A, B);
+tuple_query_params!(A, B, C);
+
+/// Deserialize a query struct with `Query`, then validate it
+pub fn parse_query<T>(parts: &Parts, config: &AppConfig) -> Result<T, ServerError>
+where
+    T: DeserializeOwned + Validate,
+{
+    let Query(value) = Query::<T>::try_from_uri(&parts.uri)?;
+    value
+        .validate()
+        .map_err(|errors| ServerError::validation(errors, config))?;
+    Ok(value)
+}
+
+/// The field names serde's derive hands to `deserialize_struct`, read by deserializing from a
+/// deserializer that only records them
+pub fn struct_fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("query parameters must be a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct QueryModeParams {
+    strict_query: Option<bool>,
+}
+
+/// A query key that isn't known, with the known key it most likely misspells
+#[derive(Debug, Serialize)]
+pub struct UnknownParam {
+    pub name: String,
+    pub suggestion: Option<&'static str>,
+}
+
+fn unknown_query_message(unknown: &[UnknownParam]) -> String {
+    let names = unknown
+        .iter()
+        .map(|param| match param.suggestion {
+            Some(suggestion) => format!("{} (did you mean {}?)", param.name, suggestion),
+            None => param.name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    match unknown.len() {
+        1 => format!("unknown query parameter {names}"),
+        _ => format!("unknown query parameters {names}"),
+    }
+}
+
+/// Refuse the query's keys that aren't `known`, each named with its likely intended key
+fn reject_unknown_params(parts: &Parts, known: &[&'static str]) -> Result<(), ServerError> {
+    let Ok(Query(pairs)) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri) else {
+        // Undecodable queries are reported by the struct extraction
+        return Ok(());
+    };
+    let mut unknown: Vec<UnknownParam> = Vec::new();
+    for (name, _) in pairs {
+        if name == "strict_query"
+            || known.contains(&name.as_str())
+            || unknown.iter().any(|param| param.name == name)
+        {
+            continue;
+        }
+        unknown.push(UnknownParam {
+            suggestion: closest_key(&name, known),
+            name,
+        });
+    }
+    match unknown.is_empty() {
+        true => Ok(()),
+        false => Err(ServerError::UnknownQueryParams { unknown }),
+    }
+}
+
+/// The known key within a third of the name's length in edits, closest first
+fn closest_key(name: &str, known: &[&'static str]) -> Option<&'static str> {
+    let threshold = (name.chars().count() / 3).max(1);
+    known
+        .iter()
+        .map(|&key| (edit_distance(name, key), key))
+        .filter(|&(distance, _)| distance <= threshold)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, key)| key)
+}
+
+/// Levenshtein distance, case-insensitive
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().flat_map(char::to_lowercase).enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: QueryParams,
+    AppConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Legacy clients that send extra parameters can opt out with `?strict_query=false`,
+        // or all of them with `LENIENT_QUERY`
+        let config = AppConfig::from_ref(state);
+        let strict = !config.lenient_query
+            && Query::<QueryModeParams>::try_from_uri(&parts.uri)
+                .map_or(true, |Query(params)| params.strict_query.unwrap_or(true));
+        if strict {
+            let mut known = Vec::new();
+            T::known_keys(&mut known);
+            reject_unknown_params(parts, &known)?;
+        }
+        Ok(ValidatedQuery(T::from_query(parts, &config)?))
+    }
+}
+
+impl<T, S> FromRequestParts<S> for ValidatedPath<T>
+where
+    T: DeserializeOwned + Validate + Send,
+    S: Send + Sync,
+    AppConfig: FromRef<S>,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let value = match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => value,
+            Err(rejection) => {
+                let raw = RawPathParams::from_request_parts(parts, state).await.ok();
+                return Err(path_rejection(rejection, raw.as_ref()));
+            }
+        };
+        value
+            .validate()
+            .map_err(|errors| ServerError::validation(errors, &AppConfig::from_ref(state)))?;
+        Ok(ValidatedPath(value))
+    }
+}
+
+/// A malformed segment is the client's fault and names the segment; anything else means the
+/// route and the extractor disagree, which is a bug on our side
+fn path_rejection(rejection: PathRejection, raw: Option<&RawPathParams>) -> ServerError {
+    let PathRejection::FailedToDeserializePathParams(error) = &rejection else {
+        return ServerError::Internal(rejection.into());
+    };
+    let nth = |index: usize| {
+        raw.and_then(|raw| raw.iter().nth(index))
+            .map(|(key, _)| key)
+    };
+    let segment = match error.kind() {
+        ErrorKind::ParseErrorAtKey { key, .. }
+        | ErrorKind::DeserializeError { key, .. }
+        | ErrorKind::InvalidUtf8InPathParam { key } => Some(key.as_str()),
+        ErrorKind::ParseErrorAtIndex { index, .. } => nth(*index),
+        // A single value was expected, so the route only has the one segment
+        ErrorKind::ParseError { .. } | ErrorKind::Message(_) => nth(0),
+        ErrorKind::WrongNumberOfParameters { .. } | ErrorKind::UnsupportedType { .. } => {
+            return ServerError::Internal(rejection.into());
+        }
+        _ => None,
+    };
+    // The raw segment, as percent-decoded by the router
+    let value = segment.and_then(|segment| {
+        raw.and_then(|raw| raw.iter().find(|(key, _)| *key == segment))
+            .map(|(_, value)| value.to_string())
+    });
+    ServerError::InvalidPath {
+        segment: segment.map(String::from),
+        value,
+        message: error.kind().to_string(),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PayloadParams {
+    lenient: Option<bool>,
+}
+
+/// Unknown fields are dropped instead of rejected when `?lenient=true` is passed or
+/// `LENIENT_JSON` is enabled, so legacy clients keep working during a migration
+fn lenient_requested<S>(req: &Request, state: &S) -> bool
+where
+    AppConfig: FromRef<S>,
+{
+    AppConfig::from_ref(state).lenient_json
+        || Query::<PayloadParams>::try_from_uri(req.uri())
+            .is_ok_and(|Query(params)| params.lenient.unwrap_or(false))
+}
+
+/// The serde error behind a JSON rejection, with the path to the field it is about if it got
+/// past the syntax; axum keeps it a few sources down the chain
+fn json_error(rejection: &JsonRejection) -> Option<(&serde_json::Error, Option<String>)> {
+    let mut source = std::error::Error::source(rejection);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+            // Before the first key is read the path is empty, or `?` inside an object
+            let known = error
+                .path()
+                .iter()
+                .any(|segment| !matches!(segment, Segment::Unknown));
+            return Some((error.inner(), known.then(|| error.path().to_string())));
+        }
+        if let Some(error) = error.downcast_ref::<serde_json::Error>() {
+            return Some((error, None));
+        }
+        source = error.source();
+    }
+    None
+}
+
+/// serde's own classification: `syntax`, `eof` for a truncated body, `data` or `io`
+fn json_error_category(error: &serde_json::Error) -> &'static str {
+    match error.classify() {
+        Category::Io => "io",
+        Category::Syntax => "syntax",
+        Category::Data => "data",
+        Category::Eof => "eof",
+    }
+}
+
+/// `missing_field` or `type_mismatch`, for a serde error about JSON that parsed but doesn't fit
+fn data_error_kind(message: &str) -> &'static str {
+    if message.contains("missing field") {
+        "missing_field"
+    } else {
+        "type_mismatch"
+    }
+}
+
+/// Deserialize a parsed JSON body, reporting missing fields and type mismatches against the
+/// offending field
+fn deserialize_fields<T: DeserializeOwned>(
+    mut value: Value,
+    lenient: bool,
+) -> Result<T, ServerError> {
+    loop {
+        let error = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(parsed) => return Ok(parsed),
+            Err(error) => error,
+        };
+        let path = error.path().clone();
+        if error.inner().to_string().starts_with("unknown field") {
+            if lenient && remove_at(&mut value, &path) {
+                debug!("Ignoring unknown field {} in lenient mode", path);
+                continue;
+            }
+            return Err(ServerError::UnknownField {
+                field: path.to_string(),
+                message: error.inner().to_string(),
+            });
+        }
+
+        let message = error.inner().to_string();
+        let kind = data_error_kind(&message);
+        // Missing fields are reported at the struct missing them, so name the field itself
+        let missing = (kind == "missing_field")
+            .then(|| message.split('`').nth(1))
+            .flatten();
+        let field = match (path.to_string(), missing) {
+            (path, None) if path == "." => None,
+            (path, Some(name)) if path == "." => Some(name.to_string()),
+            (path, Some(name)) => Some(format!("{path}.{name}")),
+            (path, None) => Some(path),
+        };
+        return Err(ServerError::InvalidBody {
+            kind,
+            field,
+            message,
+        });
+    }
+}
+
+/// `mileage_km` -> `mileageKm`; names starting with an underscore such as `__all__` are kept
+pub fn camel_case(name: &str) -> String {
+    if name.starts_with('_') {
+        return name.to_string();
+    }
+    let mut parts = name.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// Body fields are validated under their Rust names; report them the way the client sent them
+fn camel_case_keys(errors: ValidationErrors) -> ValidationErrors {
+    ValidationErrors(
+        errors
+            .0
+            .into_iter()
+            .map(|(field, kind)| {
+                let kind = match kind {
+                    ValidationErrorsKind::Struct(errors) => {
+                        ValidationErrorsKind::Struct(Box::new(camel_case_keys(*errors)))
+                    }
+                    ValidationErrorsKind::List(items) => ValidationErrorsKind::List(
+                        items
+                            .into_iter()
+                            .map(|(index, errors)| (index, Box::new(camel_case_keys(*errors))))
+                            .collect(),
+                    ),
+                    field_errors => field_errors,
+                };
+                (Cow::Owned(camel_case(&field)), kind)
+            })
+            .collect(),
+    )
+}
+
+/// Remove the object key `path` points at; false if the path doesn't end in an object key
+fn remove_at(value: &mut Value, path: &Path) -> bool {
+    let segments: Vec<&Segment> = path.iter().collect();
+    let Some((Segment::Map { key }, parents)) = segments.split_last() else {
+        return false;
+    };
+    let mut current = value;
+    for segment in parents {
+        let next = match (segment, current) {
+            (Segment::Map { key }, Value::Object(map)) => map.get_mut(key),
+            (Segment::Seq { index }, Value::Array(items)) => items.get_mut(*index),
+            _ => None,
+        };
+        let Some(next) = next else {
+            return false;
+        };
+        current = next;
+    }
+    match current {
+        Value::Object(map) => map.remove(key).is_some(),
+        _ => false,
+    }
+}
+
+/// Weights applied to each VIN position when computing the ISO 3779 check digit
+const VIN_WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+/// Transliterate a VIN character to its check-digit value; I, O and Q are not allowed
+fn vin_value(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        'A' | 'J' => Some(1),
+        'B' | 'K' | 'S' => Some(2),
+        'C' | 'L' | 'T' => Some(3),
+        'D' | 'M' | 'U' => Some(4),
+        'E' | 'N' | 'V' => Some(5),
+        'F' | 'W' => Some(6),
+        'G' | 'P' | 'X' => Some(7),
+        'H' | 'Y' => Some(8),
+        'R' | 'Z' => Some(9),
+        _ => None,
+    }
+}
+
+/// Validate a 17 character VIN including its check digit, ignoring case
+pub fn validate_vin(vin: &str) -> Result<(), ValidationError> {
+    let vin = vin.trim().to_ascii_uppercase();
+    let error =
+        |message: &str| ValidationError::new("vin").with_message(message.to_string().into());
+
+    if vin.chars().count() != 17 {
+        return Err(error("vin must be exactly 17 characters"));
+    }
+    let values: Vec<u32> = vin
+        .chars()
+        .map(vin_value)
+        .collect::<Option<_>>()
+        .ok_or_else(|| error("vin may only contain digits and letters other than I, O and Q"))?;
+
+    let remainder = values
+        .iter()
+        .zip(VIN_WEIGHTS)
+        .map(|(value, weight)| value * weight)
+        .sum::<u32>()
+        % 11;
+    let expected = match remainder {
+        10 => 'X',
+        digit => char::from_digit(digit, 10).unwrap_or_default(),
+    };
+    if vin.chars().nth(8) != Some(expected) {
+        return Err(error("vin check digit is invalid"));
+    }
+    Ok(())
+}
+
+/// Uppercase letters, digits and dashes, as plates are validated after normalisation
+pub const DEFAULT_PLATE_PATTERN: &str = "^[A-Z0-9]+(-[A-Z0-9]+)*$";
+
+/// Validate a license plate against the deployment's pattern once whitespace is removed and it
+/// is uppercased, reporting the pattern so clients know what is expected
+pub fn validate_license_plate(plate: &str, config: &AppConfig) -> Result<(), ValidationError> {
+    let normalised: String = plate
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    if !config.plate_pattern.is_match(&normalised) {
+        let pattern = config.plate_pattern.as_str();
+        let mut error = ValidationError::new("license_plate").with_message(
+            format!("licensePlate '{normalised}' must match the pattern {pattern}").into(),
+        );
+        error.add_param(Cow::Borrowed("pattern"), &pattern);
+        return Err(error);
     }
+    Ok(())
 }